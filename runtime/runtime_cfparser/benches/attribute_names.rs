@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Times parsing a class whose methods all carry the debug attributes javac writes with -g, so that most of
+// the work is telling attribute names apart. Run with `cargo bench --bench attribute_names`.
+
+use std::hint::black_box;
+use std::time::Duration;
+use std::time::Instant;
+
+use runtime_cfparser::parse::classfile_from_bytes;
+use runtime_cfparser::spec::Attribute;
+use runtime_cfparser::spec::AttributeInfo;
+use runtime_cfparser::spec::LineNumber;
+use runtime_cfparser::spec::LocalVariable;
+use runtime_cfparser::spec::Version;
+use runtime_cfparser::write::classfile_to_bytes;
+use runtime_cfparser::write::ClassBuilder;
+use runtime_cfparser::write::MethodBuilder;
+
+const METHODS: usize = 2000;
+
+const SAMPLES: usize = 50;
+
+const ITERATIONS: usize = 20;
+
+fn main() {
+    let bytes = debug_info_class();
+    println!("class of {} bytes with {METHODS} methods", bytes.len());
+
+    for _ in 0..ITERATIONS {
+        black_box(classfile_from_bytes(black_box(&bytes)).unwrap());
+    }
+
+    let mut samples = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                black_box(classfile_from_bytes(black_box(&bytes)).unwrap());
+            }
+
+            start.elapsed() / ITERATIONS as u32
+        })
+        .collect::<Vec<Duration>>();
+    samples.sort();
+    println!(
+        "parse: min {:?}, median {:?} per class",
+        samples[0],
+        samples[SAMPLES / 2]
+    );
+}
+
+// a class whose methods each have a Code attribute holding a LineNumberTable and a LocalVariableTable
+fn debug_info_class() -> Vec<u8> {
+    let mut class = ClassBuilder::new(
+        Version {
+            minor: 0,
+            major: 50,
+        },
+        0x21,
+        "Debug",
+        Some("java/lang/Object"),
+    );
+    let line_numbers = class.constant_pool().utf8("LineNumberTable").unwrap();
+    let local_variables = class.constant_pool().utf8("LocalVariableTable").unwrap();
+    let name = class.constant_pool().utf8("value").unwrap();
+    let descriptor = class.constant_pool().utf8("I").unwrap();
+    for i in 0..METHODS {
+        // static int methodN(int value) { return value; }
+        let mut method = MethodBuilder::new(0x8, &format!("method{i}"), "(I)I");
+        method.code(1, 1, vec![0x1A, 0xAC]);
+        class.add_method(method).unwrap();
+    }
+    let bytes = class.build().unwrap();
+
+    let (_, mut classfile) = classfile_from_bytes(&bytes).unwrap();
+    for method in &mut classfile.methods {
+        let Some(AttributeInfo::Code { attributes, .. }) = method
+            .attributes
+            .first_mut()
+            .map(|code| &mut code.info)
+        else {
+            unreachable!("every method has code");
+        };
+        attributes.push(Attribute {
+            name_index: line_numbers,
+            info: AttributeInfo::LineNumberTable {
+                line_number_table: vec![LineNumber {
+                    start_pc: 0,
+                    line_number: 1,
+                }],
+            },
+            raw: None,
+        });
+        attributes.push(Attribute {
+            name_index: local_variables,
+            info: AttributeInfo::LocalVariableTable {
+                local_variable_table: vec![LocalVariable {
+                    start_pc: 0,
+                    length: 2,
+                    name_index: name,
+                    descriptor_index: descriptor,
+                    index: 0,
+                }],
+            },
+            raw: None,
+        });
+    }
+
+    classfile_to_bytes(&classfile).unwrap()
+}
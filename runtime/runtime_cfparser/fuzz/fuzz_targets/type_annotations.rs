@@ -0,0 +1,49 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use runtime_cfparser::parse::classfile_from_bytes;
+
+const ATTRIBUTE_NAME: &[u8] = b"RuntimeVisibleTypeAnnotations";
+
+// the input becomes the body of a RuntimeVisibleTypeAnnotations attribute on an otherwise empty class
+fuzz_target!(|data: &[u8]| {
+    let Ok(length) = u32::try_from(data.len()) else {
+        return;
+    };
+
+    let mut bytes = Vec::with_capacity(data.len() + 64);
+    bytes.extend_from_slice(&[0xCA, 0xFE, 0xBA, 0xBE, 0x00, 0x00, 0x00, 0x34]);
+
+    // constant pool holding just the attribute name
+    bytes.extend_from_slice(&2u16.to_be_bytes());
+    bytes.push(1);
+    bytes.extend_from_slice(&(ATTRIBUTE_NAME.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(ATTRIBUTE_NAME);
+
+    // access flags, this class, super class, and no interfaces, fields or methods
+    bytes.extend_from_slice(&[0x00, 0x21, 0x00, 0x00, 0x00, 0x00]);
+    bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&length.to_be_bytes());
+    bytes.extend_from_slice(data);
+
+    let _ = classfile_from_bytes(&bytes);
+});
@@ -0,0 +1,970 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::descriptor::method_descriptor_from_str;
+use crate::descriptor::MethodDescriptor;
+use crate::mutf8::mutf8_to_str;
+use crate::spec::ConstantPoolEntry;
+use crate::spec::Version;
+
+// constant pool indices start at 1; index 0 is reserved and never refers to an entry
+const INDEX_BASE: u16 = 1;
+
+// the first major version in which invokestatic and invokespecial method handles may refer to interface methods
+const INTERFACE_METHOD_HANDLE_MAJOR_VERSION: u16 = 52;
+
+// references between entries nest at most four levels deep in a well-formed constant pool, from a method
+// handle down to the Utf8 entries naming its target; anything deeper must be cyclic
+const MAX_REFERENCE_DEPTH: usize = 8;
+
+#[derive(Debug)]
+pub enum ConstantPoolError {
+    InvalidIndex { index: u16 },
+    InvalidUtf8 { index: u16 },
+    UnexpectedEntry { index: u16, expected: &'static str },
+}
+
+impl Display for ConstantPoolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidIndex { index } => {
+                write!(f, "constant pool index {index} is out of range")
+            }
+            Self::InvalidUtf8 { index } => {
+                write!(f, "constant pool entry {index} is not valid modified UTF-8")
+            }
+            Self::UnexpectedEntry { index, expected } => {
+                write!(f, "constant pool entry {index} is not a {expected} entry")
+            }
+        }
+    }
+}
+
+impl Error for ConstantPoolError {}
+
+// the reference_kind of a MethodHandle entry, which says how the handle reaches its field or method
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReferenceKind {
+    GetField,
+    GetStatic,
+    PutField,
+    PutStatic,
+    InvokeVirtual,
+    InvokeStatic,
+    InvokeSpecial,
+    NewInvokeSpecial,
+    InvokeInterface,
+}
+
+impl ReferenceKind {
+    // `None` for values outside of 1 to 9
+    pub fn from_u8(reference_kind: u8) -> Option<Self> {
+        let kind = match reference_kind {
+            1 => Self::GetField,
+            2 => Self::GetStatic,
+            3 => Self::PutField,
+            4 => Self::PutStatic,
+            5 => Self::InvokeVirtual,
+            6 => Self::InvokeStatic,
+            7 => Self::InvokeSpecial,
+            8 => Self::NewInvokeSpecial,
+            9 => Self::InvokeInterface,
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+}
+
+// The contents of a Utf8 entry. Obfuscators may fill entries that are not used as names with bytes that are
+// not modified UTF-8, which are kept as they are.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Utf8Constant<'a> {
+    Raw(Cow<'a, [u8]>),
+    Text(Cow<'a, str>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ConstantPool<'a> {
+    entries: Vec<ConstantPoolEntry<'a>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DuplicateGroup {
+    pub tag: u8,
+    // in ascending order, the first being the entry the others could have been merged into
+    pub indices: Vec<u16>,
+}
+
+// a method a Methodref or InterfaceMethodref entry refers to
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MethodRefInfo<'a> {
+    pub owner: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    pub descriptor: Cow<'a, str>,
+    // whether the entry is an InterfaceMethodref, which invokeinterface requires and invokestatic and
+    // invokespecial need to call a method of an interface
+    pub is_interface: bool,
+}
+
+// the field or method a MethodHandle entry refers to, and how
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResolvedMethodHandle<'a> {
+    pub kind: ReferenceKind,
+    pub owner: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    pub descriptor: Cow<'a, str>,
+}
+
+impl<'a> ConstantPool<'a> {
+    pub fn new(entries: Vec<ConstantPoolEntry<'a>>) -> Self {
+        Self { entries }
+    }
+
+    pub fn class_name(&self, index: u16) -> Result<Cow<'a, str>, ConstantPoolError> {
+        let ConstantPoolEntry::Class { name_index } = *self.get(index)? else {
+            return Err(ConstantPoolError::UnexpectedEntry {
+                index,
+                expected: "Class",
+            });
+        };
+
+        self.utf8(name_index)
+    }
+
+    // A one-line description of the entry at `index` for display, with the entries it refers to resolved, such
+    // as `Fieldref -> com/example/Foo.bar : I`. References that do not resolve are shown as their index.
+    pub fn describe_entry(&self, index: u16) -> String {
+        let Ok(entry) = self.get(index) else {
+            return format!("invalid index {index}");
+        };
+
+        match *entry {
+            ConstantPoolEntry::Utf8 { ref bytes } => match mutf8_to_str(bytes) {
+                Some(value) => format!("Utf8 {value:?}"),
+                None => format!("Utf8 <{} bytes of invalid modified UTF-8>", bytes.len()),
+            },
+            ConstantPoolEntry::Integer { bytes } => format!("Integer {}", bytes as i32),
+            ConstantPoolEntry::Float { value } => format!("Float {value:?}"),
+            ConstantPoolEntry::Long { value } => format!("Long {}", value as i64),
+            ConstantPoolEntry::Double { value } => format!("Double {value:?}"),
+            ConstantPoolEntry::Class { .. } => {
+                format!("Class -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::String { .. } => {
+                format!("String -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::FieldRef { .. } => {
+                format!("Fieldref -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::MethodRef { .. } => {
+                format!("Methodref -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::InstanceMethodRef { .. } => {
+                format!(
+                    "InterfaceMethodref -> {}",
+                    self.describe_reference(index, 0)
+                )
+            }
+            ConstantPoolEntry::NameAndType { .. } => {
+                format!("NameAndType -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::MethodHandle { .. } => {
+                format!("MethodHandle -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::MethodType { .. } => {
+                format!("MethodType -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::Dynamic { .. } => {
+                format!("Dynamic -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::InvokeDynamic { .. } => {
+                format!("InvokeDynamic -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::Module { .. } => {
+                format!("Module -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::Package { .. } => {
+                format!("Package -> {}", self.describe_reference(index, 0))
+            }
+            ConstantPoolEntry::Unusable => "Unusable".to_string(),
+        }
+    }
+
+    // Entries are compared by content, following references, so that two Methodref entries whose Class
+    // entries are distinct but name the same class are duplicates too. Long and Double entries are left out,
+    // and so are the Unusable entries after them.
+    pub fn duplicate_report(&self) -> Vec<DuplicateGroup> {
+        let mut groups = HashMap::<Vec<u8>, Vec<u16>>::new();
+
+        for (offset, entry) in self.entries.iter().enumerate() {
+            if matches!(
+                entry,
+                ConstantPoolEntry::Long { .. }
+                    | ConstantPoolEntry::Double { .. }
+                    | ConstantPoolEntry::Unusable
+            ) {
+                continue;
+            }
+
+            let index = offset as u16 + INDEX_BASE;
+            let mut key = Vec::new();
+            self.content_key(index, 0, &mut key);
+
+            groups.entry(key).or_default().push(index);
+        }
+
+        let mut report = groups
+            .into_iter()
+            .filter(|(_, indices)| indices.len() > 1)
+            .map(|(key, indices)| DuplicateGroup {
+                tag: key[0],
+                indices,
+            })
+            .collect::<Vec<_>>();
+        report.sort_unstable_by_key(|group| group.indices[0]);
+
+        report
+    }
+
+    pub fn entries(&self) -> &[ConstantPoolEntry<'a>] {
+        &self.entries
+    }
+
+    pub fn get(&self, index: u16) -> Result<&ConstantPoolEntry<'a>, ConstantPoolError> {
+        index
+            .checked_sub(INDEX_BASE)
+            .and_then(|offset| self.entries.get(offset as usize))
+            .ok_or(ConstantPoolError::InvalidIndex { index })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Whether the entry at `index` may be loaded by ldc or ldc_w, or by ldc2_w if `wide` is set. Long and
+    // Double entries are loaded by ldc2_w alone, and the other loadable entries by ldc and ldc_w alone; a
+    // Dynamic entry goes with the former if its descriptor is `J` or `D` and with the latter otherwise.
+    pub fn is_loadable(&self, index: u16, wide: bool) -> bool {
+        let Ok(entry) = self.get(index) else {
+            return false;
+        };
+
+        match *entry {
+            ConstantPoolEntry::Long { .. } | ConstantPoolEntry::Double { .. } => wide,
+            ConstantPoolEntry::Integer { .. }
+            | ConstantPoolEntry::Float { .. }
+            | ConstantPoolEntry::Class { .. }
+            | ConstantPoolEntry::String { .. }
+            | ConstantPoolEntry::MethodHandle { .. }
+            | ConstantPoolEntry::MethodType { .. } => !wide,
+            ConstantPoolEntry::Dynamic {
+                name_and_type_index,
+                ..
+            } => {
+                let Ok(&ConstantPoolEntry::NameAndType {
+                    descriptor_index, ..
+                }) = self.get(name_and_type_index)
+                else {
+                    return false;
+                };
+
+                match self.utf8(descriptor_index).as_deref() {
+                    Ok("J" | "D") => wide,
+                    Ok(_) => !wide,
+                    Err(_) => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // the index and parsed descriptor of every MethodType entry, skipping those that do not resolve
+    pub fn method_types(&self) -> impl Iterator<Item = (u16, MethodDescriptor)> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| matches!(entry, ConstantPoolEntry::MethodType { .. }))
+            .filter_map(|(offset, _)| {
+                let index = offset as u16 + INDEX_BASE;
+                self.resolve_method_type(index)
+                    .map(|descriptor| (index, descriptor))
+            })
+    }
+
+    // the member a MethodHandle entry refers to, checked against the rules of JVMS 4.4.8: the kinds accessing
+    // fields need a Fieldref, invokeinterface an InterfaceMethodref, and the others a Methodref, though
+    // invokestatic and invokespecial may also refer to an InterfaceMethodref from version 52.0 on;
+    // newinvokespecial must name `<init>` and no other kind may name `<init>` or `<clinit>`
+    pub fn resolve_method_handle(
+        &self,
+        index: u16,
+        version: Version,
+    ) -> Result<ResolvedMethodHandle<'a>, ConstantPoolError> {
+        let ConstantPoolEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        } = *self.get(index)?
+        else {
+            return Err(ConstantPoolError::UnexpectedEntry {
+                index,
+                expected: "MethodHandle",
+            });
+        };
+        let kind =
+            ReferenceKind::from_u8(reference_kind).ok_or(ConstantPoolError::UnexpectedEntry {
+                index,
+                expected: "MethodHandle with a reference kind of 1 to 9",
+            })?;
+        let interface_allowed = version.major >= INTERFACE_METHOD_HANDLE_MAJOR_VERSION;
+        let unexpected = |expected| ConstantPoolError::UnexpectedEntry {
+            index: reference_index,
+            expected,
+        };
+
+        let (class_index, name_and_type_index) = match (kind, self.get(reference_index)?) {
+            (
+                ReferenceKind::GetField
+                | ReferenceKind::GetStatic
+                | ReferenceKind::PutField
+                | ReferenceKind::PutStatic,
+                &ConstantPoolEntry::FieldRef {
+                    class_index,
+                    name_and_type_index,
+                },
+            )
+            | (
+                ReferenceKind::InvokeVirtual
+                | ReferenceKind::InvokeStatic
+                | ReferenceKind::InvokeSpecial
+                | ReferenceKind::NewInvokeSpecial,
+                &ConstantPoolEntry::MethodRef {
+                    class_index,
+                    name_and_type_index,
+                },
+            )
+            | (
+                ReferenceKind::InvokeInterface,
+                &ConstantPoolEntry::InstanceMethodRef {
+                    class_index,
+                    name_and_type_index,
+                },
+            ) => (class_index, name_and_type_index),
+            (
+                ReferenceKind::InvokeStatic | ReferenceKind::InvokeSpecial,
+                &ConstantPoolEntry::InstanceMethodRef {
+                    class_index,
+                    name_and_type_index,
+                },
+            ) if interface_allowed => (class_index, name_and_type_index),
+            (
+                ReferenceKind::GetField
+                | ReferenceKind::GetStatic
+                | ReferenceKind::PutField
+                | ReferenceKind::PutStatic,
+                _,
+            ) => return Err(unexpected("Fieldref")),
+            (ReferenceKind::InvokeInterface, _) => return Err(unexpected("InterfaceMethodref")),
+            (ReferenceKind::InvokeStatic | ReferenceKind::InvokeSpecial, _)
+                if interface_allowed =>
+            {
+                return Err(unexpected("Methodref or InterfaceMethodref"))
+            }
+            _ => return Err(unexpected("Methodref")),
+        };
+        let ConstantPoolEntry::NameAndType {
+            name_index,
+            descriptor_index,
+        } = *self.get(name_and_type_index)?
+        else {
+            return Err(ConstantPoolError::UnexpectedEntry {
+                index: name_and_type_index,
+                expected: "NameAndType",
+            });
+        };
+        let name = self.utf8(name_index)?;
+
+        match kind {
+            ReferenceKind::NewInvokeSpecial if name != "<init>" => {
+                return Err(unexpected("Methodref naming <init>"))
+            }
+            ReferenceKind::InvokeVirtual
+            | ReferenceKind::InvokeStatic
+            | ReferenceKind::InvokeSpecial
+            | ReferenceKind::InvokeInterface
+                if name == "<init>" || name == "<clinit>" =>
+            {
+                return Err(unexpected("method reference not naming <init> or <clinit>"))
+            }
+            _ => {}
+        }
+
+        Ok(ResolvedMethodHandle {
+            kind,
+            owner: self.class_name(class_index)?,
+            name,
+            descriptor: self.utf8(descriptor_index)?,
+        })
+    }
+
+    // `None` if the entry is not a Methodref or InterfaceMethodref entry or does not resolve
+    pub fn resolve_method_ref(&self, index: u16) -> Option<MethodRefInfo<'a>> {
+        let (class_index, name_and_type_index, is_interface) = match *self.get(index).ok()? {
+            ConstantPoolEntry::MethodRef {
+                class_index,
+                name_and_type_index,
+            } => (class_index, name_and_type_index, false),
+            ConstantPoolEntry::InstanceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => (class_index, name_and_type_index, true),
+            _ => return None,
+        };
+        let ConstantPoolEntry::NameAndType {
+            name_index,
+            descriptor_index,
+        } = *self.get(name_and_type_index).ok()?
+        else {
+            return None;
+        };
+
+        Some(MethodRefInfo {
+            owner: self.class_name(class_index).ok()?,
+            name: self.utf8(name_index).ok()?,
+            descriptor: self.utf8(descriptor_index).ok()?,
+            is_interface,
+        })
+    }
+
+    // `None` if the entry is not a MethodType entry or its descriptor is missing or malformed
+    pub fn resolve_method_type(&self, index: u16) -> Option<MethodDescriptor> {
+        let ConstantPoolEntry::MethodType { reference_index } = *self.get(index).ok()? else {
+            return None;
+        };
+
+        method_descriptor_from_str(&self.utf8(reference_index).ok()?)
+    }
+
+    // A GraphViz digraph of the pool, with a node for each entry labelled with its description and an edge
+    // from each entry to every entry it refers to. References outside the pool get a node of their own, so
+    // that they stand out.
+    pub fn to_reference_dot(&self) -> String {
+        let mut dot = String::from("digraph constant_pool {\n");
+        let mut dangling = BTreeSet::new();
+
+        for (offset, entry) in self.entries.iter().enumerate() {
+            if *entry == ConstantPoolEntry::Unusable {
+                continue;
+            }
+
+            let index = offset as u16 + INDEX_BASE;
+            dot.push_str(&format!(
+                "    n{index} [label=\"#{index} {}\"];\n",
+                dot_escape(&self.describe_entry(index))
+            ));
+
+            for reference in entry.references() {
+                if self.get(reference).is_err() {
+                    dangling.insert(reference);
+                }
+                dot.push_str(&format!("    n{index} -> n{reference};\n"));
+            }
+        }
+
+        for index in dangling {
+            dot.push_str(&format!(
+                "    n{index} [label=\"{}\", color=red];\n",
+                dot_escape(&self.describe_entry(index))
+            ));
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    pub fn utf8(&self, index: u16) -> Result<Cow<'a, str>, ConstantPoolError> {
+        let ConstantPoolEntry::Utf8 { bytes } = self.get(index)? else {
+            return Err(ConstantPoolError::UnexpectedEntry {
+                index,
+                expected: "Utf8",
+            });
+        };
+
+        let value = match bytes {
+            Cow::Borrowed(bytes) => mutf8_to_str(bytes),
+            // strings cannot borrow from bytes the pool owns itself, as they may outlive it
+            Cow::Owned(bytes) => mutf8_to_str(bytes).map(|value| Cow::Owned(value.into_owned())),
+        };
+
+        value.ok_or(ConstantPoolError::InvalidUtf8 { index })
+    }
+
+    // like `utf8`, but with the bytes of an entry that is not valid modified UTF-8 instead of an error; names
+    // should be read with `utf8`, as a name that does not decode cannot be resolved
+    pub fn utf8_or_raw(&self, index: u16) -> Result<Utf8Constant<'a>, ConstantPoolError> {
+        match self.utf8(index) {
+            Ok(value) => Ok(Utf8Constant::Text(value)),
+            Err(ConstantPoolError::InvalidUtf8 { .. }) => match self.get(index)? {
+                ConstantPoolEntry::Utf8 {
+                    bytes: Cow::Borrowed(bytes),
+                } => Ok(Utf8Constant::Raw(Cow::Borrowed(bytes))),
+                entry => Ok(Utf8Constant::Raw(Cow::Owned(
+                    entry.as_raw_utf8().unwrap_or_default().to_vec(),
+                ))),
+            },
+            Err(error) => Err(error),
+        }
+    }
+
+    // what the entry at `index` stands for when another entry refers to it, such as the name of a class or
+    // `name : descriptor` for a NameAndType entry
+    fn describe_reference(&self, index: u16, depth: usize) -> String {
+        let entry = match self.get(index) {
+            Ok(entry) if depth < MAX_REFERENCE_DEPTH => entry,
+            _ => return format!("#{index}"),
+        };
+
+        match *entry {
+            ConstantPoolEntry::Utf8 { .. } => match self.utf8(index) {
+                Ok(value) => value.into_owned(),
+                Err(_) => format!("#{index}"),
+            },
+            ConstantPoolEntry::Class { name_index }
+            | ConstantPoolEntry::Module { name_index }
+            | ConstantPoolEntry::Package { name_index } => {
+                self.describe_reference(name_index, depth + 1)
+            }
+            ConstantPoolEntry::String { string_index } => match self.utf8(string_index) {
+                Ok(value) => format!("{value:?}"),
+                Err(_) => format!("#{string_index}"),
+            },
+            ConstantPoolEntry::FieldRef {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolEntry::MethodRef {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolEntry::InstanceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => format!(
+                "{}.{}",
+                self.describe_reference(class_index, depth + 1),
+                self.describe_reference(name_and_type_index, depth + 1)
+            ),
+            ConstantPoolEntry::NameAndType {
+                name_index,
+                descriptor_index,
+            } => format!(
+                "{} : {}",
+                self.describe_reference(name_index, depth + 1),
+                self.describe_reference(descriptor_index, depth + 1)
+            ),
+            ConstantPoolEntry::MethodHandle {
+                reference_kind,
+                reference_index,
+            } => {
+                let kind = match reference_kind {
+                    1 => "REF_getField",
+                    2 => "REF_getStatic",
+                    3 => "REF_putField",
+                    4 => "REF_putStatic",
+                    5 => "REF_invokeVirtual",
+                    6 => "REF_invokeStatic",
+                    7 => "REF_invokeSpecial",
+                    8 => "REF_newInvokeSpecial",
+                    9 => "REF_invokeInterface",
+                    _ => "REF_unknown",
+                };
+
+                format!(
+                    "{kind} {}",
+                    self.describe_reference(reference_index, depth + 1)
+                )
+            }
+            ConstantPoolEntry::MethodType { reference_index } => {
+                self.describe_reference(reference_index, depth + 1)
+            }
+            // the bootstrap method is an index into the BootstrapMethods attribute, not the constant pool
+            ConstantPoolEntry::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }
+            | ConstantPoolEntry::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => format!(
+                "bootstrap {bootstrap_method_attr_index} {}",
+                self.describe_reference(name_and_type_index, depth + 1)
+            ),
+            // no entry refers to a number
+            _ => format!("#{index}"),
+        }
+    }
+
+    // appends a self-delimiting encoding of the content of the entry at `index` to `key`, with referenced
+    // entries encoded in place of their indices
+    fn content_key(&self, index: u16, depth: usize, key: &mut Vec<u8>) {
+        let entry = match self.get(index) {
+            Ok(entry) if depth < MAX_REFERENCE_DEPTH => entry,
+            // no entry has the tag 0, so an unresolvable reference cannot be mistaken for an entry
+            _ => {
+                key.push(0);
+                key.extend_from_slice(&index.to_be_bytes());
+                return;
+            }
+        };
+
+        match *entry {
+            ConstantPoolEntry::Utf8 { ref bytes } => {
+                key.push(1);
+                key.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                key.extend_from_slice(bytes);
+            }
+            ConstantPoolEntry::Integer { bytes } => {
+                key.push(3);
+                key.extend_from_slice(&bytes.to_be_bytes());
+            }
+            ConstantPoolEntry::Float { value } => {
+                key.push(4);
+                key.extend_from_slice(&value.to_bits().to_be_bytes());
+            }
+            ConstantPoolEntry::Long { value } => {
+                key.push(5);
+                key.extend_from_slice(&value.to_be_bytes());
+            }
+            ConstantPoolEntry::Double { value } => {
+                key.push(6);
+                key.extend_from_slice(&value.to_bits().to_be_bytes());
+            }
+            ConstantPoolEntry::Class { name_index } => {
+                key.push(7);
+                self.content_key(name_index, depth + 1, key);
+            }
+            ConstantPoolEntry::String { string_index } => {
+                key.push(8);
+                self.content_key(string_index, depth + 1, key);
+            }
+            ConstantPoolEntry::FieldRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                key.push(9);
+                self.content_key(class_index, depth + 1, key);
+                self.content_key(name_and_type_index, depth + 1, key);
+            }
+            ConstantPoolEntry::MethodRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                key.push(10);
+                self.content_key(class_index, depth + 1, key);
+                self.content_key(name_and_type_index, depth + 1, key);
+            }
+            ConstantPoolEntry::InstanceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                key.push(11);
+                self.content_key(class_index, depth + 1, key);
+                self.content_key(name_and_type_index, depth + 1, key);
+            }
+            ConstantPoolEntry::NameAndType {
+                name_index,
+                descriptor_index,
+            } => {
+                key.push(12);
+                self.content_key(name_index, depth + 1, key);
+                self.content_key(descriptor_index, depth + 1, key);
+            }
+            ConstantPoolEntry::MethodHandle {
+                reference_kind,
+                reference_index,
+            } => {
+                key.push(15);
+                key.push(reference_kind);
+                self.content_key(reference_index, depth + 1, key);
+            }
+            ConstantPoolEntry::MethodType { reference_index } => {
+                key.push(16);
+                self.content_key(reference_index, depth + 1, key);
+            }
+            ConstantPoolEntry::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                key.push(17);
+                key.extend_from_slice(&bootstrap_method_attr_index.to_be_bytes());
+                self.content_key(name_and_type_index, depth + 1, key);
+            }
+            ConstantPoolEntry::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                key.push(18);
+                key.extend_from_slice(&bootstrap_method_attr_index.to_be_bytes());
+                self.content_key(name_and_type_index, depth + 1, key);
+            }
+            ConstantPoolEntry::Module { name_index } => {
+                key.push(19);
+                self.content_key(name_index, depth + 1, key);
+            }
+            ConstantPoolEntry::Package { name_index } => {
+                key.push(20);
+                self.content_key(name_index, depth + 1, key);
+            }
+            // keyed like a reference that does not resolve, as nothing may refer to it
+            ConstantPoolEntry::Unusable => {
+                key.push(0);
+                key.extend_from_slice(&index.to_be_bytes());
+            }
+        }
+    }
+}
+
+impl<'a> ConstantPoolEntry<'a> {
+    // the undecoded bytes of a Utf8 entry, whether or not they are valid modified UTF-8
+    pub fn as_raw_utf8(&self) -> Option<&[u8]> {
+        match self {
+            Self::Utf8 { bytes } => Some(bytes),
+            _ => None,
+        }
+    }
+
+    // the indices of the entries this entry refers to, in the order of its fields; the bootstrap method index of
+    // a Dynamic or InvokeDynamic entry refers to the BootstrapMethods attribute instead
+    pub fn references(&self) -> Vec<u16> {
+        match *self {
+            Self::Class { name_index }
+            | Self::Module { name_index }
+            | Self::Package { name_index } => {
+                vec![name_index]
+            }
+            Self::String { string_index } => vec![string_index],
+            Self::FieldRef {
+                class_index,
+                name_and_type_index,
+            }
+            | Self::MethodRef {
+                class_index,
+                name_and_type_index,
+            }
+            | Self::InstanceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => vec![class_index, name_and_type_index],
+            Self::NameAndType {
+                name_index,
+                descriptor_index,
+            } => vec![name_index, descriptor_index],
+            Self::MethodHandle {
+                reference_index, ..
+            }
+            | Self::MethodType { reference_index } => vec![reference_index],
+            Self::Dynamic {
+                name_and_type_index,
+                ..
+            }
+            | Self::InvokeDynamic {
+                name_and_type_index,
+                ..
+            } => vec![name_and_type_index],
+            Self::Utf8 { .. }
+            | Self::Integer { .. }
+            | Self::Float { .. }
+            | Self::Long { .. }
+            | Self::Double { .. }
+            | Self::Unusable => Vec::new(),
+        }
+    }
+}
+
+// escapes a string for a double-quoted DOT label
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::spec::ConstantPoolEntry;
+    use crate::spec::Version;
+
+    use super::ConstantPool;
+    use super::ConstantPoolError;
+    use super::ReferenceKind;
+
+    #[test]
+    fn get_is_one_based() {
+        let constant_pool = ConstantPool::new(vec![
+            ConstantPoolEntry::Utf8 {
+                bytes: Cow::Borrowed(b"first"),
+            },
+            ConstantPoolEntry::Integer { bytes: 7 },
+        ]);
+
+        assert!(matches!(
+            constant_pool.get(0),
+            Err(ConstantPoolError::InvalidIndex { index: 0 })
+        ));
+        assert_eq!(constant_pool.utf8(1).unwrap(), "first");
+        // the last entry sits at the index equal to the length
+        assert!(matches!(
+            constant_pool.get(constant_pool.len() as u16),
+            Ok(ConstantPoolEntry::Integer { bytes: 7 })
+        ));
+        assert!(matches!(
+            constant_pool.get(constant_pool.len() as u16 + 1),
+            Err(ConstantPoolError::InvalidIndex { index: 3 })
+        ));
+        assert!(matches!(
+            ConstantPool::new(Vec::new()).get(0),
+            Err(ConstantPoolError::InvalidIndex { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn method_handles_follow_jvms_4_4_8() {
+        let utf8 = |text: &'static str| ConstantPoolEntry::Utf8 {
+            bytes: Cow::Borrowed(text.as_bytes()),
+        };
+        let handle = |reference_kind, reference_index| ConstantPoolEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        };
+        let constant_pool = ConstantPool::new(vec![
+            utf8("Foo"),
+            ConstantPoolEntry::Class { name_index: 1 },
+            utf8("bar"),
+            utf8("()V"),
+            ConstantPoolEntry::NameAndType {
+                name_index: 3,
+                descriptor_index: 4,
+            },
+            utf8("<init>"),
+            ConstantPoolEntry::NameAndType {
+                name_index: 6,
+                descriptor_index: 4,
+            },
+            ConstantPoolEntry::FieldRef {
+                class_index: 2,
+                name_and_type_index: 5,
+            },
+            ConstantPoolEntry::MethodRef {
+                class_index: 2,
+                name_and_type_index: 5,
+            },
+            ConstantPoolEntry::InstanceMethodRef {
+                class_index: 2,
+                name_and_type_index: 5,
+            },
+            ConstantPoolEntry::MethodRef {
+                class_index: 2,
+                name_and_type_index: 7,
+            },
+            handle(1, 8),
+            handle(1, 9),
+            handle(6, 10),
+            handle(8, 11),
+            handle(8, 9),
+            handle(5, 11),
+            handle(10, 9),
+        ]);
+        let java_7 = Version {
+            minor: 0,
+            major: 51,
+        };
+        let java_8 = Version {
+            minor: 0,
+            major: 52,
+        };
+
+        let field = constant_pool.resolve_method_handle(12, java_8).unwrap();
+        assert_eq!(
+            (field.kind, &*field.owner, &*field.name, &*field.descriptor),
+            (ReferenceKind::GetField, "Foo", "bar", "()V")
+        );
+        assert!(matches!(
+            constant_pool.resolve_method_handle(13, java_8),
+            Err(ConstantPoolError::UnexpectedEntry {
+                index: 9,
+                expected: "Fieldref"
+            })
+        ));
+        // invokestatic may refer to an interface method only from version 52.0 on
+        assert_eq!(
+            constant_pool
+                .resolve_method_handle(14, java_8)
+                .unwrap()
+                .kind,
+            ReferenceKind::InvokeStatic
+        );
+        assert!(matches!(
+            constant_pool.resolve_method_handle(14, java_7),
+            Err(ConstantPoolError::UnexpectedEntry {
+                index: 10,
+                expected: "Methodref"
+            })
+        ));
+        assert_eq!(
+            constant_pool
+                .resolve_method_handle(15, java_7)
+                .unwrap()
+                .name,
+            "<init>"
+        );
+        assert!(matches!(
+            constant_pool.resolve_method_handle(16, java_8),
+            Err(ConstantPoolError::UnexpectedEntry { index: 9, .. })
+        ));
+        assert!(matches!(
+            constant_pool.resolve_method_handle(17, java_8),
+            Err(ConstantPoolError::UnexpectedEntry { index: 11, .. })
+        ));
+        assert!(matches!(
+            constant_pool.resolve_method_handle(18, java_8),
+            Err(ConstantPoolError::UnexpectedEntry { index: 18, .. })
+        ));
+        assert!(matches!(
+            constant_pool.resolve_method_handle(8, java_8),
+            Err(ConstantPoolError::UnexpectedEntry {
+                index: 8,
+                expected: "MethodHandle"
+            })
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use nom::bytes::complete::take_till1;
+use nom::character::complete::anychar;
+use nom::character::complete::char;
+use nom::combinator::all_consuming;
+use nom::error::Error;
+use nom::error::ErrorKind;
+use nom::multi::many0;
+use nom::sequence::delimited;
+use nom::Err;
+use nom::IResult;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FieldType {
+    // Descriptor: B
+    Byte,
+    // Descriptor: C
+    Char,
+    // Descriptor: D
+    Double,
+    // Descriptor: F
+    Float,
+    // Descriptor: I
+    Int,
+    // Descriptor: J
+    Long,
+    // Descriptor: S
+    Short,
+    // Descriptor: Z
+    Boolean,
+    // Descriptor: L ClassName ;
+    Object(String),
+    // Descriptor: [ ComponentType
+    Array(Box<FieldType>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    // `None` for a void return descriptor
+    pub return_type: Option<FieldType>,
+}
+
+pub fn field_descriptor_from_str(descriptor: &str) -> Option<FieldType> {
+    all_consuming(field_type_from_str)(descriptor)
+        .ok()
+        .map(|(_, field_type)| field_type)
+}
+
+pub fn method_descriptor_from_str(descriptor: &str) -> Option<MethodDescriptor> {
+    all_consuming(method_type_from_str)(descriptor)
+        .ok()
+        .map(|(_, method_descriptor)| method_descriptor)
+}
+
+fn field_type_from_str(input: &str) -> IResult<&str, FieldType> {
+    let (input_1, tag) = anychar(input)?;
+
+    Ok(match tag {
+        'B' => (input_1, FieldType::Byte),
+        'C' => (input_1, FieldType::Char),
+        'D' => (input_1, FieldType::Double),
+        'F' => (input_1, FieldType::Float),
+        'I' => (input_1, FieldType::Int),
+        'J' => (input_1, FieldType::Long),
+        'S' => (input_1, FieldType::Short),
+        'Z' => (input_1, FieldType::Boolean),
+        'L' => {
+            let (input_2, class_name) = take_till1(|c| c == ';')(input_1)?;
+            let (input_3, _) = char(';')(input_2)?;
+
+            // a binary name may never contain the characters reserved by the descriptor grammar
+            if class_name.contains(['.', '[']) {
+                return Err(Err::Error(Error::new(input_1, ErrorKind::Verify)));
+            }
+
+            (input_3, FieldType::Object(class_name.to_string()))
+        }
+        '[' => {
+            let (input_2, component_type) = field_type_from_str(input_1)?;
+
+            (input_2, FieldType::Array(Box::new(component_type)))
+        }
+        _ => return Err(Err::Error(Error::new(input, ErrorKind::Tag))),
+    })
+}
+
+fn method_type_from_str(input: &str) -> IResult<&str, MethodDescriptor> {
+    let (input_1, parameters) = delimited(char('('), many0(field_type_from_str), char(')'))(input)?;
+    let (input_2, return_type) = return_type_from_str(input_1)?;
+
+    Ok((
+        input_2,
+        MethodDescriptor {
+            parameters,
+            return_type,
+        },
+    ))
+}
+
+fn return_type_from_str(input: &str) -> IResult<&str, Option<FieldType>> {
+    if let Some(input_1) = input.strip_prefix('V') {
+        return Ok((input_1, None));
+    }
+
+    let (input_1, field_type) = field_type_from_str(input)?;
+
+    Ok((input_1, Some(field_type)))
+}
@@ -0,0 +1,481 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::LowerExp;
+
+use crate::constant_pool::ConstantPool;
+use crate::instruction::instructions_from_bytes;
+use crate::instruction::Instruction;
+use crate::spec::AttributeInfo;
+use crate::spec::ConstantPoolEntry;
+
+// the column the comments resolving constant pool references start at, as in `javap -c`
+const COMMENT_COLUMN: usize = 44;
+
+// the width of the values of the cases of a switch, which are right-aligned before their targets
+const SWITCH_CASE_WIDTH: usize = 12;
+
+const SWITCH_INDENT: &str = "          ";
+
+#[derive(Debug)]
+pub enum DisassembleError {
+    MalformedCode,
+    NotCode,
+}
+
+impl Display for DisassembleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedCode => write!(f, "code does not decode"),
+            Self::NotCode => write!(f, "attribute is not a Code attribute"),
+        }
+    }
+}
+
+impl Error for DisassembleError {}
+
+// Renders the instructions of a Code attribute one per line in the layout of `javap -c`, such as
+// `       1: invokespecial #1                  // Method java/lang/Object."<init>":()V`, with branch targets
+// as absolute offsets and switches followed by their case tables. Unlike javap, which leaves out the class
+// of the members of the class being disassembled, references to fields and methods always name their class.
+pub fn disassemble_code(
+    code: &AttributeInfo,
+    constant_pool: &ConstantPool,
+) -> Result<String, DisassembleError> {
+    let AttributeInfo::Code { code, .. } = code else {
+        return Err(DisassembleError::NotCode);
+    };
+    let Ok((_, instructions)) = instructions_from_bytes(code) else {
+        return Err(DisassembleError::MalformedCode);
+    };
+
+    let mut output = String::new();
+    for (offset, instruction) in instructions {
+        // wide forms decode to the same instructions, so they are told apart by their prefix
+        let mnemonic = if code[offset] == 0xC4 {
+            format!("{}_w", instruction.mnemonic())
+        } else {
+            instruction.mnemonic().to_string()
+        };
+        let mut line = format!("    {offset:>4}: {mnemonic:<13} ");
+
+        match instruction {
+            Instruction::TableSwitch {
+                default,
+                low,
+                high,
+                ref targets,
+            } => {
+                line.push_str(&format!("{{ // {low} to {high}"));
+                for (value, target) in (low..).zip(targets) {
+                    line.push_str(&switch_case(&value.to_string(), offset, *target));
+                }
+                line.push_str(&switch_case("default", offset, default));
+                line.push_str(&format!("\n{SWITCH_INDENT}}}"));
+            }
+            Instruction::LookupSwitch { default, ref pairs } => {
+                line.push_str(&format!("{{ // {}", pairs.len()));
+                for (value, target) in pairs {
+                    line.push_str(&switch_case(&value.to_string(), offset, *target));
+                }
+                line.push_str(&switch_case("default", offset, default));
+                line.push_str(&format!("\n{SWITCH_INDENT}}}"));
+            }
+            _ => {
+                let (operands, index) = operands(&instruction, offset);
+                line.push_str(&operands);
+
+                match index.and_then(|index| constant_comment(constant_pool, index)) {
+                    Some(comment) => {
+                        let padding = COMMENT_COLUMN.saturating_sub(line.len()).max(1);
+                        line.push_str(&" ".repeat(padding));
+                        line.push_str("// ");
+                        line.push_str(&comment);
+                    }
+                    None => line.truncate(line.trim_end().len()),
+                }
+            }
+        }
+
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+// the comment javap writes for a reference to the constant pool entry at `index`, or `None` if the entry or
+// anything it refers to does not resolve
+fn constant_comment(constant_pool: &ConstantPool, index: u16) -> Option<String> {
+    let comment = match *constant_pool.get(index).ok()? {
+        ConstantPoolEntry::Integer { bytes } => format!("int {}", bytes as i32),
+        ConstantPoolEntry::Float { value } => format!("float {}f", java_floating(value)),
+        ConstantPoolEntry::Long { value } => format!("long {}l", value as i64),
+        ConstantPoolEntry::Double { value } => format!("double {}d", java_floating(value)),
+        ConstantPoolEntry::Class { .. } => {
+            format!(
+                "class {}",
+                java_name(&constant_pool.class_name(index).ok()?)
+            )
+        }
+        ConstantPoolEntry::String { string_index } => {
+            format!(
+                "String {}",
+                java_escape(&constant_pool.utf8(string_index).ok()?)
+            )
+        }
+        ConstantPoolEntry::FieldRef { .. } => format!("Field {}", member(constant_pool, index)?),
+        ConstantPoolEntry::MethodRef { .. } => format!("Method {}", member(constant_pool, index)?),
+        ConstantPoolEntry::InstanceMethodRef { .. } => {
+            format!("InterfaceMethod {}", member(constant_pool, index)?)
+        }
+        ConstantPoolEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        } => {
+            let kind = match reference_kind {
+                1 => "REF_getField",
+                2 => "REF_getStatic",
+                3 => "REF_putField",
+                4 => "REF_putStatic",
+                5 => "REF_invokeVirtual",
+                6 => "REF_invokeStatic",
+                7 => "REF_invokeSpecial",
+                8 => "REF_newInvokeSpecial",
+                9 => "REF_invokeInterface",
+                _ => return None,
+            };
+
+            format!(
+                "MethodHandle {kind} {}",
+                member(constant_pool, reference_index)?
+            )
+        }
+        ConstantPoolEntry::MethodType { reference_index } => {
+            format!("MethodType {}", constant_pool.utf8(reference_index).ok()?)
+        }
+        ConstantPoolEntry::Dynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => format!(
+            "Dynamic #{bootstrap_method_attr_index}:{}",
+            name_and_type(constant_pool, name_and_type_index)?
+        ),
+        ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => format!(
+            "InvokeDynamic #{bootstrap_method_attr_index}:{}",
+            name_and_type(constant_pool, name_and_type_index)?
+        ),
+        _ => return None,
+    };
+
+    Some(comment)
+}
+
+// string constants are shown with the escapes of Java string literals, but not enclosed in quotes
+fn java_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        match character {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            '"' => escaped.push_str("\\\""),
+            '\'' => escaped.push_str("\\'"),
+            '\\' => escaped.push_str("\\\\"),
+            _ => escaped.push(character),
+        }
+    }
+
+    escaped
+}
+
+// as `Float.toString` and `Double.toString` format the value, which switch to scientific notation with a
+// capital `E` for magnitudes outside of [10^-3, 10^7)
+fn java_floating<T: Copy + Debug + LowerExp + Into<f64>>(value: T) -> String {
+    let magnitude = value.into().abs();
+
+    if value.into().is_nan() {
+        "NaN".to_string()
+    } else if value.into().is_infinite() {
+        if value.into() > 0.0 {
+            "Infinity".to_string()
+        } else {
+            "-Infinity".to_string()
+        }
+    } else if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        format!("{value:?}")
+    } else {
+        let scientific = format!("{value:e}");
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+
+        if mantissa.contains('.') {
+            format!("{mantissa}E{exponent}")
+        } else {
+            format!("{mantissa}.0E{exponent}")
+        }
+    }
+}
+
+// names that are not a sequence of Java identifiers separated by `/`, such as `<init>` and array classes,
+// are quoted
+fn java_name(name: &str) -> String {
+    if name.is_empty() {
+        return "\"\"".to_string();
+    }
+
+    let mut previous = '/';
+
+    for character in name.chars() {
+        let start = character.is_alphabetic() || matches!(character, '$' | '_');
+        let part = start || character.is_alphanumeric();
+
+        if (previous == '/' && !start) || (character != '/' && !part) {
+            return format!("\"{}\"", java_escape(name));
+        }
+        previous = character;
+    }
+
+    name.to_string()
+}
+
+// a field or method reference as `class.name:descriptor`
+fn member(constant_pool: &ConstantPool, index: u16) -> Option<String> {
+    let (ConstantPoolEntry::FieldRef {
+        class_index,
+        name_and_type_index,
+    }
+    | ConstantPoolEntry::MethodRef {
+        class_index,
+        name_and_type_index,
+    }
+    | ConstantPoolEntry::InstanceMethodRef {
+        class_index,
+        name_and_type_index,
+    }) = *constant_pool.get(index).ok()?
+    else {
+        return None;
+    };
+
+    Some(format!(
+        "{}.{}",
+        java_name(&constant_pool.class_name(class_index).ok()?),
+        name_and_type(constant_pool, name_and_type_index)?
+    ))
+}
+
+fn name_and_type(constant_pool: &ConstantPool, index: u16) -> Option<String> {
+    let ConstantPoolEntry::NameAndType {
+        name_index,
+        descriptor_index,
+    } = *constant_pool.get(index).ok()?
+    else {
+        return None;
+    };
+
+    Some(format!(
+        "{}:{}",
+        java_name(&constant_pool.utf8(name_index).ok()?),
+        constant_pool.utf8(descriptor_index).ok()?
+    ))
+}
+
+// The operands of an instruction other than a switch, and the constant pool index it refers to, if any.
+// Branch targets are absolute offsets.
+fn operands(instruction: &Instruction, offset: usize) -> (String, Option<u16>) {
+    match *instruction {
+        Instruction::BiPush(value) => (value.to_string(), None),
+        Instruction::SiPush(value) => (value.to_string(), None),
+        Instruction::Ldc(index) => (format!("#{index}"), Some(index.into())),
+        Instruction::LdcW(index)
+        | Instruction::Ldc2W(index)
+        | Instruction::GetStatic(index)
+        | Instruction::PutStatic(index)
+        | Instruction::GetField(index)
+        | Instruction::PutField(index)
+        | Instruction::InvokeVirtual(index)
+        | Instruction::InvokeSpecial(index)
+        | Instruction::InvokeStatic(index)
+        | Instruction::New(index)
+        | Instruction::ANewArray(index)
+        | Instruction::CheckCast(index)
+        | Instruction::InstanceOf(index) => (format!("#{index}"), Some(index)),
+        Instruction::ILoad(index)
+        | Instruction::LLoad(index)
+        | Instruction::FLoad(index)
+        | Instruction::DLoad(index)
+        | Instruction::ALoad(index)
+        | Instruction::IStore(index)
+        | Instruction::LStore(index)
+        | Instruction::FStore(index)
+        | Instruction::DStore(index)
+        | Instruction::AStore(index)
+        | Instruction::Ret(index) => (index.to_string(), None),
+        Instruction::IInc { index, delta } => (format!("{index}, {delta}"), None),
+        Instruction::InvokeInterface { index, count } => {
+            (format!("#{index},  {count}"), Some(index))
+        }
+        Instruction::InvokeDynamic(index) => (format!("#{index},  0"), Some(index)),
+        Instruction::MultiANewArray { index, dimensions } => {
+            (format!("#{index},  {dimensions}"), Some(index))
+        }
+        // javap sets the element type one column further apart than other operands
+        Instruction::NewArray(element_type) => {
+            let element_type = match element_type {
+                4 => "boolean".to_string(),
+                5 => "char".to_string(),
+                6 => "float".to_string(),
+                7 => "double".to_string(),
+                8 => "byte".to_string(),
+                9 => "short".to_string(),
+                10 => "int".to_string(),
+                11 => "long".to_string(),
+                element_type => element_type.to_string(),
+            };
+
+            (format!(" {element_type}"), None)
+        }
+        _ => match instruction.branch_targets(offset).first() {
+            Some(target) => (target.to_string(), None),
+            None => (String::new(), None),
+        },
+    }
+}
+
+fn switch_case(value: &str, offset: usize, target: i32) -> String {
+    format!(
+        "\n{SWITCH_INDENT}{value:>SWITCH_CASE_WIDTH$}: {}",
+        offset as i64 + target as i64
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::classfile_from_bytes;
+    use crate::spec::Version;
+    use crate::write::ClassBuilder;
+    use crate::write::MethodBuilder;
+
+    use super::disassemble_code;
+
+    #[test]
+    fn switches_match_javap() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 50,
+            },
+            0x20,
+            "Switch",
+            Some("java/lang/Object"),
+        );
+        let [one, two, three, four] = ["one", "two", "three", "four"]
+            .map(|value| class.constant_pool().string(value).unwrap() as u8);
+        let [v1, v2] = class
+            .constant_pool()
+            .method_ref("java/lang/String", "valueOf", "(I)Ljava/lang/String;")
+            .unwrap()
+            .to_be_bytes();
+
+        // static String name(int day) { switch (day) { case 1: return "one"; ... case 4: return "four"; }
+        // return String.valueOf(day); }
+        let mut name = MethodBuilder::new(0x8, "name", "(I)Ljava/lang/String;");
+        name.code(
+            1,
+            1,
+            vec![
+                0x1A, 0xAA, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+                0x00, 0x04, 0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00, 0x22, 0x00, 0x00, 0x00, 0x25,
+                0x00, 0x00, 0x00, 0x28, 0x12, one, 0xB0, 0x12, two, 0xB0, 0x12, three, 0xB0, 0x12,
+                four, 0xB0, 0x1A, 0xB8, v1, v2, 0xB0,
+            ],
+        );
+        class.add_method(name).unwrap();
+        // static int sparse(int key) { switch (key) { case -5: return 1; case 10: return 2; case 1000: return 3; }
+        // return 0; }
+        let mut sparse = MethodBuilder::new(0x8, "sparse", "(I)I");
+        sparse.code(
+            1,
+            1,
+            vec![
+                0x1A, 0xAB, 0x00, 0x00, 0x00, 0x00, 0x00, 0x29, 0x00, 0x00, 0x00, 0x03, 0xFF, 0xFF,
+                0xFF, 0xFB, 0x00, 0x00, 0x00, 0x23, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x25,
+                0x00, 0x00, 0x03, 0xE8, 0x00, 0x00, 0x00, 0x27, 0x04, 0xAC, 0x05, 0xAC, 0x06, 0xAC,
+                0x03, 0xAC,
+            ],
+        );
+        class.add_method(sparse).unwrap();
+        let bytes = class.build().unwrap();
+
+        // as printed by `javap -c`, which names the class of String.valueOf as it is not the class itself
+        let name = concat!(
+            "       0: iload_0\n",
+            "       1: tableswitch   { // 1 to 4\n",
+            "                     1: 32\n",
+            "                     2: 35\n",
+            "                     3: 38\n",
+            "                     4: 41\n",
+            "               default: 44\n",
+            "          }\n",
+            "      32: ldc           #6                  // String one\n",
+            "      34: areturn\n",
+            "      35: ldc           #8                  // String two\n",
+            "      37: areturn\n",
+            "      38: ldc           #10                 // String three\n",
+            "      40: areturn\n",
+            "      41: ldc           #12                 // String four\n",
+            "      43: areturn\n",
+            "      44: iload_0\n",
+            "      45: invokestatic  #18                 // Method java/lang/String.valueOf:(I)Ljava/lang/String;\n",
+            "      48: areturn\n",
+        );
+        let sparse = concat!(
+            "       0: iload_0\n",
+            "       1: lookupswitch  { // 3\n",
+            "                    -5: 36\n",
+            "                    10: 38\n",
+            "                  1000: 40\n",
+            "               default: 42\n",
+            "          }\n",
+            "      36: iconst_1\n",
+            "      37: ireturn\n",
+            "      38: iconst_2\n",
+            "      39: ireturn\n",
+            "      40: iconst_3\n",
+            "      41: ireturn\n",
+            "      42: iconst_0\n",
+            "      43: ireturn\n",
+        );
+        let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+        for (method, expected) in classfile.methods.iter().zip([name, sparse]) {
+            let code = &method.code().unwrap().info;
+            assert_eq!(
+                disassemble_code(code, &classfile.constant_pool).unwrap(),
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,302 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::spec::AccessFlags;
+use crate::spec::Attribute;
+use crate::spec::AttributeInfo;
+use crate::spec::Classfile;
+use crate::spec::MethodAccessFlags;
+use crate::spec::Version;
+use crate::validate::min_version_for_features;
+use crate::validate::ValidationError;
+use crate::validate::VersionedFeature;
+
+#[derive(Debug)]
+pub enum DownlevelError {
+    // an attribute the JVM acts on only from `version` on, found in `location`, such as `class` or
+    // `methods[2].attributes[0]`
+    Attribute {
+        name: &'static str,
+        location: String,
+        version: Version,
+    },
+    Feature(VersionedFeature),
+    // a method of an interface that is not public and abstract, as default, static and private interface
+    // methods are, which needs version 52.0
+    InterfaceMethod {
+        method: String,
+    },
+    Validation(ValidationError),
+}
+
+impl Display for DownlevelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Attribute {
+                name,
+                location,
+                version,
+            } => {
+                write!(
+                    f,
+                    "{name} attribute of {location} needs class file version {}.{}",
+                    version.major, version.minor
+                )
+            }
+            Self::Feature(feature) => {
+                let version = feature.version();
+                write!(
+                    f,
+                    "{feature} needs class file version {}.{}",
+                    version.major, version.minor
+                )
+            }
+            Self::InterfaceMethod { method } => {
+                write!(
+                    f,
+                    "interface method `{method}` has a body or is not public, which needs class file \
+                     version 52.0"
+                )
+            }
+            Self::Validation(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for DownlevelError {}
+
+impl From<ValidationError> for DownlevelError {
+    fn from(error: ValidationError) -> Self {
+        Self::Validation(error)
+    }
+}
+
+impl<'a> Classfile<'a> {
+    // Changes the version of the class to `target`, for backporting, unless the class uses something that the
+    // JVM of the target version does not have, such as condy, nestmates or records, and so would not load or
+    // would behave differently. The attributes a JVM of the target version merely ignores are left in place;
+    // strip_newer_attributes removes them.
+    pub fn set_version(&mut self, target: Version) -> Result<(), DownlevelError> {
+        let required = min_version_for_features(self, &self.constant_pool)?;
+        if let Some(feature) = required
+            .features
+            .into_iter()
+            .find(|feature| is_newer(feature.version(), target))
+        {
+            return Err(DownlevelError::Feature(feature));
+        }
+
+        check_attributes(&self.attributes, "class", target)?;
+        for (i, field) in self.fields.iter().enumerate() {
+            check_attributes(&field.attributes, &format!("fields[{i}]"), target)?;
+        }
+        for (i, method) in self.methods.iter().enumerate() {
+            check_attributes(&method.attributes, &format!("methods[{i}]"), target)?;
+        }
+
+        if self.access_flags & AccessFlags::INTERFACE != 0 && target.major < 52 {
+            let abstract_method = MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT;
+            for method in &self.methods {
+                let name = self
+                    .constant_pool
+                    .utf8(method.name_index)
+                    .map_err(ValidationError::from)?;
+                if name != "<clinit>" && method.access_flags & abstract_method != abstract_method {
+                    return Err(DownlevelError::InterfaceMethod {
+                        method: name.into_owned(),
+                    });
+                }
+            }
+        }
+
+        self.version = target;
+
+        Ok(())
+    }
+
+    // Removes the attributes that a JVM of the `target` version does not know and ignores, such as
+    // MethodParameters below 52.0 or StackMapTable below 50.0, including those nested in Code attributes and
+    // record components.
+    pub fn strip_newer_attributes(&mut self, target: Version) {
+        strip_attributes(&mut self.attributes, target);
+        for field in &mut self.fields {
+            strip_attributes(&mut field.attributes, target);
+        }
+        for method in &mut self.methods {
+            strip_attributes(&mut method.attributes, target);
+        }
+    }
+}
+
+fn check_attributes(
+    attributes: &[Attribute],
+    location: &str,
+    target: Version,
+) -> Result<(), DownlevelError> {
+    for (i, attribute) in attributes.iter().enumerate() {
+        // the class file format has the Module attribute since 53.0, nestmates since 55.0, records since 60.0
+        // and sealed classes since 61.0
+        let major = match attribute.info {
+            AttributeInfo::Module { .. }
+            | AttributeInfo::ModuleMainClass { .. }
+            | AttributeInfo::ModulePackages { .. } => 53,
+            AttributeInfo::NestHost { .. } | AttributeInfo::NestMembers { .. } => 55,
+            AttributeInfo::Record { .. } => 60,
+            AttributeInfo::PermittedSubclasses { .. } => 61,
+            AttributeInfo::Code { ref attributes, .. } => {
+                check_attributes(attributes, &format!("{location}.attributes[{i}]"), target)?;
+                continue;
+            }
+            _ => continue,
+        };
+
+        let version = Version { minor: 0, major };
+        if let (Some(name), true) = (attribute.info.standard_name(), is_newer(version, target)) {
+            return Err(DownlevelError::Attribute {
+                name,
+                location: location.to_string(),
+                version,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn is_newer(version: Version, target: Version) -> bool {
+    (version.major, version.minor) > (target.major, target.minor)
+}
+
+fn strip_attributes(attributes: &mut Vec<Attribute>, target: Version) {
+    attributes.retain(|attribute| {
+        // the first version of the class file format to define each attribute that only serves reflection,
+        // tools or the type checking verifier
+        let major = match attribute.info {
+            AttributeInfo::AnnotationDefault { .. }
+            | AttributeInfo::EnclosingMethod { .. }
+            | AttributeInfo::LocalVariableTypeTable { .. }
+            | AttributeInfo::RuntimeInvisibleAnnotations { .. }
+            | AttributeInfo::RuntimeInvisibleParameterAnnotations { .. }
+            | AttributeInfo::RuntimeVisibleAnnotations { .. }
+            | AttributeInfo::RuntimeVisibleParameterAnnotations { .. }
+            | AttributeInfo::Signature { .. }
+            | AttributeInfo::SourceDebugExtension { .. } => 49,
+            AttributeInfo::StackMapTable { .. } => 50,
+            AttributeInfo::MethodParameters { .. }
+            | AttributeInfo::RuntimeInvisibleTypeAnnotations { .. }
+            | AttributeInfo::RuntimeVisibleTypeAnnotations { .. } => 52,
+            _ => return true,
+        };
+
+        !is_newer(Version { minor: 0, major }, target)
+    });
+
+    for attribute in attributes {
+        match &mut attribute.info {
+            AttributeInfo::Code { attributes, .. } => {
+                let len = attributes.len();
+                strip_attributes(attributes, target);
+                // a retained body would still hold the stripped attributes
+                if attributes.len() != len {
+                    attribute.raw = None;
+                }
+            }
+            AttributeInfo::Record { components } => {
+                let len = components
+                    .iter()
+                    .map(|component| component.attributes.len())
+                    .sum::<usize>();
+                for component in components.iter_mut() {
+                    strip_attributes(&mut component.attributes, target);
+                }
+                if components
+                    .iter()
+                    .map(|component| component.attributes.len())
+                    .sum::<usize>()
+                    != len
+                {
+                    attribute.raw = None;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::classfile_from_bytes;
+    use crate::spec::Attribute;
+    use crate::spec::AttributeInfo;
+    use crate::spec::RecordComponent;
+    use crate::spec::Version;
+    use crate::write::classfile_to_bytes;
+    use crate::write::ClassBuilder;
+
+    use super::DownlevelError;
+
+    #[test]
+    fn records_are_not_lowered() {
+        let java_16 = Version {
+            minor: 0,
+            major: 60,
+        };
+        let mut class = ClassBuilder::new(java_16, 0x31, "Point", Some("java/lang/Record"));
+        let record = class.constant_pool().utf8("Record").unwrap();
+        let name = class.constant_pool().utf8("x").unwrap();
+        let descriptor = class.constant_pool().utf8("I").unwrap();
+        let bytes = class.build().unwrap();
+
+        let (_, mut classfile) = classfile_from_bytes(&bytes).unwrap();
+        classfile.attributes.push(Attribute {
+            name_index: record,
+            info: AttributeInfo::Record {
+                components: vec![RecordComponent {
+                    name_index: name,
+                    descriptor_index: descriptor,
+                    attributes: Vec::new(),
+                }],
+            },
+            raw: None,
+        });
+        let bytes = classfile_to_bytes(&classfile).unwrap();
+
+        let (_, mut classfile) = classfile_from_bytes(&bytes).unwrap();
+        let error = classfile
+            .set_version(Version {
+                minor: 0,
+                major: 52,
+            })
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            DownlevelError::Attribute {
+                name: "Record",
+                ref location,
+                version: Version {
+                    minor: 0,
+                    major: 60
+                },
+            } if location == "class"
+        ));
+        assert_eq!(classfile.version, java_16);
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::DecodeError;
+use base64::Engine;
+use hex::FromHexError;
+
+use crate::owned::OwnedClassfile;
+use crate::parse::classfile_from_bytes;
+use crate::parse::error_with_offset;
+use crate::parse::ParseError;
+use crate::spec::Classfile;
+
+#[derive(Debug)]
+pub enum EncodingError {
+    Base64(DecodeError),
+    Hex(FromHexError),
+    // the input of the parse error is the offset into the decoded bytes, which are gone by now
+    Parse(ParseError<usize>),
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base64(error) => write!(f, "invalid base64: {error}"),
+            Self::Hex(error) => write!(f, "invalid hex: {error}"),
+            Self::Parse(ParseError::Nom(error)) => write!(
+                f,
+                "malformed class file at offset {}: {}",
+                error.input,
+                error.code.description()
+            ),
+            Self::Parse(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for EncodingError {}
+
+impl From<DecodeError> for EncodingError {
+    fn from(error: DecodeError) -> Self {
+        Self::Base64(error)
+    }
+}
+
+impl From<FromHexError> for EncodingError {
+    fn from(error: FromHexError) -> Self {
+        Self::Hex(error)
+    }
+}
+
+// Whitespace is ignored in both encodings, so that wrapped base64 and spaced-out hex dumps can be pasted
+// as they are.
+impl Classfile<'static> {
+    pub fn from_base64(encoded: &str) -> Result<OwnedClassfile, EncodingError> {
+        let bytes = STANDARD.decode(without_whitespace(encoded))?;
+
+        from_decoded(&bytes)
+    }
+
+    pub fn from_hex(encoded: &str) -> Result<OwnedClassfile, EncodingError> {
+        let bytes = hex::decode(without_whitespace(encoded))?;
+
+        from_decoded(&bytes)
+    }
+}
+
+fn from_decoded(bytes: &[u8]) -> Result<OwnedClassfile, EncodingError> {
+    match classfile_from_bytes(bytes) {
+        Ok((_, classfile)) => Ok(classfile.into_owned()),
+        Err(error) => Err(EncodingError::Parse(error_with_offset(bytes, error))),
+    }
+}
+
+fn without_whitespace(encoded: &str) -> String {
+    encoded.split_whitespace().collect()
+}
@@ -0,0 +1,224 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::ops::Range;
+
+use crate::instruction::instructions_from_bytes;
+use crate::instruction::Instruction;
+use crate::spec::AttributeInfo;
+
+// a transfer of control that does not land on the start of an instruction of the code array
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BadBranch {
+    // a branch or switch target of the instruction at `offset`, relative to the start of the code array
+    Branch { offset: usize, target: i64 },
+    // the start_pc, end_pc or handler_pc of the exception table entry at `entry`; an end_pc may also be the
+    // length of the code array, but must be past the start_pc
+    ExceptionTable { entry: usize, pc: u16 },
+    // the last instruction, at `offset`, lets execution continue past the end of the code array
+    FallsOffEnd { offset: usize },
+}
+
+#[derive(Debug)]
+pub enum FlowError {
+    MalformedCode,
+    NotCode,
+}
+
+impl Display for FlowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedCode => write!(f, "code does not decode"),
+            Self::NotCode => write!(f, "attribute is not a Code attribute"),
+        }
+    }
+}
+
+impl Error for FlowError {}
+
+// The byte ranges of the runs of instructions that no path from the start of the method reaches, in
+// ascending order. An exception handler is only entered once an instruction it protects is reached, so a
+// handler for a region that is itself unreachable is unreachable as well.
+pub fn unreachable_regions(code: &AttributeInfo) -> Result<Vec<Range<usize>>, FlowError> {
+    let AttributeInfo::Code {
+        code,
+        exception_table,
+        ..
+    } = code
+    else {
+        return Err(FlowError::NotCode);
+    };
+    let Ok((_, instructions)) = instructions_from_bytes(code) else {
+        return Err(FlowError::MalformedCode);
+    };
+
+    // branch targets are offsets, so instructions are also looked up by their offset
+    let positions = instructions
+        .iter()
+        .enumerate()
+        .map(|(position, (offset, _))| (*offset, position))
+        .collect::<HashMap<_, _>>();
+
+    let mut reachable = vec![false; instructions.len()];
+    let mut pending = if instructions.is_empty() {
+        Vec::new()
+    } else {
+        vec![0]
+    };
+    let mut handlers = exception_table.iter().collect::<Vec<_>>();
+
+    while !pending.is_empty() {
+        while let Some(position) = pending.pop() {
+            if reachable[position] {
+                continue;
+            }
+            reachable[position] = true;
+
+            let (offset, instruction) = &instructions[position];
+            // targets that are not the offset of an instruction lead nowhere
+            for target in instruction.branch_targets(*offset) {
+                if let Some(&target) = usize::try_from(target)
+                    .ok()
+                    .and_then(|target| positions.get(&target))
+                {
+                    pending.push(target);
+                }
+            }
+            if falls_through(instruction) && position + 1 < instructions.len() {
+                pending.push(position + 1);
+            }
+        }
+
+        handlers.retain(|handler| {
+            let protected = handler.start_pc as usize..handler.end_pc as usize;
+            let entered = instructions
+                .iter()
+                .zip(&reachable)
+                .any(|((offset, _), &reachable)| reachable && protected.contains(offset));
+
+            if entered {
+                if let Some(&handler) = positions.get(&(handler.handler_pc as usize)) {
+                    pending.push(handler);
+                }
+            }
+
+            !entered
+        });
+    }
+
+    let mut regions = Vec::<Range<usize>>::new();
+    for (position, (offset, _)) in instructions.iter().enumerate() {
+        if reachable[position] {
+            continue;
+        }
+
+        let end = instructions
+            .get(position + 1)
+            .map_or(code.len(), |(next, _)| *next);
+        match regions.last_mut() {
+            Some(region) if region.end == *offset => region.end = end,
+            _ => regions.push(*offset..end),
+        }
+    }
+
+    Ok(regions)
+}
+
+// Every branch and switch target and exception table pc that is not the offset of an instruction, in the
+// order of the instructions and then of the exception table, and the last instruction if execution can
+// continue past it. Code without any of these problems has none.
+pub fn validate_branch_targets(code: &AttributeInfo) -> Result<Vec<BadBranch>, FlowError> {
+    let AttributeInfo::Code {
+        code,
+        exception_table,
+        ..
+    } = code
+    else {
+        return Err(FlowError::NotCode);
+    };
+    let Ok((_, instructions)) = instructions_from_bytes(code) else {
+        return Err(FlowError::MalformedCode);
+    };
+
+    let boundaries = instructions
+        .iter()
+        .map(|(offset, _)| *offset)
+        .collect::<HashSet<_>>();
+    let is_boundary =
+        |target: i64| usize::try_from(target).is_ok_and(|target| boundaries.contains(&target));
+
+    let mut bad = Vec::new();
+    for (offset, instruction) in &instructions {
+        for target in instruction.branch_targets(*offset) {
+            if !is_boundary(target) {
+                bad.push(BadBranch::Branch {
+                    offset: *offset,
+                    target,
+                });
+            }
+        }
+    }
+    // the code array may not be empty either
+    match instructions.last() {
+        Some((offset, instruction)) if falls_through(instruction) => {
+            bad.push(BadBranch::FallsOffEnd { offset: *offset })
+        }
+        None => bad.push(BadBranch::FallsOffEnd { offset: 0 }),
+        _ => {}
+    }
+
+    for (entry, handler) in exception_table.iter().enumerate() {
+        let end_is_valid = handler.end_pc > handler.start_pc
+            && (handler.end_pc as usize == code.len() || is_boundary(handler.end_pc.into()));
+
+        for (pc, valid) in [
+            (handler.start_pc, is_boundary(handler.start_pc.into())),
+            (handler.end_pc, end_is_valid),
+            (handler.handler_pc, is_boundary(handler.handler_pc.into())),
+        ] {
+            if !valid {
+                bad.push(BadBranch::ExceptionTable { entry, pc });
+            }
+        }
+    }
+
+    Ok(bad)
+}
+
+// whether execution may continue with the next instruction; jsr does, as its subroutine returns past it
+fn falls_through(instruction: &Instruction) -> bool {
+    !matches!(
+        instruction,
+        Instruction::Goto(_)
+            | Instruction::GotoW(_)
+            | Instruction::Ret(_)
+            | Instruction::TableSwitch { .. }
+            | Instruction::LookupSwitch { .. }
+            | Instruction::IReturn
+            | Instruction::LReturn
+            | Instruction::FReturn
+            | Instruction::DReturn
+            | Instruction::AReturn
+            | Instruction::Return
+            | Instruction::AThrow
+    )
+}
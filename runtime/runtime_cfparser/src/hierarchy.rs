@@ -0,0 +1,157 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::resolve::ResolveError;
+use crate::resolve::ResolvedClass;
+use crate::spec::Classfile;
+
+const OBJECT: &str = "java/lang/Object";
+
+#[derive(Debug)]
+pub enum HierarchyError {
+    Cycle { name: String },
+    DuplicateClass { name: String },
+    Resolve(ResolveError),
+}
+
+impl Display for HierarchyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle { name } => write!(f, "type hierarchy has a cycle through `{name}`"),
+            Self::DuplicateClass { name } => write!(f, "class `{name}` was added more than once"),
+            Self::Resolve(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for HierarchyError {}
+
+impl From<ResolveError> for HierarchyError {
+    fn from(error: ResolveError) -> Self {
+        Self::Resolve(error)
+    }
+}
+
+// Classes referenced as a supertype but never added are kept as opaque leaves: they appear in query
+// results, but nothing is known about their own supertypes.
+#[derive(Default)]
+pub struct TypeHierarchy {
+    supertypes: HashMap<String, Vec<String>>,
+    subtypes: HashMap<String, Vec<String>>,
+}
+
+impl TypeHierarchy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, classfile: &Classfile) -> Result<&mut Self, HierarchyError> {
+        let class = ResolvedClass::new(classfile);
+        let name = class.class_name(classfile.this_class)?.into_owned();
+
+        if self.supertypes.contains_key(&name) {
+            return Err(HierarchyError::DuplicateClass { name });
+        }
+
+        let mut supertypes = Vec::with_capacity(classfile.interfaces.len() + 1);
+        if let Some(super_class) = classfile.super_class_name()? {
+            supertypes.push(super_class.into_owned());
+        }
+        for &interface in &classfile.interfaces {
+            supertypes.push(class.class_name(interface)?.into_owned());
+        }
+
+        for supertype in &supertypes {
+            self.subtypes
+                .entry(supertype.clone())
+                .or_default()
+                .push(name.clone());
+        }
+        self.supertypes.insert(name, supertypes);
+
+        Ok(self)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.supertypes.contains_key(name)
+    }
+
+    pub fn is_assignable(&self, subtype: &str, supertype: &str) -> Result<bool, HierarchyError> {
+        if subtype == supertype || supertype == OBJECT {
+            return Ok(true);
+        }
+
+        Ok(self.supertypes_of(subtype)?.contains(&supertype))
+    }
+
+    // all direct and indirect supertypes, in depth-first order starting with the superclass
+    pub fn supertypes_of(&self, name: &str) -> Result<Vec<&str>, HierarchyError> {
+        Self::walk(&self.supertypes, name)
+    }
+
+    // all direct and indirect subtypes among the added classes
+    pub fn subtypes_of(&self, name: &str) -> Result<Vec<&str>, HierarchyError> {
+        Self::walk(&self.subtypes, name)
+    }
+
+    fn walk<'h>(
+        edges: &'h HashMap<String, Vec<String>>,
+        name: &str,
+    ) -> Result<Vec<&'h str>, HierarchyError> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        let mut path = Vec::new();
+
+        // each stack entry is a type together with how many of its edges have been followed
+        let mut stack = vec![(name, 0)];
+        while let Some((current, next)) = stack.pop() {
+            let targets = edges.get(current).map(Vec::as_slice).unwrap_or_default();
+            let Some(target) = targets.get(next) else {
+                if next > 0 {
+                    path.pop();
+                }
+                continue;
+            };
+
+            if next == 0 {
+                path.push(current);
+            }
+            stack.push((current, next + 1));
+
+            // a type still on the path is being revisited through one of its own supertypes or subtypes
+            if path.contains(&target.as_str()) {
+                return Err(HierarchyError::Cycle {
+                    name: target.clone(),
+                });
+            }
+            if !seen.insert(target.as_str()) {
+                continue;
+            }
+
+            found.push(target.as_str());
+            stack.push((target.as_str(), 0));
+        }
+
+        Ok(found)
+    }
+}
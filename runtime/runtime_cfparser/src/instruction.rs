@@ -0,0 +1,1385 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use nom::bytes::complete::take;
+use nom::combinator::map;
+use nom::error::Error;
+use nom::error::ErrorKind;
+use nom::multi::count;
+use nom::number::complete::be_i16;
+use nom::number::complete::be_i32;
+use nom::number::complete::be_i8;
+use nom::number::complete::be_u16;
+use nom::number::complete::be_u8;
+use nom::sequence::pair;
+use nom::Err;
+use nom::IResult;
+
+#[derive(Debug)]
+pub enum InstructionError {
+    MalformedSwitch { offset: usize },
+    TruncatedInstruction { offset: usize },
+    UnknownOpcode { offset: usize, opcode: u8 },
+}
+
+impl Display for InstructionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedSwitch { offset } => {
+                write!(
+                    f,
+                    "switch at offset {offset} has a negative number of cases"
+                )
+            }
+            Self::TruncatedInstruction { offset } => {
+                write!(
+                    f,
+                    "instruction at offset {offset} runs past the end of the code array"
+                )
+            }
+            Self::UnknownOpcode { offset, opcode } => {
+                write!(f, "unknown opcode {opcode:#04x} at offset {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InstructionError {}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Instruction {
+    // Opcode: 0x00
+    Nop,
+    // Opcode: 0x01
+    AConstNull,
+    // Opcode: 0x02
+    IConstM1,
+    // Opcode: 0x03
+    IConst0,
+    // Opcode: 0x04
+    IConst1,
+    // Opcode: 0x05
+    IConst2,
+    // Opcode: 0x06
+    IConst3,
+    // Opcode: 0x07
+    IConst4,
+    // Opcode: 0x08
+    IConst5,
+    // Opcode: 0x09
+    LConst0,
+    // Opcode: 0x0A
+    LConst1,
+    // Opcode: 0x0B
+    FConst0,
+    // Opcode: 0x0C
+    FConst1,
+    // Opcode: 0x0D
+    FConst2,
+    // Opcode: 0x0E
+    DConst0,
+    // Opcode: 0x0F
+    DConst1,
+    // Opcode: 0x10
+    BiPush(i8),
+    // Opcode: 0x11
+    SiPush(i16),
+    // Opcode: 0x12
+    Ldc(u8),
+    // Opcode: 0x13
+    LdcW(u16),
+    // Opcode: 0x14
+    Ldc2W(u16),
+    // Opcode: 0x15 (wide: 0xC4 0x15)
+    ILoad(u16),
+    // Opcode: 0x16 (wide: 0xC4 0x16)
+    LLoad(u16),
+    // Opcode: 0x17 (wide: 0xC4 0x17)
+    FLoad(u16),
+    // Opcode: 0x18 (wide: 0xC4 0x18)
+    DLoad(u16),
+    // Opcode: 0x19 (wide: 0xC4 0x19)
+    ALoad(u16),
+    // Opcode: 0x1A
+    ILoad0,
+    // Opcode: 0x1B
+    ILoad1,
+    // Opcode: 0x1C
+    ILoad2,
+    // Opcode: 0x1D
+    ILoad3,
+    // Opcode: 0x1E
+    LLoad0,
+    // Opcode: 0x1F
+    LLoad1,
+    // Opcode: 0x20
+    LLoad2,
+    // Opcode: 0x21
+    LLoad3,
+    // Opcode: 0x22
+    FLoad0,
+    // Opcode: 0x23
+    FLoad1,
+    // Opcode: 0x24
+    FLoad2,
+    // Opcode: 0x25
+    FLoad3,
+    // Opcode: 0x26
+    DLoad0,
+    // Opcode: 0x27
+    DLoad1,
+    // Opcode: 0x28
+    DLoad2,
+    // Opcode: 0x29
+    DLoad3,
+    // Opcode: 0x2A
+    ALoad0,
+    // Opcode: 0x2B
+    ALoad1,
+    // Opcode: 0x2C
+    ALoad2,
+    // Opcode: 0x2D
+    ALoad3,
+    // Opcode: 0x2E
+    IALoad,
+    // Opcode: 0x2F
+    LALoad,
+    // Opcode: 0x30
+    FALoad,
+    // Opcode: 0x31
+    DALoad,
+    // Opcode: 0x32
+    AALoad,
+    // Opcode: 0x33
+    BALoad,
+    // Opcode: 0x34
+    CALoad,
+    // Opcode: 0x35
+    SALoad,
+    // Opcode: 0x36 (wide: 0xC4 0x36)
+    IStore(u16),
+    // Opcode: 0x37 (wide: 0xC4 0x37)
+    LStore(u16),
+    // Opcode: 0x38 (wide: 0xC4 0x38)
+    FStore(u16),
+    // Opcode: 0x39 (wide: 0xC4 0x39)
+    DStore(u16),
+    // Opcode: 0x3A (wide: 0xC4 0x3A)
+    AStore(u16),
+    // Opcode: 0x3B
+    IStore0,
+    // Opcode: 0x3C
+    IStore1,
+    // Opcode: 0x3D
+    IStore2,
+    // Opcode: 0x3E
+    IStore3,
+    // Opcode: 0x3F
+    LStore0,
+    // Opcode: 0x40
+    LStore1,
+    // Opcode: 0x41
+    LStore2,
+    // Opcode: 0x42
+    LStore3,
+    // Opcode: 0x43
+    FStore0,
+    // Opcode: 0x44
+    FStore1,
+    // Opcode: 0x45
+    FStore2,
+    // Opcode: 0x46
+    FStore3,
+    // Opcode: 0x47
+    DStore0,
+    // Opcode: 0x48
+    DStore1,
+    // Opcode: 0x49
+    DStore2,
+    // Opcode: 0x4A
+    DStore3,
+    // Opcode: 0x4B
+    AStore0,
+    // Opcode: 0x4C
+    AStore1,
+    // Opcode: 0x4D
+    AStore2,
+    // Opcode: 0x4E
+    AStore3,
+    // Opcode: 0x4F
+    IAStore,
+    // Opcode: 0x50
+    LAStore,
+    // Opcode: 0x51
+    FAStore,
+    // Opcode: 0x52
+    DAStore,
+    // Opcode: 0x53
+    AAStore,
+    // Opcode: 0x54
+    BAStore,
+    // Opcode: 0x55
+    CAStore,
+    // Opcode: 0x56
+    SAStore,
+    // Opcode: 0x57
+    Pop,
+    // Opcode: 0x58
+    Pop2,
+    // Opcode: 0x59
+    Dup,
+    // Opcode: 0x5A
+    DupX1,
+    // Opcode: 0x5B
+    DupX2,
+    // Opcode: 0x5C
+    Dup2,
+    // Opcode: 0x5D
+    Dup2X1,
+    // Opcode: 0x5E
+    Dup2X2,
+    // Opcode: 0x5F
+    Swap,
+    // Opcode: 0x60
+    IAdd,
+    // Opcode: 0x61
+    LAdd,
+    // Opcode: 0x62
+    FAdd,
+    // Opcode: 0x63
+    DAdd,
+    // Opcode: 0x64
+    ISub,
+    // Opcode: 0x65
+    LSub,
+    // Opcode: 0x66
+    FSub,
+    // Opcode: 0x67
+    DSub,
+    // Opcode: 0x68
+    IMul,
+    // Opcode: 0x69
+    LMul,
+    // Opcode: 0x6A
+    FMul,
+    // Opcode: 0x6B
+    DMul,
+    // Opcode: 0x6C
+    IDiv,
+    // Opcode: 0x6D
+    LDiv,
+    // Opcode: 0x6E
+    FDiv,
+    // Opcode: 0x6F
+    DDiv,
+    // Opcode: 0x70
+    IRem,
+    // Opcode: 0x71
+    LRem,
+    // Opcode: 0x72
+    FRem,
+    // Opcode: 0x73
+    DRem,
+    // Opcode: 0x74
+    INeg,
+    // Opcode: 0x75
+    LNeg,
+    // Opcode: 0x76
+    FNeg,
+    // Opcode: 0x77
+    DNeg,
+    // Opcode: 0x78
+    IShl,
+    // Opcode: 0x79
+    LShl,
+    // Opcode: 0x7A
+    IShr,
+    // Opcode: 0x7B
+    LShr,
+    // Opcode: 0x7C
+    IUShr,
+    // Opcode: 0x7D
+    LUShr,
+    // Opcode: 0x7E
+    IAnd,
+    // Opcode: 0x7F
+    LAnd,
+    // Opcode: 0x80
+    IOr,
+    // Opcode: 0x81
+    LOr,
+    // Opcode: 0x82
+    IXor,
+    // Opcode: 0x83
+    LXor,
+    // Opcode: 0x84 (wide: 0xC4 0x84)
+    IInc {
+        index: u16,
+        delta: i16,
+    },
+    // Opcode: 0x85
+    I2L,
+    // Opcode: 0x86
+    I2F,
+    // Opcode: 0x87
+    I2D,
+    // Opcode: 0x88
+    L2I,
+    // Opcode: 0x89
+    L2F,
+    // Opcode: 0x8A
+    L2D,
+    // Opcode: 0x8B
+    F2I,
+    // Opcode: 0x8C
+    F2L,
+    // Opcode: 0x8D
+    F2D,
+    // Opcode: 0x8E
+    D2I,
+    // Opcode: 0x8F
+    D2L,
+    // Opcode: 0x90
+    D2F,
+    // Opcode: 0x91
+    I2B,
+    // Opcode: 0x92
+    I2C,
+    // Opcode: 0x93
+    I2S,
+    // Opcode: 0x94
+    LCmp,
+    // Opcode: 0x95
+    FCmpL,
+    // Opcode: 0x96
+    FCmpG,
+    // Opcode: 0x97
+    DCmpL,
+    // Opcode: 0x98
+    DCmpG,
+    // Opcode: 0x99
+    IfEq(i16),
+    // Opcode: 0x9A
+    IfNe(i16),
+    // Opcode: 0x9B
+    IfLt(i16),
+    // Opcode: 0x9C
+    IfGe(i16),
+    // Opcode: 0x9D
+    IfGt(i16),
+    // Opcode: 0x9E
+    IfLe(i16),
+    // Opcode: 0x9F
+    IfICmpEq(i16),
+    // Opcode: 0xA0
+    IfICmpNe(i16),
+    // Opcode: 0xA1
+    IfICmpLt(i16),
+    // Opcode: 0xA2
+    IfICmpGe(i16),
+    // Opcode: 0xA3
+    IfICmpGt(i16),
+    // Opcode: 0xA4
+    IfICmpLe(i16),
+    // Opcode: 0xA5
+    IfACmpEq(i16),
+    // Opcode: 0xA6
+    IfACmpNe(i16),
+    // Opcode: 0xA7
+    Goto(i16),
+    // Opcode: 0xA8
+    Jsr(i16),
+    // Opcode: 0xA9 (wide: 0xC4 0xA9)
+    Ret(u16),
+    // Opcode: 0xAA
+    TableSwitch {
+        default: i32,
+        low: i32,
+        high: i32,
+        // the offsets for the values from `low` to `high`, in order
+        targets: Vec<i32>,
+    },
+    // Opcode: 0xAB
+    LookupSwitch {
+        default: i32,
+        // match and offset pairs, sorted by match
+        pairs: Vec<(i32, i32)>,
+    },
+    // Opcode: 0xAC
+    IReturn,
+    // Opcode: 0xAD
+    LReturn,
+    // Opcode: 0xAE
+    FReturn,
+    // Opcode: 0xAF
+    DReturn,
+    // Opcode: 0xB0
+    AReturn,
+    // Opcode: 0xB1
+    Return,
+    // Opcode: 0xB2
+    GetStatic(u16),
+    // Opcode: 0xB3
+    PutStatic(u16),
+    // Opcode: 0xB4
+    GetField(u16),
+    // Opcode: 0xB5
+    PutField(u16),
+    // Opcode: 0xB6
+    InvokeVirtual(u16),
+    // Opcode: 0xB7
+    InvokeSpecial(u16),
+    // Opcode: 0xB8
+    InvokeStatic(u16),
+    // Opcode: 0xB9
+    InvokeInterface {
+        index: u16,
+        count: u8,
+    },
+    // Opcode: 0xBA
+    InvokeDynamic(u16),
+    // Opcode: 0xBB
+    New(u16),
+    // Opcode: 0xBC
+    NewArray(u8),
+    // Opcode: 0xBD
+    ANewArray(u16),
+    // Opcode: 0xBE
+    ArrayLength,
+    // Opcode: 0xBF
+    AThrow,
+    // Opcode: 0xC0
+    CheckCast(u16),
+    // Opcode: 0xC1
+    InstanceOf(u16),
+    // Opcode: 0xC2
+    MonitorEnter,
+    // Opcode: 0xC3
+    MonitorExit,
+    // Opcode: 0xC5
+    MultiANewArray {
+        index: u16,
+        dimensions: u8,
+    },
+    // Opcode: 0xC6
+    IfNull(i16),
+    // Opcode: 0xC7
+    IfNonNull(i16),
+    // Opcode: 0xC8
+    GotoW(i32),
+    // Opcode: 0xC9
+    JsrW(i32),
+}
+
+impl Instruction {
+    // The offsets the instruction may branch to, for an instruction at `offset` in the code array; the
+    // default of a switch comes first. Branch offsets are signed and relative to the instruction, two bytes
+    // wide except for those of goto_w, jsr_w and the switches; a target may lie outside the code array.
+    pub fn branch_targets(&self, offset: usize) -> Vec<i64> {
+        let offset = offset as i64;
+
+        match self {
+            Self::IfEq(delta)
+            | Self::IfNe(delta)
+            | Self::IfLt(delta)
+            | Self::IfGe(delta)
+            | Self::IfGt(delta)
+            | Self::IfLe(delta)
+            | Self::IfICmpEq(delta)
+            | Self::IfICmpNe(delta)
+            | Self::IfICmpLt(delta)
+            | Self::IfICmpGe(delta)
+            | Self::IfICmpGt(delta)
+            | Self::IfICmpLe(delta)
+            | Self::IfACmpEq(delta)
+            | Self::IfACmpNe(delta)
+            | Self::Goto(delta)
+            | Self::Jsr(delta)
+            | Self::IfNull(delta)
+            | Self::IfNonNull(delta) => vec![offset + *delta as i64],
+            Self::GotoW(delta) | Self::JsrW(delta) => vec![offset + *delta as i64],
+            Self::TableSwitch {
+                default, targets, ..
+            } => [default]
+                .into_iter()
+                .chain(targets)
+                .map(|delta| offset + *delta as i64)
+                .collect(),
+            Self::LookupSwitch { default, pairs } => [default]
+                .into_iter()
+                .chain(pairs.iter().map(|(_, delta)| delta))
+                .map(|delta| offset + *delta as i64)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // the Class constant pool index operand of instructions creating or testing against a class or array type
+    pub fn class_index(&self) -> Option<u16> {
+        match *self {
+            Self::ANewArray(index)
+            | Self::CheckCast(index)
+            | Self::InstanceOf(index)
+            | Self::New(index)
+            | Self::MultiANewArray { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    // The encoded length of the instruction at `offset` in the code array, which the padding before the
+    // operands of a switch depends on. Local variable instructions are assumed to use their `wide` form only
+    // when the operands need it, as the decoded instruction does not say which form was read.
+    pub fn length(&self, offset: usize) -> usize {
+        let padding = 3 - offset % 4;
+
+        match self {
+            Self::TableSwitch { targets, .. } => 1 + padding + 12 + targets.len() * 4,
+            Self::LookupSwitch { pairs, .. } => 1 + padding + 8 + pairs.len() * 8,
+            Self::ILoad(index)
+            | Self::LLoad(index)
+            | Self::FLoad(index)
+            | Self::DLoad(index)
+            | Self::ALoad(index)
+            | Self::IStore(index)
+            | Self::LStore(index)
+            | Self::FStore(index)
+            | Self::DStore(index)
+            | Self::AStore(index)
+            | Self::Ret(index) => {
+                if u8::try_from(*index).is_ok() {
+                    2
+                } else {
+                    4
+                }
+            }
+            Self::IInc { index, delta } => {
+                if u8::try_from(*index).is_ok() && i8::try_from(*delta).is_ok() {
+                    3
+                } else {
+                    6
+                }
+            }
+            Self::BiPush(_) | Self::Ldc(_) | Self::NewArray(_) => 2,
+            Self::SiPush(_)
+            | Self::LdcW(_)
+            | Self::Ldc2W(_)
+            | Self::IfEq(_)
+            | Self::IfNe(_)
+            | Self::IfLt(_)
+            | Self::IfGe(_)
+            | Self::IfGt(_)
+            | Self::IfLe(_)
+            | Self::IfICmpEq(_)
+            | Self::IfICmpNe(_)
+            | Self::IfICmpLt(_)
+            | Self::IfICmpGe(_)
+            | Self::IfICmpGt(_)
+            | Self::IfICmpLe(_)
+            | Self::IfACmpEq(_)
+            | Self::IfACmpNe(_)
+            | Self::Goto(_)
+            | Self::Jsr(_)
+            | Self::GetStatic(_)
+            | Self::PutStatic(_)
+            | Self::GetField(_)
+            | Self::PutField(_)
+            | Self::InvokeVirtual(_)
+            | Self::InvokeSpecial(_)
+            | Self::InvokeStatic(_)
+            | Self::New(_)
+            | Self::ANewArray(_)
+            | Self::CheckCast(_)
+            | Self::InstanceOf(_)
+            | Self::IfNull(_)
+            | Self::IfNonNull(_) => 3,
+            Self::MultiANewArray { .. } => 4,
+            Self::InvokeInterface { .. }
+            | Self::InvokeDynamic(_)
+            | Self::GotoW(_)
+            | Self::JsrW(_) => 5,
+            _ => 1,
+        }
+    }
+
+    // the mnemonic of the instruction as the JVMS writes it, without telling wide forms apart
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Nop => "nop",
+            Self::AConstNull => "aconst_null",
+            Self::IConstM1 => "iconst_m1",
+            Self::IConst0 => "iconst_0",
+            Self::IConst1 => "iconst_1",
+            Self::IConst2 => "iconst_2",
+            Self::IConst3 => "iconst_3",
+            Self::IConst4 => "iconst_4",
+            Self::IConst5 => "iconst_5",
+            Self::LConst0 => "lconst_0",
+            Self::LConst1 => "lconst_1",
+            Self::FConst0 => "fconst_0",
+            Self::FConst1 => "fconst_1",
+            Self::FConst2 => "fconst_2",
+            Self::DConst0 => "dconst_0",
+            Self::DConst1 => "dconst_1",
+            Self::BiPush(_) => "bipush",
+            Self::SiPush(_) => "sipush",
+            Self::Ldc(_) => "ldc",
+            Self::LdcW(_) => "ldc_w",
+            Self::Ldc2W(_) => "ldc2_w",
+            Self::ILoad(_) => "iload",
+            Self::LLoad(_) => "lload",
+            Self::FLoad(_) => "fload",
+            Self::DLoad(_) => "dload",
+            Self::ALoad(_) => "aload",
+            Self::ILoad0 => "iload_0",
+            Self::ILoad1 => "iload_1",
+            Self::ILoad2 => "iload_2",
+            Self::ILoad3 => "iload_3",
+            Self::LLoad0 => "lload_0",
+            Self::LLoad1 => "lload_1",
+            Self::LLoad2 => "lload_2",
+            Self::LLoad3 => "lload_3",
+            Self::FLoad0 => "fload_0",
+            Self::FLoad1 => "fload_1",
+            Self::FLoad2 => "fload_2",
+            Self::FLoad3 => "fload_3",
+            Self::DLoad0 => "dload_0",
+            Self::DLoad1 => "dload_1",
+            Self::DLoad2 => "dload_2",
+            Self::DLoad3 => "dload_3",
+            Self::ALoad0 => "aload_0",
+            Self::ALoad1 => "aload_1",
+            Self::ALoad2 => "aload_2",
+            Self::ALoad3 => "aload_3",
+            Self::IALoad => "iaload",
+            Self::LALoad => "laload",
+            Self::FALoad => "faload",
+            Self::DALoad => "daload",
+            Self::AALoad => "aaload",
+            Self::BALoad => "baload",
+            Self::CALoad => "caload",
+            Self::SALoad => "saload",
+            Self::IStore(_) => "istore",
+            Self::LStore(_) => "lstore",
+            Self::FStore(_) => "fstore",
+            Self::DStore(_) => "dstore",
+            Self::AStore(_) => "astore",
+            Self::IStore0 => "istore_0",
+            Self::IStore1 => "istore_1",
+            Self::IStore2 => "istore_2",
+            Self::IStore3 => "istore_3",
+            Self::LStore0 => "lstore_0",
+            Self::LStore1 => "lstore_1",
+            Self::LStore2 => "lstore_2",
+            Self::LStore3 => "lstore_3",
+            Self::FStore0 => "fstore_0",
+            Self::FStore1 => "fstore_1",
+            Self::FStore2 => "fstore_2",
+            Self::FStore3 => "fstore_3",
+            Self::DStore0 => "dstore_0",
+            Self::DStore1 => "dstore_1",
+            Self::DStore2 => "dstore_2",
+            Self::DStore3 => "dstore_3",
+            Self::AStore0 => "astore_0",
+            Self::AStore1 => "astore_1",
+            Self::AStore2 => "astore_2",
+            Self::AStore3 => "astore_3",
+            Self::IAStore => "iastore",
+            Self::LAStore => "lastore",
+            Self::FAStore => "fastore",
+            Self::DAStore => "dastore",
+            Self::AAStore => "aastore",
+            Self::BAStore => "bastore",
+            Self::CAStore => "castore",
+            Self::SAStore => "sastore",
+            Self::Pop => "pop",
+            Self::Pop2 => "pop2",
+            Self::Dup => "dup",
+            Self::DupX1 => "dup_x1",
+            Self::DupX2 => "dup_x2",
+            Self::Dup2 => "dup2",
+            Self::Dup2X1 => "dup2_x1",
+            Self::Dup2X2 => "dup2_x2",
+            Self::Swap => "swap",
+            Self::IAdd => "iadd",
+            Self::LAdd => "ladd",
+            Self::FAdd => "fadd",
+            Self::DAdd => "dadd",
+            Self::ISub => "isub",
+            Self::LSub => "lsub",
+            Self::FSub => "fsub",
+            Self::DSub => "dsub",
+            Self::IMul => "imul",
+            Self::LMul => "lmul",
+            Self::FMul => "fmul",
+            Self::DMul => "dmul",
+            Self::IDiv => "idiv",
+            Self::LDiv => "ldiv",
+            Self::FDiv => "fdiv",
+            Self::DDiv => "ddiv",
+            Self::IRem => "irem",
+            Self::LRem => "lrem",
+            Self::FRem => "frem",
+            Self::DRem => "drem",
+            Self::INeg => "ineg",
+            Self::LNeg => "lneg",
+            Self::FNeg => "fneg",
+            Self::DNeg => "dneg",
+            Self::IShl => "ishl",
+            Self::LShl => "lshl",
+            Self::IShr => "ishr",
+            Self::LShr => "lshr",
+            Self::IUShr => "iushr",
+            Self::LUShr => "lushr",
+            Self::IAnd => "iand",
+            Self::LAnd => "land",
+            Self::IOr => "ior",
+            Self::LOr => "lor",
+            Self::IXor => "ixor",
+            Self::LXor => "lxor",
+            Self::IInc { .. } => "iinc",
+            Self::I2L => "i2l",
+            Self::I2F => "i2f",
+            Self::I2D => "i2d",
+            Self::L2I => "l2i",
+            Self::L2F => "l2f",
+            Self::L2D => "l2d",
+            Self::F2I => "f2i",
+            Self::F2L => "f2l",
+            Self::F2D => "f2d",
+            Self::D2I => "d2i",
+            Self::D2L => "d2l",
+            Self::D2F => "d2f",
+            Self::I2B => "i2b",
+            Self::I2C => "i2c",
+            Self::I2S => "i2s",
+            Self::LCmp => "lcmp",
+            Self::FCmpL => "fcmpl",
+            Self::FCmpG => "fcmpg",
+            Self::DCmpL => "dcmpl",
+            Self::DCmpG => "dcmpg",
+            Self::IfEq(_) => "ifeq",
+            Self::IfNe(_) => "ifne",
+            Self::IfLt(_) => "iflt",
+            Self::IfGe(_) => "ifge",
+            Self::IfGt(_) => "ifgt",
+            Self::IfLe(_) => "ifle",
+            Self::IfICmpEq(_) => "if_icmpeq",
+            Self::IfICmpNe(_) => "if_icmpne",
+            Self::IfICmpLt(_) => "if_icmplt",
+            Self::IfICmpGe(_) => "if_icmpge",
+            Self::IfICmpGt(_) => "if_icmpgt",
+            Self::IfICmpLe(_) => "if_icmple",
+            Self::IfACmpEq(_) => "if_acmpeq",
+            Self::IfACmpNe(_) => "if_acmpne",
+            Self::Goto(_) => "goto",
+            Self::Jsr(_) => "jsr",
+            Self::Ret(_) => "ret",
+            Self::TableSwitch { .. } => "tableswitch",
+            Self::LookupSwitch { .. } => "lookupswitch",
+            Self::IReturn => "ireturn",
+            Self::LReturn => "lreturn",
+            Self::FReturn => "freturn",
+            Self::DReturn => "dreturn",
+            Self::AReturn => "areturn",
+            Self::Return => "return",
+            Self::GetStatic(_) => "getstatic",
+            Self::PutStatic(_) => "putstatic",
+            Self::GetField(_) => "getfield",
+            Self::PutField(_) => "putfield",
+            Self::InvokeVirtual(_) => "invokevirtual",
+            Self::InvokeSpecial(_) => "invokespecial",
+            Self::InvokeStatic(_) => "invokestatic",
+            Self::InvokeInterface { .. } => "invokeinterface",
+            Self::InvokeDynamic(_) => "invokedynamic",
+            Self::New(_) => "new",
+            Self::NewArray(_) => "newarray",
+            Self::ANewArray(_) => "anewarray",
+            Self::ArrayLength => "arraylength",
+            Self::AThrow => "athrow",
+            Self::CheckCast(_) => "checkcast",
+            Self::InstanceOf(_) => "instanceof",
+            Self::MonitorEnter => "monitorenter",
+            Self::MonitorExit => "monitorexit",
+            Self::MultiANewArray { .. } => "multianewarray",
+            Self::IfNull(_) => "ifnull",
+            Self::IfNonNull(_) => "ifnonnull",
+            Self::GotoW(_) => "goto_w",
+            Self::JsrW(_) => "jsr_w",
+        }
+    }
+}
+
+// The offset of every instruction, in ascending order, found from the opcodes alone without decoding the
+// operands. `code` may start partway into a code array, at `code_start_offset`, which the returned offsets
+// are relative to as well; the padding before the operands of a switch aligns them with the start of the
+// whole code array, not of the slice.
+pub fn instruction_boundaries(
+    code: &[u8],
+    code_start_offset: usize,
+) -> Result<Vec<usize>, InstructionError> {
+    let mut boundaries = Vec::new();
+    let mut position = 0;
+
+    while position < code.len() {
+        let offset = code_start_offset + position;
+        let length = encoded_length(&code[position..], offset)?;
+        if length > code.len() - position {
+            return Err(InstructionError::TruncatedInstruction { offset });
+        }
+
+        boundaries.push(offset);
+        position += length;
+    }
+
+    Ok(boundaries)
+}
+
+// decodes a complete code array, pairing every instruction with its offset from the start of the array
+pub fn instructions_from_bytes(code: &[u8]) -> IResult<&[u8], Vec<(usize, Instruction)>> {
+    let mut instructions = Vec::new();
+    let mut input = code;
+
+    while !input.is_empty() {
+        let offset = code.len() - input.len();
+        let (input_1, instruction) = instruction_from_bytes(input, offset)?;
+
+        instructions.push((offset, instruction));
+        input = input_1;
+    }
+
+    Ok((input, instructions))
+}
+
+// the length of the instruction at the start of `bytes`, which is at `offset` in the code array
+fn encoded_length(bytes: &[u8], offset: usize) -> Result<usize, InstructionError> {
+    let read_i32 = |position: usize| {
+        bytes
+            .get(position..position + 4)
+            .map(|operand| {
+                i32::from_be_bytes([operand[0], operand[1], operand[2], operand[3]]) as i64
+            })
+            .ok_or(InstructionError::TruncatedInstruction { offset })
+    };
+
+    match bytes[0] {
+        // tableswitch, lookupswitch
+        opcode @ (0xAA | 0xAB) => {
+            // the operands are aligned to a multiple of four bytes from the start of the code array
+            let operands = 1 + (3 - offset % 4);
+            let length = if opcode == 0xAA {
+                let cases = read_i32(operands + 8)? - read_i32(operands + 4)? + 1;
+                usize::try_from(cases).map(|cases| operands + 12 + cases * 4)
+            } else {
+                let pairs = read_i32(operands + 4)?;
+                usize::try_from(pairs).map(|pairs| operands + 8 + pairs * 8)
+            };
+
+            length.map_err(|_| InstructionError::MalformedSwitch { offset })
+        }
+        // wide
+        0xC4 => match bytes.get(1) {
+            Some(0x84) => Ok(6),
+            Some(0x15..=0x19 | 0x36..=0x3A | 0xA9) => Ok(4),
+            Some(&opcode) => Err(InstructionError::UnknownOpcode {
+                offset: offset + 1,
+                opcode,
+            }),
+            None => Err(InstructionError::TruncatedInstruction { offset }),
+        },
+        opcode => opcode_length(opcode).ok_or(InstructionError::UnknownOpcode { offset, opcode }),
+    }
+}
+
+fn instruction_from_bytes(bytes: &[u8], offset: usize) -> IResult<&[u8], Instruction> {
+    let (input, opcode) = be_u8(bytes)?;
+
+    Ok(match opcode {
+        0x00 => (input, Instruction::Nop),
+        0x01 => (input, Instruction::AConstNull),
+        0x02 => (input, Instruction::IConstM1),
+        0x03 => (input, Instruction::IConst0),
+        0x04 => (input, Instruction::IConst1),
+        0x05 => (input, Instruction::IConst2),
+        0x06 => (input, Instruction::IConst3),
+        0x07 => (input, Instruction::IConst4),
+        0x08 => (input, Instruction::IConst5),
+        0x09 => (input, Instruction::LConst0),
+        0x0A => (input, Instruction::LConst1),
+        0x0B => (input, Instruction::FConst0),
+        0x0C => (input, Instruction::FConst1),
+        0x0D => (input, Instruction::FConst2),
+        0x0E => (input, Instruction::DConst0),
+        0x0F => (input, Instruction::DConst1),
+        0x10 => map(be_i8, Instruction::BiPush)(input)?,
+        0x11 => map(be_i16, Instruction::SiPush)(input)?,
+        0x12 => map(be_u8, Instruction::Ldc)(input)?,
+        0x13 => map(be_u16, Instruction::LdcW)(input)?,
+        0x14 => map(be_u16, Instruction::Ldc2W)(input)?,
+        0x15 => map(be_u8, |index| Instruction::ILoad(index.into()))(input)?,
+        0x16 => map(be_u8, |index| Instruction::LLoad(index.into()))(input)?,
+        0x17 => map(be_u8, |index| Instruction::FLoad(index.into()))(input)?,
+        0x18 => map(be_u8, |index| Instruction::DLoad(index.into()))(input)?,
+        0x19 => map(be_u8, |index| Instruction::ALoad(index.into()))(input)?,
+        0x1A => (input, Instruction::ILoad0),
+        0x1B => (input, Instruction::ILoad1),
+        0x1C => (input, Instruction::ILoad2),
+        0x1D => (input, Instruction::ILoad3),
+        0x1E => (input, Instruction::LLoad0),
+        0x1F => (input, Instruction::LLoad1),
+        0x20 => (input, Instruction::LLoad2),
+        0x21 => (input, Instruction::LLoad3),
+        0x22 => (input, Instruction::FLoad0),
+        0x23 => (input, Instruction::FLoad1),
+        0x24 => (input, Instruction::FLoad2),
+        0x25 => (input, Instruction::FLoad3),
+        0x26 => (input, Instruction::DLoad0),
+        0x27 => (input, Instruction::DLoad1),
+        0x28 => (input, Instruction::DLoad2),
+        0x29 => (input, Instruction::DLoad3),
+        0x2A => (input, Instruction::ALoad0),
+        0x2B => (input, Instruction::ALoad1),
+        0x2C => (input, Instruction::ALoad2),
+        0x2D => (input, Instruction::ALoad3),
+        0x2E => (input, Instruction::IALoad),
+        0x2F => (input, Instruction::LALoad),
+        0x30 => (input, Instruction::FALoad),
+        0x31 => (input, Instruction::DALoad),
+        0x32 => (input, Instruction::AALoad),
+        0x33 => (input, Instruction::BALoad),
+        0x34 => (input, Instruction::CALoad),
+        0x35 => (input, Instruction::SALoad),
+        0x36 => map(be_u8, |index| Instruction::IStore(index.into()))(input)?,
+        0x37 => map(be_u8, |index| Instruction::LStore(index.into()))(input)?,
+        0x38 => map(be_u8, |index| Instruction::FStore(index.into()))(input)?,
+        0x39 => map(be_u8, |index| Instruction::DStore(index.into()))(input)?,
+        0x3A => map(be_u8, |index| Instruction::AStore(index.into()))(input)?,
+        0x3B => (input, Instruction::IStore0),
+        0x3C => (input, Instruction::IStore1),
+        0x3D => (input, Instruction::IStore2),
+        0x3E => (input, Instruction::IStore3),
+        0x3F => (input, Instruction::LStore0),
+        0x40 => (input, Instruction::LStore1),
+        0x41 => (input, Instruction::LStore2),
+        0x42 => (input, Instruction::LStore3),
+        0x43 => (input, Instruction::FStore0),
+        0x44 => (input, Instruction::FStore1),
+        0x45 => (input, Instruction::FStore2),
+        0x46 => (input, Instruction::FStore3),
+        0x47 => (input, Instruction::DStore0),
+        0x48 => (input, Instruction::DStore1),
+        0x49 => (input, Instruction::DStore2),
+        0x4A => (input, Instruction::DStore3),
+        0x4B => (input, Instruction::AStore0),
+        0x4C => (input, Instruction::AStore1),
+        0x4D => (input, Instruction::AStore2),
+        0x4E => (input, Instruction::AStore3),
+        0x4F => (input, Instruction::IAStore),
+        0x50 => (input, Instruction::LAStore),
+        0x51 => (input, Instruction::FAStore),
+        0x52 => (input, Instruction::DAStore),
+        0x53 => (input, Instruction::AAStore),
+        0x54 => (input, Instruction::BAStore),
+        0x55 => (input, Instruction::CAStore),
+        0x56 => (input, Instruction::SAStore),
+        0x57 => (input, Instruction::Pop),
+        0x58 => (input, Instruction::Pop2),
+        0x59 => (input, Instruction::Dup),
+        0x5A => (input, Instruction::DupX1),
+        0x5B => (input, Instruction::DupX2),
+        0x5C => (input, Instruction::Dup2),
+        0x5D => (input, Instruction::Dup2X1),
+        0x5E => (input, Instruction::Dup2X2),
+        0x5F => (input, Instruction::Swap),
+        0x60 => (input, Instruction::IAdd),
+        0x61 => (input, Instruction::LAdd),
+        0x62 => (input, Instruction::FAdd),
+        0x63 => (input, Instruction::DAdd),
+        0x64 => (input, Instruction::ISub),
+        0x65 => (input, Instruction::LSub),
+        0x66 => (input, Instruction::FSub),
+        0x67 => (input, Instruction::DSub),
+        0x68 => (input, Instruction::IMul),
+        0x69 => (input, Instruction::LMul),
+        0x6A => (input, Instruction::FMul),
+        0x6B => (input, Instruction::DMul),
+        0x6C => (input, Instruction::IDiv),
+        0x6D => (input, Instruction::LDiv),
+        0x6E => (input, Instruction::FDiv),
+        0x6F => (input, Instruction::DDiv),
+        0x70 => (input, Instruction::IRem),
+        0x71 => (input, Instruction::LRem),
+        0x72 => (input, Instruction::FRem),
+        0x73 => (input, Instruction::DRem),
+        0x74 => (input, Instruction::INeg),
+        0x75 => (input, Instruction::LNeg),
+        0x76 => (input, Instruction::FNeg),
+        0x77 => (input, Instruction::DNeg),
+        0x78 => (input, Instruction::IShl),
+        0x79 => (input, Instruction::LShl),
+        0x7A => (input, Instruction::IShr),
+        0x7B => (input, Instruction::LShr),
+        0x7C => (input, Instruction::IUShr),
+        0x7D => (input, Instruction::LUShr),
+        0x7E => (input, Instruction::IAnd),
+        0x7F => (input, Instruction::LAnd),
+        0x80 => (input, Instruction::IOr),
+        0x81 => (input, Instruction::LOr),
+        0x82 => (input, Instruction::IXor),
+        0x83 => (input, Instruction::LXor),
+        0x84 => {
+            let (input_1, index) = be_u8(input)?;
+            let (input_2, delta) = be_i8(input_1)?;
+
+            (
+                input_2,
+                Instruction::IInc {
+                    index: index.into(),
+                    delta: delta.into(),
+                },
+            )
+        }
+        0x85 => (input, Instruction::I2L),
+        0x86 => (input, Instruction::I2F),
+        0x87 => (input, Instruction::I2D),
+        0x88 => (input, Instruction::L2I),
+        0x89 => (input, Instruction::L2F),
+        0x8A => (input, Instruction::L2D),
+        0x8B => (input, Instruction::F2I),
+        0x8C => (input, Instruction::F2L),
+        0x8D => (input, Instruction::F2D),
+        0x8E => (input, Instruction::D2I),
+        0x8F => (input, Instruction::D2L),
+        0x90 => (input, Instruction::D2F),
+        0x91 => (input, Instruction::I2B),
+        0x92 => (input, Instruction::I2C),
+        0x93 => (input, Instruction::I2S),
+        0x94 => (input, Instruction::LCmp),
+        0x95 => (input, Instruction::FCmpL),
+        0x96 => (input, Instruction::FCmpG),
+        0x97 => (input, Instruction::DCmpL),
+        0x98 => (input, Instruction::DCmpG),
+        0x99 => map(be_i16, Instruction::IfEq)(input)?,
+        0x9A => map(be_i16, Instruction::IfNe)(input)?,
+        0x9B => map(be_i16, Instruction::IfLt)(input)?,
+        0x9C => map(be_i16, Instruction::IfGe)(input)?,
+        0x9D => map(be_i16, Instruction::IfGt)(input)?,
+        0x9E => map(be_i16, Instruction::IfLe)(input)?,
+        0x9F => map(be_i16, Instruction::IfICmpEq)(input)?,
+        0xA0 => map(be_i16, Instruction::IfICmpNe)(input)?,
+        0xA1 => map(be_i16, Instruction::IfICmpLt)(input)?,
+        0xA2 => map(be_i16, Instruction::IfICmpGe)(input)?,
+        0xA3 => map(be_i16, Instruction::IfICmpGt)(input)?,
+        0xA4 => map(be_i16, Instruction::IfICmpLe)(input)?,
+        0xA5 => map(be_i16, Instruction::IfACmpEq)(input)?,
+        0xA6 => map(be_i16, Instruction::IfACmpNe)(input)?,
+        0xA7 => map(be_i16, Instruction::Goto)(input)?,
+        0xA8 => map(be_i16, Instruction::Jsr)(input)?,
+        0xA9 => map(be_u8, |index| Instruction::Ret(index.into()))(input)?,
+        0xAA => table_switch_from_bytes(input, offset)?,
+        0xAB => lookup_switch_from_bytes(input, offset)?,
+        0xAC => (input, Instruction::IReturn),
+        0xAD => (input, Instruction::LReturn),
+        0xAE => (input, Instruction::FReturn),
+        0xAF => (input, Instruction::DReturn),
+        0xB0 => (input, Instruction::AReturn),
+        0xB1 => (input, Instruction::Return),
+        0xB2 => map(be_u16, Instruction::GetStatic)(input)?,
+        0xB3 => map(be_u16, Instruction::PutStatic)(input)?,
+        0xB4 => map(be_u16, Instruction::GetField)(input)?,
+        0xB5 => map(be_u16, Instruction::PutField)(input)?,
+        0xB6 => map(be_u16, Instruction::InvokeVirtual)(input)?,
+        0xB7 => map(be_u16, Instruction::InvokeSpecial)(input)?,
+        0xB8 => map(be_u16, Instruction::InvokeStatic)(input)?,
+        0xB9 => {
+            let (input_1, index) = be_u16(input)?;
+            let (input_2, count) = be_u8(input_1)?;
+            // a reserved byte which must be zero
+            let (input_3, _) = be_u8(input_2)?;
+
+            (input_3, Instruction::InvokeInterface { index, count })
+        }
+        0xBA => {
+            let (input_1, index) = be_u16(input)?;
+            // two reserved bytes which must be zero
+            let (input_2, _) = take(2usize)(input_1)?;
+
+            (input_2, Instruction::InvokeDynamic(index))
+        }
+        0xBB => map(be_u16, Instruction::New)(input)?,
+        0xBC => map(be_u8, Instruction::NewArray)(input)?,
+        0xBD => map(be_u16, Instruction::ANewArray)(input)?,
+        0xBE => (input, Instruction::ArrayLength),
+        0xBF => (input, Instruction::AThrow),
+        0xC0 => map(be_u16, Instruction::CheckCast)(input)?,
+        0xC1 => map(be_u16, Instruction::InstanceOf)(input)?,
+        0xC2 => (input, Instruction::MonitorEnter),
+        0xC3 => (input, Instruction::MonitorExit),
+        0xC4 => wide_instruction_from_bytes(input)?,
+        0xC5 => {
+            let (input_1, index) = be_u16(input)?;
+            let (input_2, dimensions) = be_u8(input_1)?;
+
+            (input_2, Instruction::MultiANewArray { index, dimensions })
+        }
+        0xC6 => map(be_i16, Instruction::IfNull)(input)?,
+        0xC7 => map(be_i16, Instruction::IfNonNull)(input)?,
+        0xC8 => map(be_i32, Instruction::GotoW)(input)?,
+        0xC9 => map(be_i32, Instruction::JsrW)(input)?,
+        _ => return Err(Err::Failure(Error::new(bytes, ErrorKind::Tag))),
+    })
+}
+
+fn lookup_switch_from_bytes(bytes: &[u8], offset: usize) -> IResult<&[u8], Instruction> {
+    // the operands are aligned to a multiple of four bytes from the start of the code array
+    let (input_1, _) = take(3 - offset % 4)(bytes)?;
+    let (input_2, default) = be_i32(input_1)?;
+    let (input_3, npairs) = be_i32(input_2)?;
+
+    let Ok(npairs) = usize::try_from(npairs) else {
+        return Err(Err::Failure(Error::new(input_2, ErrorKind::Verify)));
+    };
+    let (input_4, pairs) = count(pair(be_i32, be_i32), npairs)(input_3)?;
+
+    // the matches have to be in increasing order, so that the JVM may search them
+    if pairs.windows(2).any(|window| window[0].0 >= window[1].0) {
+        return Err(Err::Failure(Error::new(input_3, ErrorKind::Verify)));
+    }
+
+    Ok((input_4, Instruction::LookupSwitch { default, pairs }))
+}
+
+// lengths of the fixed-size instructions, including the opcode byte
+fn opcode_length(opcode: u8) -> Option<usize> {
+    Some(match opcode {
+        0x00..=0x0F => 1,
+        0x10 => 2,
+        0x11 => 3,
+        0x12 => 2,
+        0x13 | 0x14 => 3,
+        0x15..=0x19 => 2,
+        0x1A..=0x35 => 1,
+        0x36..=0x3A => 2,
+        0x3B..=0x83 => 1,
+        0x84 => 3,
+        0x85..=0x98 => 1,
+        0x99..=0xA8 => 3,
+        0xA9 => 2,
+        0xAC..=0xB1 => 1,
+        0xB2..=0xB8 => 3,
+        0xB9 | 0xBA => 5,
+        0xBB => 3,
+        0xBC => 2,
+        0xBD => 3,
+        0xBE | 0xBF => 1,
+        0xC0 | 0xC1 => 3,
+        0xC2 | 0xC3 => 1,
+        0xC5 => 4,
+        0xC6 | 0xC7 => 3,
+        0xC8 | 0xC9 => 5,
+        _ => return None,
+    })
+}
+
+fn table_switch_from_bytes(bytes: &[u8], offset: usize) -> IResult<&[u8], Instruction> {
+    // the operands are aligned to a multiple of four bytes from the start of the code array
+    let (input_1, _) = take(3 - offset % 4)(bytes)?;
+    let (input_2, default) = be_i32(input_1)?;
+    let (input_3, low) = be_i32(input_2)?;
+    let (input_4, high) = be_i32(input_3)?;
+
+    if low > high {
+        return Err(Err::Failure(Error::new(input_2, ErrorKind::Verify)));
+    }
+    let (input_5, targets) = count(be_i32, (high as i64 - low as i64 + 1) as usize)(input_4)?;
+
+    Ok((
+        input_5,
+        Instruction::TableSwitch {
+            default,
+            low,
+            high,
+            targets,
+        },
+    ))
+}
+
+fn wide_instruction_from_bytes(bytes: &[u8]) -> IResult<&[u8], Instruction> {
+    let (input_1, opcode) = be_u8(bytes)?;
+    let (input_2, index) = be_u16(input_1)?;
+
+    Ok(match opcode {
+        0x15 => (input_2, Instruction::ILoad(index)),
+        0x16 => (input_2, Instruction::LLoad(index)),
+        0x17 => (input_2, Instruction::FLoad(index)),
+        0x18 => (input_2, Instruction::DLoad(index)),
+        0x19 => (input_2, Instruction::ALoad(index)),
+        0x36 => (input_2, Instruction::IStore(index)),
+        0x37 => (input_2, Instruction::LStore(index)),
+        0x38 => (input_2, Instruction::FStore(index)),
+        0x39 => (input_2, Instruction::DStore(index)),
+        0x3A => (input_2, Instruction::AStore(index)),
+        0xA9 => (input_2, Instruction::Ret(index)),
+        0x84 => {
+            let (input_3, delta) = be_i16(input_2)?;
+
+            (input_3, Instruction::IInc { index, delta })
+        }
+        _ => return Err(Err::Failure(Error::new(bytes, ErrorKind::Tag))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::instructions_from_bytes;
+    use super::Instruction;
+
+    #[test]
+    fn iinc_delta_is_signed() {
+        // while (i > 0) i--;
+        let (_, instructions) = instructions_from_bytes(&[
+            0x1A, 0x9E, 0x00, 0x09, 0x84, 0x00, 0xFF, 0xA7, 0xFF, 0xF9, 0xB1,
+        ])
+        .unwrap();
+        assert_eq!(
+            instructions,
+            [
+                (0, Instruction::ILoad0),
+                (1, Instruction::IfLe(9)),
+                (
+                    4,
+                    Instruction::IInc {
+                        index: 0,
+                        delta: -1
+                    }
+                ),
+                (7, Instruction::Goto(-7)),
+                (10, Instruction::Return),
+            ]
+        );
+
+        // the same loop with the wide form, as used for locals past 255 or increments past a byte
+        let (_, instructions) = instructions_from_bytes(&[
+            0x1A, 0x9E, 0x00, 0x0C, 0xC4, 0x84, 0x00, 0x00, 0xFF, 0xFF, 0xA7, 0xFF, 0xF6, 0xB1,
+        ])
+        .unwrap();
+        assert_eq!(
+            instructions,
+            [
+                (0, Instruction::ILoad0),
+                (1, Instruction::IfLe(12)),
+                (
+                    4,
+                    Instruction::IInc {
+                        index: 0,
+                        delta: -1
+                    }
+                ),
+                (10, Instruction::Goto(-10)),
+                (13, Instruction::Return),
+            ]
+        );
+    }
+
+    #[test]
+    fn switch_operands_align_to_code_start() {
+        let mut code = vec![0x1A, 0xAA, 0x00, 0x00];
+        for operand in [30, 0, 1, 20, 25] {
+            code.extend_from_slice(&i32::to_be_bytes(operand));
+        }
+        code.extend_from_slice(&[0x1A, 0x00, 0xAB, 0x00]);
+        for operand in [10, 2, -1, 5, 7, 9] {
+            code.extend_from_slice(&i32::to_be_bytes(operand));
+        }
+        code.push(0xB1);
+
+        // the tableswitch at offset 1 is followed by two bytes of padding, the lookupswitch at 26 by one
+        let (_, instructions) = instructions_from_bytes(&code).unwrap();
+        assert_eq!(
+            instructions,
+            [
+                (0, Instruction::ILoad0),
+                (
+                    1,
+                    Instruction::TableSwitch {
+                        default: 30,
+                        low: 0,
+                        high: 1,
+                        targets: vec![20, 25],
+                    }
+                ),
+                (24, Instruction::ILoad0),
+                (25, Instruction::Nop),
+                (
+                    26,
+                    Instruction::LookupSwitch {
+                        default: 10,
+                        pairs: vec![(-1, 5), (7, 9)],
+                    }
+                ),
+                (52, Instruction::Return),
+            ]
+        );
+        assert_eq!(instructions[1].1.length(1), 23);
+        assert_eq!(instructions[4].1.length(26), 26);
+    }
+
+    #[test]
+    fn multianewarray_reads_its_dimensions() {
+        // int[][] grid = new int[2][3];
+        let (_, instructions) =
+            instructions_from_bytes(&[0x05, 0x06, 0xC5, 0x00, 0x07, 0x02, 0x4B, 0xB1]).unwrap();
+        assert_eq!(
+            instructions,
+            [
+                (0, Instruction::IConst2),
+                (1, Instruction::IConst3),
+                (
+                    2,
+                    Instruction::MultiANewArray {
+                        index: 7,
+                        dimensions: 2,
+                    }
+                ),
+                (6, Instruction::AStore0),
+                (7, Instruction::Return),
+            ]
+        );
+        assert_eq!(instructions[2].1.length(2), 4);
+    }
+
+    #[test]
+    fn wide_jumps_read_four_byte_deltas() {
+        // a jsr_w over 40000 nops to the return, and a goto_w back to the start, further than a goto reaches
+        let mut code = vec![0xC9];
+        code.extend_from_slice(&i32::to_be_bytes(40010));
+        code.resize(40005, 0x00);
+        code.push(0xC8);
+        code.extend_from_slice(&i32::to_be_bytes(-40005));
+        code.push(0xB1);
+
+        let (_, instructions) = instructions_from_bytes(&code).unwrap();
+        assert_eq!(instructions.len(), 40003);
+        assert_eq!(instructions[0], (0, Instruction::JsrW(40010)));
+        assert_eq!(instructions[40001], (40005, Instruction::GotoW(-40005)));
+        assert_eq!(instructions[40002], (40010, Instruction::Return));
+        assert_eq!(instructions[0].1.branch_targets(0), [40010]);
+        assert_eq!(instructions[40001].1.branch_targets(40005), [0]);
+    }
+}
@@ -14,6 +14,29 @@
  * limitations under the License.
  */
 
+pub mod constant_pool;
 pub mod cowext;
+pub mod descriptor;
+pub mod disassemble;
+pub mod downlevel;
+#[cfg(feature = "encoding")]
+pub mod encoding;
+pub mod flow;
+pub mod hierarchy;
+pub mod instruction;
+pub mod modifier;
+pub mod mutf8;
+pub mod nest;
+pub mod owned;
 pub mod parse;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod remap;
+pub mod resolve;
+pub mod signature;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
 pub mod spec;
+pub mod usage;
+pub mod validate;
+pub mod write;
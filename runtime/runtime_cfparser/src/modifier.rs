@@ -0,0 +1,115 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::spec::AccessFlags;
+use crate::spec::FieldAccessFlags;
+use crate::spec::MethodAccessFlags;
+
+// Each function renders the modifiers in the order the JLS grammar lists them, separated by spaces, leaving
+// out the ones a compiler adds implicitly and flags with no source modifier of their own (such as
+// ACC_SYNTHETIC). `in_interface` is whether the member is declared in an interface.
+
+pub fn format_class_modifiers(flags: u16) -> String {
+    let is_interface = flags & AccessFlags::INTERFACE != 0;
+    let is_enum = flags & AccessFlags::ENUM != 0;
+
+    let mut modifiers = Vec::new();
+    if flags & AccessFlags::PUBLIC != 0 {
+        modifiers.push("public");
+    }
+    // interfaces are implicitly abstract, and enums may declare neither modifier
+    if flags & AccessFlags::ABSTRACT != 0 && !is_interface && !is_enum {
+        modifiers.push("abstract");
+    }
+    if flags & AccessFlags::FINAL != 0 && !is_enum {
+        modifiers.push("final");
+    }
+
+    modifiers.join(" ")
+}
+
+pub fn format_field_modifiers(flags: u16, in_interface: bool) -> String {
+    let mut modifiers = Vec::new();
+
+    // interface fields are implicitly public, static and final
+    if !in_interface {
+        if flags & FieldAccessFlags::PUBLIC != 0 {
+            modifiers.push("public");
+        }
+        if flags & FieldAccessFlags::PROTECTED != 0 {
+            modifiers.push("protected");
+        }
+        if flags & FieldAccessFlags::PRIVATE != 0 {
+            modifiers.push("private");
+        }
+        if flags & FieldAccessFlags::STATIC != 0 {
+            modifiers.push("static");
+        }
+        if flags & FieldAccessFlags::FINAL != 0 {
+            modifiers.push("final");
+        }
+    }
+    if flags & FieldAccessFlags::TRANSIENT != 0 {
+        modifiers.push("transient");
+    }
+    if flags & FieldAccessFlags::VOLATILE != 0 {
+        modifiers.push("volatile");
+    }
+
+    modifiers.join(" ")
+}
+
+pub fn format_method_modifiers(flags: u16, in_interface: bool) -> String {
+    let is_abstract = flags & MethodAccessFlags::ABSTRACT != 0;
+    let is_private = flags & MethodAccessFlags::PRIVATE != 0;
+    let is_static = flags & MethodAccessFlags::STATIC != 0;
+
+    let mut modifiers = Vec::new();
+    // interface methods are implicitly public, and implicitly abstract unless they have a body
+    if flags & MethodAccessFlags::PUBLIC != 0 && !in_interface {
+        modifiers.push("public");
+    }
+    if flags & MethodAccessFlags::PROTECTED != 0 {
+        modifiers.push("protected");
+    }
+    if is_private {
+        modifiers.push("private");
+    }
+    if is_abstract && !in_interface {
+        modifiers.push("abstract");
+    }
+    // an interface method with a body that is neither static nor private must have been declared default
+    if in_interface && !is_abstract && !is_static && !is_private {
+        modifiers.push("default");
+    }
+    if is_static {
+        modifiers.push("static");
+    }
+    if flags & MethodAccessFlags::FINAL != 0 {
+        modifiers.push("final");
+    }
+    if flags & MethodAccessFlags::SYNCHRONIZED != 0 {
+        modifiers.push("synchronized");
+    }
+    if flags & MethodAccessFlags::NATIVE != 0 {
+        modifiers.push("native");
+    }
+    if flags & MethodAccessFlags::STRICT != 0 {
+        modifiers.push("strictfp");
+    }
+
+    modifiers.join(" ")
+}
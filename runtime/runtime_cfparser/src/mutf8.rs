@@ -0,0 +1,183 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+
+// Modified UTF-8 differs from UTF-8 in two ways: the null character is encoded in two bytes as 0xC0 0x80,
+// and a supplementary character is encoded as its UTF-16 surrogate pair, taking three bytes per surrogate.
+// Neither form is valid UTF-8, and modified UTF-8 has neither null bytes nor four byte sequences, so input
+// without any of these is the same in both encodings.
+
+pub fn mutf8_to_str(bytes: &[u8]) -> Option<Cow<'_, str>> {
+    if !bytes.iter().any(|&byte| byte == 0x00 || byte >= 0xF0) {
+        if let Ok(string) = std::str::from_utf8(bytes) {
+            return Some(Cow::Borrowed(string));
+        }
+    }
+
+    let mut string = String::with_capacity(bytes.len());
+    let mut input = bytes;
+
+    while let Some((code_unit, input_1)) = code_unit_from_bytes(input) {
+        let code_point = match code_unit {
+            0xD800..=0xDBFF => {
+                let (low_surrogate @ 0xDC00..=0xDFFF, input_2) = code_unit_from_bytes(input_1)?
+                else {
+                    return None;
+                };
+                input = input_2;
+
+                0x10000 + ((code_unit as u32 - 0xD800) << 10) + (low_surrogate as u32 - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return None,
+            _ => {
+                input = input_1;
+
+                code_unit as u32
+            }
+        };
+
+        string.push(char::from_u32(code_point)?);
+    }
+
+    input.is_empty().then_some(Cow::Owned(string))
+}
+
+pub fn str_to_mutf8(string: &str) -> Cow<'_, [u8]> {
+    if !string.bytes().any(|byte| byte == 0x00 || byte >= 0xF0) {
+        return Cow::Borrowed(string.as_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(string.len() + 2);
+    for character in string.chars() {
+        match character {
+            '\0' => bytes.extend_from_slice(&[0xC0, 0x80]),
+            '\u{10000}'.. => {
+                for code_unit in character.encode_utf16(&mut [0; 2]) {
+                    bytes.extend_from_slice(&[
+                        0xE0 | (*code_unit >> 12) as u8,
+                        0x80 | ((*code_unit >> 6) & 0x3F) as u8,
+                        0x80 | (*code_unit & 0x3F) as u8,
+                    ]);
+                }
+            }
+            _ => bytes.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+
+    Cow::Owned(bytes)
+}
+
+// decodes a single one, two or three byte sequence into the UTF-16 code unit it encodes
+fn code_unit_from_bytes(bytes: &[u8]) -> Option<(u16, &[u8])> {
+    let (&first, input) = bytes.split_first()?;
+    let continuation = |index: usize| {
+        input
+            .get(index)
+            .filter(|&&byte| byte & 0xC0 == 0x80)
+            .map(|&byte| (byte & 0x3F) as u16)
+    };
+
+    match first {
+        0x01..=0x7F => Some((first as u16, input)),
+        0xC0..=0xDF => {
+            let code_unit = (((first & 0x1F) as u16) << 6) | continuation(0)?;
+
+            // only the null character may be encoded in two bytes when it would fit in one
+            (code_unit >= 0x80 || code_unit == 0).then_some((code_unit, &input[1..]))
+        }
+        0xE0..=0xEF => {
+            let code_unit =
+                (((first & 0x0F) as u16) << 12) | (continuation(0)? << 6) | continuation(1)?;
+
+            (code_unit >= 0x800).then_some((code_unit, &input[2..]))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::parse::classfile_from_bytes;
+    use crate::resolve::ResolvedClass;
+    use crate::spec::Version;
+    use crate::write::ClassBuilder;
+
+    use super::mutf8_to_str;
+    use super::str_to_mutf8;
+
+    #[test]
+    fn supplementary_characters_take_two_surrogates() {
+        // U+1F600 is the surrogate pair D83D DE00, three bytes each
+        let bytes = [0x61, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80, 0x62];
+
+        assert_eq!(str_to_mutf8("a\u{1F600}b"), &bytes[..]);
+        assert_eq!(mutf8_to_str(&bytes).unwrap(), "a\u{1F600}b");
+    }
+
+    #[test]
+    fn null_characters_take_two_bytes() {
+        let bytes = [0x61, 0xC0, 0x80, 0x62];
+
+        assert_eq!(str_to_mutf8("a\0b"), &bytes[..]);
+        assert_eq!(mutf8_to_str(&bytes).unwrap(), "a\0b");
+        assert!(mutf8_to_str(b"a\0b").is_none());
+    }
+
+    #[test]
+    fn plain_text_is_borrowed() {
+        assert!(matches!(str_to_mutf8("caf\u{E9}"), Cow::Borrowed(_)));
+        assert!(matches!(
+            mutf8_to_str("caf\u{E9}".as_bytes()),
+            Some(Cow::Borrowed("caf\u{E9}"))
+        ));
+    }
+
+    #[test]
+    fn invalid_sequences_are_rejected() {
+        // a high surrogate without its low surrogate, at the end and before another character
+        assert!(mutf8_to_str(&[0xED, 0xA0, 0xBD]).is_none());
+        assert!(mutf8_to_str(&[0xED, 0xA0, 0xBD, 0x61]).is_none());
+        // a low surrogate on its own
+        assert!(mutf8_to_str(&[0xED, 0xB8, 0x80]).is_none());
+        // U+1F600 as standard UTF-8, in four bytes
+        assert!(mutf8_to_str("\u{1F600}".as_bytes()).is_none());
+    }
+
+    #[test]
+    fn class_names_resolve_with_supplementary_characters() {
+        let name = "pkg/Smile\u{1F600}";
+        let class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 50,
+            },
+            0x21,
+            name,
+            Some("java/lang/Object"),
+        );
+        let bytes = class.build().unwrap();
+        assert!(bytes
+            .windows(6)
+            .any(|window| window == [0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]));
+
+        let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+        let class = ResolvedClass::new(&classfile);
+        assert_eq!(class.class_name(classfile.this_class).unwrap(), name);
+    }
+}
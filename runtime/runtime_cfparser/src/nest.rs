@@ -0,0 +1,177 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::resolve::ResolveError;
+use crate::resolve::ResolvedClass;
+use crate::spec::Classfile;
+
+#[derive(Debug)]
+pub enum NestError {
+    DuplicateClass { name: String },
+    Resolve(ResolveError),
+}
+
+impl Display for NestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateClass { name } => write!(f, "class `{name}` was added more than once"),
+            Self::Resolve(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for NestError {}
+
+impl From<ResolveError> for NestError {
+    fn from(error: ResolveError) -> Self {
+        Self::Resolve(error)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NestInconsistency {
+    // the host was added, but its NestMembers attribute does not list the member
+    HostMissingMember {
+        host: String,
+        member: String,
+    },
+    // the member was added, but its NestHost attribute names another host or is missing
+    MemberMissingHost {
+        host: String,
+        member: String,
+        declared_host: Option<String>,
+    },
+}
+
+// Only the classes that were added are checked; a host or member that is referenced but never added is
+// trusted to agree with the declarations that name it.
+#[derive(Default)]
+pub struct NestGraph {
+    // the declared host of every added class, `None` for classes without a NestHost attribute
+    hosts: HashMap<String, Option<String>>,
+    members: HashMap<String, Vec<String>>,
+    // in the order the classes were added, for deterministic reports
+    order: Vec<String>,
+}
+
+impl NestGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, classfile: &Classfile) -> Result<&mut Self, NestError> {
+        let name = ResolvedClass::new(classfile)
+            .class_name(classfile.this_class)?
+            .into_owned();
+
+        if self.hosts.contains_key(&name) {
+            return Err(NestError::DuplicateClass { name });
+        }
+
+        let host = classfile.nest_host()?.map(|host| host.into_owned());
+        let members = classfile
+            .nest_members()?
+            .into_iter()
+            .map(|member| member.into_owned())
+            .collect();
+
+        self.hosts.insert(name.clone(), host);
+        self.members.insert(name.clone(), members);
+        self.order.push(name);
+
+        Ok(self)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.hosts.contains_key(name)
+    }
+
+    pub fn inconsistencies(&self) -> Vec<NestInconsistency> {
+        let mut found = Vec::new();
+
+        for name in &self.order {
+            if let Some(Some(host)) = self.hosts.get(name) {
+                if self
+                    .members
+                    .get(host)
+                    .is_some_and(|members| !members.contains(name))
+                {
+                    found.push(NestInconsistency::HostMissingMember {
+                        host: host.clone(),
+                        member: name.clone(),
+                    });
+                }
+            }
+
+            for member in &self.members[name] {
+                let Some(declared_host) = self.hosts.get(member) else {
+                    continue;
+                };
+
+                if declared_host.as_ref() != Some(name) {
+                    found.push(NestInconsistency::MemberMissingHost {
+                        host: name.clone(),
+                        member: member.clone(),
+                        declared_host: declared_host.clone(),
+                    });
+                }
+            }
+        }
+
+        found
+    }
+
+    // The host of the nest the class belongs to, which is the class itself if it declares no host. `None` if
+    // the class was never added.
+    pub fn nest_host_of(&self, name: &str) -> Option<&str> {
+        let (name, host) = self.hosts.get_key_value(name)?;
+
+        Some(host.as_deref().unwrap_or(name))
+    }
+
+    // The other classes of the nest the class belongs to, host first, going by the declarations on either side;
+    // a class that was never added has no nestmates.
+    pub fn nestmates_of(&self, name: &str) -> Vec<&str> {
+        let Some(host) = self.nest_host_of(name) else {
+            return Vec::new();
+        };
+
+        let mut nest = vec![host];
+        if let Some(members) = self.members.get(host) {
+            nest.extend(members.iter().map(String::as_str));
+        }
+        for class in &self.order {
+            if self.hosts[class].as_deref() == Some(host) {
+                nest.push(class);
+            }
+        }
+
+        let mut nestmates = Vec::with_capacity(nest.len());
+        for class in nest {
+            if class != name && !nestmates.contains(&class) {
+                nestmates.push(class);
+            }
+        }
+
+        nestmates
+    }
+}
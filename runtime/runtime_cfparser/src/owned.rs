@@ -0,0 +1,454 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::io::Read;
+
+use crate::constant_pool::ConstantPool;
+use crate::parse::classfile_from_bytes_with_options;
+use crate::parse::error_with_offset;
+use crate::parse::LazyCode;
+use crate::parse::Limit;
+use crate::parse::ParseError;
+use crate::parse::ParseOptions;
+use crate::spec::Annotation;
+use crate::spec::Attribute;
+use crate::spec::AttributeInfo;
+use crate::spec::Classfile;
+use crate::spec::ConstantPoolEntry;
+use crate::spec::ElementValue;
+use crate::spec::ElementValuePair;
+use crate::spec::Field;
+use crate::spec::Method;
+use crate::spec::RecordComponent;
+use crate::spec::TypeAnnotation;
+
+// A classfile that owns all of its data rather than borrowing from the bytes it was parsed from, so that it
+// can outlive them.
+pub type OwnedClassfile = Classfile<'static>;
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    // the input of the parse error is the offset into the bytes read, which are gone by now
+    Parse(ParseError<usize>),
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to read class file: {error}"),
+            Self::Parse(ParseError::Nom(error)) => write!(
+                f,
+                "malformed class file at offset {}: {}",
+                error.input,
+                error.code.description()
+            ),
+            Self::Parse(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl Classfile<'static> {
+    pub fn from_reader(reader: impl Read) -> Result<OwnedClassfile, ReadError> {
+        Self::from_reader_with_options(reader, &ParseOptions::default())
+    }
+
+    // Reads the stream to its end and parses what it read. A stream longer than the max_read_bytes limit is
+    // rejected without buffering more than one byte past it.
+    pub fn from_reader_with_options(
+        reader: impl Read,
+        options: &ParseOptions,
+    ) -> Result<OwnedClassfile, ReadError> {
+        let max_read_bytes = options.limits.max_read_bytes;
+
+        let mut bytes = Vec::new();
+        reader
+            .take((max_read_bytes as u64).saturating_add(1))
+            .read_to_end(&mut bytes)?;
+        if bytes.len() > max_read_bytes {
+            return Err(ReadError::Parse(ParseError::LimitExceeded {
+                which: Limit::ReadBytes,
+            }));
+        }
+
+        match classfile_from_bytes_with_options(&bytes, options) {
+            Ok((_, classfile)) => Ok(classfile.into_owned()),
+            Err(error) => Err(ReadError::Parse(error_with_offset(&bytes, error))),
+        }
+    }
+}
+
+impl<'a> Attribute<'a> {
+    pub fn into_owned(self) -> Attribute<'static> {
+        Attribute {
+            name_index: self.name_index,
+            info: attribute_info_into_owned(self.info),
+            raw: self.raw.map(bytes_into_owned),
+        }
+    }
+}
+
+impl<'a> Classfile<'a> {
+    pub fn into_owned(self) -> OwnedClassfile {
+        Classfile {
+            version: self.version,
+            constant_pool: self.constant_pool.into_owned(),
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| Field {
+                    access_flags: field.access_flags,
+                    name_index: field.name_index,
+                    descriptor_index: field.descriptor_index,
+                    attributes: attributes_into_owned(field.attributes),
+                })
+                .collect(),
+            methods: self
+                .methods
+                .into_iter()
+                .map(|method| Method {
+                    access_flags: method.access_flags,
+                    name_index: method.name_index,
+                    descriptor_index: method.descriptor_index,
+                    attributes: attributes_into_owned(method.attributes),
+                    // the bytes a lazy Code attribute is decoded from are not kept, so it is decoded now
+                    lazy_code: LazyCode::from(method.lazy_code.get().cloned()),
+                })
+                .collect(),
+            attributes: attributes_into_owned(self.attributes),
+        }
+    }
+}
+
+impl<'a> ConstantPool<'a> {
+    pub fn into_owned(self) -> ConstantPool<'static> {
+        ConstantPool::new(
+            self.entries()
+                .iter()
+                .cloned()
+                .map(constant_pool_entry_into_owned)
+                .collect(),
+        )
+    }
+}
+
+fn annotation_into_owned(annotation: Annotation) -> Annotation<'static> {
+    Annotation {
+        type_index: annotation.type_index,
+        element_value_pairs: element_value_pairs_into_owned(annotation.element_value_pairs),
+        raw: annotation.raw.map(bytes_into_owned),
+    }
+}
+
+fn annotations_into_owned(annotations: Vec<Annotation>) -> Vec<Annotation<'static>> {
+    annotations.into_iter().map(annotation_into_owned).collect()
+}
+
+fn attribute_info_into_owned(info: AttributeInfo) -> AttributeInfo<'static> {
+    match info {
+        AttributeInfo::AnnotationDefault { default_value } => AttributeInfo::AnnotationDefault {
+            default_value: element_value_into_owned(default_value),
+        },
+        AttributeInfo::BootstrapMethods { bootstrap_methods } => {
+            AttributeInfo::BootstrapMethods { bootstrap_methods }
+        }
+        AttributeInfo::Code {
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+            attributes,
+        } => AttributeInfo::Code {
+            max_stack,
+            max_locals,
+            code: bytes_into_owned(code),
+            exception_table,
+            attributes: attributes_into_owned(attributes),
+        },
+        AttributeInfo::ConstantValue {
+            constantvalue_index,
+        } => AttributeInfo::ConstantValue {
+            constantvalue_index,
+        },
+        AttributeInfo::Deprecated => AttributeInfo::Deprecated,
+        AttributeInfo::EnclosingMethod {
+            class_index,
+            method_index,
+        } => AttributeInfo::EnclosingMethod {
+            class_index,
+            method_index,
+        },
+        AttributeInfo::Exceptions {
+            exception_index_table,
+        } => AttributeInfo::Exceptions {
+            exception_index_table,
+        },
+        AttributeInfo::InnerClasses { classes } => AttributeInfo::InnerClasses { classes },
+        AttributeInfo::LineNumberTable { line_number_table } => {
+            AttributeInfo::LineNumberTable { line_number_table }
+        }
+        AttributeInfo::LocalVariableTable {
+            local_variable_table,
+        } => AttributeInfo::LocalVariableTable {
+            local_variable_table,
+        },
+        AttributeInfo::LocalVariableTypeTable {
+            local_variable_type_table,
+        } => AttributeInfo::LocalVariableTypeTable {
+            local_variable_type_table,
+        },
+        AttributeInfo::MethodParameters { parameters } => {
+            AttributeInfo::MethodParameters { parameters }
+        }
+        AttributeInfo::Module {
+            module_name_index,
+            module_flags,
+            module_version_index,
+            requires,
+            exports,
+            opens,
+            uses,
+            provides,
+        } => AttributeInfo::Module {
+            module_name_index,
+            module_flags,
+            module_version_index,
+            requires,
+            exports,
+            opens,
+            uses,
+            provides,
+        },
+        AttributeInfo::ModuleMainClass { main_class_index } => {
+            AttributeInfo::ModuleMainClass { main_class_index }
+        }
+        AttributeInfo::ModulePackages { package_index } => {
+            AttributeInfo::ModulePackages { package_index }
+        }
+        AttributeInfo::NestHost { host_class_index } => {
+            AttributeInfo::NestHost { host_class_index }
+        }
+        AttributeInfo::NestMembers { classes } => AttributeInfo::NestMembers { classes },
+        AttributeInfo::PermittedSubclasses { classes } => {
+            AttributeInfo::PermittedSubclasses { classes }
+        }
+        AttributeInfo::Record { components } => AttributeInfo::Record {
+            components: components
+                .into_iter()
+                .map(|component| RecordComponent {
+                    name_index: component.name_index,
+                    descriptor_index: component.descriptor_index,
+                    attributes: attributes_into_owned(component.attributes),
+                })
+                .collect(),
+        },
+        AttributeInfo::RuntimeInvisibleAnnotations { annotations } => {
+            AttributeInfo::RuntimeInvisibleAnnotations {
+                annotations: annotations_into_owned(annotations),
+            }
+        }
+        AttributeInfo::RuntimeInvisibleParameterAnnotations {
+            parameter_annotations,
+        } => AttributeInfo::RuntimeInvisibleParameterAnnotations {
+            parameter_annotations: annotations_into_owned(parameter_annotations),
+        },
+        AttributeInfo::RuntimeInvisibleTypeAnnotations { type_annotations } => {
+            AttributeInfo::RuntimeInvisibleTypeAnnotations {
+                type_annotations: type_annotations_into_owned(type_annotations),
+            }
+        }
+        AttributeInfo::RuntimeVisibleAnnotations { annotations } => {
+            AttributeInfo::RuntimeVisibleAnnotations {
+                annotations: annotations_into_owned(annotations),
+            }
+        }
+        AttributeInfo::RuntimeVisibleParameterAnnotations {
+            parameter_annotations,
+        } => AttributeInfo::RuntimeVisibleParameterAnnotations {
+            parameter_annotations: annotations_into_owned(parameter_annotations),
+        },
+        AttributeInfo::RuntimeVisibleTypeAnnotations { type_annotations } => {
+            AttributeInfo::RuntimeVisibleTypeAnnotations {
+                type_annotations: type_annotations_into_owned(type_annotations),
+            }
+        }
+        AttributeInfo::Signature { signature_index } => {
+            AttributeInfo::Signature { signature_index }
+        }
+        AttributeInfo::SourceDebugExtension { debug_extension } => {
+            AttributeInfo::SourceDebugExtension {
+                debug_extension: bytes_into_owned(debug_extension),
+            }
+        }
+        AttributeInfo::SourceFile { sourcefile_index } => {
+            AttributeInfo::SourceFile { sourcefile_index }
+        }
+        AttributeInfo::StackMapTable { entries } => AttributeInfo::StackMapTable { entries },
+        AttributeInfo::Synthetic => AttributeInfo::Synthetic,
+        AttributeInfo::Unknown { name_index, info } => AttributeInfo::Unknown {
+            name_index,
+            info: bytes_into_owned(info),
+        },
+    }
+}
+
+fn attributes_into_owned(attributes: Vec<Attribute>) -> Vec<Attribute<'static>> {
+    attributes.into_iter().map(Attribute::into_owned).collect()
+}
+
+fn bytes_into_owned(bytes: Cow<[u8]>) -> Cow<'static, [u8]> {
+    Cow::Owned(bytes.into_owned())
+}
+
+fn constant_pool_entry_into_owned(entry: ConstantPoolEntry) -> ConstantPoolEntry<'static> {
+    match entry {
+        ConstantPoolEntry::Utf8 { bytes } => ConstantPoolEntry::Utf8 {
+            bytes: bytes_into_owned(bytes),
+        },
+        ConstantPoolEntry::Integer { bytes } => ConstantPoolEntry::Integer { bytes },
+        ConstantPoolEntry::Float { value } => ConstantPoolEntry::Float { value },
+        ConstantPoolEntry::Long { value } => ConstantPoolEntry::Long { value },
+        ConstantPoolEntry::Double { value } => ConstantPoolEntry::Double { value },
+        ConstantPoolEntry::Unusable => ConstantPoolEntry::Unusable,
+        ConstantPoolEntry::Class { name_index } => ConstantPoolEntry::Class { name_index },
+        ConstantPoolEntry::String { string_index } => ConstantPoolEntry::String { string_index },
+        ConstantPoolEntry::FieldRef {
+            class_index,
+            name_and_type_index,
+        } => ConstantPoolEntry::FieldRef {
+            class_index,
+            name_and_type_index,
+        },
+        ConstantPoolEntry::MethodRef {
+            class_index,
+            name_and_type_index,
+        } => ConstantPoolEntry::MethodRef {
+            class_index,
+            name_and_type_index,
+        },
+        ConstantPoolEntry::InstanceMethodRef {
+            class_index,
+            name_and_type_index,
+        } => ConstantPoolEntry::InstanceMethodRef {
+            class_index,
+            name_and_type_index,
+        },
+        ConstantPoolEntry::NameAndType {
+            name_index,
+            descriptor_index,
+        } => ConstantPoolEntry::NameAndType {
+            name_index,
+            descriptor_index,
+        },
+        ConstantPoolEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        } => ConstantPoolEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        },
+        ConstantPoolEntry::MethodType { reference_index } => {
+            ConstantPoolEntry::MethodType { reference_index }
+        }
+        ConstantPoolEntry::Dynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => ConstantPoolEntry::Dynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        },
+        ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        },
+        ConstantPoolEntry::Module { name_index } => ConstantPoolEntry::Module { name_index },
+        ConstantPoolEntry::Package { name_index } => ConstantPoolEntry::Package { name_index },
+    }
+}
+
+fn element_value_into_owned(value: ElementValue) -> ElementValue<'static> {
+    match value {
+        ElementValue::Annotation(annotation) => {
+            ElementValue::Annotation(annotation_into_owned(annotation))
+        }
+        ElementValue::ClassInfo(class_info_index) => ElementValue::ClassInfo(class_info_index),
+        ElementValue::ConstValue {
+            tag,
+            const_value_index,
+        } => ElementValue::ConstValue {
+            tag,
+            const_value_index,
+        },
+        ElementValue::EnumConst {
+            type_name_index,
+            const_name_index,
+        } => ElementValue::EnumConst {
+            type_name_index,
+            const_name_index,
+        },
+        ElementValue::Array { values } => ElementValue::Array {
+            values: values.into_iter().map(element_value_into_owned).collect(),
+        },
+    }
+}
+
+fn element_value_pairs_into_owned(pairs: Vec<ElementValuePair>) -> Vec<ElementValuePair<'static>> {
+    pairs
+        .into_iter()
+        .map(|pair| ElementValuePair {
+            element_name_index: pair.element_name_index,
+            value: element_value_into_owned(pair.value),
+        })
+        .collect()
+}
+
+fn type_annotations_into_owned(
+    type_annotations: Vec<TypeAnnotation>,
+) -> Vec<TypeAnnotation<'static>> {
+    type_annotations
+        .into_iter()
+        .map(|type_annotation| TypeAnnotation {
+            target_type: type_annotation.target_type,
+            target_info: type_annotation.target_info,
+            target_path: type_annotation.target_path,
+            type_index: type_annotation.type_index,
+            element_value_pairs: element_value_pairs_into_owned(
+                type_annotation.element_value_pairs,
+            ),
+        })
+        .collect()
+}
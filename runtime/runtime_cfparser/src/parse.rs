@@ -14,9 +14,22 @@
  * limitations under the License.
  */
 
-use mutf8::mutf8_to_utf8;
-use nom::bytes::complete::tag;
+use std::borrow::Cow;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::iter;
+use std::sync::Arc;
+use std::sync::OnceLock;
+
+#[cfg(feature = "bumpalo")]
+use bumpalo::collections::Vec as BumpVec;
+#[cfg(feature = "bumpalo")]
+use bumpalo::Bump;
 use nom::bytes::complete::take;
+use nom::combinator::consumed;
+use nom::combinator::rest;
 use nom::error::Error;
 use nom::error::ErrorKind;
 use nom::multi::count;
@@ -24,10 +37,21 @@ use nom::multi::length_count;
 use nom::number::complete::be_u16;
 use nom::number::complete::be_u32;
 use nom::number::complete::be_u8;
+use nom::sequence::pair;
 use nom::Err;
 use nom::IResult;
-
-use crate::cowext::CowExt;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Deserializer;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::Serializer;
+
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::ConstantPoolError;
+use crate::spec::AccessFlags;
 use crate::spec::Annotation;
 use crate::spec::Attribute;
 use crate::spec::AttributeInfo;
@@ -38,12 +62,14 @@ use crate::spec::ElementValue;
 use crate::spec::ElementValuePair;
 use crate::spec::ExceptionTableEntry;
 use crate::spec::Field;
+use crate::spec::FieldAccessFlags;
 use crate::spec::InnerClass;
 use crate::spec::LineNumber;
 use crate::spec::LocalVar;
 use crate::spec::LocalVariable;
 use crate::spec::LocalVariableType;
 use crate::spec::Method;
+use crate::spec::MethodAccessFlags;
 use crate::spec::MethodParameter;
 use crate::spec::ModuleExports;
 use crate::spec::ModuleOpens;
@@ -58,143 +84,874 @@ use crate::spec::TypePathSegment;
 use crate::spec::VerificationTypeInfo;
 use crate::spec::Version;
 
-pub fn classfile_from_bytes(bytes: &[u8]) -> IResult<&[u8], Classfile> {
+const MAGIC: [u8; 4] = [0xCA, 0xFE, 0xBA, 0xBE];
+// the attributes of which a class, field, method, Code attribute or record component may have at most one,
+// with the section of the JVMS that says so
+const SINGLE_ATTRIBUTES: [(&str, &str); 25] = [
+    ("AnnotationDefault", "4.7.22"),
+    ("BootstrapMethods", "4.7.23"),
+    ("Code", "4.7.3"),
+    ("ConstantValue", "4.7.2"),
+    ("EnclosingMethod", "4.7.7"),
+    ("Exceptions", "4.7.5"),
+    ("InnerClasses", "4.7.6"),
+    ("MethodParameters", "4.7.24"),
+    ("Module", "4.7.25"),
+    ("ModuleMainClass", "4.7.27"),
+    ("ModulePackages", "4.7.26"),
+    ("NestHost", "4.7.28"),
+    ("NestMembers", "4.7.29"),
+    ("PermittedSubclasses", "4.7.31"),
+    ("Record", "4.7.30"),
+    ("RuntimeInvisibleAnnotations", "4.7.17"),
+    ("RuntimeInvisibleParameterAnnotations", "4.7.19"),
+    ("RuntimeInvisibleTypeAnnotations", "4.7.21"),
+    ("RuntimeVisibleAnnotations", "4.7.16"),
+    ("RuntimeVisibleParameterAnnotations", "4.7.18"),
+    ("RuntimeVisibleTypeAnnotations", "4.7.20"),
+    ("Signature", "4.7.9"),
+    ("SourceDebugExtension", "4.7.11"),
+    ("SourceFile", "4.7.10"),
+    ("StackMapTable", "4.7.4"),
+];
+
+// Which attributes are decoded. Any other attribute is kept as an Unknown attribute holding its undecoded
+// body, which is cheaper to read past; this applies to attributes nested in Code and Record attributes too,
+// and the code of a method whose Code attribute is not decoded is not counted against the code limit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AttributeFilter {
+    #[default]
+    All,
+    // the names of the attributes to decode
+    Only(&'static [&'static str]),
+}
+
+impl AttributeFilter {
+    pub fn allows(&self, name: &str) -> bool {
+        self.allows_bytes(name.as_bytes())
+    }
+
+    fn allows_bytes(&self, name: &[u8]) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(names) => names.iter().any(|allowed| allowed.as_bytes() == name),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Limit {
+    AttributeDepth,
+    CodeBytes,
+    ConstantPoolEntries,
+    Methods,
+    ReadBytes,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AttributeDepth => write!(f, "attribute nesting depth"),
+            Self::CodeBytes => write!(f, "total code length"),
+            Self::ConstantPoolEntries => write!(f, "constant pool size"),
+            Self::Methods => write!(f, "method count"),
+            Self::ReadBytes => write!(f, "class file size"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError<I> {
+    // the first four bytes are not 0xCAFEBABE, as with a ZIP or JMOD file passed by mistake
+    BadMagic {
+        found: [u8; 4],
+    },
+    // something the strict_jvms option rejects, with the section of the JVMS that it violates
+    JvmsViolation {
+        section: &'static str,
+        message: String,
+    },
+    LimitExceeded {
+        which: Limit,
+    },
+    Nom(Error<I>),
+    TrailingBytes {
+        length: usize,
+    },
+    // the input ends early, in the section named, if it is known which; a section of the class file ends up
+    // reported as truncated when its count cannot be read
+    Truncated {
+        section: Option<&'static str>,
+    },
+}
+
+impl<I> Display for ParseError<I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic { found } => write!(
+                f,
+                "not a class file: starts with {:#010X} instead of 0xCAFEBABE",
+                u32::from_be_bytes(*found)
+            ),
+            Self::JvmsViolation { section, message } => {
+                write!(f, "class file violates JVMS §{section}: {message}")
+            }
+            Self::LimitExceeded { which } => write!(f, "class file exceeds the {which} limit"),
+            Self::Nom(error) => write!(f, "malformed class file: {}", error.code.description()),
+            Self::TrailingBytes { length } => {
+                write!(f, "class file is followed by {length} trailing bytes")
+            }
+            Self::Truncated { section: None } => write!(f, "class file is truncated"),
+            Self::Truncated {
+                section: Some(section),
+            } => write!(f, "class file is truncated in the {section}"),
+        }
+    }
+}
+
+impl<I: Debug> std::error::Error for ParseError<I> {}
+
+impl<I> nom::error::ParseError<I> for ParseError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        Self::Nom(Error::new(input, kind))
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+// An attribute of a class file read by parse_in, left undecoded
+#[cfg(feature = "bumpalo")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ArenaAttribute<'a> {
+    pub name_index: u16,
+    pub info: &'a [u8],
+    // the whole attribute, name index and length included
+    bytes: &'a [u8],
+}
+
+#[cfg(feature = "bumpalo")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ArenaMember<'a> {
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: &'a [ArenaAttribute<'a>],
+}
+
+// A class file whose interfaces, fields, methods and attributes are slices allocated in a bump arena instead
+// of vectors of their own, for reading a whole class path in a loop and resetting the arena between classes.
+// The constant pool stays a ConstantPool so that the usual lookups work on it.
+#[cfg(feature = "bumpalo")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassfileArena<'a> {
+    pub version: Version,
+    pub constant_pool: ConstantPool<'a>,
+    pub access_flags: u16,
+    pub this_class: u16,
+    pub super_class: u16,
+    pub interfaces: &'a [u16],
+    pub fields: &'a [ArenaMember<'a>],
+    pub methods: &'a [ArenaMember<'a>],
+    pub attributes: &'a [ArenaAttribute<'a>],
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    // from the start of the class file
+    pub offset: usize,
+    pub message: String,
+}
+
+// The Code attribute of a method parsed with lazy_code, decoded when Method::code is first called and kept
+// from then on. The methods of a class share its constant pool, which the attributes of the Code attribute
+// are looked up in. The decoded attribute owns its data, as a cell borrowing from the class file would tie
+// the lifetime of the method down.
+#[derive(Clone, Default)]
+pub struct LazyCode<'a> {
+    name_index: u16,
+    info: &'a [u8],
+    constant_pool: Option<Arc<ConstantPool<'a>>>,
+    options: ParseOptions,
+    code: OnceLock<Option<Attribute<'static>>>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParseOptions {
+    // keep the undecoded body of every attribute next to its decoded form, so that it can be written
+    // back byte for byte
+    pub retain_raw_attributes: bool,
+    // accept bytes after the end of the class file, as some packers append them, and leave them as the
+    // remaining input instead of failing with TrailingBytes
+    pub allow_trailing_bytes: bool,
+    // Reject what does not strictly conform to the JVMS instead of reading past it: attributes it does not
+    // define, attribute lengths that disagree with their contents, constant pool references to entries of the
+    // wrong kind, attributes repeated where at most one is allowed, trailing bytes whether or not they are
+    // otherwise allowed, and invalid combinations of access flags.
+    pub strict_jvms: bool,
+    pub attribute_filter: AttributeFilter,
+    // Leave the Code attribute of each method undecoded, among its attributes as one the filter left out,
+    // until Method::code decodes it, so that only the code of the methods looked at is decoded. Its code
+    // still counts against the code limit. A Code attribute that does not decode reads as missing, and the
+    // strict_jvms checks of what it holds are not made.
+    pub lazy_code: bool,
+    pub limits: ResourceLimits,
+}
+
+// Caps on untrusted input, checked as the class file is read so that parsing stops before the work is
+// done. The defaults are far above anything a compiler emits.
+#[derive(Clone, Copy, Debug)]
+pub struct ResourceLimits {
+    pub max_constant_pool_entries: u16,
+    pub max_methods: u16,
+    // summed over the Code attributes of all methods
+    pub max_code_bytes: u32,
+    // the attributes of a class, field or method are at depth 1, those of a Code attribute at depth 2
+    pub max_attribute_depth: usize,
+    // how much of a stream Classfile::from_reader buffers before giving up on it
+    pub max_read_bytes: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_constant_pool_entries: u16::MAX,
+            max_methods: u16::MAX,
+            max_code_bytes: 64 * 1024 * 1024,
+            max_attribute_depth: 8,
+            max_read_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+#[cfg(feature = "bumpalo")]
+impl<'a> ArenaAttribute<'a> {
+    // the attribute decoded as classfile_from_bytes decodes it, allocating as that does
+    pub fn decode(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<Attribute<'a>, Err<ParseError<&'a [u8]>>> {
+        let (_, attribute) =
+            attribute_from_bytes(self.bytes, constant_pool, &ParseOptions::default(), 1)?;
+
+        Ok(attribute)
+    }
+}
+
+impl<'a> LazyCode<'a> {
+    // the decoded Code attribute, or `None` if the method has none or it does not decode
+    pub fn get(&self) -> Option<&Attribute<'static>> {
+        self.code
+            .get_or_init(|| {
+                let constant_pool = self.constant_pool.as_ref()?;
+                let (rest, info) =
+                    attribute_code_from_bytes(self.info, constant_pool, &self.options, 1).ok()?;
+
+                let attribute = Attribute {
+                    name_index: self.name_index,
+                    info,
+                    raw: self
+                        .options
+                        .retain_raw_attributes
+                        .then_some(Cow::Borrowed(self.info)),
+                };
+
+                rest.is_empty().then(|| attribute.into_owned())
+            })
+            .as_ref()
+    }
+}
+
+impl<'a> Method<'a> {
+    // Puts the Code attribute that parsing with lazy_code left undecoded among the attributes in place of its
+    // body, for code that changes the attributes in place. `false` if it does not decode, in which case the
+    // method is left as it was.
+    pub fn decode_lazy_code(&mut self) -> bool {
+        let Some(code) = self.lazy_code.get() else {
+            return self.lazy_code.constant_pool.is_none();
+        };
+
+        let code = code.clone();
+        if let Some(attribute) = self.attributes.iter_mut().find(|attribute| {
+            matches!(attribute.info, AttributeInfo::Unknown { name_index, .. } if name_index == code.name_index)
+        }) {
+            *attribute = code;
+        }
+        self.lazy_code = LazyCode::default();
+
+        true
+    }
+}
+
+impl<'a> Debug for LazyCode<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyCode")
+            .field("code", &self.code.get())
+            .finish_non_exhaustive()
+    }
+}
+
+// a Code attribute that is decoded already, as when a class file is made owned or deserialized
+impl<'a> From<Option<Attribute<'static>>> for LazyCode<'a> {
+    fn from(code: Option<Attribute<'static>>) -> Self {
+        Self {
+            code: OnceLock::from(code),
+            ..Self::default()
+        }
+    }
+}
+
+impl<'a> PartialEq for LazyCode<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, 'a> Deserialize<'de> for LazyCode<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Option::<Attribute<'static>>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a> Serialize for LazyCode<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+
+pub fn classfile_from_bytes(bytes: &[u8]) -> IResult<&[u8], Classfile<'_>, ParseError<&[u8]>> {
+    classfile_from_bytes_with_options(bytes, &ParseOptions::default())
+}
+
+// Parses as much of the class file as it can, collecting every problem instead of stopping at the first.
+// An attribute whose body does not parse is kept as an Unknown attribute holding the body, and parsing goes
+// on after it, as its length is known; any other problem ends the class file there, leaving the sections
+// that were not reached empty.
+pub fn classfile_from_bytes_diagnostic(bytes: &[u8]) -> (Classfile<'_>, Vec<Diagnostic>) {
+    let mut classfile = Classfile {
+        version: Version { minor: 0, major: 0 },
+        constant_pool: ConstantPool::new(Vec::new()),
+        access_flags: 0,
+        this_class: 0,
+        super_class: 0,
+        interfaces: Vec::new(),
+        fields: Vec::new(),
+        methods: Vec::new(),
+        attributes: Vec::new(),
+    };
+    let mut diagnostics = Vec::new();
+
+    if let Err(error) = classfile_sections_diagnostic(bytes, &mut classfile, &mut diagnostics) {
+        diagnostics.push(diagnostic(bytes, bytes, error));
+    }
+
+    (classfile, diagnostics)
+}
+
+pub fn classfile_from_bytes_with_options<'a>(
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Classfile<'a>, ParseError<&'a [u8]>> {
     // make sure the magic bytes are there, to indicate a valid Java classfile
-    let (input_1, _) = tag([0xCA, 0xFE, 0xBA, 0xBE])(bytes)?;
+    let (input_1, magic) = magic_from_bytes(bytes)?;
+    if magic != MAGIC {
+        return Err(Err::Failure(ParseError::BadMagic { found: magic }));
+    }
 
     // parse classfile version
     let (input_2, version) = classfile_version_from_bytes(input_1)?;
 
     // parse constant pool length and constant pool
-    let (input_3, constant_pool) = length_count(be_u16, constant_pool_entry_from_bytes)(input_2)?;
+    let (input_3, constant_pool_count) = section_count("constant pool")(input_2)?;
+    if constant_pool_count.saturating_sub(1) > options.limits.max_constant_pool_entries {
+        return Err(limit_exceeded(Limit::ConstantPoolEntries));
+    }
+    let (input_4, constant_pool) = constant_pool_from_bytes(input_3, constant_pool_count)?;
 
     // parse access flags
-    let (input_4, access_flags) = be_u16(input_3)?;
+    let (input_5, access_flags) = be_u16(input_4)?;
 
     // parse this class
-    let (input_5, this_class) = be_u16(input_4)?;
+    let (input_6, this_class) = be_u16(input_5)?;
 
     // parse super class
-    let (input_6, super_class) = be_u16(input_5)?;
+    let (input_7, super_class) = be_u16(input_6)?;
 
     // parse interfaces
-    let (input_7, interfaces) = length_count(be_u16, be_u16)(input_6)?;
+    let (input_8, interfaces) = length_count(section_count("interfaces"), be_u16)(input_7)?;
 
     // parse fields
-    let (input_8, fields) = length_count(be_u16, |bytes| {
-        field_from_bytes(bytes, constant_pool.as_slice())
-    })(input_7)?;
-
-    // parse methods
-    let (input_9, methods) = length_count(be_u16, |bytes| {
-        method_from_bytes(bytes, constant_pool.as_slice())
+    let (input_9, fields) = length_count(section_count("fields"), |bytes| {
+        field_from_bytes(bytes, &constant_pool, options)
     })(input_8)?;
 
-    // parse attributes
-    let (input_10, attributes) = length_count(be_u16, |bytes| {
-        attribute_from_bytes(bytes, constant_pool.as_slice())
-    })(input_9)?;
+    // parse methods, keeping a running total of their code
+    let (input_10, methods_count) = section_count("methods")(input_9)?;
+    if methods_count > options.limits.max_methods {
+        return Err(limit_exceeded(Limit::Methods));
+    }
+    let mut code_bytes = 0u32;
+    // with lazy_code the methods share one copy of the constant pool to decode their code with later
+    let shared_constant_pool = (options.lazy_code && options.attribute_filter.allows("Code"))
+        .then(|| Arc::new(constant_pool.clone()));
+    let (input_11, methods) = count(
+        |bytes| {
+            let (input, mut method) = method_from_bytes(bytes, &constant_pool, options)?;
+            for attribute in &method.attributes {
+                if let AttributeInfo::Code { code, .. } = &attribute.info {
+                    code_bytes = code_bytes.saturating_add(code.len() as u32);
+                }
+            }
+            if let Some(shared_constant_pool) = &shared_constant_pool {
+                method.lazy_code = lazy_code(&method.attributes, shared_constant_pool, options);
+                // the code length follows the max_stack and max_locals of the undecoded attribute
+                if let Some(code_length) = method
+                    .lazy_code
+                    .info
+                    .get(4..8)
+                    .and_then(|bytes| bytes.try_into().ok())
+                {
+                    code_bytes = code_bytes.saturating_add(u32::from_be_bytes(code_length));
+                }
+            }
+            if code_bytes > options.limits.max_code_bytes {
+                return Err(limit_exceeded(Limit::CodeBytes));
+            }
 
-    Ok((
-        input_10,
-        Classfile {
-            version,
-            constant_pool,
-            access_flags,
-            this_class,
-            super_class,
-            interfaces,
-            fields,
-            methods,
-            attributes,
+            Ok((input, method))
         },
-    ))
+        methods_count as usize,
+    )(input_10)?;
+
+    // parse attributes
+    let (input_12, attributes) = length_count(section_count("attributes"), |bytes| {
+        attribute_from_bytes(bytes, &constant_pool, options, 1)
+    })(input_11)?;
+    if !input_12.is_empty() && options.strict_jvms {
+        return Err(jvms_violation(
+            "4.1",
+            format!(
+                "the class file is followed by {} trailing bytes",
+                input_12.len()
+            ),
+        ));
+    }
+    if !input_12.is_empty() && !options.allow_trailing_bytes {
+        return Err(Err::Failure(ParseError::TrailingBytes {
+            length: input_12.len(),
+        }));
+    }
+
+    let classfile = Classfile {
+        version,
+        constant_pool,
+        access_flags,
+        this_class,
+        super_class,
+        interfaces,
+        fields,
+        methods,
+        attributes,
+    };
+    if options.strict_jvms {
+        if let Some((section, message)) = strict_violation(&classfile) {
+            return Err(jvms_violation(section, message));
+        }
+    }
+
+    Ok((input_12, classfile))
+}
+
+// the classfile together with the number of bytes it took up, so that a caller reading class files stored
+// back to back knows where the next one starts
+pub fn classfile_with_consumed(
+    bytes: &[u8],
+) -> Result<(Classfile<'_>, usize), Err<ParseError<&[u8]>>> {
+    let options = ParseOptions {
+        allow_trailing_bytes: true,
+        ..ParseOptions::default()
+    };
+    let (rest, classfile) = classfile_from_bytes_with_options(bytes, &options)?;
+
+    Ok((classfile, bytes.len() - rest.len()))
+}
+
+// Parses the class files stored back to back in `bytes` until the buffer is used up, each borrowing from its
+// own part of it. Iteration ends with the first error; a class file that the end of the buffer cuts off is
+// reported as Truncated, but one whose attribute body ends early is reported as it is, as the body is read
+// from the bytes the attribute's length bounds.
+pub fn classfiles_from_bytes(
+    bytes: &[u8],
+) -> impl Iterator<Item = Result<Classfile<'_>, Err<ParseError<&[u8]>>>> {
+    let mut input = bytes;
+    let mut failed = false;
+
+    iter::from_fn(move || {
+        if input.is_empty() || failed {
+            return None;
+        }
+
+        match classfile_with_consumed(input) {
+            Ok((classfile, consumed)) => {
+                input = &input[consumed..];
+                Some(Ok(classfile))
+            }
+            Err(Err::Error(ParseError::Nom(error)) | Err::Failure(ParseError::Nom(error)))
+                if error.code == ErrorKind::Eof
+                    && error.input.as_ptr_range().end == input.as_ptr_range().end =>
+            {
+                failed = true;
+                Some(Err(Err::Failure(ParseError::Truncated { section: None })))
+            }
+            Err(error) => {
+                failed = true;
+                Some(Err(error))
+            }
+        }
+    })
+}
+
+// the error with the input it points at replaced by its offset into `bytes`, for callers that do not keep
+// the bytes around
+pub fn error_with_offset(bytes: &[u8], error: Err<ParseError<&[u8]>>) -> ParseError<usize> {
+    let error = match error {
+        Err::Error(error) | Err::Failure(error) => error,
+        // the parsers all work on complete input
+        Err::Incomplete(_) => ParseError::Nom(Error::new(&bytes[bytes.len()..], ErrorKind::Eof)),
+    };
+
+    match error {
+        ParseError::BadMagic { found } => ParseError::BadMagic { found },
+        ParseError::JvmsViolation { section, message } => {
+            ParseError::JvmsViolation { section, message }
+        }
+        ParseError::LimitExceeded { which } => ParseError::LimitExceeded { which },
+        ParseError::Nom(error) => {
+            ParseError::Nom(Error::new(bytes.len() - error.input.len(), error.code))
+        }
+        ParseError::TrailingBytes { length } => ParseError::TrailingBytes { length },
+        ParseError::Truncated { section } => ParseError::Truncated { section },
+    }
+}
+
+// Parses a class file like classfile_from_bytes does with the default options, but puts its interfaces,
+// fields, methods and attributes into `arena` and leaves the attributes undecoded, so that nothing but the
+// constant pool is allocated on the heap. As Code attributes are not decoded, the limit on code bytes is not
+// checked.
+#[cfg(feature = "bumpalo")]
+pub fn parse_in<'a>(
+    bytes: &'a [u8],
+    arena: &'a Bump,
+) -> Result<ClassfileArena<'a>, Err<ParseError<&'a [u8]>>> {
+    let limits = ResourceLimits::default();
+
+    let (input_1, magic) = magic_from_bytes(bytes)?;
+    if magic != MAGIC {
+        return Err(Err::Failure(ParseError::BadMagic { found: magic }));
+    }
+    let (input_2, version) = classfile_version_from_bytes(input_1)?;
+
+    let (input_3, constant_pool_count) = section_count("constant pool")(input_2)?;
+    if constant_pool_count.saturating_sub(1) > limits.max_constant_pool_entries {
+        return Err(limit_exceeded(Limit::ConstantPoolEntries));
+    }
+    let (input_4, constant_pool) = constant_pool_from_bytes(input_3, constant_pool_count)?;
+
+    let (input_5, access_flags) = be_u16(input_4)?;
+    let (input_6, this_class) = be_u16(input_5)?;
+    let (input_7, super_class) = be_u16(input_6)?;
+
+    let (input_8, interfaces_count) = section_count("interfaces")(input_7)?;
+    let (input_9, interfaces) = arena_slice_from_bytes(input_8, interfaces_count, arena, be_u16)?;
+
+    let (input_10, fields_count) = section_count("fields")(input_9)?;
+    let (input_11, fields) = arena_slice_from_bytes(input_10, fields_count, arena, |bytes| {
+        arena_member_from_bytes(bytes, arena)
+    })?;
+
+    let (input_12, methods_count) = section_count("methods")(input_11)?;
+    if methods_count > limits.max_methods {
+        return Err(limit_exceeded(Limit::Methods));
+    }
+    let (input_13, methods) = arena_slice_from_bytes(input_12, methods_count, arena, |bytes| {
+        arena_member_from_bytes(bytes, arena)
+    })?;
+
+    let (input_14, attributes_count) = section_count("attributes")(input_13)?;
+    let (input_15, attributes) = arena_slice_from_bytes(
+        input_14,
+        attributes_count,
+        arena,
+        arena_attribute_from_bytes,
+    )?;
+    if !input_15.is_empty() {
+        return Err(Err::Failure(ParseError::TrailingBytes {
+            length: input_15.len(),
+        }));
+    }
+
+    Ok(ClassfileArena {
+        version,
+        constant_pool,
+        access_flags,
+        this_class,
+        super_class,
+        interfaces,
+        fields,
+        methods,
+        attributes,
+    })
+}
+
+// The version of a class file, read from its first eight bytes without parsing any further, for scans that
+// only sort class files by the Java release they need. The magic bytes are still checked.
+pub fn peek_version(bytes: &[u8]) -> Result<Version, Err<ParseError<&[u8]>>> {
+    let (input, magic) = magic_from_bytes(bytes)?;
+    if magic != MAGIC {
+        return Err(Err::Failure(ParseError::BadMagic { found: magic }));
+    }
+
+    // the version can only fail to parse for want of bytes
+    match classfile_version_from_bytes(input) {
+        Ok((_, version)) => Ok(version),
+        Err(_) => Err(Err::Failure(ParseError::Truncated {
+            section: Some("version"),
+        })),
+    }
 }
 
-fn annotation_from_bytes(bytes: &[u8]) -> IResult<&[u8], Annotation> {
-    let (input_1, type_index) = be_u16(bytes)?;
-    let (input_2, element_value_pairs) =
-        length_count(be_u16, element_value_pair_from_bytes)(input_1)?;
+fn annotation_from_bytes<'a>(
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Annotation<'a>, ParseError<&'a [u8]>> {
+    let (input, (raw, (type_index, element_value_pairs))) = consumed(pair(
+        be_u16,
+        length_count(be_u16, |bytes| {
+            element_value_pair_from_bytes(bytes, options)
+        }),
+    ))(bytes)?;
 
     Ok((
-        input_2,
+        input,
         Annotation {
             type_index,
             element_value_pairs,
+            raw: options.retain_raw_attributes.then_some(Cow::Borrowed(raw)),
+        },
+    ))
+}
+
+#[cfg(feature = "bumpalo")]
+fn arena_attribute_from_bytes<'a>(
+    bytes: &'a [u8],
+) -> IResult<&'a [u8], ArenaAttribute<'a>, ParseError<&'a [u8]>> {
+    let (input_1, name_index) = be_u16(bytes)?;
+    let (input_2, length) = be_u32(input_1)?;
+    let (input_3, info) = take(length)(input_2)?;
+
+    Ok((
+        input_3,
+        ArenaAttribute {
+            name_index,
+            info,
+            bytes: &bytes[..bytes.len() - input_3.len()],
+        },
+    ))
+}
+
+#[cfg(feature = "bumpalo")]
+fn arena_member_from_bytes<'a>(
+    bytes: &'a [u8],
+    arena: &'a Bump,
+) -> IResult<&'a [u8], ArenaMember<'a>, ParseError<&'a [u8]>> {
+    let (input_1, access_flags) = be_u16(bytes)?;
+    let (input_2, name_index) = be_u16(input_1)?;
+    let (input_3, descriptor_index) = be_u16(input_2)?;
+    let (input_4, attributes_count) = section_count("attributes")(input_3)?;
+    let (input_5, attributes) =
+        arena_slice_from_bytes(input_4, attributes_count, arena, arena_attribute_from_bytes)?;
+
+    Ok((
+        input_5,
+        ArenaMember {
+            access_flags,
+            name_index,
+            descriptor_index,
+            attributes,
         },
     ))
 }
 
+// `length` items read by `parser`, collected into a slice in `arena`
+#[cfg(feature = "bumpalo")]
+fn arena_slice_from_bytes<'a, T, F>(
+    bytes: &'a [u8],
+    length: u16,
+    arena: &'a Bump,
+    mut parser: F,
+) -> IResult<&'a [u8], &'a [T], ParseError<&'a [u8]>>
+where
+    F: FnMut(&'a [u8]) -> IResult<&'a [u8], T, ParseError<&'a [u8]>>,
+{
+    // every item takes up at least a byte, so a count beyond the input is not allocated for
+    let mut items = BumpVec::with_capacity_in(bytes.len().min(length as usize), arena);
+    let mut input = bytes;
+    for _ in 0..length {
+        let (rest, item) = parser(input)?;
+        items.push(item);
+        input = rest;
+    }
+
+    Ok((input, items.into_bump_slice()))
+}
+
 fn attribute_from_bytes<'a>(
     bytes: &'a [u8],
-    constant_pool: &[ConstantPoolEntry<'a>],
-) -> IResult<&'a [u8], Attribute<'a>> {
+    constant_pool: &ConstantPool<'a>,
+    options: &ParseOptions,
+    depth: usize,
+) -> IResult<&'a [u8], Attribute<'a>, ParseError<&'a [u8]>> {
+    if depth > options.limits.max_attribute_depth {
+        return Err(limit_exceeded(Limit::AttributeDepth));
+    }
+
+    // The names of the attributes that are decoded are ASCII, which is encoded the same in modified UTF-8,
+    // so they are told apart by their bytes without decoding them. Only the name of an attribute that is
+    // kept undecoded is decoded, to make sure it is well-formed.
     let (input_1, attribute_name_index) = be_u16(bytes)?;
-    let ConstantPoolEntry::Utf8 { bytes } = constant_pool[attribute_name_index as usize - 1] else {
-        return Err(Err::Failure(Error::new(bytes, ErrorKind::IsNot)));
+    let attribute_name = match constant_pool
+        .get(attribute_name_index)
+        .map(ConstantPoolEntry::as_raw_utf8)
+    {
+        Ok(Some(attribute_name)) => attribute_name,
+        _ => {
+            return Err(Err::Failure(ParseError::Nom(Error::new(
+                bytes,
+                ErrorKind::IsNot,
+            ))))
+        }
     };
 
     let (input_2, length) = be_u32(input_1)?;
-
-    let Ok(utf8) = mutf8_to_utf8(bytes) else {
-        return Err(Err::Failure(Error::new(bytes, ErrorKind::Verify)));
+    // the body is parsed from the attribute's own bytes, so that a malformed body cannot run on into
+    // whatever follows the attribute; the length of those bytes is the attribute's length, and a body that
+    // leaves any of them unread is malformed
+    let (input_3, raw) = take(length)(input_2)?;
+
+    // with lazy_code the Code attribute of a method is left for Method::code to decode
+    let lazy = options.lazy_code && depth == 1 && attribute_name == b"Code";
+    // no attribute that is decoded has an empty name, so one the filter leaves out is read as unknown
+    let decoded_name = match options.attribute_filter.allows_bytes(attribute_name) && !lazy {
+        true => attribute_name,
+        false => b"",
     };
-    let (input_3, info) = unsafe {
-        // SAFETY: the UTF-8 conversion above would have been failed if the MUTF-8 from Java cannot be converted
-        // into conventional UTF-8 and returned an error; it is guaranteed that at this point the slice contains
-        // bytes of valid UTF-8.
-        match utf8.to_str_lossy().as_ref() {
-            "AnnotationDefault" => attribute_annotation_default_from_bytes(input_2)?,
-            "BootstrapMethods" => attribute_bootstrap_methods_from_bytes(input_2)?,
-            "Code" => attribute_code_from_bytes(input_2, constant_pool)?,
-            "ConstantValue" => attribute_constant_value_from_bytes(input_2)?,
-            "Deprecated" => (input_2, AttributeInfo::Deprecated),
-            "EnclosingMethod" => attribute_enclosing_method_from_bytes(input_2)?,
-            "Exceptions" => attribute_exceptions_from_bytes(input_2)?,
-            "InnerClasses" => attribute_inner_classes_from_bytes(input_2)?,
-            "LineNumberTable" => attribute_line_number_table_from_bytes(input_2)?,
-            "LocalVariableTable" => attribute_local_variable_table_from_bytes(input_2)?,
-            "LocalVariableTypeTable" => attribute_local_variable_type_table_from_bytes(input_2)?,
-            "MethodParameters" => attribute_method_parameters_from_bytes(input_2)?,
-            "Module" => attribute_module_from_bytes(input_2)?,
-            "ModuleMainClass" => attribute_module_main_class_from_bytes(input_2)?,
-            "ModulePackages" => attribute_module_packages_from_bytes(input_2)?,
-            "NestHost" => attribute_nest_host_from_bytes(input_2)?,
-            "NestMembers" => attribute_nest_members_from_bytes(input_2)?,
-            "PermittedSubclasses" => attribute_permitted_subclasses_from_bytes(input_2)?,
-            "Record" => attribute_record_from_bytes(input_2, constant_pool)?,
-            "RuntimeInvisibleAnnotations" => {
-                attribute_runtime_invisible_annotations_from_bytes(input_2)?
-            }
-            "RuntimeInvisibleParameterAnnotations" => {
-                attribute_runtime_invisible_parameter_annotations_from_bytes(input_2)?
-            }
-            "RuntimeInvisibleTypeAnnotations" => {
-                attribute_runtime_invisible_type_annotations_from_bytes(input_2)?
-            }
-            "RuntimeVisibleAnnotations" => {
-                attribute_runtime_visible_annotations_from_bytes(input_2)?
-            }
-            "RuntimeVisibleParameterAnnotations" => {
-                attribute_runtime_visible_parameter_annotations_from_bytes(input_2)?
+    let (rest, info) = match decoded_name {
+        b"AnnotationDefault" => attribute_annotation_default_from_bytes(raw, options)?,
+        b"BootstrapMethods" => attribute_bootstrap_methods_from_bytes(raw)?,
+        b"Code" => attribute_code_from_bytes(raw, constant_pool, options, depth)?,
+        b"ConstantValue" => attribute_constant_value_from_bytes(raw)?,
+        b"Deprecated" => (raw, AttributeInfo::Deprecated),
+        b"EnclosingMethod" => attribute_enclosing_method_from_bytes(raw)?,
+        b"Exceptions" => attribute_exceptions_from_bytes(raw)?,
+        b"InnerClasses" => attribute_inner_classes_from_bytes(raw)?,
+        b"LineNumberTable" => attribute_line_number_table_from_bytes(raw)?,
+        b"LocalVariableTable" => attribute_local_variable_table_from_bytes(raw)?,
+        b"LocalVariableTypeTable" => attribute_local_variable_type_table_from_bytes(raw)?,
+        b"MethodParameters" => attribute_method_parameters_from_bytes(raw)?,
+        b"Module" => attribute_module_from_bytes(raw)?,
+        b"ModuleMainClass" => attribute_module_main_class_from_bytes(raw)?,
+        b"ModulePackages" => attribute_module_packages_from_bytes(raw)?,
+        b"NestHost" => attribute_nest_host_from_bytes(raw)?,
+        b"NestMembers" => attribute_nest_members_from_bytes(raw)?,
+        b"PermittedSubclasses" => attribute_permitted_subclasses_from_bytes(raw)?,
+        b"Record" => attribute_record_from_bytes(raw, constant_pool, options, depth)?,
+        b"RuntimeInvisibleAnnotations" => {
+            attribute_runtime_invisible_annotations_from_bytes(raw, options)?
+        }
+        b"RuntimeInvisibleParameterAnnotations" => {
+            attribute_runtime_invisible_parameter_annotations_from_bytes(raw, options)?
+        }
+        b"RuntimeInvisibleTypeAnnotations" => {
+            attribute_runtime_invisible_type_annotations_from_bytes(raw, options)?
+        }
+        b"RuntimeVisibleAnnotations" => {
+            attribute_runtime_visible_annotations_from_bytes(raw, options)?
+        }
+        b"RuntimeVisibleParameterAnnotations" => {
+            attribute_runtime_visible_parameter_annotations_from_bytes(raw, options)?
+        }
+        b"RuntimeVisibleTypeAnnotations" => {
+            attribute_runtime_visible_type_annotations_from_bytes(raw, options)?
+        }
+        b"Signature" => attribute_signature_from_bytes(raw)?,
+        b"SourceDebugExtension" => attribute_source_debug_extension_from_bytes(raw)?,
+        b"SourceFile" => attribute_source_file_from_bytes(raw)?,
+        b"StackMapTable" => attribute_stack_map_table_from_bytes(raw)?,
+        b"Synthetic" => (raw, AttributeInfo::Synthetic),
+        _ => {
+            if let Err(ConstantPoolError::InvalidUtf8 { .. }) =
+                constant_pool.utf8(attribute_name_index)
+            {
+                return Err(Err::Failure(ParseError::Nom(Error::new(
+                    bytes,
+                    ErrorKind::Verify,
+                ))));
             }
-            "RuntimeVisibleTypeAnnotations" => {
-                attribute_runtime_visible_type_annotations_from_bytes(input_2)?
+            // an attribute the filter leaves out is not unknown, only undecoded
+            if options.strict_jvms && !decoded_name.is_empty() {
+                return Err(jvms_violation(
+                    "4.7",
+                    format!(
+                        "attribute `{}` is not one the JVMS defines",
+                        String::from_utf8_lossy(attribute_name)
+                    ),
+                ));
             }
-            "Signature" => attribute_signature_from_bytes(input_2)?,
-            "SourceDebugExtension" => attribute_source_debug_extension_from_bytes(input_2, length)?,
-            "SourceFile" => attribute_source_file_from_bytes(input_2)?,
-            "StackMapTable" => attribute_stack_map_table_from_bytes(input_2)?,
-            "Synthetic" => (input_2, AttributeInfo::Synthetic),
-            _ => return Err(Err::Failure(Error::new(bytes, ErrorKind::Tag))),
+
+            (
+                &raw[raw.len()..],
+                AttributeInfo::Unknown {
+                    name_index: attribute_name_index,
+                    info: Cow::Borrowed(raw),
+                },
+            )
         }
     };
+    if !rest.is_empty() && options.strict_jvms {
+        return Err(jvms_violation(
+            "4.7",
+            format!(
+                "attribute `{}` is {length} bytes long, but its contents end after {}",
+                String::from_utf8_lossy(attribute_name),
+                raw.len() - rest.len()
+            ),
+        ));
+    }
+    if !rest.is_empty() {
+        return Err(Err::Failure(ParseError::Nom(Error::new(
+            rest,
+            ErrorKind::LengthValue,
+        ))));
+    }
 
-    Ok((input_3, Attribute { info }))
+    Ok((
+        input_3,
+        Attribute {
+            name_index: attribute_name_index,
+            info,
+            raw: options.retain_raw_attributes.then_some(Cow::Borrowed(raw)),
+        },
+    ))
 }
 
-fn attribute_annotation_default_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, element_value) = element_value_from_bytes(bytes)?;
+fn attribute_annotation_default_from_bytes<'a>(
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
+    let (input, element_value) = element_value_from_bytes(bytes, options)?;
 
     Ok((
         input,
@@ -204,7 +961,9 @@ fn attribute_annotation_default_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], A
     ))
 }
 
-fn attribute_bootstrap_methods_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_bootstrap_methods_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, bootstrap_methods) = length_count(be_u16, bootstrap_method_from_bytes)(bytes)?;
 
     Ok((input, AttributeInfo::BootstrapMethods { bootstrap_methods }))
@@ -212,29 +971,34 @@ fn attribute_bootstrap_methods_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], At
 
 fn attribute_code_from_bytes<'a>(
     bytes: &'a [u8],
-    constant_pool: &[ConstantPoolEntry<'a>],
-) -> IResult<&'a [u8], AttributeInfo<'a>> {
+    constant_pool: &ConstantPool<'a>,
+    options: &ParseOptions,
+    depth: usize,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
     let (input_1, max_stack) = be_u16(bytes)?;
     let (input_2, max_locals) = be_u16(input_1)?;
-    let (input_3, code_length) = be_u16(input_2)?;
+    let (input_3, code_length) = be_u32(input_2)?;
     let (input_4, code) = take(code_length as usize)(input_3)?;
     let (input_5, exception_table) = exception_table_from_bytes(input_4)?;
-    let (input_6, attributes) =
-        length_count(be_u16, |bytes| attribute_from_bytes(bytes, constant_pool))(input_5)?;
+    let (input_6, attributes) = length_count(be_u16, |bytes| {
+        attribute_from_bytes(bytes, constant_pool, options, depth + 1)
+    })(input_5)?;
 
     Ok((
         input_6,
         AttributeInfo::Code {
             max_stack,
             max_locals,
-            code,
+            code: Cow::Borrowed(code),
             exception_table,
             attributes,
         },
     ))
 }
 
-fn attribute_constant_value_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_constant_value_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, constantvalue_index) = be_u16(bytes)?;
 
     Ok((
@@ -245,7 +1009,9 @@ fn attribute_constant_value_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], Attri
     ))
 }
 
-fn attribute_enclosing_method_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_enclosing_method_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input_1, class_index) = be_u16(bytes)?;
     let (input_2, method_index) = be_u16(input_1)?;
 
@@ -258,7 +1024,9 @@ fn attribute_enclosing_method_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], Att
     ))
 }
 
-fn attribute_exceptions_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_exceptions_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, exception_index_table) = length_count(be_u16, be_u16)(bytes)?;
 
     Ok((
@@ -269,13 +1037,17 @@ fn attribute_exceptions_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], Attribute
     ))
 }
 
-fn attribute_inner_classes_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_inner_classes_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, classes) = length_count(be_u16, inner_class_from_bytes)(bytes)?;
 
     Ok((input, AttributeInfo::InnerClasses { classes }))
 }
 
-fn attribute_line_number_table_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_line_number_table_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, line_number_table) = length_count(be_u16, line_number_from_bytes)(bytes)?;
 
     Ok((input, AttributeInfo::LineNumberTable { line_number_table }))
@@ -283,7 +1055,7 @@ fn attribute_line_number_table_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], At
 
 fn attribute_local_variable_table_from_bytes<'a>(
     bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, local_variable_table) = length_count(be_u16, local_variable_from_bytes)(bytes)?;
 
     Ok((
@@ -296,7 +1068,7 @@ fn attribute_local_variable_table_from_bytes<'a>(
 
 fn attribute_local_variable_type_table_from_bytes<'a>(
     bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, local_variable_type_table) =
         length_count(be_u16, local_variable_type_from_bytes)(bytes)?;
 
@@ -308,13 +1080,17 @@ fn attribute_local_variable_type_table_from_bytes<'a>(
     ))
 }
 
-fn attribute_method_parameters_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, parameters) = length_count(be_u16, method_parameter_from_bytes)(bytes)?;
+fn attribute_method_parameters_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
+    let (input, parameters) = length_count(be_u8, method_parameter_from_bytes)(bytes)?;
 
     Ok((input, AttributeInfo::MethodParameters { parameters }))
 }
 
-fn attribute_module_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_module_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input_1, module_name_index) = be_u16(bytes)?;
     let (input_2, module_flags) = be_u16(input_1)?;
     let (input_3, module_version_index) = be_u16(input_2)?;
@@ -339,25 +1115,33 @@ fn attribute_module_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo
     ))
 }
 
-fn attribute_module_main_class_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_module_main_class_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, main_class_index) = be_u16(bytes)?;
 
     Ok((input, AttributeInfo::ModuleMainClass { main_class_index }))
 }
 
-fn attribute_module_packages_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_module_packages_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, package_index) = length_count(be_u16, be_u16)(bytes)?;
 
     Ok((input, AttributeInfo::ModulePackages { package_index }))
 }
 
-fn attribute_nest_host_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_nest_host_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, host_class_index) = be_u16(bytes)?;
 
     Ok((input, AttributeInfo::NestHost { host_class_index }))
 }
 
-fn attribute_nest_members_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_nest_members_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, classes) = length_count(be_u16, be_u16)(bytes)?;
 
     Ok((input, AttributeInfo::NestMembers { classes }))
@@ -365,7 +1149,7 @@ fn attribute_nest_members_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], Attribu
 
 fn attribute_permitted_subclasses_from_bytes<'a>(
     bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, classes) = length_count(be_u16, be_u16)(bytes)?;
 
     Ok((input, AttributeInfo::PermittedSubclasses { classes }))
@@ -373,19 +1157,23 @@ fn attribute_permitted_subclasses_from_bytes<'a>(
 
 fn attribute_record_from_bytes<'a>(
     bytes: &'a [u8],
-    constant_pool: &[ConstantPoolEntry<'a>],
-) -> IResult<&'a [u8], AttributeInfo<'a>> {
+    constant_pool: &ConstantPool<'a>,
+    options: &ParseOptions,
+    depth: usize,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
     let (input, components) = length_count(be_u16, |bytes| {
-        record_component_from_bytes(bytes, constant_pool)
+        record_component_from_bytes(bytes, constant_pool, options, depth)
     })(bytes)?;
 
     Ok((input, AttributeInfo::Record { components }))
 }
 
 fn attribute_runtime_invisible_annotations_from_bytes<'a>(
-    bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, annotations) = length_count(be_u16, annotation_from_bytes)(bytes)?;
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
+    let (input, annotations) =
+        length_count(be_u16, |bytes| annotation_from_bytes(bytes, options))(bytes)?;
 
     Ok((
         input,
@@ -394,9 +1182,11 @@ fn attribute_runtime_invisible_annotations_from_bytes<'a>(
 }
 
 fn attribute_runtime_invisible_parameter_annotations_from_bytes<'a>(
-    bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, parameter_annotations) = length_count(be_u16, annotation_from_bytes)(bytes)?;
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
+    let (input, parameter_annotations) =
+        length_count(be_u16, |bytes| annotation_from_bytes(bytes, options))(bytes)?;
 
     Ok((
         input,
@@ -407,9 +1197,11 @@ fn attribute_runtime_invisible_parameter_annotations_from_bytes<'a>(
 }
 
 fn attribute_runtime_invisible_type_annotations_from_bytes<'a>(
-    bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, type_annotations) = length_count(be_u16, type_annotation_from_bytes)(bytes)?;
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
+    let (input, type_annotations) =
+        length_count(be_u16, |bytes| type_annotation_from_bytes(bytes, options))(bytes)?;
 
     Ok((
         input,
@@ -418,9 +1210,11 @@ fn attribute_runtime_invisible_type_annotations_from_bytes<'a>(
 }
 
 fn attribute_runtime_visible_annotations_from_bytes<'a>(
-    bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, annotations) = length_count(be_u16, annotation_from_bytes)(bytes)?;
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
+    let (input, annotations) =
+        length_count(be_u16, |bytes| annotation_from_bytes(bytes, options))(bytes)?;
 
     Ok((
         input,
@@ -429,9 +1223,11 @@ fn attribute_runtime_visible_annotations_from_bytes<'a>(
 }
 
 fn attribute_runtime_visible_parameter_annotations_from_bytes<'a>(
-    bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, parameter_annotations) = length_count(be_u16, annotation_from_bytes)(bytes)?;
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
+    let (input, parameter_annotations) =
+        length_count(be_u16, |bytes| annotation_from_bytes(bytes, options))(bytes)?;
 
     Ok((
         input,
@@ -442,9 +1238,11 @@ fn attribute_runtime_visible_parameter_annotations_from_bytes<'a>(
 }
 
 fn attribute_runtime_visible_type_annotations_from_bytes<'a>(
-    bytes: &[u8],
-) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, type_annotations) = length_count(be_u16, type_annotation_from_bytes)(bytes)?;
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
+    let (input, type_annotations) =
+        length_count(be_u16, |bytes| type_annotation_from_bytes(bytes, options))(bytes)?;
 
     Ok((
         input,
@@ -452,7 +1250,9 @@ fn attribute_runtime_visible_type_annotations_from_bytes<'a>(
     ))
 }
 
-fn attribute_signature_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_signature_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, signature_index) = be_u16(bytes)?;
 
     Ok((input, AttributeInfo::Signature { signature_index }))
@@ -460,29 +1260,78 @@ fn attribute_signature_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeI
 
 fn attribute_source_debug_extension_from_bytes<'a>(
     bytes: &'a [u8],
-    length: u32,
-) -> IResult<&[u8], AttributeInfo<'a>> {
-    let (input, debug_extension) = take(length as usize)(bytes)?;
+) -> IResult<&'a [u8], AttributeInfo<'a>, ParseError<&'a [u8]>> {
+    // the extension is the whole body, with no length of its own
+    let (input, debug_extension) = rest(bytes)?;
 
     Ok((
         input,
-        AttributeInfo::SourceDebugExtension { debug_extension },
+        AttributeInfo::SourceDebugExtension {
+            debug_extension: Cow::Borrowed(debug_extension),
+        },
     ))
 }
 
-fn attribute_source_file_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_source_file_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, sourcefile_index) = be_u16(bytes)?;
 
     Ok((input, AttributeInfo::SourceFile { sourcefile_index }))
 }
 
-fn attribute_stack_map_table_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], AttributeInfo<'a>> {
+fn attribute_stack_map_table_from_bytes<'a>(
+    bytes: &[u8],
+) -> IResult<&[u8], AttributeInfo<'a>, ParseError<&[u8]>> {
     let (input, entries) = length_count(be_u16, stack_map_frame_from_bytes)(bytes)?;
 
     Ok((input, AttributeInfo::StackMapTable { entries }))
 }
 
-fn bootstrap_method_from_bytes(bytes: &[u8]) -> IResult<&[u8], BootstrapMethod> {
+// the attributes of a class, field or method, each of which is replaced by an Unknown attribute if its body
+// does not parse
+fn attributes_diagnostic<'a>(
+    classfile_bytes: &'a [u8],
+    bytes: &'a [u8],
+    constant_pool: &ConstantPool<'a>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> IResult<&'a [u8], Vec<Attribute<'a>>, ParseError<&'a [u8]>> {
+    let options = ParseOptions::default();
+
+    let (mut input, attributes_count) = section_count("attributes")(bytes)?;
+    let mut attributes = Vec::with_capacity(attributes_count as usize);
+
+    for _ in 0..attributes_count {
+        let error = match attribute_from_bytes(input, constant_pool, &options, 1) {
+            Ok((input_1, attribute)) => {
+                attributes.push(attribute);
+                input = input_1;
+                continue;
+            }
+            Err(error) => error,
+        };
+
+        // if even the name index and length are missing, there is nothing to skip to
+        let (input_1, attribute_name_index) = be_u16(input)?;
+        let (input_2, length) = be_u32(input_1)?;
+        let (input_3, raw) = take(length)(input_2)?;
+
+        diagnostics.push(diagnostic(classfile_bytes, input, error));
+        attributes.push(Attribute {
+            name_index: attribute_name_index,
+            info: AttributeInfo::Unknown {
+                name_index: attribute_name_index,
+                info: Cow::Borrowed(raw),
+            },
+            raw: None,
+        });
+        input = input_3;
+    }
+
+    Ok((input, attributes))
+}
+
+fn bootstrap_method_from_bytes(bytes: &[u8]) -> IResult<&[u8], BootstrapMethod, ParseError<&[u8]>> {
     let (input_1, bootstrap_method_ref) = be_u16(bytes)?;
     let (input_2, bootstrap_arguments) = length_count(be_u16, be_u16)(input_1)?;
 
@@ -495,14 +1344,112 @@ fn bootstrap_method_from_bytes(bytes: &[u8]) -> IResult<&[u8], BootstrapMethod>
     ))
 }
 
-fn classfile_version_from_bytes(bytes: &[u8]) -> IResult<&[u8], Version> {
+// fills in `classfile` section by section, failing with the problem that ends the class file
+fn classfile_sections_diagnostic<'a>(
+    bytes: &'a [u8],
+    classfile: &mut Classfile<'a>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(), Err<ParseError<&'a [u8]>>> {
+    // a class file with the wrong magic bytes may still have the right structure
+    let (input_1, magic) = magic_from_bytes(bytes)?;
+    if magic != MAGIC {
+        diagnostics.push(diagnostic(
+            bytes,
+            bytes,
+            Err::Failure(ParseError::BadMagic { found: magic }),
+        ));
+    }
+
+    let (input_2, version) = classfile_version_from_bytes(input_1)?;
+    classfile.version = version;
+
+    let (input_3, constant_pool_count) = section_count("constant pool")(input_2)?;
+    let (input_4, constant_pool) = constant_pool_from_bytes(input_3, constant_pool_count)?;
+    classfile.constant_pool = constant_pool;
+
+    let (input_5, access_flags) = be_u16(input_4)?;
+    let (input_6, this_class) = be_u16(input_5)?;
+    let (input_7, super_class) = be_u16(input_6)?;
+    classfile.access_flags = access_flags;
+    classfile.this_class = this_class;
+    classfile.super_class = super_class;
+
+    let (input_8, interfaces) = length_count(section_count("interfaces"), be_u16)(input_7)?;
+    classfile.interfaces = interfaces;
+
+    let constant_pool = &classfile.constant_pool;
+    let (mut input, fields_count) = section_count("fields")(input_8)?;
+    for _ in 0..fields_count {
+        let (input_1, member) = member_diagnostic(bytes, input, constant_pool, diagnostics)?;
+        classfile.fields.push(Field {
+            access_flags: member.access_flags,
+            name_index: member.name_index,
+            descriptor_index: member.descriptor_index,
+            attributes: member.attributes,
+        });
+        input = input_1;
+    }
+
+    let (mut input, methods_count) = section_count("methods")(input)?;
+    for _ in 0..methods_count {
+        let (input_1, member) = member_diagnostic(bytes, input, constant_pool, diagnostics)?;
+        classfile.methods.push(member);
+        input = input_1;
+    }
+
+    let (input, attributes) = attributes_diagnostic(bytes, input, constant_pool, diagnostics)?;
+    classfile.attributes = attributes;
+    if !input.is_empty() {
+        diagnostics.push(diagnostic(
+            bytes,
+            input,
+            Err::Failure(ParseError::TrailingBytes {
+                length: input.len(),
+            }),
+        ));
+    }
+
+    Ok(())
+}
+
+fn classfile_version_from_bytes(bytes: &[u8]) -> IResult<&[u8], Version, ParseError<&[u8]>> {
     let (input_1, minor) = be_u16(bytes)?;
     let (input_2, major) = be_u16(input_1)?;
 
     Ok((input_2, Version { minor, major }))
 }
 
-fn constant_pool_entry_from_bytes<'a>(bytes: &'a [u8]) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+// The entries of a constant pool whose count is `constant_pool_count`, which is one more than the number of
+// indices the entries take up, as index 0 is reserved. A Long or Double entry takes up two, so it is followed
+// by an Unusable entry for the second, keeping every entry at the offset its index points to.
+fn constant_pool_from_bytes<'a>(
+    bytes: &'a [u8],
+    constant_pool_count: u16,
+) -> IResult<&'a [u8], ConstantPool<'a>, ParseError<&'a [u8]>> {
+    let slots = constant_pool_count.saturating_sub(1) as usize;
+    let mut entries = Vec::new();
+    let mut input = bytes;
+    while entries.len() < slots {
+        let (rest, entry) = constant_pool_entry_from_bytes(input)?;
+        let is_wide = matches!(
+            entry,
+            ConstantPoolEntry::Long { .. } | ConstantPoolEntry::Double { .. }
+        );
+
+        entries.push(entry);
+        // a Long or Double entry in the last slot has no second one to give up
+        if is_wide && entries.len() < slots {
+            entries.push(ConstantPoolEntry::Unusable);
+        }
+        input = rest;
+    }
+
+    Ok((input, ConstantPool::new(entries)))
+}
+
+fn constant_pool_entry_from_bytes<'a>(
+    bytes: &'a [u8],
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input, tag) = be_u8(bytes)?;
 
     match tag {
@@ -523,13 +1470,16 @@ fn constant_pool_entry_from_bytes<'a>(bytes: &'a [u8]) -> IResult<&[u8], Constan
         18 => constant_pool_invoke_dynamic_entry_from_bytes(input),
         19 => constant_pool_module_entry_from_bytes(input),
         20 => constant_pool_package_entry_from_bytes(input),
-        _ => Err(Err::Error(Error::new(bytes, ErrorKind::Tag))),
+        _ => Err(Err::Error(ParseError::Nom(Error::new(
+            bytes,
+            ErrorKind::Tag,
+        )))),
     }
 }
 
 fn constant_pool_class_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input, name_index) = be_u16(bytes)?;
 
     Ok((input, ConstantPoolEntry::Class { name_index }))
@@ -537,21 +1487,21 @@ fn constant_pool_class_entry_from_bytes<'a>(
 
 fn constant_pool_double_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, high_bytes) = be_u32(bytes)?;
     let (input_2, low_bytes) = be_u32(input_1)?;
 
     Ok((
         input_2,
         ConstantPoolEntry::Double {
-            value: f64::from_bits((high_bytes as u64) << 32 + low_bytes as u64),
+            value: f64::from_bits(((high_bytes as u64) << 32) | low_bytes as u64),
         },
     ))
 }
 
 fn constant_pool_dynamic_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, bootstrap_method_attr_index) = be_u16(bytes)?;
     let (input_2, name_and_type_index) = be_u16(input_1)?;
 
@@ -566,7 +1516,7 @@ fn constant_pool_dynamic_entry_from_bytes<'a>(
 
 fn constant_pool_float_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input, float) = be_u32(bytes)?;
 
     Ok((
@@ -579,7 +1529,7 @@ fn constant_pool_float_entry_from_bytes<'a>(
 
 fn constant_pool_field_ref_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, class_index) = be_u16(bytes)?;
     let (input_2, name_and_type_index) = be_u16(input_1)?;
 
@@ -594,7 +1544,7 @@ fn constant_pool_field_ref_entry_from_bytes<'a>(
 
 fn constant_pool_instance_method_ref_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, class_index) = be_u16(bytes)?;
     let (input_2, name_and_type_index) = be_u16(input_1)?;
 
@@ -609,7 +1559,7 @@ fn constant_pool_instance_method_ref_entry_from_bytes<'a>(
 
 fn constant_pool_integer_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input, integer) = be_u32(bytes)?;
 
     Ok((input, ConstantPoolEntry::Integer { bytes: integer }))
@@ -617,7 +1567,7 @@ fn constant_pool_integer_entry_from_bytes<'a>(
 
 fn constant_pool_invoke_dynamic_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, bootstrap_method_attr_index) = be_u16(bytes)?;
     let (input_2, name_and_type_index) = be_u16(input_1)?;
 
@@ -632,21 +1582,21 @@ fn constant_pool_invoke_dynamic_entry_from_bytes<'a>(
 
 fn constant_pool_long_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, high_bytes) = be_u32(bytes)?;
     let (input_2, low_bytes) = be_u32(input_1)?;
 
     Ok((
         input_2,
         ConstantPoolEntry::Long {
-            value: (high_bytes as u64) << 32 + low_bytes as u64,
+            value: ((high_bytes as u64) << 32) | low_bytes as u64,
         },
     ))
 }
 
 fn constant_pool_method_handle_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, reference_kind) = be_u8(bytes)?;
     let (input_2, reference_index) = be_u16(input_1)?;
 
@@ -661,7 +1611,7 @@ fn constant_pool_method_handle_entry_from_bytes<'a>(
 
 fn constant_pool_method_type_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input, reference_index) = be_u16(bytes)?;
 
     Ok((input, ConstantPoolEntry::MethodType { reference_index }))
@@ -669,7 +1619,7 @@ fn constant_pool_method_type_entry_from_bytes<'a>(
 
 fn constant_pool_method_ref_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, class_index) = be_u16(bytes)?;
     let (input_2, name_and_type_index) = be_u16(input_1)?;
 
@@ -684,7 +1634,7 @@ fn constant_pool_method_ref_entry_from_bytes<'a>(
 
 fn constant_pool_module_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input, name_index) = be_u16(bytes)?;
 
     Ok((input, ConstantPoolEntry::Module { name_index }))
@@ -692,7 +1642,7 @@ fn constant_pool_module_entry_from_bytes<'a>(
 
 fn constant_pool_name_and_type_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, name_index) = be_u16(bytes)?;
     let (input_2, descriptor_index) = be_u16(input_1)?;
 
@@ -707,7 +1657,7 @@ fn constant_pool_name_and_type_entry_from_bytes<'a>(
 
 fn constant_pool_package_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input, name_index) = be_u16(bytes)?;
 
     Ok((input, ConstantPoolEntry::Package { name_index }))
@@ -715,7 +1665,7 @@ fn constant_pool_package_entry_from_bytes<'a>(
 
 fn constant_pool_string_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input, string_index) = be_u16(bytes)?;
 
     Ok((input, ConstantPoolEntry::String { string_index }))
@@ -723,21 +1673,61 @@ fn constant_pool_string_entry_from_bytes<'a>(
 
 fn constant_pool_utf8_entry_from_bytes<'a>(
     bytes: &'a [u8],
-) -> IResult<&[u8], ConstantPoolEntry<'a>> {
+) -> IResult<&'a [u8], ConstantPoolEntry<'a>, ParseError<&'a [u8]>> {
     let (input_1, length) = be_u16(bytes)?;
     let (input_2, str_bytes) = take(length as usize)(input_1)?;
 
-    Ok((input_2, ConstantPoolEntry::Utf8 { bytes: str_bytes }))
+    Ok((
+        input_2,
+        ConstantPoolEntry::Utf8 {
+            bytes: Cow::Borrowed(str_bytes),
+        },
+    ))
+}
+
+// `at` is where the failed parse started, for the errors that do not point at the input themselves
+fn diagnostic(classfile_bytes: &[u8], at: &[u8], error: Err<ParseError<&[u8]>>) -> Diagnostic {
+    let offset_of = |input: &[u8]| input.as_ptr() as usize - classfile_bytes.as_ptr() as usize;
+
+    match error {
+        Err::Error(ParseError::Nom(error)) | Err::Failure(ParseError::Nom(error)) => Diagnostic {
+            offset: offset_of(error.input),
+            message: ParseError::Nom(error).to_string(),
+        },
+        // the input ran out, so the problem is at its end
+        Err::Failure(error @ ParseError::Truncated { .. }) => Diagnostic {
+            offset: classfile_bytes.len(),
+            message: error.to_string(),
+        },
+        Err::Error(error) | Err::Failure(error) => Diagnostic {
+            offset: offset_of(at),
+            message: error.to_string(),
+        },
+        // the parsers all work on complete input
+        Err::Incomplete(_) => Diagnostic {
+            offset: classfile_bytes.len(),
+            message: "class file ends early".to_string(),
+        },
+    }
 }
 
-fn element_value_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], ElementValue> {
+fn element_value_from_bytes<'a>(
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], ElementValue<'a>, ParseError<&'a [u8]>> {
     let (input_1, tag) = be_u8(bytes)?;
 
     Ok(match tag as char {
         // byte or char or double or float or int or long or short or boolean or string
         'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' | 's' => {
             let (input_2, const_value_index) = be_u16(input_1)?;
-            (input_2, ElementValue::ConstValue(const_value_index))
+            (
+                input_2,
+                ElementValue::ConstValue {
+                    tag,
+                    const_value_index,
+                },
+            )
         }
         // enum class
         'e' => {
@@ -758,21 +1748,30 @@ fn element_value_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], ElementValue> {
         }
         // annotation interface
         '@' => {
-            let (input_2, annotation) = annotation_from_bytes(input_1)?;
+            let (input_2, annotation) = annotation_from_bytes(input_1, options)?;
             (input_2, ElementValue::Annotation(annotation))
         }
         // array type
         '[' => {
-            let (input_2, values) = length_count(be_u16, element_value_from_bytes)(input_1)?;
+            let (input_2, values) =
+                length_count(be_u16, |bytes| element_value_from_bytes(bytes, options))(input_1)?;
             (input_2, ElementValue::Array { values })
         }
-        _ => return Err(Err::Failure(Error::new(bytes, ErrorKind::Tag))),
+        _ => {
+            return Err(Err::Failure(ParseError::Nom(Error::new(
+                bytes,
+                ErrorKind::Tag,
+            ))))
+        }
     })
 }
 
-fn element_value_pair_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], ElementValuePair> {
+fn element_value_pair_from_bytes<'a>(
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], ElementValuePair<'a>, ParseError<&'a [u8]>> {
     let (input_1, element_name_index) = be_u16(bytes)?;
-    let (input_2, element_value) = element_value_from_bytes(input_1)?;
+    let (input_2, element_value) = element_value_from_bytes(input_1, options)?;
 
     Ok((
         input_2,
@@ -783,11 +1782,15 @@ fn element_value_pair_from_bytes<'a>(bytes: &[u8]) -> IResult<&[u8], ElementValu
     ))
 }
 
-fn exception_table_from_bytes(bytes: &[u8]) -> IResult<&[u8], Vec<ExceptionTableEntry>> {
+fn exception_table_from_bytes(
+    bytes: &[u8],
+) -> IResult<&[u8], Vec<ExceptionTableEntry>, ParseError<&[u8]>> {
     length_count(be_u16, exception_table_entry_from_bytes)(bytes)
 }
 
-fn exception_table_entry_from_bytes(bytes: &[u8]) -> IResult<&[u8], ExceptionTableEntry> {
+fn exception_table_entry_from_bytes(
+    bytes: &[u8],
+) -> IResult<&[u8], ExceptionTableEntry, ParseError<&[u8]>> {
     let (input_1, start_pc) = be_u16(bytes)?;
     let (input_2, end_pc) = be_u16(input_1)?;
     let (input_3, handler_pc) = be_u16(input_2)?;
@@ -806,13 +1809,15 @@ fn exception_table_entry_from_bytes(bytes: &[u8]) -> IResult<&[u8], ExceptionTab
 
 fn field_from_bytes<'a>(
     bytes: &'a [u8],
-    constant_pool: &[ConstantPoolEntry<'a>],
-) -> IResult<&'a [u8], Field<'a>> {
+    constant_pool: &ConstantPool<'a>,
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Field<'a>, ParseError<&'a [u8]>> {
     let (input_1, access_flags) = be_u16(bytes)?;
     let (input_2, name_index) = be_u16(input_1)?;
     let (input_3, descriptor_index) = be_u16(input_2)?;
-    let (input_4, attributes) =
-        length_count(be_u16, |bytes| attribute_from_bytes(bytes, constant_pool))(input_3)?;
+    let (input_4, attributes) = length_count(section_count("attributes"), |bytes| {
+        attribute_from_bytes(bytes, constant_pool, options, 1)
+    })(input_3)?;
 
     Ok((
         input_4,
@@ -825,7 +1830,7 @@ fn field_from_bytes<'a>(
     ))
 }
 
-fn inner_class_from_bytes(bytes: &[u8]) -> IResult<&[u8], InnerClass> {
+fn inner_class_from_bytes(bytes: &[u8]) -> IResult<&[u8], InnerClass, ParseError<&[u8]>> {
     let (input_1, inner_class_info_index) = be_u16(bytes)?;
     let (input_2, outer_class_info_index) = be_u16(input_1)?;
     let (input_3, inner_name_index) = be_u16(input_2)?;
@@ -842,7 +1847,49 @@ fn inner_class_from_bytes(bytes: &[u8]) -> IResult<&[u8], InnerClass> {
     ))
 }
 
-fn line_number_from_bytes(bytes: &[u8]) -> IResult<&[u8], LineNumber> {
+fn jvms_violation<I>(section: &'static str, message: String) -> Err<ParseError<I>> {
+    Err::Failure(ParseError::JvmsViolation { section, message })
+}
+
+// The method's Code attribute, which parsing with lazy_code left undecoded among its attributes, to be
+// decoded by Method::code. A method without one gets a LazyCode that decodes to `None`.
+fn lazy_code<'a>(
+    attributes: &[Attribute<'a>],
+    constant_pool: &Arc<ConstantPool<'a>>,
+    options: &ParseOptions,
+) -> LazyCode<'a> {
+    let body = attributes
+        .iter()
+        .find_map(|attribute| match &attribute.info {
+            AttributeInfo::Unknown {
+                name_index,
+                info: Cow::Borrowed(info),
+            } if constant_pool
+                .utf8(*name_index)
+                .is_ok_and(|name| name == "Code") =>
+            {
+                Some((*name_index, *info))
+            }
+            _ => None,
+        });
+    let Some((name_index, info)) = body else {
+        return LazyCode::default();
+    };
+
+    LazyCode {
+        name_index,
+        info,
+        constant_pool: Some(Arc::clone(constant_pool)),
+        options: *options,
+        code: OnceLock::new(),
+    }
+}
+
+fn limit_exceeded<I>(which: Limit) -> Err<ParseError<I>> {
+    Err::Failure(ParseError::LimitExceeded { which })
+}
+
+fn line_number_from_bytes(bytes: &[u8]) -> IResult<&[u8], LineNumber, ParseError<&[u8]>> {
     let (input_1, start_pc) = be_u16(bytes)?;
     let (input_2, line_number) = be_u16(input_1)?;
 
@@ -855,7 +1902,7 @@ fn line_number_from_bytes(bytes: &[u8]) -> IResult<&[u8], LineNumber> {
     ))
 }
 
-fn local_var_from_bytes(bytes: &[u8]) -> IResult<&[u8], LocalVar> {
+fn local_var_from_bytes(bytes: &[u8]) -> IResult<&[u8], LocalVar, ParseError<&[u8]>> {
     let (input_1, start_pc) = be_u16(bytes)?;
     let (input_2, length) = be_u16(input_1)?;
     let (input_3, index) = be_u16(input_2)?;
@@ -870,7 +1917,7 @@ fn local_var_from_bytes(bytes: &[u8]) -> IResult<&[u8], LocalVar> {
     ))
 }
 
-fn local_variable_from_bytes(bytes: &[u8]) -> IResult<&[u8], LocalVariable> {
+fn local_variable_from_bytes(bytes: &[u8]) -> IResult<&[u8], LocalVariable, ParseError<&[u8]>> {
     let (input_1, start_pc) = be_u16(bytes)?;
     let (input_2, length) = be_u16(input_1)?;
     let (input_3, name_index) = be_u16(input_2)?;
@@ -889,7 +1936,9 @@ fn local_variable_from_bytes(bytes: &[u8]) -> IResult<&[u8], LocalVariable> {
     ))
 }
 
-fn local_variable_type_from_bytes(bytes: &[u8]) -> IResult<&[u8], LocalVariableType> {
+fn local_variable_type_from_bytes(
+    bytes: &[u8],
+) -> IResult<&[u8], LocalVariableType, ParseError<&[u8]>> {
     let (input_1, start_pc) = be_u16(bytes)?;
     let (input_2, length) = be_u16(input_1)?;
     let (input_3, name_index) = be_u16(input_2)?;
@@ -908,15 +1957,52 @@ fn local_variable_type_from_bytes(bytes: &[u8]) -> IResult<&[u8], LocalVariableT
     ))
 }
 
-fn method_from_bytes<'a>(
+// the first four bytes, whatever they are
+fn magic_from_bytes(bytes: &[u8]) -> IResult<&[u8], [u8; 4], ParseError<&[u8]>> {
+    match bytes.split_first_chunk() {
+        Some((magic, input)) => Ok((input, *magic)),
+        None => Err(Err::Failure(ParseError::Truncated {
+            section: Some("magic bytes"),
+        })),
+    }
+}
+
+// fields have the same layout as methods, and are read as such
+fn member_diagnostic<'a>(
+    classfile_bytes: &'a [u8],
     bytes: &'a [u8],
-    constant_pool: &[ConstantPoolEntry<'a>],
-) -> IResult<&'a [u8], Method<'a>> {
+    constant_pool: &ConstantPool<'a>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> IResult<&'a [u8], Method<'a>, ParseError<&'a [u8]>> {
     let (input_1, access_flags) = be_u16(bytes)?;
     let (input_2, name_index) = be_u16(input_1)?;
     let (input_3, descriptor_index) = be_u16(input_2)?;
     let (input_4, attributes) =
-        length_count(be_u16, |bytes| attribute_from_bytes(bytes, constant_pool))(input_3)?;
+        attributes_diagnostic(classfile_bytes, input_3, constant_pool, diagnostics)?;
+
+    Ok((
+        input_4,
+        Method {
+            access_flags,
+            name_index,
+            descriptor_index,
+            attributes,
+            lazy_code: LazyCode::default(),
+        },
+    ))
+}
+
+fn method_from_bytes<'a>(
+    bytes: &'a [u8],
+    constant_pool: &ConstantPool<'a>,
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Method<'a>, ParseError<&'a [u8]>> {
+    let (input_1, access_flags) = be_u16(bytes)?;
+    let (input_2, name_index) = be_u16(input_1)?;
+    let (input_3, descriptor_index) = be_u16(input_2)?;
+    let (input_4, attributes) = length_count(section_count("attributes"), |bytes| {
+        attribute_from_bytes(bytes, constant_pool, options, 1)
+    })(input_3)?;
 
     Ok((
         input_4,
@@ -925,11 +2011,12 @@ fn method_from_bytes<'a>(
             name_index,
             descriptor_index,
             attributes,
+            lazy_code: LazyCode::default(),
         },
     ))
 }
 
-fn method_parameter_from_bytes(bytes: &[u8]) -> IResult<&[u8], MethodParameter> {
+fn method_parameter_from_bytes(bytes: &[u8]) -> IResult<&[u8], MethodParameter, ParseError<&[u8]>> {
     let (input_1, name_index) = be_u16(bytes)?;
     let (input_2, access_flags) = be_u16(input_1)?;
 
@@ -942,7 +2029,7 @@ fn method_parameter_from_bytes(bytes: &[u8]) -> IResult<&[u8], MethodParameter>
     ))
 }
 
-fn module_export_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleExports> {
+fn module_export_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleExports, ParseError<&[u8]>> {
     let (input_1, exports_index) = be_u16(bytes)?;
     let (input_2, exports_flags) = be_u16(input_1)?;
     let (input_3, exports_to_indices) = length_count(be_u16, be_u16)(input_2)?;
@@ -957,7 +2044,7 @@ fn module_export_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleExports> {
     ))
 }
 
-fn module_opens_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleOpens> {
+fn module_opens_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleOpens, ParseError<&[u8]>> {
     let (input_1, opens_index) = be_u16(bytes)?;
     let (input_2, opens_flags) = be_u16(input_1)?;
     let (input_3, opens_to_indices) = length_count(be_u16, be_u16)(input_2)?;
@@ -972,7 +2059,7 @@ fn module_opens_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleOpens> {
     ))
 }
 
-fn module_provides_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleProvides> {
+fn module_provides_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleProvides, ParseError<&[u8]>> {
     let (input_1, provides_index) = be_u16(bytes)?;
     let (input_2, provides_with_indices) = length_count(be_u16, be_u16)(input_1)?;
 
@@ -985,7 +2072,7 @@ fn module_provides_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleProvides> {
     ))
 }
 
-fn module_require_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleRequires> {
+fn module_require_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleRequires, ParseError<&[u8]>> {
     let (input_1, requires_index) = be_u16(bytes)?;
     let (input_2, requires_flags) = be_u16(input_1)?;
     let (input_3, requires_version_index) = be_u16(input_2)?;
@@ -1002,12 +2089,15 @@ fn module_require_from_bytes(bytes: &[u8]) -> IResult<&[u8], ModuleRequires> {
 
 fn record_component_from_bytes<'a>(
     bytes: &'a [u8],
-    constant_pool: &[ConstantPoolEntry<'a>],
-) -> IResult<&'a [u8], RecordComponent<'a>> {
+    constant_pool: &ConstantPool<'a>,
+    options: &ParseOptions,
+    depth: usize,
+) -> IResult<&'a [u8], RecordComponent<'a>, ParseError<&'a [u8]>> {
     let (input_1, name_index) = be_u16(bytes)?;
     let (input_2, descriptor_index) = be_u16(input_1)?;
-    let (input_3, attributes) =
-        length_count(be_u16, |bytes| attribute_from_bytes(bytes, constant_pool))(input_2)?;
+    let (input_3, attributes) = length_count(be_u16, |bytes| {
+        attribute_from_bytes(bytes, constant_pool, options, depth + 1)
+    })(input_2)?;
 
     Ok((
         input_3,
@@ -1019,15 +2109,40 @@ fn record_component_from_bytes<'a>(
     ))
 }
 
-fn stack_map_frame_from_bytes(bytes: &[u8]) -> IResult<&[u8], StackMapFrame> {
+// The count a section of the class file starts with. The class file cannot be complete if it is missing,
+// so running out of input there is reported naming the section.
+fn section_count<'a>(
+    section: &'static str,
+) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], u16, ParseError<&'a [u8]>> {
+    move |bytes| {
+        be_u16(bytes).map_err(|_: Err<ParseError<&[u8]>>| {
+            Err::Failure(ParseError::Truncated {
+                section: Some(section),
+            })
+        })
+    }
+}
+
+fn stack_map_frame_from_bytes(bytes: &[u8]) -> IResult<&[u8], StackMapFrame, ParseError<&[u8]>> {
     let (input_1, tag) = be_u8(bytes)?;
 
     Ok(match tag {
-        0..=63 => (input_1, StackMapFrame::SameFrame),
-        64..=127 => {
-            let (input_2, stack) = verification_type_info_from_bytes(bytes)?;
+        frame_type @ 0..=63 => (
+            input_1,
+            StackMapFrame::SameFrame {
+                offset_delta: frame_type,
+            },
+        ),
+        frame_type @ 64..=127 => {
+            let (input_2, stack) = verification_type_info_from_bytes(input_1)?;
 
-            (input_2, StackMapFrame::SameLocals1StackItemFrame { stack })
+            (
+                input_2,
+                StackMapFrame::SameLocals1StackItemFrame {
+                    offset_delta: frame_type - 64,
+                    stack,
+                },
+            )
         }
         247 => {
             let (input_2, offset_delta) = be_u16(input_1)?;
@@ -1041,10 +2156,16 @@ fn stack_map_frame_from_bytes(bytes: &[u8]) -> IResult<&[u8], StackMapFrame> {
                 },
             )
         }
-        248..=250 => {
+        frame_type @ 248..=250 => {
             let (input_2, offset_delta) = be_u16(input_1)?;
 
-            (input_2, StackMapFrame::ChopFrame { offset_delta })
+            (
+                input_2,
+                StackMapFrame::ChopFrame {
+                    offset_delta,
+                    absent_locals: 251 - frame_type,
+                },
+            )
         }
         251 => {
             let (input_2, offset_delta) = be_u16(input_1)?;
@@ -1052,9 +2173,11 @@ fn stack_map_frame_from_bytes(bytes: &[u8]) -> IResult<&[u8], StackMapFrame> {
             (input_2, StackMapFrame::SameFrameExtended { offset_delta })
         }
         frame_type @ 252..=254 => {
-            let (input_2, offset_delta) = be_u16(bytes)?;
-            let (input_3, locals) =
-                count(verification_type_info_from_bytes, frame_type - 251)(input_2)?;
+            let (input_2, offset_delta) = be_u16(input_1)?;
+            let (input_3, locals) = count(
+                verification_type_info_from_bytes,
+                (frame_type - 251) as usize,
+            )(input_2)?;
 
             (
                 input_3,
@@ -1065,7 +2188,7 @@ fn stack_map_frame_from_bytes(bytes: &[u8]) -> IResult<&[u8], StackMapFrame> {
             )
         }
         255 => {
-            let (input_2, offset_delta) = be_u16(bytes)?;
+            let (input_2, offset_delta) = be_u16(input_1)?;
             let (input_3, locals) =
                 length_count(be_u16, verification_type_info_from_bytes)(input_2)?;
             let (input_4, stack) =
@@ -1080,11 +2203,270 @@ fn stack_map_frame_from_bytes(bytes: &[u8]) -> IResult<&[u8], StackMapFrame> {
                 },
             )
         }
-        _ => return Err(Err::Failure(Error::new(input_1, ErrorKind::Tag))),
+        _ => {
+            return Err(Err::Failure(ParseError::Nom(Error::new(
+                input_1,
+                ErrorKind::Tag,
+            ))))
+        }
     })
 }
 
-fn target_info_from_bytes(bytes: &[u8], target_type: u8) -> IResult<&[u8], TargetInfo> {
+// the first of the attributes that repeats one of which there may be only one, or the first such attribute
+// nested in them
+fn strict_attributes_violation(attributes: &[Attribute]) -> Option<(&'static str, String)> {
+    for (i, attribute) in attributes.iter().enumerate() {
+        let name = attribute.info.standard_name();
+        let single = SINGLE_ATTRIBUTES
+            .iter()
+            .find(|(single, _)| Some(*single) == name);
+        if let (Some(name), Some(&(_, section))) = (name, single) {
+            if attributes[..i]
+                .iter()
+                .any(|earlier| earlier.info.standard_name() == Some(name))
+            {
+                return Some((
+                    section,
+                    format!("attribute `{name}` appears more than once"),
+                ));
+            }
+        }
+
+        let nested = match &attribute.info {
+            AttributeInfo::Code { attributes, .. } => strict_attributes_violation(attributes),
+            AttributeInfo::Record { components } => components
+                .iter()
+                .find_map(|component| strict_attributes_violation(&component.attributes)),
+            _ => None,
+        };
+        if nested.is_some() {
+            return nested;
+        }
+    }
+
+    None
+}
+
+fn strict_class_flags_violation(flags: u16) -> Option<&'static str> {
+    if flags & AccessFlags::MODULE != 0 && flags != AccessFlags::MODULE {
+        Some("the module is ACC_MODULE together with other flags")
+    } else if flags & AccessFlags::INTERFACE != 0 {
+        if flags & AccessFlags::ABSTRACT == 0 {
+            Some("the interface is not ACC_ABSTRACT")
+        } else if flags & (AccessFlags::FINAL | AccessFlags::SUPER | AccessFlags::ENUM) != 0 {
+            Some("the interface is ACC_FINAL, ACC_SUPER or ACC_ENUM")
+        } else {
+            None
+        }
+    } else if flags & AccessFlags::ANNOTATION != 0 {
+        Some("the class is ACC_ANNOTATION without being ACC_INTERFACE")
+    } else if flags & AccessFlags::FINAL != 0 && flags & AccessFlags::ABSTRACT != 0 {
+        Some("the class is both ACC_FINAL and ACC_ABSTRACT")
+    } else {
+        None
+    }
+}
+
+// the first entry that refers to an entry outside the pool or of a kind it may not refer to
+fn strict_constant_pool_violation(
+    constant_pool: &ConstantPool,
+    version: Version,
+) -> Option<(&'static str, String)> {
+    for (index, entry) in (1..).zip(constant_pool.entries()) {
+        let (section, expected): (_, &[&str]) = match entry {
+            ConstantPoolEntry::Class { .. } => ("4.4.1", &["Utf8"]),
+            ConstantPoolEntry::FieldRef { .. }
+            | ConstantPoolEntry::MethodRef { .. }
+            | ConstantPoolEntry::InstanceMethodRef { .. } => ("4.4.2", &["Class", "NameAndType"]),
+            ConstantPoolEntry::String { .. } => ("4.4.3", &["Utf8"]),
+            ConstantPoolEntry::NameAndType { .. } => ("4.4.6", &["Utf8", "Utf8"]),
+            ConstantPoolEntry::MethodHandle { reference_kind, .. } => {
+                if let Err(error) = constant_pool.resolve_method_handle(index, version) {
+                    return Some((
+                        "4.4.8",
+                        format!(
+                            "MethodHandle entry {index} does not refer to a member that reference kind \
+                             {reference_kind} allows: {error}"
+                        ),
+                    ));
+                }
+                continue;
+            }
+            ConstantPoolEntry::MethodType { .. } => ("4.4.9", &["Utf8"]),
+            ConstantPoolEntry::Dynamic { .. } | ConstantPoolEntry::InvokeDynamic { .. } => {
+                ("4.4.10", &["NameAndType"])
+            }
+            ConstantPoolEntry::Module { .. } => ("4.4.11", &["Utf8"]),
+            ConstantPoolEntry::Package { .. } => ("4.4.12", &["Utf8"]),
+            _ => continue,
+        };
+
+        for (reference, &expected) in entry.references().into_iter().zip(expected) {
+            let is_expected = match constant_pool.get(reference) {
+                Ok(ConstantPoolEntry::Utf8 { .. }) => expected == "Utf8",
+                Ok(ConstantPoolEntry::Class { .. }) => expected == "Class",
+                Ok(ConstantPoolEntry::NameAndType { .. }) => expected == "NameAndType",
+                _ => false,
+            };
+            if !is_expected {
+                return Some((
+                    section,
+                    format!(
+                        "constant pool entry {index} refers to entry {reference}, which is not a {expected} \
+                         entry"
+                    ),
+                ));
+            }
+        }
+    }
+
+    None
+}
+
+fn strict_field_flags_violation(flags: u16, in_interface: bool) -> Option<&'static str> {
+    let access = FieldAccessFlags::PUBLIC | FieldAccessFlags::PRIVATE | FieldAccessFlags::PROTECTED;
+    let interface_field =
+        FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC | FieldAccessFlags::FINAL;
+
+    if (flags & access).count_ones() > 1 {
+        Some("is more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED")
+    } else if flags & FieldAccessFlags::FINAL != 0 && flags & FieldAccessFlags::VOLATILE != 0 {
+        Some("is both ACC_FINAL and ACC_VOLATILE")
+    } else if in_interface
+        && (flags & interface_field != interface_field
+            || flags & !(interface_field | FieldAccessFlags::SYNTHETIC) != 0)
+    {
+        Some("is in an interface, but not ACC_PUBLIC, ACC_STATIC and ACC_FINAL with at most ACC_SYNTHETIC")
+    } else {
+        None
+    }
+}
+
+fn strict_method_flags_violation(
+    name: &str,
+    flags: u16,
+    in_interface: bool,
+    major: u16,
+) -> Option<&'static str> {
+    let access =
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::PRIVATE | MethodAccessFlags::PROTECTED;
+
+    // the flags of a class initialization method other than ACC_STATIC and ACC_STRICT are ignored
+    if name == "<clinit>" {
+        return None;
+    }
+    if (flags & access).count_ones() > 1 {
+        return Some("is more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED");
+    }
+    if name == "<init>" {
+        let allowed = access
+            | MethodAccessFlags::VARARGS
+            | MethodAccessFlags::STRICT
+            | MethodAccessFlags::SYNTHETIC;
+        return (flags & !allowed != 0).then_some(
+            "is an instance initialization method with flags other than ACC_VARARGS, ACC_STRICT and \
+             ACC_SYNTHETIC",
+        );
+    }
+
+    if in_interface && major < 52 {
+        let required = MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT;
+        let allowed = required
+            | MethodAccessFlags::VARARGS
+            | MethodAccessFlags::BRIDGE
+            | MethodAccessFlags::SYNTHETIC;
+        if flags & required != required || flags & !allowed != 0 {
+            return Some(
+                "is in an interface before version 52.0, but not ACC_PUBLIC and ACC_ABSTRACT with at most \
+                 ACC_VARARGS, ACC_BRIDGE and ACC_SYNTHETIC",
+            );
+        }
+    } else if in_interface {
+        let forbidden = MethodAccessFlags::PROTECTED
+            | MethodAccessFlags::FINAL
+            | MethodAccessFlags::SYNCHRONIZED
+            | MethodAccessFlags::NATIVE;
+        if (flags & access).count_ones() != 1 || flags & forbidden != 0 {
+            return Some(
+                "is in an interface, but not one of ACC_PUBLIC and ACC_PRIVATE, or ACC_PROTECTED, ACC_FINAL, \
+                 ACC_SYNCHRONIZED or ACC_NATIVE",
+            );
+        }
+    }
+
+    // ACC_STRICT only had a meaning from version 46.0 up to 60.0
+    let mut not_abstract = MethodAccessFlags::PRIVATE
+        | MethodAccessFlags::STATIC
+        | MethodAccessFlags::FINAL
+        | MethodAccessFlags::SYNCHRONIZED
+        | MethodAccessFlags::NATIVE;
+    if (46..=60).contains(&major) {
+        not_abstract |= MethodAccessFlags::STRICT;
+    }
+
+    (flags & MethodAccessFlags::ABSTRACT != 0 && flags & not_abstract != 0).then_some(
+        "is ACC_ABSTRACT together with ACC_PRIVATE, ACC_STATIC, ACC_FINAL, ACC_SYNCHRONIZED, ACC_NATIVE or \
+         ACC_STRICT",
+    )
+}
+
+// the first violation of the rules that strict_jvms checks once the whole class file is read, with the
+// section of the JVMS it violates
+fn strict_violation(classfile: &Classfile) -> Option<(&'static str, String)> {
+    let constant_pool = &classfile.constant_pool;
+    let name = |index: u16| {
+        constant_pool
+            .utf8(index)
+            .map_or_else(|_| format!("#{index}"), Cow::into_owned)
+    };
+    let in_interface = classfile.access_flags & AccessFlags::INTERFACE != 0;
+
+    strict_constant_pool_violation(constant_pool, classfile.version)
+        .or_else(|| {
+            strict_class_flags_violation(classfile.access_flags)
+                .map(|message| ("4.1", message.to_string()))
+        })
+        .or_else(|| {
+            classfile.fields.iter().find_map(|field| {
+                strict_field_flags_violation(field.access_flags, in_interface).map(|reason| {
+                    (
+                        "4.5",
+                        format!("field `{}` {reason}", name(field.name_index)),
+                    )
+                })
+            })
+        })
+        .or_else(|| {
+            classfile.methods.iter().find_map(|method| {
+                let method_name = name(method.name_index);
+                strict_method_flags_violation(
+                    &method_name,
+                    method.access_flags,
+                    in_interface,
+                    classfile.version.major,
+                )
+                .map(|reason| ("4.6", format!("method `{method_name}` {reason}")))
+            })
+        })
+        .or_else(|| strict_attributes_violation(&classfile.attributes))
+        .or_else(|| {
+            classfile
+                .fields
+                .iter()
+                .find_map(|field| strict_attributes_violation(&field.attributes))
+        })
+        .or_else(|| {
+            classfile
+                .methods
+                .iter()
+                .find_map(|method| strict_attributes_violation(&method.attributes))
+        })
+}
+
+fn target_info_from_bytes(
+    bytes: &[u8],
+    target_type: u8,
+) -> IResult<&[u8], TargetInfo, ParseError<&[u8]>> {
     Ok(match target_type {
         0x00 | 0x01 => {
             let (input_1, type_parameter_index) = be_u8(bytes)?;
@@ -1146,17 +2528,26 @@ fn target_info_from_bytes(bytes: &[u8], target_type: u8) -> IResult<&[u8], Targe
                 },
             )
         }
-        _ => return Err(Err::Failure(Error::new(bytes, ErrorKind::Tag))),
+        _ => {
+            return Err(Err::Failure(ParseError::Nom(Error::new(
+                bytes,
+                ErrorKind::Tag,
+            ))))
+        }
     })
 }
 
-fn type_annotation_from_bytes(bytes: &[u8]) -> IResult<&[u8], TypeAnnotation> {
+fn type_annotation_from_bytes<'a>(
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], TypeAnnotation<'a>, ParseError<&'a [u8]>> {
     let (input_1, target_type) = be_u8(bytes)?;
     let (input_2, target_info) = target_info_from_bytes(input_1, target_type)?;
     let (input_3, target_path) = type_path_from_bytes(input_2)?;
     let (input_4, type_index) = be_u16(input_3)?;
-    let (input_5, element_value_pairs) =
-        length_count(be_u16, element_value_pair_from_bytes)(input_4)?;
+    let (input_5, element_value_pairs) = length_count(be_u16, |bytes| {
+        element_value_pair_from_bytes(bytes, options)
+    })(input_4)?;
 
     Ok((
         input_5,
@@ -1170,13 +2561,15 @@ fn type_annotation_from_bytes(bytes: &[u8]) -> IResult<&[u8], TypeAnnotation> {
     ))
 }
 
-fn type_path_from_bytes(bytes: &[u8]) -> IResult<&[u8], TypePath> {
+fn type_path_from_bytes(bytes: &[u8]) -> IResult<&[u8], TypePath, ParseError<&[u8]>> {
     let (input, path) = length_count(be_u8, type_path_segment_from_bytes)(bytes)?;
 
     Ok((input, TypePath { path }))
 }
 
-fn type_path_segment_from_bytes(bytes: &[u8]) -> IResult<&[u8], TypePathSegment> {
+fn type_path_segment_from_bytes(
+    bytes: &[u8],
+) -> IResult<&[u8], TypePathSegment, ParseError<&[u8]>> {
     let (input_1, type_path_kind) = be_u8(bytes)?;
     let (input_2, type_argument_index) = be_u8(input_1)?;
 
@@ -1189,7 +2582,9 @@ fn type_path_segment_from_bytes(bytes: &[u8]) -> IResult<&[u8], TypePathSegment>
     ))
 }
 
-fn verification_type_info_from_bytes(bytes: &[u8]) -> IResult<&[u8], VerificationTypeInfo> {
+fn verification_type_info_from_bytes(
+    bytes: &[u8],
+) -> IResult<&[u8], VerificationTypeInfo, ParseError<&[u8]>> {
     let (input_1, tag) = be_u8(bytes)?;
 
     Ok(match tag {
@@ -1210,6 +2605,435 @@ fn verification_type_info_from_bytes(bytes: &[u8]) -> IResult<&[u8], Verificatio
 
             (input_2, VerificationTypeInfo::UninitializedVariable(offset))
         }
-        _ => return Err(Err::Failure(Error::new(input_1, ErrorKind::Tag))),
+        _ => {
+            return Err(Err::Failure(ParseError::Nom(Error::new(
+                input_1,
+                ErrorKind::Tag,
+            ))))
+        }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use nom::error::ErrorKind;
+    use nom::Err;
+
+    use crate::spec::Attribute;
+    use crate::spec::AttributeInfo;
+    use crate::spec::ConstantPoolEntry;
+    use crate::spec::StackMapFrame;
+    use crate::spec::VerificationTypeInfo;
+    use crate::spec::Version;
+    use crate::write::classfile_to_bytes;
+    use crate::write::ClassBuilder;
+    use crate::write::MethodBuilder;
+
+    use super::attribute_stack_map_table_from_bytes;
+    use super::classfile_from_bytes;
+    use super::classfile_from_bytes_with_options;
+    use super::classfiles_from_bytes;
+    use super::constant_pool_double_entry_from_bytes;
+    use super::constant_pool_from_bytes;
+    use super::constant_pool_long_entry_from_bytes;
+    use super::peek_version;
+    use super::Limit;
+    use super::ParseError;
+    use super::ParseOptions;
+    use super::ResourceLimits;
+
+    // `class Hello {}` compiled by javac with `--release 8 -g:source`
+    const HELLO: [u8; 154] = [
+        0xCA, 0xFE, 0xBA, 0xBE, 0x00, 0x00, 0x00, 0x34, 0x00, 0x0C, 0x0A, 0x00, 0x02, 0x00, 0x03,
+        0x07, 0x00, 0x04, 0x0C, 0x00, 0x05, 0x00, 0x06, 0x01, 0x00, 0x10, 0x6A, 0x61, 0x76, 0x61,
+        0x2F, 0x6C, 0x61, 0x6E, 0x67, 0x2F, 0x4F, 0x62, 0x6A, 0x65, 0x63, 0x74, 0x01, 0x00, 0x06,
+        0x3C, 0x69, 0x6E, 0x69, 0x74, 0x3E, 0x01, 0x00, 0x03, 0x28, 0x29, 0x56, 0x07, 0x00, 0x08,
+        0x01, 0x00, 0x05, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x01, 0x00, 0x04, 0x43, 0x6F, 0x64, 0x65,
+        0x01, 0x00, 0x0A, 0x53, 0x6F, 0x75, 0x72, 0x63, 0x65, 0x46, 0x69, 0x6C, 0x65, 0x01, 0x00,
+        0x0A, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x2E, 0x6A, 0x61, 0x76, 0x61, 0x00, 0x20, 0x00, 0x07,
+        0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x06, 0x00,
+        0x01, 0x00, 0x09, 0x00, 0x00, 0x00, 0x11, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05,
+        0x2A, 0xB7, 0x00, 0x01, 0xB1, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x0A, 0x00, 0x00,
+        0x00, 0x02, 0x00, 0x0B,
+    ];
+
+    // a class with a single static method whose code is `code`
+    fn class_with_code(code: Vec<u8>) -> Vec<u8> {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 50,
+            },
+            0x21,
+            "Test",
+            Some("java/lang/Object"),
+        );
+        let mut method = MethodBuilder::new(0x8, "run", "()V");
+        method.code(0, 0, code);
+        class.add_method(method).unwrap();
+
+        class.build().unwrap()
+    }
+
+    #[test]
+    fn bad_magic_is_reported() {
+        assert_eq!(
+            classfile_from_bytes(b"PK\x03\x04\x14\x00").unwrap_err(),
+            Err::Failure(ParseError::BadMagic {
+                found: *b"PK\x03\x04"
+            })
+        );
+        for length in 0..4 {
+            assert_eq!(
+                classfile_from_bytes(&[0xCA, 0xFE, 0xBA, 0xBE][..length]).unwrap_err(),
+                Err::Failure(ParseError::Truncated {
+                    section: Some("magic bytes")
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn code_length_is_u32() {
+        let mut code = vec![0x00; 70_000];
+        code.push(0xB1);
+        let bytes = class_with_code(code);
+
+        let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+        let AttributeInfo::Code { code, .. } = &classfile.methods[0].attributes[0].info else {
+            panic!("run has no Code attribute");
+        };
+        assert_eq!(code.len(), 70_001);
+
+        let options = ParseOptions {
+            limits: ResourceLimits {
+                max_code_bytes: 65_535,
+                ..ResourceLimits::default()
+            },
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            classfile_from_bytes_with_options(&bytes, &options).unwrap_err(),
+            Err::Failure(ParseError::LimitExceeded {
+                which: Limit::CodeBytes
+            })
+        );
+    }
+
+    #[test]
+    fn classfiles_end_with_a_truncated_one() {
+        let class = class_with_code(vec![0xB1]);
+        let mut bytes = [class.as_slice(), &class].concat();
+        // cut off in the middle of the constant pool
+        bytes.extend_from_slice(&class[..20]);
+
+        let mut classfiles = classfiles_from_bytes(&bytes);
+        assert!(classfiles.next().unwrap().is_ok());
+        assert!(classfiles.next().unwrap().is_ok());
+        assert_eq!(
+            classfiles.next().unwrap().unwrap_err(),
+            Err::Failure(ParseError::Truncated { section: None })
+        );
+        assert!(classfiles.next().is_none());
+    }
+
+    #[test]
+    fn classfiles_tell_truncation_from_malformed_bodies() {
+        let class = class_with_code(vec![0x00, 0xB1]);
+        let code_length = class
+            .windows(6)
+            .position(|window| window == [0x00, 0x00, 0x00, 0x02, 0x00, 0xB1])
+            .unwrap();
+
+        // cut off in the middle of the code
+        let bytes = [class.as_slice(), &class[..code_length + 5]].concat();
+        let mut classfiles = classfiles_from_bytes(&bytes);
+        assert!(classfiles.next().unwrap().is_ok());
+        assert_eq!(
+            classfiles.next().unwrap().unwrap_err(),
+            Err::Failure(ParseError::Truncated { section: None })
+        );
+
+        // a code length running past the end of the Code attribute, followed by a whole class
+        let mut malformed = class.clone();
+        malformed[code_length + 3] = 0x20;
+        let bytes = [malformed.as_slice(), &class].concat();
+        let mut classfiles = classfiles_from_bytes(&bytes);
+        assert!(matches!(
+            classfiles.next().unwrap().unwrap_err(),
+            Err::Error(ParseError::Nom(error)) if error.code == ErrorKind::Eof
+        ));
+        assert!(classfiles.next().is_none());
+    }
+
+    #[test]
+    fn lazy_code_is_decoded_on_demand() {
+        let bytes = class_with_code(vec![0x00, 0xB1]);
+        let options = ParseOptions {
+            lazy_code: true,
+            ..ParseOptions::default()
+        };
+
+        let (_, classfile) = classfile_from_bytes_with_options(&bytes, &options).unwrap();
+        let method = &classfile.methods[0];
+        assert!(matches!(
+            method.attributes[0].info,
+            AttributeInfo::Unknown { .. }
+        ));
+        assert!(method.lazy_code.code.get().is_none());
+
+        let (_, eager) = classfile_from_bytes(&bytes).unwrap();
+        assert!(method.code().is_some());
+        assert_eq!(method.code(), eager.methods[0].code());
+        assert!(method.lazy_code.code.get().is_some());
+
+        let options = ParseOptions {
+            lazy_code: true,
+            limits: ResourceLimits {
+                max_code_bytes: 1,
+                ..ResourceLimits::default()
+            },
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            classfile_from_bytes_with_options(&bytes, &options).unwrap_err(),
+            Err::Failure(ParseError::LimitExceeded {
+                which: Limit::CodeBytes
+            })
+        );
+    }
+
+    #[test]
+    fn peek_version_reads_the_header() {
+        // a Java 17 class file, cut off after its version
+        let bytes = [0xCA, 0xFE, 0xBA, 0xBE, 0x00, 0x00, 0x00, 0x3D];
+        assert_eq!(
+            peek_version(&bytes).unwrap(),
+            Version {
+                minor: 0,
+                major: 61
+            }
+        );
+        assert_eq!(
+            peek_version(&bytes[..6]).unwrap_err(),
+            Err::Failure(ParseError::Truncated {
+                section: Some("version")
+            })
+        );
+    }
+
+    #[test]
+    fn source_file_names_the_source() {
+        let (_, classfile) = classfile_from_bytes(&HELLO).unwrap();
+        let sourcefile_index = classfile
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute.info {
+                AttributeInfo::SourceFile { sourcefile_index } => Some(sourcefile_index),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            classfile.constant_pool.utf8(sourcefile_index).unwrap(),
+            "Hello.java"
+        );
+    }
+
+    #[test]
+    fn wide_constants_join_their_words() {
+        // Long.MAX_VALUE, as its high and low words
+        let (_, long) =
+            constant_pool_long_entry_from_bytes(&[0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF])
+                .unwrap();
+        assert_eq!(
+            long,
+            ConstantPoolEntry::Long {
+                value: i64::MAX as u64
+            }
+        );
+
+        // 3.14159d, as the high word 0x400921F9 and the low word 0xF01B866E
+        let (_, double) = constant_pool_double_entry_from_bytes(&[
+            0x40, 0x09, 0x21, 0xF9, 0xF0, 0x1B, 0x86, 0x6E,
+        ])
+        .unwrap();
+        let ConstantPoolEntry::Double { value } = double else {
+            panic!("not a Double entry");
+        };
+        assert_eq!(value.to_string(), "3.14159");
+    }
+
+    #[test]
+    fn wide_constants_take_two_slots() {
+        // two Longs at indices 1 and 3, each taking up the index after it too, then a Utf8 at index 5
+        let long_1 = [0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+        let long_2 = [0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02];
+        let utf8 = [0x01, 0x00, 0x05, b'H', b'e', b'l', b'l', b'o'];
+        let bytes = [&long_1[..], &long_2, &utf8].concat();
+
+        let (rest, constant_pool) = constant_pool_from_bytes(&bytes, 6).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(constant_pool.get(2).unwrap(), &ConstantPoolEntry::Unusable);
+        assert_eq!(constant_pool.get(4).unwrap(), &ConstantPoolEntry::Unusable);
+        assert_eq!(constant_pool.utf8(5).unwrap(), "Hello");
+    }
+
+    #[test]
+    fn constant_pool_count_is_one_more_than_its_entries() {
+        let utf8 = [0x01, 0x00, 0x01, b'A'];
+        let bytes = [&utf8[..], &utf8, &utf8].concat();
+        let (rest, constant_pool) = constant_pool_from_bytes(&bytes, 3).unwrap();
+        assert_eq!(constant_pool.len(), 2);
+        assert_eq!(rest, utf8);
+
+        // a count of 0, which no compiler writes, holds no entries rather than underflowing
+        let (_, constant_pool) = constant_pool_from_bytes(&bytes, 0).unwrap();
+        assert!(constant_pool.is_empty());
+
+        let bytes = class_with_code(vec![0xB1]);
+        let count = u16::from_be_bytes([bytes[8], bytes[9]]);
+        let options = |max_constant_pool_entries| ParseOptions {
+            limits: ResourceLimits {
+                max_constant_pool_entries,
+                ..ResourceLimits::default()
+            },
+            ..ParseOptions::default()
+        };
+        assert!(classfile_from_bytes_with_options(&bytes, &options(count - 1)).is_ok());
+        assert_eq!(
+            classfile_from_bytes_with_options(&bytes, &options(count - 2)).unwrap_err(),
+            Err::Failure(ParseError::LimitExceeded {
+                which: Limit::ConstantPoolEntries
+            })
+        );
+    }
+
+    #[test]
+    fn stack_map_frames_round_trip() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 50,
+            },
+            0x21,
+            "Frames",
+            Some("java/lang/Object"),
+        );
+        let name_index = class.constant_pool().utf8("StackMapTable").unwrap();
+        let object = class.constant_pool().class("java/lang/String").unwrap();
+        let [o1, o2] = object.to_be_bytes();
+        let mut method = MethodBuilder::new(0x8, "run", "()V");
+        method.code(0, 0, vec![0xB1]);
+        class.add_method(method).unwrap();
+        let bytes = class.build().unwrap();
+
+        // one frame of each kind, with the lowest and highest tags of those spanning several
+        let table = [
+            vec![0x00, 0x0B],
+            vec![0x00],
+            vec![0x3F],
+            vec![0x40, 0x01],
+            vec![0x7F, 0x07, o1, o2],
+            vec![0xF7, 0x01, 0x2C, 0x00],
+            vec![0xF8, 0x00, 0x0A],
+            vec![0xFA, 0x00, 0x0B],
+            vec![0xFB, 0x01, 0x90],
+            vec![0xFC, 0x00, 0x02, 0x04],
+            vec![0xFE, 0x00, 0x03, 0x02, 0x03, 0x05],
+            vec![
+                0xFF, 0x00, 0x04, 0x00, 0x02, 0x06, 0x00, 0x00, 0x02, 0x08, 0x00, 0x11,
+            ],
+            vec![0x07, o1, o2],
+        ]
+        .concat();
+        let (rest, info) = attribute_stack_map_table_from_bytes(&table).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            info,
+            AttributeInfo::StackMapTable {
+                entries: vec![
+                    StackMapFrame::SameFrame { offset_delta: 0 },
+                    StackMapFrame::SameFrame { offset_delta: 63 },
+                    StackMapFrame::SameLocals1StackItemFrame {
+                        offset_delta: 0,
+                        stack: VerificationTypeInfo::IntegerVariable,
+                    },
+                    StackMapFrame::SameLocals1StackItemFrame {
+                        offset_delta: 63,
+                        stack: VerificationTypeInfo::ObjectVariable(object),
+                    },
+                    StackMapFrame::SameLocals1StackItemFrameExtended {
+                        offset_delta: 300,
+                        stack: VerificationTypeInfo::TopVariable,
+                    },
+                    StackMapFrame::ChopFrame {
+                        offset_delta: 10,
+                        absent_locals: 3,
+                    },
+                    StackMapFrame::ChopFrame {
+                        offset_delta: 11,
+                        absent_locals: 1,
+                    },
+                    StackMapFrame::SameFrameExtended { offset_delta: 400 },
+                    StackMapFrame::AppendFrame {
+                        offset_delta: 2,
+                        locals: vec![VerificationTypeInfo::LongVariable],
+                    },
+                    StackMapFrame::AppendFrame {
+                        offset_delta: 3,
+                        locals: vec![
+                            VerificationTypeInfo::FloatVariable,
+                            VerificationTypeInfo::DoubleVariable,
+                            VerificationTypeInfo::NullVariable,
+                        ],
+                    },
+                    StackMapFrame::FullFrame {
+                        offset_delta: 4,
+                        locals: vec![
+                            VerificationTypeInfo::UninitializedThisVariable,
+                            VerificationTypeInfo::TopVariable,
+                        ],
+                        stack: vec![
+                            VerificationTypeInfo::UninitializedVariable(17),
+                            VerificationTypeInfo::ObjectVariable(object),
+                        ],
+                    },
+                ],
+            }
+        );
+
+        // written back into the Code attribute of a class, the frames encode to the same bytes
+        let (_, mut classfile) = classfile_from_bytes(&bytes).unwrap();
+        let Some(AttributeInfo::Code { attributes, .. }) = classfile.methods[0]
+            .attributes
+            .first_mut()
+            .map(|code| &mut code.info)
+        else {
+            panic!("run has no Code attribute");
+        };
+        attributes.push(Attribute {
+            name_index,
+            info,
+            raw: None,
+        });
+        let bytes = classfile_to_bytes(&classfile).unwrap();
+        let options = ParseOptions {
+            retain_raw_attributes: true,
+            ..ParseOptions::default()
+        };
+        let (_, classfile) = classfile_from_bytes_with_options(&bytes, &options).unwrap();
+        let Some(AttributeInfo::Code { attributes, .. }) =
+            classfile.methods[0].code().map(|code| &code.info)
+        else {
+            panic!("run has no Code attribute");
+        };
+        assert_eq!(attributes[0].raw.as_deref(), Some(&table[..]));
+
+        // tags 128 to 246 are reserved
+        assert!(matches!(
+            attribute_stack_map_table_from_bytes(&[0x00, 0x01, 0x80]).unwrap_err(),
+            Err::Failure(ParseError::Nom(error)) if error.code == ErrorKind::Tag
+        ));
+        assert!(attribute_stack_map_table_from_bytes(&[0x00, 0x01, 0xF6]).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use dashmap::DashMap;
+
+use crate::owned::OwnedClassfile;
+use crate::owned::ReadError;
+use crate::parse::classfile_from_bytes_with_options;
+use crate::parse::error_with_offset;
+use crate::parse::ParseOptions;
+
+// A cache of parsed classes keyed by binary name, to be shared between threads. Each name has its own lock,
+// so the first get_or_parse for a name parses the class while other calls for that name wait for it instead
+// of parsing it again, and calls for other names go ahead.
+#[derive(Debug, Default)]
+pub struct ClassRegistry {
+    classes: DashMap<String, Arc<Mutex<Option<Arc<OwnedClassfile>>>>>,
+    options: ParseOptions,
+}
+
+impl ClassRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: ParseOptions) -> Self {
+        Self {
+            classes: DashMap::new(),
+            options,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<OwnedClassfile>> {
+        let slot = self.classes.get(name)?.clone();
+        let class = slot.lock().unwrap_or_else(PoisonError::into_inner);
+
+        class.clone()
+    }
+
+    // `bytes` are only parsed if no class is cached under `name` yet. A class that fails to parse is not
+    // cached, so the next call for its name parses again.
+    pub fn get_or_parse(&self, name: &str, bytes: &[u8]) -> Result<Arc<OwnedClassfile>, ReadError> {
+        // the map itself is only locked while looking up the slot, not while parsing
+        let slot = match self.classes.get(name) {
+            Some(slot) => slot.clone(),
+            None => self.classes.entry(name.to_string()).or_default().clone(),
+        };
+        let mut class = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(class) = &*class {
+            return Ok(Arc::clone(class));
+        }
+
+        let parsed = match classfile_from_bytes_with_options(bytes, &self.options) {
+            Ok((_, classfile)) => Arc::new(classfile.into_owned()),
+            Err(error) => return Err(ReadError::Parse(error_with_offset(bytes, error))),
+        };
+        *class = Some(Arc::clone(&parsed));
+
+        Ok(parsed)
+    }
+}
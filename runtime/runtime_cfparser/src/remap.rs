@@ -0,0 +1,938 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::ConstantPoolError;
+use crate::instruction::instruction_boundaries;
+use crate::instruction::InstructionError;
+use crate::spec::Annotation;
+use crate::spec::Attribute;
+use crate::spec::AttributeInfo;
+use crate::spec::Classfile;
+use crate::spec::ConstantPoolEntry;
+use crate::spec::ElementValue;
+use crate::spec::ElementValuePair;
+use crate::spec::Method;
+use crate::spec::StackMapFrame;
+use crate::spec::VerificationTypeInfo;
+use crate::write::ConstantPoolBuilder;
+use crate::write::WriteError;
+
+#[derive(Debug)]
+pub enum RemapError {
+    ConstantPool(ConstantPoolError),
+    Instruction(InstructionError),
+    // an ldc instruction has a single byte for its index, so the entry it loads must stay below 256
+    LdcIndexOutOfRange { offset: usize, index: u16 },
+    // a Code attribute that parsing with lazy_code left undecoded does not decode, so its indices cannot be
+    // rewritten
+    MalformedCode { name_index: u16 },
+    UnmappedIndex { index: u16 },
+    Write(WriteError),
+}
+
+impl Display for RemapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConstantPool(error) => write!(f, "{error}"),
+            Self::Instruction(error) => write!(f, "{error}"),
+            Self::LdcIndexOutOfRange { offset, index } => {
+                write!(
+                    f,
+                    "ldc at offset {offset} would load constant pool entry {index}, beyond its one byte index"
+                )
+            }
+            Self::MalformedCode { name_index } => {
+                write!(
+                    f,
+                    "the code of the method named by entry {name_index} does not decode"
+                )
+            }
+            Self::UnmappedIndex { index } => {
+                write!(f, "constant pool index {index} has no mapping")
+            }
+            Self::Write(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for RemapError {}
+
+impl From<ConstantPoolError> for RemapError {
+    fn from(error: ConstantPoolError) -> Self {
+        Self::ConstantPool(error)
+    }
+}
+
+impl From<InstructionError> for RemapError {
+    fn from(error: InstructionError) -> Self {
+        Self::Instruction(error)
+    }
+}
+
+impl From<WriteError> for RemapError {
+    fn from(error: WriteError) -> Self {
+        Self::Write(error)
+    }
+}
+
+impl<'a> Classfile<'a> {
+    // Removes the attributes that only serve debuggers and tools: LineNumberTable, LocalVariableTable,
+    // LocalVariableTypeTable, SourceFile and SourceDebugExtension, including those nested in Code attributes.
+    // The constant pool entries they referred to stay behind until compact_constant_pool drops them.
+    pub fn strip_debug(&mut self) {
+        strip_debug_attributes(&mut self.attributes);
+        for field in &mut self.fields {
+            strip_debug_attributes(&mut field.attributes);
+        }
+        for method in &mut self.methods {
+            // code that does not decode is left as it is, as the body of an unknown attribute would be
+            method.decode_lazy_code();
+            strip_debug_attributes(&mut method.attributes);
+        }
+    }
+}
+
+// Rebuilds the constant pool of `classfile` with only the entries something in the class still refers to,
+// whether directly or through another entry, and rewrites every index to point into the new pool. Attributes
+// assembled by hand keep their standard names in the new pool, so the class can still be written. The bodies
+// of unknown attributes cannot be decoded, so entries that only they refer to are dropped as well.
+pub fn compact_constant_pool(classfile: &mut Classfile) -> Result<(), RemapError> {
+    let mut used = BTreeSet::new();
+    visit_classfile(classfile, &mut |index| {
+        if *index != 0 {
+            used.insert(*index);
+        }
+
+        Ok(())
+    })?;
+
+    let mut destination = ConstantPoolBuilder::new();
+    let mut map = HashMap::new();
+    for index in used {
+        let new_index = remap_entry(&classfile.constant_pool, &mut destination, index, &map)?;
+        map.insert(index, new_index);
+    }
+
+    visit_classfile(classfile, &mut |index| remap_index(index, &map))?;
+    name_attributes(&mut classfile.attributes, &mut destination)?;
+    for field in &mut classfile.fields {
+        name_attributes(&mut field.attributes, &mut destination)?;
+    }
+    for method in &mut classfile.methods {
+        name_attributes(&mut method.attributes, &mut destination)?;
+    }
+
+    classfile.constant_pool = destination.to_constant_pool();
+
+    Ok(())
+}
+
+// Rewrites every constant pool index in the attributes through `map`, including the operands of the
+// instructions in a Code attribute. Retained raw bytes are dropped so that the attributes are encoded again
+// with the new indices; the bodies of unknown attributes cannot be decoded, so only their names are remapped.
+pub fn remap_attributes(
+    attributes: &mut [Attribute],
+    map: &HashMap<u16, u16>,
+) -> Result<(), RemapError> {
+    visit_attributes(attributes, &mut |index| remap_index(index, map))
+}
+
+// Adds every entry of `source` to `destination`, returning the index each entry of `source` has there.
+// Entries are added by content, so one that `destination` already holds is reused rather than duplicated,
+// and the indices of later entries are the builder's, which already accounts for the two indices a Long or
+// Double takes. The bootstrap method indices of Dynamic and InvokeDynamic entries are kept as they are, as
+// they refer to the BootstrapMethods attribute rather than to the constant pool.
+pub fn remap_constant_pool(
+    source: &ConstantPool,
+    destination: &mut ConstantPoolBuilder,
+) -> Result<HashMap<u16, u16>, RemapError> {
+    let mut map = HashMap::new();
+
+    for index in (1..).take(source.len()) {
+        // nothing may refer to the second index of a Long or Double, and the builder leaves one of its own
+        if let Ok(ConstantPoolEntry::Unusable) = source.get(index) {
+            continue;
+        }
+
+        let new_index = remap_entry(source, destination, index, &map)?;
+        map.insert(index, new_index);
+    }
+
+    Ok(map)
+}
+
+pub fn remap_method(method: &mut Method, map: &HashMap<u16, u16>) -> Result<(), RemapError> {
+    decode_lazy_code(method)?;
+    remap_index(&mut method.name_index, map)?;
+    remap_index(&mut method.descriptor_index, map)?;
+
+    remap_attributes(&mut method.attributes, map)
+}
+
+// decodes the code that parsing with lazy_code left undecoded, as its indices are rewritten in place
+fn decode_lazy_code(method: &mut Method) -> Result<(), RemapError> {
+    match method.decode_lazy_code() {
+        true => Ok(()),
+        false => Err(RemapError::MalformedCode {
+            name_index: method.name_index,
+        }),
+    }
+}
+
+// adds the field or method reference at `index` of `source` to `destination`
+fn member_ref(
+    source: &ConstantPool,
+    destination: &mut ConstantPoolBuilder,
+    index: u16,
+) -> Result<u16, RemapError> {
+    let (ConstantPoolEntry::FieldRef {
+        class_index,
+        name_and_type_index,
+    }
+    | ConstantPoolEntry::MethodRef {
+        class_index,
+        name_and_type_index,
+    }
+    | ConstantPoolEntry::InstanceMethodRef {
+        class_index,
+        name_and_type_index,
+    }) = *source.get(index)?
+    else {
+        return Err(ConstantPoolError::UnexpectedEntry {
+            index,
+            expected: "Fieldref, Methodref or InterfaceMethodref",
+        }
+        .into());
+    };
+
+    let class = source.class_name(class_index)?;
+    let (name, descriptor) = name_and_type(source, name_and_type_index)?;
+
+    let new_index = match source.get(index)? {
+        ConstantPoolEntry::FieldRef { .. } => destination.field_ref(&class, &name, &descriptor),
+        ConstantPoolEntry::MethodRef { .. } => destination.method_ref(&class, &name, &descriptor),
+        _ => destination.interface_method_ref(&class, &name, &descriptor),
+    };
+
+    Ok(new_index?)
+}
+
+fn name_and_type(constant_pool: &ConstantPool, index: u16) -> Result<(String, String), RemapError> {
+    let ConstantPoolEntry::NameAndType {
+        name_index,
+        descriptor_index,
+    } = *constant_pool.get(index)?
+    else {
+        return Err(ConstantPoolError::UnexpectedEntry {
+            index,
+            expected: "NameAndType",
+        }
+        .into());
+    };
+
+    Ok((
+        constant_pool.utf8(name_index)?.into_owned(),
+        constant_pool.utf8(descriptor_index)?.into_owned(),
+    ))
+}
+
+// gives the attributes assembled by hand the index of their standard name, adding it to `destination`
+fn name_attributes(
+    attributes: &mut [Attribute],
+    destination: &mut ConstantPoolBuilder,
+) -> Result<(), RemapError> {
+    for attribute in attributes {
+        if attribute.name_index == 0 {
+            if let Some(name) = attribute.info.standard_name() {
+                attribute.name_index = destination.utf8(name)?;
+            }
+        }
+
+        match &mut attribute.info {
+            AttributeInfo::Code { attributes, .. } => name_attributes(attributes, destination)?,
+            AttributeInfo::Record { components } => {
+                for component in components {
+                    name_attributes(&mut component.attributes, destination)?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// adds the entry at `index` of `source` to `destination`, reusing the entries already remapped in `map`
+fn remap_entry(
+    source: &ConstantPool,
+    destination: &mut ConstantPoolBuilder,
+    index: u16,
+    map: &HashMap<u16, u16>,
+) -> Result<u16, RemapError> {
+    let new_index = match *source.get(index)? {
+        ConstantPoolEntry::Utf8 { .. } => destination.utf8(&source.utf8(index)?),
+        ConstantPoolEntry::Integer { bytes } => destination.integer(bytes as i32),
+        ConstantPoolEntry::Float { value } => destination.float(value),
+        ConstantPoolEntry::Long { value } => destination.long(value as i64),
+        ConstantPoolEntry::Double { value } => destination.double(value),
+        ConstantPoolEntry::Class { name_index } => destination.class(&source.utf8(name_index)?),
+        ConstantPoolEntry::String { string_index } => {
+            destination.string(&source.utf8(string_index)?)
+        }
+        ConstantPoolEntry::FieldRef {
+            class_index,
+            name_and_type_index,
+        } => {
+            let (name, descriptor) = name_and_type(source, name_and_type_index)?;
+            destination.field_ref(&source.class_name(class_index)?, &name, &descriptor)
+        }
+        ConstantPoolEntry::MethodRef {
+            class_index,
+            name_and_type_index,
+        } => {
+            let (name, descriptor) = name_and_type(source, name_and_type_index)?;
+            destination.method_ref(&source.class_name(class_index)?, &name, &descriptor)
+        }
+        ConstantPoolEntry::InstanceMethodRef {
+            class_index,
+            name_and_type_index,
+        } => {
+            let (name, descriptor) = name_and_type(source, name_and_type_index)?;
+            destination.interface_method_ref(&source.class_name(class_index)?, &name, &descriptor)
+        }
+        ConstantPoolEntry::NameAndType { .. } => {
+            let (name, descriptor) = name_and_type(source, index)?;
+            destination.name_and_type(&name, &descriptor)
+        }
+        // the entry referred to comes first in a well-formed pool, but is not required to
+        ConstantPoolEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        } => {
+            let reference_index = match map.get(&reference_index) {
+                Some(&reference_index) => reference_index,
+                None => member_ref(source, destination, reference_index)?,
+            };
+            destination.method_handle(reference_kind, reference_index)
+        }
+        ConstantPoolEntry::MethodType { reference_index } => {
+            destination.method_type(&source.utf8(reference_index)?)
+        }
+        ConstantPoolEntry::Dynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => {
+            let (name, descriptor) = name_and_type(source, name_and_type_index)?;
+            destination.dynamic(bootstrap_method_attr_index, &name, &descriptor)
+        }
+        ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => {
+            let (name, descriptor) = name_and_type(source, name_and_type_index)?;
+            destination.invoke_dynamic(bootstrap_method_attr_index, &name, &descriptor)
+        }
+        ConstantPoolEntry::Module { name_index } => destination.module(&source.utf8(name_index)?),
+        ConstantPoolEntry::Package { name_index } => destination.package(&source.utf8(name_index)?),
+        ConstantPoolEntry::Unusable => return Err(ConstantPoolError::InvalidIndex { index }.into()),
+    };
+
+    Ok(new_index?)
+}
+
+fn remap_index(index: &mut u16, map: &HashMap<u16, u16>) -> Result<(), RemapError> {
+    *index = remapped(*index, map)?;
+
+    Ok(())
+}
+
+// index 0 stands for an absent optional reference, and stays 0
+fn remapped(index: u16, map: &HashMap<u16, u16>) -> Result<u16, RemapError> {
+    if index == 0 {
+        return Ok(0);
+    }
+
+    map.get(&index)
+        .copied()
+        .ok_or(RemapError::UnmappedIndex { index })
+}
+
+fn strip_debug_attributes(attributes: &mut Vec<Attribute>) {
+    attributes.retain(|attribute| {
+        !matches!(
+            attribute.info,
+            AttributeInfo::LineNumberTable { .. }
+                | AttributeInfo::LocalVariableTable { .. }
+                | AttributeInfo::LocalVariableTypeTable { .. }
+                | AttributeInfo::SourceDebugExtension { .. }
+                | AttributeInfo::SourceFile { .. }
+        )
+    });
+
+    for attribute in attributes {
+        if let AttributeInfo::Code { attributes, .. } = &mut attribute.info {
+            let len = attributes.len();
+            strip_debug_attributes(attributes);
+            // a retained body would still hold the stripped attributes
+            if attributes.len() != len {
+                attribute.raw = None;
+            }
+        }
+    }
+}
+
+fn visit_annotation<F>(annotation: &mut Annotation, visit: &mut F) -> Result<(), RemapError>
+where
+    F: FnMut(&mut u16) -> Result<(), RemapError>,
+{
+    annotation.raw = None;
+    visit(&mut annotation.type_index)?;
+
+    visit_element_value_pairs(&mut annotation.element_value_pairs, visit)
+}
+
+fn visit_attributes<F>(attributes: &mut [Attribute], visit: &mut F) -> Result<(), RemapError>
+where
+    F: FnMut(&mut u16) -> Result<(), RemapError>,
+{
+    for attribute in attributes {
+        attribute.raw = None;
+        visit(&mut attribute.name_index)?;
+
+        match &mut attribute.info {
+            AttributeInfo::AnnotationDefault { default_value } => {
+                visit_element_value(default_value, visit)?;
+            }
+            AttributeInfo::BootstrapMethods { bootstrap_methods } => {
+                for bootstrap_method in bootstrap_methods {
+                    visit(&mut bootstrap_method.bootstrap_method_ref)?;
+                    visit_indices(&mut bootstrap_method.bootstrap_arguments, visit)?;
+                }
+            }
+            AttributeInfo::Code {
+                code,
+                exception_table,
+                attributes,
+                ..
+            } => {
+                visit_code(code.to_mut(), visit)?;
+                for entry in exception_table {
+                    visit(&mut entry.catch_type)?;
+                }
+                visit_attributes(attributes, visit)?;
+            }
+            AttributeInfo::ConstantValue {
+                constantvalue_index,
+            } => visit(constantvalue_index)?,
+            AttributeInfo::Deprecated | AttributeInfo::Synthetic => {}
+            AttributeInfo::EnclosingMethod {
+                class_index,
+                method_index,
+            } => {
+                visit(class_index)?;
+                visit(method_index)?;
+            }
+            AttributeInfo::Exceptions {
+                exception_index_table,
+            } => visit_indices(exception_index_table, visit)?,
+            AttributeInfo::InnerClasses { classes } => {
+                for class in classes {
+                    visit(&mut class.inner_class_info_index)?;
+                    visit(&mut class.outer_class_info_index)?;
+                    visit(&mut class.inner_name_index)?;
+                }
+            }
+            AttributeInfo::LineNumberTable { .. } => {}
+            AttributeInfo::LocalVariableTable {
+                local_variable_table,
+            } => {
+                for variable in local_variable_table {
+                    visit(&mut variable.name_index)?;
+                    visit(&mut variable.descriptor_index)?;
+                }
+            }
+            AttributeInfo::LocalVariableTypeTable {
+                local_variable_type_table,
+            } => {
+                for variable in local_variable_type_table {
+                    visit(&mut variable.name_index)?;
+                    visit(&mut variable.descriptor_index)?;
+                }
+            }
+            AttributeInfo::MethodParameters { parameters } => {
+                for parameter in parameters {
+                    visit(&mut parameter.name_index)?;
+                }
+            }
+            AttributeInfo::Module {
+                module_name_index,
+                module_version_index,
+                requires,
+                exports,
+                opens,
+                uses,
+                provides,
+                ..
+            } => {
+                visit(module_name_index)?;
+                visit(module_version_index)?;
+                for require in requires {
+                    visit(&mut require.requires_index)?;
+                    visit(&mut require.requires_version_index)?;
+                }
+                for export in exports {
+                    visit(&mut export.exports_index)?;
+                    visit_indices(&mut export.exports_to_indices, visit)?;
+                }
+                for open in opens {
+                    visit(&mut open.opens_index)?;
+                    visit_indices(&mut open.opens_to_indices, visit)?;
+                }
+                visit_indices(uses, visit)?;
+                for provide in provides {
+                    visit(&mut provide.provides_index)?;
+                    visit_indices(&mut provide.provides_with_indices, visit)?;
+                }
+            }
+            AttributeInfo::ModuleMainClass { main_class_index } => {
+                visit(main_class_index)?;
+            }
+            AttributeInfo::ModulePackages { package_index } => visit_indices(package_index, visit)?,
+            AttributeInfo::NestHost { host_class_index } => visit(host_class_index)?,
+            AttributeInfo::NestMembers { classes }
+            | AttributeInfo::PermittedSubclasses { classes } => {
+                visit_indices(classes, visit)?;
+            }
+            AttributeInfo::Record { components } => {
+                for component in components {
+                    visit(&mut component.name_index)?;
+                    visit(&mut component.descriptor_index)?;
+                    visit_attributes(&mut component.attributes, visit)?;
+                }
+            }
+            AttributeInfo::RuntimeInvisibleAnnotations { annotations }
+            | AttributeInfo::RuntimeVisibleAnnotations { annotations }
+            | AttributeInfo::RuntimeInvisibleParameterAnnotations {
+                parameter_annotations: annotations,
+            }
+            | AttributeInfo::RuntimeVisibleParameterAnnotations {
+                parameter_annotations: annotations,
+            } => {
+                for annotation in annotations {
+                    visit_annotation(annotation, visit)?;
+                }
+            }
+            AttributeInfo::RuntimeInvisibleTypeAnnotations { type_annotations }
+            | AttributeInfo::RuntimeVisibleTypeAnnotations { type_annotations } => {
+                for type_annotation in type_annotations {
+                    visit(&mut type_annotation.type_index)?;
+                    visit_element_value_pairs(&mut type_annotation.element_value_pairs, visit)?;
+                }
+            }
+            AttributeInfo::Signature { signature_index } => visit(signature_index)?,
+            AttributeInfo::SourceDebugExtension { .. } => {}
+            AttributeInfo::SourceFile { sourcefile_index } => visit(sourcefile_index)?,
+            AttributeInfo::StackMapTable { entries } => {
+                for frame in entries {
+                    visit_stack_map_frame(frame, visit)?;
+                }
+            }
+            AttributeInfo::Unknown { name_index, .. } => visit(name_index)?,
+        }
+    }
+
+    Ok(())
+}
+
+// visits every constant pool index of the class, outside of the constant pool itself
+fn visit_classfile<F>(classfile: &mut Classfile, visit: &mut F) -> Result<(), RemapError>
+where
+    F: FnMut(&mut u16) -> Result<(), RemapError>,
+{
+    visit(&mut classfile.this_class)?;
+    visit(&mut classfile.super_class)?;
+    visit_indices(&mut classfile.interfaces, visit)?;
+    for field in &mut classfile.fields {
+        visit(&mut field.name_index)?;
+        visit(&mut field.descriptor_index)?;
+        visit_attributes(&mut field.attributes, visit)?;
+    }
+    for method in &mut classfile.methods {
+        decode_lazy_code(method)?;
+        visit(&mut method.name_index)?;
+        visit(&mut method.descriptor_index)?;
+        visit_attributes(&mut method.attributes, visit)?;
+    }
+
+    visit_attributes(&mut classfile.attributes, visit)
+}
+
+fn visit_code<F>(code: &mut [u8], visit: &mut F) -> Result<(), RemapError>
+where
+    F: FnMut(&mut u16) -> Result<(), RemapError>,
+{
+    for offset in instruction_boundaries(code, 0)? {
+        match code[offset] {
+            // ldc
+            0x12 => {
+                let mut index = code[offset + 1].into();
+                visit(&mut index)?;
+                code[offset + 1] = u8::try_from(index)
+                    .map_err(|_| RemapError::LdcIndexOutOfRange { offset, index })?;
+            }
+            // ldc_w, ldc2_w, getstatic ... invokedynamic, new, anewarray, checkcast, instanceof,
+            // multianewarray
+            0x13 | 0x14 | 0xB2..=0xBB | 0xBD | 0xC0 | 0xC1 | 0xC5 => {
+                let mut index = u16::from_be_bytes([code[offset + 1], code[offset + 2]]);
+                visit(&mut index)?;
+                code[offset + 1..offset + 3].copy_from_slice(&index.to_be_bytes());
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn visit_element_value<F>(value: &mut ElementValue, visit: &mut F) -> Result<(), RemapError>
+where
+    F: FnMut(&mut u16) -> Result<(), RemapError>,
+{
+    match value {
+        ElementValue::Annotation(annotation) => visit_annotation(annotation, visit),
+        ElementValue::ClassInfo(index) => visit(index),
+        ElementValue::ConstValue {
+            const_value_index, ..
+        } => visit(const_value_index),
+        ElementValue::EnumConst {
+            type_name_index,
+            const_name_index,
+        } => {
+            visit(type_name_index)?;
+            visit(const_name_index)
+        }
+        ElementValue::Array { values } => values
+            .iter_mut()
+            .try_for_each(|value| visit_element_value(value, visit)),
+    }
+}
+
+fn visit_element_value_pairs<F>(
+    pairs: &mut [ElementValuePair],
+    visit: &mut F,
+) -> Result<(), RemapError>
+where
+    F: FnMut(&mut u16) -> Result<(), RemapError>,
+{
+    for pair in pairs {
+        visit(&mut pair.element_name_index)?;
+        visit_element_value(&mut pair.value, visit)?;
+    }
+
+    Ok(())
+}
+
+fn visit_indices<F>(indices: &mut [u16], visit: &mut F) -> Result<(), RemapError>
+where
+    F: FnMut(&mut u16) -> Result<(), RemapError>,
+{
+    indices.iter_mut().try_for_each(visit)
+}
+
+fn visit_stack_map_frame<F>(frame: &mut StackMapFrame, visit: &mut F) -> Result<(), RemapError>
+where
+    F: FnMut(&mut u16) -> Result<(), RemapError>,
+{
+    let types = match frame {
+        StackMapFrame::AppendFrame { locals, .. } => locals.iter_mut().collect::<Vec<_>>(),
+        StackMapFrame::FullFrame { locals, stack, .. } => {
+            locals.iter_mut().chain(stack.iter_mut()).collect()
+        }
+        StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+        | StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => vec![stack],
+        StackMapFrame::ChopFrame { .. }
+        | StackMapFrame::SameFrame { .. }
+        | StackMapFrame::SameFrameExtended { .. } => Vec::new(),
+    };
+
+    for verification_type in types {
+        if let VerificationTypeInfo::ObjectVariable(index) = verification_type {
+            visit(index)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::classfile_from_bytes;
+    use crate::parse::classfile_from_bytes_with_options;
+    use crate::parse::ParseOptions;
+    use crate::spec::Attribute;
+    use crate::spec::AttributeInfo;
+    use crate::spec::ConstantPoolEntry;
+    use crate::spec::LineNumber;
+    use crate::spec::LocalVariable;
+    use crate::spec::Version;
+    use crate::write::classfile_to_bytes;
+    use crate::write::ClassBuilder;
+    use crate::write::ConstantPoolBuilder;
+    use crate::write::MethodBuilder;
+
+    use super::compact_constant_pool;
+    use super::remap_constant_pool;
+    use super::remap_method;
+
+    #[test]
+    fn lazy_code_is_remapped() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 50,
+            },
+            0x21,
+            "Lazy",
+            Some("java/lang/Object"),
+        );
+        // nothing refers to this entry, so compacting the pool moves the string after it
+        class.constant_pool().utf8("unused").unwrap();
+        let string = class.constant_pool().string("hello").unwrap();
+        let mut run = MethodBuilder::new(0x8, "run", "()V");
+        run.code(1, 0, vec![0x12, string as u8, 0x57, 0xB1]);
+        class.add_method(run).unwrap();
+        let bytes = class.build().unwrap();
+
+        let options = ParseOptions {
+            lazy_code: true,
+            ..ParseOptions::default()
+        };
+        let (_, mut classfile) = classfile_from_bytes_with_options(&bytes, &options).unwrap();
+        compact_constant_pool(&mut classfile).unwrap();
+        let bytes = classfile_to_bytes(&classfile).unwrap();
+
+        let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+        let Some(AttributeInfo::Code { code, .. }) =
+            classfile.methods[0].code().map(|code| &code.info)
+        else {
+            panic!("run has no Code attribute");
+        };
+        assert_ne!(u16::from(code[1]), string);
+        let ConstantPoolEntry::String { string_index } =
+            classfile.constant_pool.get(code[1].into()).unwrap()
+        else {
+            panic!("ldc does not load a String");
+        };
+        assert_eq!(
+            classfile.constant_pool.utf8(*string_index).unwrap(),
+            "hello"
+        );
+    }
+
+    #[test]
+    fn stripped_class_is_smaller_and_resolves() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 50,
+            },
+            0x21,
+            "Debug",
+            Some("java/lang/Object"),
+        );
+        let source_file = class.constant_pool().utf8("SourceFile").unwrap();
+        let file_name = class.constant_pool().utf8("Debug.java").unwrap();
+        let line_numbers = class.constant_pool().utf8("LineNumberTable").unwrap();
+        let local_variables = class.constant_pool().utf8("LocalVariableTable").unwrap();
+        let this_name = class.constant_pool().utf8("this").unwrap();
+        let this_descriptor = class.constant_pool().utf8("LDebug;").unwrap();
+        let string = class.constant_pool().string("hello").unwrap();
+        let [p1, p2] = class
+            .constant_pool()
+            .method_ref("Debug", "print", "(Ljava/lang/String;)V")
+            .unwrap()
+            .to_be_bytes();
+
+        // void run() { print("hello"); }
+        let mut run = MethodBuilder::new(0x1, "run", "()V");
+        run.code(1, 1, vec![0x12, string as u8, 0xB8, p1, p2, 0xB1]);
+        class.add_method(run).unwrap();
+        let mut print = MethodBuilder::new(0x8, "print", "(Ljava/lang/String;)V");
+        print.code(0, 1, vec![0xB1]);
+        class.add_method(print).unwrap();
+        let bytes = class.build().unwrap();
+
+        let (_, mut classfile) = classfile_from_bytes(&bytes).unwrap();
+        classfile.attributes.push(Attribute {
+            name_index: source_file,
+            info: AttributeInfo::SourceFile {
+                sourcefile_index: file_name,
+            },
+            raw: None,
+        });
+        let Some(AttributeInfo::Code { attributes, .. }) = classfile.methods[0]
+            .attributes
+            .first_mut()
+            .map(|code| &mut code.info)
+        else {
+            panic!("run has no Code attribute");
+        };
+        attributes.push(Attribute {
+            name_index: line_numbers,
+            info: AttributeInfo::LineNumberTable {
+                line_number_table: vec![LineNumber {
+                    start_pc: 0,
+                    line_number: 3,
+                }],
+            },
+            raw: None,
+        });
+        attributes.push(Attribute {
+            name_index: local_variables,
+            info: AttributeInfo::LocalVariableTable {
+                local_variable_table: vec![LocalVariable {
+                    start_pc: 0,
+                    length: 6,
+                    name_index: this_name,
+                    descriptor_index: this_descriptor,
+                    index: 0,
+                }],
+            },
+            raw: None,
+        });
+        let debug_bytes = classfile_to_bytes(&classfile).unwrap();
+
+        let (_, mut classfile) = classfile_from_bytes(&debug_bytes).unwrap();
+        classfile.strip_debug();
+        compact_constant_pool(&mut classfile).unwrap();
+        let stripped_bytes = classfile_to_bytes(&classfile).unwrap();
+        assert!(stripped_bytes.len() < debug_bytes.len());
+
+        let (rest, classfile) = classfile_from_bytes(&stripped_bytes).unwrap();
+        assert!(rest.is_empty());
+        let constant_pool = &classfile.constant_pool;
+        assert!(classfile.attributes.is_empty());
+        // the names and values only the debug attributes used are gone from the pool
+        assert!((1..)
+            .take(constant_pool.len())
+            .filter_map(|index| constant_pool.utf8(index).ok())
+            .all(
+                |text| !["SourceFile", "Debug.java", "LineNumberTable", "this"].contains(&&*text)
+            ));
+        assert_eq!(
+            constant_pool.class_name(classfile.this_class).unwrap(),
+            "Debug"
+        );
+        let names = classfile
+            .methods
+            .iter()
+            .map(|method| constant_pool.utf8(method.name_index).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["run", "print"]);
+
+        let Some(AttributeInfo::Code {
+            code, attributes, ..
+        }) = classfile.methods[0].code().map(|code| &code.info)
+        else {
+            panic!("run has no Code attribute");
+        };
+        assert!(attributes.is_empty());
+        let ConstantPoolEntry::String { string_index } = constant_pool.get(code[1].into()).unwrap()
+        else {
+            panic!("ldc does not load a String");
+        };
+        assert_eq!(constant_pool.utf8(*string_index).unwrap(), "hello");
+        let print = constant_pool
+            .resolve_method_ref(u16::from_be_bytes([code[3], code[4]]))
+            .unwrap();
+        assert_eq!(
+            (&*print.owner, &*print.name, &*print.descriptor),
+            ("Debug", "print", "(Ljava/lang/String;)V")
+        );
+    }
+
+    #[test]
+    fn merged_methods_keep_their_references() {
+        let version = Version {
+            minor: 0,
+            major: 50,
+        };
+        let mut first = ClassBuilder::new(version, 0x21, "First", Some("java/lang/Object"));
+        // the Long takes two indices, so everything after it in the merged pool moves by one more
+        first.constant_pool().long(1).unwrap();
+        first.constant_pool().string("first").unwrap();
+        let first_bytes = first.build().unwrap();
+
+        let mut second = ClassBuilder::new(version, 0x21, "Second", Some("java/lang/Object"));
+        let [l1, l2] = second.constant_pool().long(2).unwrap().to_be_bytes();
+        let string = second.constant_pool().string("second").unwrap();
+        let [m1, m2] = second
+            .constant_pool()
+            .method_ref("Second", "take", "(J)V")
+            .unwrap()
+            .to_be_bytes();
+        // static void run() { take(2L); }, then loading and dropping "second"
+        let mut run = MethodBuilder::new(0x8, "run", "()V");
+        run.code(
+            2,
+            0,
+            vec![0x14, l1, l2, 0xB8, m1, m2, 0x12, string as u8, 0x57, 0xB1],
+        );
+        second.add_method(run).unwrap();
+        let second_bytes = second.build().unwrap();
+
+        let (_, first) = classfile_from_bytes(&first_bytes).unwrap();
+        let (_, mut second) = classfile_from_bytes(&second_bytes).unwrap();
+        let mut destination = ConstantPoolBuilder::new();
+        remap_constant_pool(&first.constant_pool, &mut destination).unwrap();
+        let map = remap_constant_pool(&second.constant_pool, &mut destination).unwrap();
+        let method = &mut second.methods[0];
+        remap_method(method, &map).unwrap();
+        let constant_pool = destination.to_constant_pool();
+
+        assert_eq!(constant_pool.utf8(method.name_index).unwrap(), "run");
+        assert_eq!(constant_pool.utf8(method.descriptor_index).unwrap(), "()V");
+        let Some(AttributeInfo::Code { code, .. }) = method.code().map(|code| &code.info) else {
+            panic!("run has no Code attribute");
+        };
+        let long = u16::from_be_bytes([code[1], code[2]]);
+        assert_ne!(long, u16::from_be_bytes([l1, l2]));
+        assert!(matches!(
+            constant_pool.get(long),
+            Ok(ConstantPoolEntry::Long { value: 2 })
+        ));
+        let take = constant_pool
+            .resolve_method_ref(u16::from_be_bytes([code[4], code[5]]))
+            .unwrap();
+        assert_eq!(
+            (&*take.owner, &*take.name, &*take.descriptor),
+            ("Second", "take", "(J)V")
+        );
+        let ConstantPoolEntry::String { string_index } = constant_pool.get(code[7].into()).unwrap()
+        else {
+            panic!("ldc does not load a String");
+        };
+        assert_eq!(constant_pool.utf8(*string_index).unwrap(), "second");
+    }
+}
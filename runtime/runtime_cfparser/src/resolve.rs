@@ -0,0 +1,3640 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::slice;
+
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::ConstantPoolError;
+use crate::constant_pool::Utf8Constant;
+use crate::descriptor::field_descriptor_from_str;
+use crate::descriptor::method_descriptor_from_str;
+use crate::descriptor::FieldType;
+use crate::descriptor::MethodDescriptor;
+use crate::instruction::instructions_from_bytes;
+use crate::instruction::Instruction;
+use crate::modifier::format_class_modifiers;
+use crate::modifier::format_field_modifiers;
+use crate::modifier::format_method_modifiers;
+use crate::signature::class_signature_from_str;
+use crate::signature::field_signature_from_str;
+use crate::signature::method_signature_from_str;
+use crate::signature::ClassSignature;
+use crate::signature::ClassTypeSignature;
+use crate::signature::TypeArgument;
+use crate::signature::TypeParameter;
+use crate::signature::TypeSignature;
+use crate::spec::AccessFlags;
+use crate::spec::Annotation;
+use crate::spec::Attribute;
+use crate::spec::AttributeInfo;
+use crate::spec::BootstrapMethod;
+use crate::spec::Classfile;
+use crate::spec::ConstantPoolEntry;
+use crate::spec::ElementValue;
+use crate::spec::FieldAccessFlags;
+use crate::spec::Method;
+use crate::spec::MethodAccessFlags;
+use crate::spec::ModuleFlags;
+use crate::spec::VerificationTypeInfo;
+
+// the 64-bit FNV-1a parameters, a hash whose output is fixed by its definition rather than by the standard
+// library version
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+const FUNCTIONAL_INTERFACE: &str = "Ljava/lang/FunctionalInterface;";
+
+const LAMBDA_METAFACTORY: &str = "java/lang/invoke/LambdaMetafactory";
+
+// The JVM caps constant_pool_count at 65535, which leaves the indices 1 through 65534 for entries. Long and
+// Double entries take up two indices each.
+const MAX_CONSTANT_POOL_SLOTS: usize = 65534;
+
+const OBJECT: &str = "java/lang/Object";
+
+// the methods of java/lang/Object other than its constructor, by name and descriptor
+const OBJECT_METHODS: [(&str, &str); 11] = [
+    ("clone", "()Ljava/lang/Object;"),
+    ("equals", "(Ljava/lang/Object;)Z"),
+    ("finalize", "()V"),
+    ("getClass", "()Ljava/lang/Class;"),
+    ("hashCode", "()I"),
+    ("notify", "()V"),
+    ("notifyAll", "()V"),
+    ("toString", "()Ljava/lang/String;"),
+    ("wait", "()V"),
+    ("wait", "(J)V"),
+    ("wait", "(JI)V"),
+];
+
+const STRING_CONCAT_FACTORY: &str = "java/lang/invoke/StringConcatFactory";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ClassKind {
+    // `@interface`
+    Annotation,
+    Class,
+    Enum,
+    Interface,
+    // `module-info`
+    Module,
+    Record,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ElementType {
+    AnnotationType,
+    Constructor,
+    Field,
+    LocalVariable,
+    Method,
+    Module,
+    Package,
+    Parameter,
+    RecordComponent,
+    Type,
+    TypeParameter,
+    TypeUse,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MemberDescriptor {
+    Field(FieldType),
+    Method(MethodDescriptor),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MemberKind {
+    // `<init>`
+    Constructor,
+    Field,
+    Method,
+    // `<clinit>`
+    StaticInitializer,
+}
+
+// how a method is declared, which in an interface is partly implied by whether it has a body
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MethodKind {
+    Abstract,
+    Concrete,
+    // a method with a body in an interface that is neither static nor private
+    Default,
+    // including the private methods of interfaces, which Java 9 allows
+    Private,
+    // including private static methods
+    Static,
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    InvalidConstantPoolIndex { index: u16 },
+    InvalidUtf8 { index: u16 },
+    MalformedCode { method_index: usize },
+    MalformedDescriptor { descriptor: String },
+    MalformedSignature { signature: String },
+    MissingBootstrapMethod { index: u16 },
+    PoolLimitExceeded { slots: usize },
+    UnexpectedConstantPoolEntry { index: u16, expected: &'static str },
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidConstantPoolIndex { index } => {
+                write!(f, "constant pool index {index} is out of range")
+            }
+            Self::InvalidUtf8 { index } => {
+                write!(f, "constant pool entry {index} is not valid modified UTF-8")
+            }
+            Self::MalformedCode { method_index } => {
+                write!(f, "code of method {method_index} does not decode")
+            }
+            Self::MalformedDescriptor { descriptor } => {
+                write!(f, "malformed descriptor `{descriptor}`")
+            }
+            Self::MalformedSignature { signature } => {
+                write!(f, "malformed signature `{signature}`")
+            }
+            Self::MissingBootstrapMethod { index } => {
+                write!(f, "class has no bootstrap method {index}")
+            }
+            Self::PoolLimitExceeded { slots } => {
+                write!(
+                    f,
+                    "constant pool needs {slots} indices, over the limit of {MAX_CONSTANT_POOL_SLOTS}"
+                )
+            }
+            Self::UnexpectedConstantPoolEntry { index, expected } => {
+                write!(f, "constant pool entry {index} is not a {expected} entry")
+            }
+        }
+    }
+}
+
+impl Error for ResolveError {}
+
+impl From<ConstantPoolError> for ResolveError {
+    fn from(error: ConstantPoolError) -> Self {
+        match error {
+            ConstantPoolError::InvalidIndex { index } => Self::InvalidConstantPoolIndex { index },
+            ConstantPoolError::InvalidUtf8 { index } => Self::InvalidUtf8 { index },
+            ConstantPoolError::UnexpectedEntry { index, expected } => {
+                Self::UnexpectedConstantPoolEntry { index, expected }
+            }
+        }
+    }
+}
+
+// the value of an annotation element, with constants read from the constant pool by their tag
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResolvedElementValue<'a> {
+    Annotation(ResolvedAnnotation<'a>),
+    Array(Vec<ResolvedElementValue<'a>>),
+    Boolean(bool),
+    Byte(i8),
+    Char(u16),
+    // the return descriptor of the class literal, `V` for `void.class`
+    Class(Cow<'a, str>),
+    Double(f64),
+    Enum {
+        type_name: Cow<'a, str>,
+        const_name: Cow<'a, str>,
+    },
+    Float(f32),
+    Int(i32),
+    Long(i64),
+    Short(i16),
+    String(Cow<'a, str>),
+    // a string that is not valid modified UTF-8, as left by some obfuscators
+    StringBytes(Cow<'a, [u8]>),
+}
+
+// a verification type of a stack map frame, with the class of an object type read from the constant pool
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ResolvedVerificationType<'a> {
+    Double,
+    Float,
+    Integer,
+    Long,
+    Null,
+    // the binary name of the class, or the descriptor of an array type
+    Object(Cow<'a, str>),
+    Top,
+    // an object created but not yet initialized, by the `new` instruction at `offset`; the offset is into
+    // the code, not the constant pool
+    Uninitialized { offset: u16 },
+    UninitializedThis,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RetentionPolicy {
+    Class,
+    Runtime,
+    Source,
+}
+
+// the class files javac compiles from a `package-info.java` or `module-info.java`, which declare no type
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SpecialClass {
+    ModuleInfo,
+    PackageInfo,
+}
+
+// A piece of a string concatenation recipe. Arguments are numbered in the order of the parameters of the
+// call site, and constants by their constant pool index.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StringConcatPart {
+    Argument(usize),
+    Constant(u16),
+    Literal(String),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SwitchConfidence {
+    // every case compares the string the hash code was computed from, against a constant with the hash code
+    // of its key, and stores the position of the case for a second switch
+    High,
+    Low,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Toolchain {
+    Groovy,
+    Javac,
+    Kotlin,
+    Scala,
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnnotatedMember<'a> {
+    // `None` for the class itself, which is named by its internal name and has no descriptor
+    pub kind: Option<MemberKind>,
+    pub name: Cow<'a, str>,
+    pub descriptor: Option<Cow<'a, str>>,
+    pub annotation: ResolvedAnnotation<'a>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnnotationMeta {
+    // `RetentionPolicy::Class` when the annotation type has no `@Retention`
+    pub retention: RetentionPolicy,
+    // `None` when the annotation type has no `@Target`, making it applicable in all declaration contexts
+    pub targets: Option<Vec<ElementType>>,
+}
+
+// which compiler-generated members `ResolvedClass::public_api` leaves out; by default, all of them
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ApiFilter {
+    pub hide_bridge: bool,
+    // members with the ACC_SYNTHETIC flag or, as older compilers emit instead, a Synthetic attribute
+    pub hide_synthetic: bool,
+    // the synthetic `access$` methods javac generated before nestmates to reach private members of other
+    // nest members, even if other synthetic members are shown
+    pub hide_accessors: bool,
+}
+
+impl ApiFilter {
+    // `attributes` are those of the member
+    fn hides(&self, member: &MemberApi, attributes: &[Attribute]) -> bool {
+        let synthetic = member.access_flags & AccessFlags::SYNTHETIC != 0
+            || attributes
+                .iter()
+                .any(|attribute| attribute.info == AttributeInfo::Synthetic);
+        // the bridge flag shares its bit with the volatile flag of fields
+        let bridge = member.kind != MemberKind::Field
+            && member.access_flags & MethodAccessFlags::BRIDGE != 0;
+        let accessor = synthetic && member.name.starts_with("access$");
+
+        self.hide_bridge && bridge
+            || self.hide_synthetic && synthetic
+            || self.hide_accessors && accessor
+    }
+}
+
+impl Default for ApiFilter {
+    fn default() -> Self {
+        Self {
+            hide_bridge: true,
+            hide_synthetic: true,
+            hide_accessors: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Deprecation<'a> {
+    // the `since` and `forRemoval` elements of `@Deprecated`, which a Deprecated attribute alone lacks
+    pub since: Option<Cow<'a, str>>,
+    pub for_removal: bool,
+}
+
+// The `@kotlin.Metadata` annotation kotlinc puts on every class it compiles. The declarations themselves are
+// a protobuf message split across the strings of d1, whose string table is d2; they are left undecoded here.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KotlinMetadata<'a> {
+    // 1 for a class, 2 for a file facade, 3 for a synthetic class, 4 for a multi-file class facade and 5 for
+    // one of its parts
+    pub kind: i32,
+    // the version of the metadata format, such as [1, 9, 0]
+    pub metadata_version: Vec<i32>,
+    pub data1: Vec<Cow<'a, str>>,
+    pub data2: Vec<Cow<'a, str>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LambdaSite<'a> {
+    // the method whose code holds the invokedynamic instruction, as an index into the class's methods
+    pub method_index: usize,
+    pub offset: usize,
+    // the name of the functional interface method the lambda implements
+    pub interface_method_name: Cow<'a, str>,
+    pub sam_method_type: MethodDescriptor,
+    pub implementation: MethodHandleTarget<'a>,
+    pub instantiated_method_type: MethodDescriptor,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MemberApi<'a> {
+    pub kind: MemberKind,
+    pub name: Cow<'a, str>,
+    pub descriptor: MemberDescriptor,
+    pub access_flags: u16,
+}
+
+// the member a method handle refers to; the descriptor is a field descriptor for the field access kinds
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MethodHandleTarget<'a> {
+    pub reference_kind: u8,
+    pub class: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    pub descriptor: Cow<'a, str>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedAnnotation<'a> {
+    // a field descriptor, such as `Ljavax/inject/Named;`
+    pub type_name: Cow<'a, str>,
+    // in classfile order; elements left at their default value are not stored in the class file
+    pub elements: Vec<(Cow<'a, str>, ResolvedElementValue<'a>)>,
+}
+
+pub struct ResolvedClass<'c, 'a> {
+    classfile: &'c Classfile<'a>,
+}
+
+// A dynamically-computed constant, which unlike an invokedynamic call site is a loadable constant, such as
+// the operand of an ldc, whose value the bootstrap method computes once. Its descriptor is therefore a field
+// descriptor, the type of the constant, rather than a method descriptor.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResolvedDynamic<'a> {
+    pub name: Cow<'a, str>,
+    pub constant_type: FieldType,
+    pub bootstrap: MethodHandleTarget<'a>,
+    // the indices of the loadable constant pool entries passed to the bootstrap method after its own arguments
+    pub bootstrap_arguments: Vec<u16>,
+}
+
+// An entry of a LocalVariableTable. Its scope is the `length` bytes of code from `start_pc`; a slot reused
+// for another variable in a later scope has an entry of its own.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedLocalVariable<'a> {
+    pub index: u16,
+    pub name: Cow<'a, str>,
+    pub field_type: FieldType,
+    pub start_pc: u16,
+    pub length: u16,
+}
+
+// An invokedynamic call site bootstrapped by StringConcatFactory. In the recipe, `\u{1}` stands for the next
+// argument of the call site and `\u{2}` for the next of the constants; makeConcat, which takes no recipe,
+// concatenates its arguments as if given a recipe of `\u{1}` alone for each.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StringConcat<'a> {
+    // the method whose code holds the invokedynamic instruction, as an index into the class's methods
+    pub method_index: usize,
+    pub offset: usize,
+    pub recipe: Cow<'a, str>,
+    // the indices of the bootstrap arguments following the recipe, in the order the recipe uses them
+    pub constants: Vec<u16>,
+    pub parts: Vec<StringConcatPart>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StringSwitch<'a> {
+    // the offset of the lookupswitch on the hash code of the string
+    pub offset: usize,
+    pub cases: Vec<StringSwitchCase<'a>>,
+    pub confidence: SwitchConfidence,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StringSwitchCase<'a> {
+    pub value: Cow<'a, str>,
+    // the offset of the code run when the string equals the value
+    pub target: usize,
+    // javac numbers the cases in source order and switches on that number in a second switch; `None` if the
+    // code at the target does not store such a number
+    pub case_index: Option<i32>,
+}
+
+// a synthetic `access$` method through which, before Java 11, javac let nested classes reach each other's
+// private members
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyntheticAccessor<'a> {
+    // the accessor, as an index into the class's methods
+    pub method_index: usize,
+    pub name: Cow<'a, str>,
+    // the member the accessor reads, writes or calls, with the reference kind of the instruction doing so
+    pub target: MethodHandleTarget<'a>,
+}
+
+// an `aload; ldc; invokevirtual String.equals; ifeq` sequence
+struct StringComparison<'a> {
+    local: u16,
+    value: Cow<'a, str>,
+    // the position of the instruction following the comparison
+    matched: usize,
+    // the offset the comparison branches to when the string does not equal the value
+    mismatched: usize,
+}
+
+impl<'c, 'a> ResolvedClass<'c, 'a> {
+    pub fn new(classfile: &'c Classfile<'a>) -> Self {
+        Self { classfile }
+    }
+
+    pub fn classfile(&self) -> &'c Classfile<'a> {
+        self.classfile
+    }
+
+    // `None` if the class is not an annotation type
+    pub fn annotation_metadata(&self) -> Result<Option<AnnotationMeta>, ResolveError> {
+        if self.classfile.access_flags & AccessFlags::ANNOTATION == 0 {
+            return Ok(None);
+        }
+
+        let mut meta = AnnotationMeta {
+            retention: RetentionPolicy::Class,
+            targets: None,
+        };
+
+        let annotations = self
+            .classfile
+            .attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.info {
+                AttributeInfo::RuntimeVisibleAnnotations { annotations } => Some(annotations),
+                _ => None,
+            })
+            .flatten();
+        for annotation in annotations {
+            let annotation_type = self.utf8(annotation.type_index)?;
+            let Some(value) = annotation
+                .element_value_pairs
+                .iter()
+                .find(|pair| {
+                    self.utf8(pair.element_name_index)
+                        .is_ok_and(|name| name == "value")
+                })
+                .map(|pair| &pair.value)
+            else {
+                continue;
+            };
+
+            match annotation_type.as_ref() {
+                "Ljava/lang/annotation/Retention;" => {
+                    let retention =
+                        self.enum_const_name(value, "Ljava/lang/annotation/RetentionPolicy;")?;
+                    match retention.as_deref() {
+                        Some("CLASS") => meta.retention = RetentionPolicy::Class,
+                        Some("RUNTIME") => meta.retention = RetentionPolicy::Runtime,
+                        Some("SOURCE") => meta.retention = RetentionPolicy::Source,
+                        _ => {}
+                    }
+                }
+                "Ljava/lang/annotation/Target;" => {
+                    // a single element may be written without the surrounding array
+                    let values = match value {
+                        ElementValue::Array { values } => values.as_slice(),
+                        value => slice::from_ref(value),
+                    };
+
+                    let mut targets = Vec::with_capacity(values.len());
+                    for value in values {
+                        let target =
+                            self.enum_const_name(value, "Ljava/lang/annotation/ElementType;")?;
+
+                        // element types introduced by newer Java releases are skipped
+                        targets.extend(match target.as_deref() {
+                            Some("ANNOTATION_TYPE") => Some(ElementType::AnnotationType),
+                            Some("CONSTRUCTOR") => Some(ElementType::Constructor),
+                            Some("FIELD") => Some(ElementType::Field),
+                            Some("LOCAL_VARIABLE") => Some(ElementType::LocalVariable),
+                            Some("METHOD") => Some(ElementType::Method),
+                            Some("MODULE") => Some(ElementType::Module),
+                            Some("PACKAGE") => Some(ElementType::Package),
+                            Some("PARAMETER") => Some(ElementType::Parameter),
+                            Some("RECORD_COMPONENT") => Some(ElementType::RecordComponent),
+                            Some("TYPE") => Some(ElementType::Type),
+                            Some("TYPE_PARAMETER") => Some(ElementType::TypeParameter),
+                            Some("TYPE_USE") => Some(ElementType::TypeUse),
+                            _ => None,
+                        });
+                    }
+
+                    meta.targets = Some(targets);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Some(meta))
+    }
+
+    // fields come before methods, each in classfile order; every member is only resolved once the
+    // iterator reaches it
+    pub fn api(&self) -> impl Iterator<Item = Result<MemberApi<'a>, ResolveError>> + 'c {
+        let classfile = self.classfile;
+
+        let fields = classfile.fields.iter().map(move |field| {
+            Self::new(classfile).member_api(
+                MemberKind::Field,
+                field.access_flags,
+                field.name_index,
+                field.descriptor_index,
+            )
+        });
+        let methods = classfile.methods.iter().map(move |method| {
+            Self::new(classfile).member_api(
+                MemberKind::Method,
+                method.access_flags,
+                method.name_index,
+                method.descriptor_index,
+            )
+        });
+
+        fields.chain(methods)
+    }
+
+    // A listing of the API of the class to commit and compare: its declaration header, then a line for each
+    // public or protected member, which code outside the package can use. Members are sorted by kind, name
+    // and descriptor, so that neither their order nor the layout of the constant pool shows. The members the
+    // default ApiFilter hides are left out, as are the modifiers of a method that callers cannot observe.
+    pub fn api_snapshot(&self) -> Result<String, ResolveError> {
+        let classfile = self.classfile;
+        let in_interface = classfile.access_flags & AccessFlags::INTERFACE != 0;
+        let filter = ApiFilter::default();
+        let class_name = self.class_name(classfile.this_class)?.replace('/', ".");
+        let exported = |access_flags: u16| {
+            access_flags & (MethodAccessFlags::PUBLIC | MethodAccessFlags::PROTECTED) != 0
+        };
+
+        // fields come first, then constructors, then methods
+        let mut members = Vec::new();
+        for field in &classfile.fields {
+            let member = self.member_api(
+                MemberKind::Field,
+                field.access_flags,
+                field.name_index,
+                field.descriptor_index,
+            )?;
+            if !exported(member.access_flags) || filter.hides(&member, &field.attributes) {
+                continue;
+            }
+
+            let descriptor = self.utf8(field.descriptor_index)?;
+            let MemberDescriptor::Field(field_type) = &member.descriptor else {
+                unreachable!();
+            };
+            let modifiers = format_field_modifiers(member.access_flags, in_interface);
+            let declaration = format!("{} {}", field_type_to_source(field_type), member.name);
+
+            members.push((0, member.name, descriptor, [modifiers, declaration]));
+        }
+        for method in &classfile.methods {
+            let member = self.member_api(
+                MemberKind::Method,
+                method.access_flags,
+                method.name_index,
+                method.descriptor_index,
+            )?;
+            if !exported(member.access_flags) || filter.hides(&member, &method.attributes) {
+                continue;
+            }
+
+            let descriptor = self.utf8(method.descriptor_index)?;
+            let flags = member.access_flags
+                & !(MethodAccessFlags::SYNCHRONIZED
+                    | MethodAccessFlags::NATIVE
+                    | MethodAccessFlags::STRICT);
+            let modifiers = format_method_modifiers(flags, in_interface);
+            let signature = method.to_source_signature(&classfile.constant_pool)?;
+            let (rank, declaration) = match member.kind {
+                // written as in Java source, named after the class and without a return type
+                MemberKind::Constructor => (1, signature.replacen("void <init>", &class_name, 1)),
+                _ => (2, signature),
+            };
+
+            members.push((rank, member.name, descriptor, [modifiers, declaration]));
+        }
+        members.sort_unstable_by(|left, right| {
+            (left.0, &left.1, &left.2).cmp(&(right.0, &right.1, &right.2))
+        });
+
+        let mut snapshot = self.declaration_header()?;
+        snapshot.push('\n');
+        for (_, _, _, parts) in members {
+            snapshot.push_str(parts.join(" ").trim_start());
+            snapshot.push('\n');
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn class_name(&self, index: u16) -> Result<Cow<'a, str>, ResolveError> {
+        Ok(self.classfile.constant_pool.class_name(index)?)
+    }
+
+    // a Class entry names an array type by its descriptor, and any other type by its binary name
+    pub fn class_type(&self, index: u16) -> Result<FieldType, ResolveError> {
+        let name = self.class_name(index)?;
+
+        if !name.starts_with('[') {
+            return Ok(FieldType::Object(name.into_owned()));
+        }
+
+        field_descriptor_from_str(&name).ok_or_else(|| ResolveError::MalformedDescriptor {
+            descriptor: name.into_owned(),
+        })
+    }
+
+    // The declaration of the class as it opens in Java source, such as
+    // `public final class com.example.Foo<T> extends java.util.AbstractList<T> implements java.io.Serializable`,
+    // with the type parameters and supertypes of its Signature attribute if it has one. The supertypes every
+    // class of its kind has, such as java/lang/Enum for an enum, are left out, and a record lists its
+    // components. A module-info class declares its module, as `module java.base`.
+    pub fn declaration_header(&self) -> Result<String, ResolveError> {
+        let classfile = self.classfile;
+        let kind = self.kind();
+        if kind == ClassKind::Module {
+            return self.module_header();
+        }
+
+        // records are implicitly final
+        let mut flags = classfile.access_flags;
+        if kind == ClassKind::Record {
+            flags &= !AccessFlags::FINAL;
+        }
+        let mut modifiers = vec![format_class_modifiers(flags)];
+        if self.is_sealed() {
+            modifiers.push("sealed".to_string());
+        }
+        modifiers.push(
+            match kind {
+                ClassKind::Annotation => "@interface",
+                ClassKind::Class => "class",
+                ClassKind::Enum => "enum",
+                ClassKind::Interface => "interface",
+                ClassKind::Record => "record",
+                ClassKind::Module => unreachable!(),
+            }
+            .to_string(),
+        );
+        modifiers.retain(|modifier| !modifier.is_empty());
+
+        let name = self.class_name(classfile.this_class)?.replace('/', ".");
+        let mut header = format!("{} {name}", modifiers.join(" "));
+        if let Some(signature) = self.class_signature()? {
+            header.push_str(&type_parameters_to_source(&signature.type_parameters));
+        }
+        if kind == ClassKind::Record {
+            header.push_str(&format!(
+                "({})",
+                self.record_components_to_source()?.join(", ")
+            ));
+        }
+
+        let superclass = self
+            .generic_superclass()?
+            .filter(|superclass| superclass.binary_name() != OBJECT);
+        let interfaces = self
+            .generic_interfaces()?
+            .iter()
+            .map(class_type_signature_to_source)
+            .collect::<Vec<_>>();
+        match (kind, superclass) {
+            (ClassKind::Class, Some(superclass)) => {
+                header.push_str(" extends ");
+                header.push_str(&class_type_signature_to_source(&superclass));
+            }
+            // an annotation interface extends java/lang/annotation/Annotation and nothing else
+            (ClassKind::Annotation, _) => return Ok(header),
+            _ => {}
+        }
+        if !interfaces.is_empty() {
+            header.push_str(match kind {
+                ClassKind::Interface => " extends ",
+                _ => " implements ",
+            });
+            header.push_str(&interfaces.join(", "));
+        }
+
+        Ok(header)
+    }
+
+    pub fn deprecation(&self) -> Result<Option<Deprecation<'a>>, ResolveError> {
+        deprecation(&self.classfile.attributes, &self.classfile.constant_pool)
+    }
+
+    // The class immediately enclosing a nested class, from its own entry in InnerClasses or, for local and
+    // anonymous classes, which have no outer class there, from EnclosingMethod. `None` for top-level classes.
+    pub fn enclosing_class(&self) -> Result<Option<Cow<'a, str>>, ResolveError> {
+        let classfile = self.classfile;
+        let this_class = self.class_name(classfile.this_class)?;
+
+        for attribute in &classfile.attributes {
+            let AttributeInfo::InnerClasses { classes } = &attribute.info else {
+                continue;
+            };
+
+            for class in classes {
+                if class.outer_class_info_index != 0
+                    && self.class_name(class.inner_class_info_index)? == this_class
+                {
+                    return self.class_name(class.outer_class_info_index).map(Some);
+                }
+            }
+        }
+
+        classfile
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute.info {
+                AttributeInfo::EnclosingMethod { class_index, .. } => Some(class_index),
+                _ => None,
+            })
+            .map(|index| self.class_name(index))
+            .transpose()
+    }
+
+    // The names of the constants of an enum class, in declaration order. The constants are its static final
+    // fields of its own type that are flagged ACC_ENUM, which leaves out other fields of that type as well as
+    // the synthetic `$VALUES` array. `None` for classes that are not enums.
+    pub fn enum_constants(&self) -> Result<Option<Vec<Cow<'a, str>>>, ResolveError> {
+        let classfile = self.classfile;
+        if classfile.access_flags & AccessFlags::ENUM == 0 {
+            return Ok(None);
+        }
+
+        let descriptor = format!("L{};", self.class_name(classfile.this_class)?);
+        let flags = FieldAccessFlags::STATIC | FieldAccessFlags::FINAL | FieldAccessFlags::ENUM;
+
+        let mut constants = Vec::new();
+        for field in &classfile.fields {
+            if field.access_flags & flags == flags
+                && self.utf8(field.descriptor_index)? == descriptor
+            {
+                constants.push(self.utf8(field.name_index)?);
+            }
+        }
+
+        Ok(Some(constants))
+    }
+
+    // the direct superinterfaces with their type arguments, falling back to the erased ones as
+    // generic_superclass does
+    pub fn generic_interfaces(&self) -> Result<Vec<ClassTypeSignature>, ResolveError> {
+        if let Some(signature) = self.class_signature()? {
+            return Ok(signature.interfaces);
+        }
+
+        self.classfile
+            .interfaces
+            .iter()
+            .map(|&index| Ok(erased_class_type(&self.class_name(index)?)))
+            .collect()
+    }
+
+    // The superclass with its type arguments, as in `java/util/AbstractList<TE;>`, from the Signature
+    // attribute. A class without one is not generic, and its erased superclass is all there is. `None` for
+    // the classes without a superclass.
+    pub fn generic_superclass(&self) -> Result<Option<ClassTypeSignature>, ResolveError> {
+        if let Some(signature) = self.class_signature()? {
+            return Ok(Some(signature.superclass));
+        }
+
+        Ok(self
+            .optional_class_name(self.classfile.super_class)?
+            .map(|name| erased_class_type(&name)))
+    }
+
+    // Whether the class is an interface annotated with @FunctionalInterface, or one declaring exactly one
+    // abstract method. Abstract redeclarations of the public methods of java/lang/Object do not count, as
+    // every implementation inherits those; static and default methods are not abstract to begin with. Only
+    // the methods the interface declares itself are looked at, not those of its superinterfaces.
+    pub fn is_functional_interface(&self) -> Result<bool, ResolveError> {
+        let classfile = self.classfile;
+        if classfile.access_flags & AccessFlags::INTERFACE == 0
+            || classfile.access_flags & AccessFlags::ANNOTATION != 0
+        {
+            return Ok(false);
+        }
+
+        for attribute in &classfile.attributes {
+            let (AttributeInfo::RuntimeVisibleAnnotations { annotations }
+            | AttributeInfo::RuntimeInvisibleAnnotations { annotations }) = &attribute.info
+            else {
+                continue;
+            };
+
+            for annotation in annotations {
+                if self.utf8(annotation.type_index)? == FUNCTIONAL_INTERFACE {
+                    return Ok(true);
+                }
+            }
+        }
+
+        let mut abstract_methods = 0;
+        for method in classfile
+            .methods
+            .iter()
+            .filter(|method| method.is_abstract())
+        {
+            let name = self.utf8(method.name_index)?;
+            let descriptor = self.utf8(method.descriptor_index)?;
+
+            // clone and finalize are protected in Object, so redeclaring them does add an abstract method
+            if !is_object_method(&name, &descriptor)
+                || matches!(name.as_ref(), "clone" | "finalize")
+            {
+                abstract_methods += 1;
+            }
+        }
+
+        Ok(abstract_methods == 1)
+    }
+
+    pub fn is_record(&self) -> bool {
+        self.classfile
+            .attributes
+            .iter()
+            .any(|attribute| matches!(attribute.info, AttributeInfo::Record { .. }))
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.classfile
+            .attributes
+            .iter()
+            .any(|attribute| matches!(attribute.info, AttributeInfo::PermittedSubclasses { .. }))
+    }
+
+    // a record is a final class extending java/lang/Record, which is told by its Record attribute
+    pub fn kind(&self) -> ClassKind {
+        let flags = self.classfile.access_flags;
+
+        if flags & AccessFlags::MODULE != 0 {
+            ClassKind::Module
+        } else if flags & AccessFlags::ANNOTATION != 0 {
+            ClassKind::Annotation
+        } else if flags & AccessFlags::INTERFACE != 0 {
+            ClassKind::Interface
+        } else if flags & AccessFlags::ENUM != 0 {
+            ClassKind::Enum
+        } else if self.is_record() {
+            ClassKind::Record
+        } else {
+            ClassKind::Class
+        }
+    }
+
+    // `None` if the class has no `@kotlin.Metadata` annotation. Elements left out take the annotation's
+    // defaults; elements of the wrong type are ill-formed and ignored, as are the non-strings of d1 and d2.
+    pub fn kotlin_metadata(&self) -> Result<Option<KotlinMetadata<'a>>, ResolveError> {
+        let constant_pool = &self.classfile.constant_pool;
+
+        let annotation = self
+            .classfile
+            .attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.info {
+                AttributeInfo::RuntimeVisibleAnnotations { annotations } => Some(annotations),
+                _ => None,
+            })
+            .flatten()
+            .find(|annotation| {
+                constant_pool
+                    .utf8(annotation.type_index)
+                    .is_ok_and(|type_name| type_name == "Lkotlin/Metadata;")
+            });
+        let Some(annotation) = annotation else {
+            return Ok(None);
+        };
+
+        let mut metadata = KotlinMetadata {
+            kind: 1,
+            metadata_version: Vec::new(),
+            data1: Vec::new(),
+            data2: Vec::new(),
+        };
+        for (name, value) in resolved_annotation(annotation, constant_pool)?.elements {
+            match (name.as_ref(), value) {
+                ("k", ResolvedElementValue::Int(kind)) => metadata.kind = kind,
+                ("mv", ResolvedElementValue::Array(values)) => {
+                    metadata.metadata_version = values
+                        .into_iter()
+                        .filter_map(|value| match value {
+                            ResolvedElementValue::Int(value) => Some(value),
+                            _ => None,
+                        })
+                        .collect();
+                }
+                ("d1", ResolvedElementValue::Array(values)) => {
+                    metadata.data1 = string_elements(values)
+                }
+                ("d2", ResolvedElementValue::Array(values)) => {
+                    metadata.data2 = string_elements(values)
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Some(metadata))
+    }
+
+    // Each lambda site implemented by one of the lambda implementation methods of the class, with the index of
+    // that method, in the order of the sites. Sites implemented by other methods, as those of method references
+    // are, are left out.
+    pub fn lambda_bodies(&self) -> Result<Vec<(LambdaSite<'a>, usize)>, ResolveError> {
+        let this_class = self.class_name(self.classfile.this_class)?;
+
+        let mut bodies = Vec::new();
+        for site in self.lambda_targets()? {
+            let implementation = &site.implementation;
+            if implementation.class != this_class {
+                continue;
+            }
+
+            for (method_index, method) in self.classfile.methods.iter().enumerate() {
+                if self.is_lambda_implementation(method)?
+                    && self.utf8(method.name_index)? == implementation.name
+                    && self.utf8(method.descriptor_index)? == implementation.descriptor
+                {
+                    bodies.push((site, method_index));
+                    break;
+                }
+            }
+        }
+
+        Ok(bodies)
+    }
+
+    // The synthetic methods named `lambda$...` that javac compiles the bodies of lambda expressions into, in
+    // method order.
+    pub fn lambda_implementation_methods(&self) -> Result<Vec<&'c Method<'a>>, ResolveError> {
+        let mut methods = Vec::new();
+        for method in &self.classfile.methods {
+            if self.is_lambda_implementation(method)? {
+                methods.push(method);
+            }
+        }
+
+        Ok(methods)
+    }
+
+    // Sites bootstrapped by LambdaMetafactory.metafactory or altMetafactory, in method and code order. Other
+    // invokedynamic sites, such as those for string concatenation, are skipped.
+    pub fn lambda_targets(&self) -> Result<Vec<LambdaSite<'a>>, ResolveError> {
+        let bootstrap_methods = self.bootstrap_methods();
+
+        let mut sites = Vec::new();
+        for (method_index, offset, index) in self.invokedynamic_sites()? {
+            if let Some(site) = self.lambda_site(bootstrap_methods, method_index, offset, index)? {
+                sites.push(site);
+            }
+        }
+
+        Ok(sites)
+    }
+
+    // Sites bootstrapped by StringConcatFactory.makeConcat or makeConcatWithConstants, in method and code
+    // order, which javac emits for `+` on strings when targeting Java 9 or later. A site whose recipe does not
+    // match its descriptor and bootstrap arguments would fail to link, and is skipped like other sites.
+    pub fn string_concatenations(&self) -> Result<Vec<StringConcat<'a>>, ResolveError> {
+        let bootstrap_methods = self.bootstrap_methods();
+
+        let mut sites = Vec::new();
+        for (method_index, offset, index) in self.invokedynamic_sites()? {
+            if let Some(site) =
+                self.string_concat_site(bootstrap_methods, method_index, offset, index)?
+            {
+                sites.push(site);
+            }
+        }
+
+        Ok(sites)
+    }
+
+    // Finds the `switch` statements on strings that javac compiled into a lookupswitch on the hash code of the
+    // string, each of whose targets compares the string against the constants with that hash code. This is a
+    // best-effort match on the shape of the code; a method without code, or an index past the last method,
+    // has none.
+    pub fn string_switches(
+        &self,
+        method_index: usize,
+    ) -> Result<Vec<StringSwitch<'a>>, ResolveError> {
+        let Some(AttributeInfo::Code { code, .. }) = self
+            .classfile
+            .methods
+            .get(method_index)
+            .and_then(Method::code)
+            .map(|code| &code.info)
+        else {
+            return Ok(Vec::new());
+        };
+        let Ok((_, instructions)) = instructions_from_bytes(code) else {
+            return Err(ResolveError::MalformedCode { method_index });
+        };
+
+        // branch targets are offsets, so instructions are also looked up by their offset
+        let positions = instructions
+            .iter()
+            .enumerate()
+            .map(|(position, &(offset, _))| (offset, position))
+            .collect::<HashMap<_, _>>();
+
+        let mut switches = Vec::new();
+        for (position, (offset, instruction)) in instructions.iter().enumerate() {
+            let Instruction::LookupSwitch { pairs, .. } = instruction else {
+                continue;
+            };
+
+            let Some((_, Instruction::InvokeVirtual(index))) = position
+                .checked_sub(1)
+                .map(|previous| &instructions[previous])
+            else {
+                continue;
+            };
+            if !self.is_string_method(*index, "hashCode", "()I")? {
+                continue;
+            }
+            let local = position
+                .checked_sub(2)
+                .and_then(|previous| reference_load_index(&instructions[previous].1));
+
+            let mut cases = Vec::new();
+            let mut confident = local.is_some();
+            for &(key, delta) in pairs {
+                let mut next = branch_target(*offset, delta.into());
+
+                // strings with the same hash code are compared one after the other, each comparison
+                // branching to the next when the string does not match
+                while let Some(start) = next.and_then(|target| positions.get(&target)) {
+                    let Some(comparison) =
+                        self.string_comparison(&instructions, &positions, *start)?
+                    else {
+                        break;
+                    };
+
+                    let case_index = instructions
+                        .get(comparison.matched..comparison.matched + 2)
+                        .and_then(|store| match store {
+                            [(_, constant), (_, store)] if is_int_store(store) => {
+                                int_constant(constant)
+                            }
+                            _ => None,
+                        });
+                    confident &= Some(comparison.local) == local
+                        && java_hash_code(&comparison.value) == key
+                        && case_index.is_some();
+
+                    cases.push(StringSwitchCase {
+                        value: comparison.value,
+                        target: instructions[comparison.matched].0,
+                        case_index,
+                    });
+                    next = Some(comparison.mismatched);
+                }
+            }
+
+            if cases.is_empty() {
+                continue;
+            }
+            switches.push(StringSwitch {
+                offset: *offset,
+                cases,
+                confidence: if confident {
+                    SwitchConfidence::High
+                } else {
+                    SwitchConfidence::Low
+                },
+            });
+        }
+
+        Ok(switches)
+    }
+
+    // This is a heuristic: markers the Kotlin, Scala and Groovy compilers leave behind are looked for first,
+    // then the extension of the source file name; javac leaves no marker of its own. Anything that cannot be
+    // resolved is treated as absent.
+    pub fn likely_toolchain(&self) -> Toolchain {
+        let classfile = self.classfile;
+
+        let annotation_types = classfile
+            .attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.info {
+                AttributeInfo::RuntimeInvisibleAnnotations { annotations }
+                | AttributeInfo::RuntimeVisibleAnnotations { annotations } => Some(annotations),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|annotation| self.utf8(annotation.type_index).ok())
+            .collect::<Vec<_>>();
+        let unknown_attribute_names = classfile
+            .attributes
+            .iter()
+            .filter_map(|attribute| match attribute.info {
+                AttributeInfo::Unknown { name_index, .. } => self.utf8(name_index).ok(),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if annotation_types
+            .iter()
+            .any(|name| name == "Lkotlin/Metadata;")
+        {
+            return Toolchain::Kotlin;
+        }
+
+        if unknown_attribute_names
+            .iter()
+            .any(|name| matches!(name.as_ref(), "Scala" | "ScalaSig" | "TASTY"))
+            || annotation_types.iter().any(|name| {
+                matches!(
+                    name.as_ref(),
+                    "Lscala/reflect/ScalaSignature;" | "Lscala/reflect/ScalaLongSignature;"
+                )
+            })
+        {
+            return Toolchain::Scala;
+        }
+
+        let implements_groovy_object = classfile.interfaces.iter().any(|&interface| {
+            self.class_name(interface)
+                .is_ok_and(|name| name == "groovy/lang/GroovyObject")
+        });
+        let has_static_meta_class = classfile.methods.iter().any(|method| {
+            self.utf8(method.name_index)
+                .is_ok_and(|name| name == "$getStaticMetaClass")
+        });
+        if implements_groovy_object || has_static_meta_class {
+            return Toolchain::Groovy;
+        }
+
+        let source_file = classfile
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute.info {
+                AttributeInfo::SourceFile { sourcefile_index } => self.utf8(sourcefile_index).ok(),
+                _ => None,
+            });
+        match source_file
+            .as_deref()
+            .and_then(|name| name.rsplit_once('.'))
+        {
+            Some((_, "groovy")) => Toolchain::Groovy,
+            Some((_, "java")) => Toolchain::Javac,
+            Some((_, "kt")) => Toolchain::Kotlin,
+            Some((_, "scala")) => Toolchain::Scala,
+            _ => Toolchain::Unknown,
+        }
+    }
+
+    // Every annotation of the given type on the class, its fields and its methods, in that order, whether
+    // visible at runtime or not. The type is given by its binary name, such as `javax.inject.Inject`.
+    pub fn members_annotated_with(
+        &self,
+        annotation_binary_name: &str,
+    ) -> Result<Vec<AnnotatedMember<'a>>, ResolveError> {
+        let classfile = self.classfile;
+        let type_name = format!("L{};", annotation_binary_name.replace('.', "/"));
+
+        let mut found = Vec::new();
+        let mut members = vec![(None, classfile.this_class, 0, &classfile.attributes)];
+        members.extend(classfile.fields.iter().map(|field| {
+            (
+                Some(MemberKind::Field),
+                field.name_index,
+                field.descriptor_index,
+                &field.attributes,
+            )
+        }));
+        members.extend(classfile.methods.iter().map(|method| {
+            (
+                Some(MemberKind::Method),
+                method.name_index,
+                method.descriptor_index,
+                &method.attributes,
+            )
+        }));
+
+        for (kind, name_index, descriptor_index, attributes) in members {
+            let annotations = attributes
+                .iter()
+                .filter_map(|attribute| match &attribute.info {
+                    AttributeInfo::RuntimeInvisibleAnnotations { annotations }
+                    | AttributeInfo::RuntimeVisibleAnnotations { annotations } => Some(annotations),
+                    _ => None,
+                })
+                .flatten();
+            for annotation in annotations {
+                if self.utf8(annotation.type_index)? != type_name {
+                    continue;
+                }
+
+                let (kind, name, descriptor) = match kind {
+                    None => (None, self.class_name(name_index)?, None),
+                    Some(kind) => {
+                        let name = self.utf8(name_index)?;
+                        let kind = match kind {
+                            MemberKind::Field => MemberKind::Field,
+                            _ => method_kind(&name),
+                        };
+
+                        (Some(kind), name, Some(self.utf8(descriptor_index)?))
+                    }
+                };
+                found.push(AnnotatedMember {
+                    kind,
+                    name,
+                    descriptor,
+                    annotation: resolved_annotation(annotation, &classfile.constant_pool)?,
+                });
+            }
+        }
+
+        Ok(found)
+    }
+
+    // index 0 is used where a class reference is optional, such as the superclass of `java/lang/Object`
+    pub fn optional_class_name(&self, index: u16) -> Result<Option<Cow<'a, str>>, ResolveError> {
+        if index == 0 {
+            return Ok(None);
+        }
+
+        self.class_name(index).map(Some)
+    }
+
+    // `api` without the compiler-generated members the filter hides, leaving those written in the source
+    pub fn public_api(
+        &self,
+        filter: ApiFilter,
+    ) -> impl Iterator<Item = Result<MemberApi<'a>, ResolveError>> + 'c {
+        let classfile = self.classfile;
+        let attributes = classfile
+            .fields
+            .iter()
+            .map(|field| &field.attributes)
+            .chain(classfile.methods.iter().map(|method| &method.attributes));
+
+        self.api()
+            .zip(attributes)
+            .filter(move |(member, attributes)| match member {
+                Ok(member) => !filter.hides(member, attributes),
+                Err(_) => true,
+            })
+            .map(|(member, _)| member)
+    }
+
+    // Every class the class file refers to, itself included: those of the Class entries of the constant pool,
+    // those in the descriptors of its members and of the members and method types it uses, and those in the
+    // type arguments and bounds of the generic signatures of the class and its members, which descriptors
+    // erase. Array types count as their element class, and type variables and primitive types are left out.
+    pub fn referenced_classes_deep(&self) -> Result<BTreeSet<String>, ResolveError> {
+        let classfile = self.classfile;
+        let mut classes = BTreeSet::new();
+
+        let mut descriptors = Vec::new();
+        for (index, entry) in (1..).zip(classfile.constant_pool.entries()) {
+            match *entry {
+                ConstantPoolEntry::Class { .. } => {
+                    field_type_classes(&self.class_type(index)?, &mut classes)
+                }
+                ConstantPoolEntry::NameAndType {
+                    descriptor_index, ..
+                }
+                | ConstantPoolEntry::MethodType {
+                    reference_index: descriptor_index,
+                } => descriptors.push(descriptor_index),
+                _ => {}
+            }
+        }
+        let members = classfile
+            .fields
+            .iter()
+            .map(|field| (field.descriptor_index, &field.attributes, false))
+            .chain(
+                classfile
+                    .methods
+                    .iter()
+                    .map(|method| (method.descriptor_index, &method.attributes, true)),
+            );
+        for (descriptor_index, attributes, is_method) in members {
+            descriptors.push(descriptor_index);
+
+            let Some(signature_index) = signature_index(attributes) else {
+                continue;
+            };
+            let signature = self.utf8(signature_index)?;
+            let malformed = || ResolveError::MalformedSignature {
+                signature: signature.to_string(),
+            };
+            if is_method {
+                let signature = method_signature_from_str(&signature).ok_or_else(malformed)?;
+                type_parameter_classes(&signature.type_parameters, &mut classes);
+                for signature in signature
+                    .parameters
+                    .iter()
+                    .chain(&signature.return_type)
+                    .chain(&signature.throws)
+                {
+                    type_signature_classes(signature, &mut classes);
+                }
+            } else {
+                let signature = field_signature_from_str(&signature).ok_or_else(malformed)?;
+                type_signature_classes(&signature, &mut classes);
+            }
+        }
+
+        // a NameAndType entry may describe a field or a method
+        for descriptor_index in descriptors {
+            let descriptor = self.utf8(descriptor_index)?;
+            if let Some(method_descriptor) = method_descriptor_from_str(&descriptor) {
+                for field_type in method_descriptor
+                    .parameters
+                    .iter()
+                    .chain(&method_descriptor.return_type)
+                {
+                    field_type_classes(field_type, &mut classes);
+                }
+            } else if let Some(field_type) = field_descriptor_from_str(&descriptor) {
+                field_type_classes(&field_type, &mut classes);
+            } else {
+                return Err(ResolveError::MalformedDescriptor {
+                    descriptor: descriptor.into_owned(),
+                });
+            }
+        }
+
+        if let Some(signature) = self.class_signature()? {
+            type_parameter_classes(&signature.type_parameters, &mut classes);
+            for class_type in std::iter::once(&signature.superclass).chain(&signature.interfaces) {
+                class_type_signature_classes(class_type, &mut classes);
+            }
+        }
+
+        Ok(classes)
+    }
+
+    // the CONSTANT_Dynamic entry at `index` with its bootstrap method from the BootstrapMethods attribute
+    pub fn resolve_dynamic(&self, index: u16) -> Result<ResolvedDynamic<'a>, ResolveError> {
+        let ConstantPoolEntry::Dynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } = *self.classfile.constant_pool.get(index)?
+        else {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index,
+                expected: "Dynamic",
+            });
+        };
+
+        let Some(bootstrap_method) = self
+            .bootstrap_methods()
+            .get(bootstrap_method_attr_index as usize)
+        else {
+            return Err(ResolveError::MissingBootstrapMethod {
+                index: bootstrap_method_attr_index,
+            });
+        };
+        let (name, descriptor) = self.name_and_type(name_and_type_index)?;
+        let Some(constant_type) = field_descriptor_from_str(&descriptor) else {
+            return Err(ResolveError::MalformedDescriptor {
+                descriptor: descriptor.into_owned(),
+            });
+        };
+
+        Ok(ResolvedDynamic {
+            name,
+            constant_type,
+            bootstrap: self.method_handle(bootstrap_method.bootstrap_method_ref)?,
+            bootstrap_arguments: bootstrap_method.bootstrap_arguments.clone(),
+        })
+    }
+
+    // The synthetic methods named `access$...` whose code only loads the parameters, gets or puts one field or
+    // calls one method, and returns, in method order. Accessors that do more, such as those javac generates
+    // for `++` on a private field, are not recognized.
+    pub fn synthetic_accessors(&self) -> Result<Vec<SyntheticAccessor<'a>>, ResolveError> {
+        let mut accessors = Vec::new();
+        for (method_index, method) in self.classfile.methods.iter().enumerate() {
+            if method.access_flags & MethodAccessFlags::SYNTHETIC == 0 {
+                continue;
+            }
+            let name = self.utf8(method.name_index)?;
+            if !name.starts_with("access$") {
+                continue;
+            }
+            let Some(AttributeInfo::Code { code, .. }) = method.code().map(|code| &code.info)
+            else {
+                continue;
+            };
+            let Ok((_, instructions)) = instructions_from_bytes(code) else {
+                return Err(ResolveError::MalformedCode { method_index });
+            };
+
+            if let Some(target) = self.accessor_target(&instructions)? {
+                accessors.push(SyntheticAccessor {
+                    method_index,
+                    name,
+                    target,
+                });
+            }
+        }
+
+        Ok(accessors)
+    }
+
+    // Java source for an interface declaring the public methods of the class without their bodies, for mocks
+    // and stubs. Methods keep their generic signatures, and their parameters are named `arg0`, `arg1` and so
+    // on. Static methods, which an interface could only declare with a body, are marked `/* static */`.
+    // Constructors, static initializers and the members the default ApiFilter hides are left out.
+    pub fn to_interface_stub(&self) -> Result<String, ResolveError> {
+        let classfile = self.classfile;
+        let filter = ApiFilter::default();
+        let class_name = self.class_name(classfile.this_class)?;
+        let (package, simple_name) = class_name.rsplit_once('/').unwrap_or(("", &class_name));
+
+        let mut stub = String::new();
+        if !package.is_empty() {
+            stub.push_str(&format!("package {};\n\n", package.replace('/', ".")));
+        }
+        stub.push_str(&format!(
+            "public interface {simple_name}{} {{\n",
+            type_parameters_to_source(&self.type_parameters()?)
+        ));
+
+        for method in &classfile.methods {
+            let member = self.member_api(
+                MemberKind::Method,
+                method.access_flags,
+                method.name_index,
+                method.descriptor_index,
+            )?;
+            if member.kind != MemberKind::Method
+                || method.access_flags & MethodAccessFlags::PUBLIC == 0
+                || filter.hides(&member, &method.attributes)
+            {
+                continue;
+            }
+            let MemberDescriptor::Method(descriptor) = &member.descriptor else {
+                unreachable!();
+            };
+
+            // a Signature attribute only lists the thrown exceptions if one of them is a type variable
+            let mut throws = Vec::new();
+            let (type_parameters, return_type, mut parameters) =
+                match signature_index(&method.attributes) {
+                    Some(signature_index) => {
+                        let signature = self.utf8(signature_index)?;
+                        let Some(signature) = method_signature_from_str(&signature) else {
+                            return Err(ResolveError::MalformedSignature {
+                                signature: signature.into_owned(),
+                            });
+                        };
+
+                        throws.extend(signature.throws.iter().map(type_signature_to_source));
+                        (
+                            type_parameters_to_source(&signature.type_parameters),
+                            signature.return_type.as_ref().map(type_signature_to_source),
+                            signature
+                                .parameters
+                                .iter()
+                                .map(type_signature_to_source)
+                                .collect::<Vec<_>>(),
+                        )
+                    }
+                    None => (
+                        String::new(),
+                        descriptor.return_type.as_ref().map(field_type_to_source),
+                        descriptor
+                            .parameters
+                            .iter()
+                            .map(field_type_to_source)
+                            .collect(),
+                    ),
+                };
+            if throws.is_empty() {
+                for exception in method.throws(&classfile.constant_pool)? {
+                    throws.push(exception.replace('/', "."));
+                }
+            }
+
+            let mut line = String::from("    ");
+            if method.access_flags & MethodAccessFlags::STATIC != 0 {
+                line.push_str("/* static */ ");
+            }
+            if !type_parameters.is_empty() {
+                line.push_str(&type_parameters);
+                line.push(' ');
+            }
+            varargs_parameter(&mut parameters, method.is_varargs());
+            let parameters = parameters
+                .iter()
+                .enumerate()
+                .map(|(i, parameter)| format!("{parameter} arg{i}"))
+                .collect::<Vec<_>>();
+            line.push_str(&format!(
+                "{} {}({})",
+                return_type.as_deref().unwrap_or("void"),
+                member.name,
+                parameters.join(", ")
+            ));
+            if !throws.is_empty() {
+                line.push_str(" throws ");
+                line.push_str(&throws.join(", "));
+            }
+            line.push_str(";\n");
+
+            stub.push_str(&line);
+        }
+        stub.push_str("}\n");
+
+        Ok(stub)
+    }
+
+    // The formal type parameters of a generic class, from the Signature attribute, as in
+    // `<T:Ljava/lang/Object;K::Ljava/lang/Comparable;>`; a parameter bounded by interfaces alone, like `K`,
+    // has no class bound. A class without a Signature attribute is not generic and has none.
+    pub fn type_parameters(&self) -> Result<Vec<TypeParameter>, ResolveError> {
+        Ok(self
+            .class_signature()?
+            .map(|signature| signature.type_parameters)
+            .unwrap_or_default())
+    }
+
+    pub fn utf8(&self, index: u16) -> Result<Cow<'a, str>, ResolveError> {
+        Ok(self.classfile.constant_pool.utf8(index)?)
+    }
+
+    pub fn utf8_or_raw(&self, index: u16) -> Result<Utf8Constant<'a>, ResolveError> {
+        Ok(self.classfile.constant_pool.utf8_or_raw(index)?)
+    }
+
+    // the member accessed by the one field or method instruction of `instructions`, if the others only load
+    // locals, duplicate stack values and return
+    fn accessor_target(
+        &self,
+        instructions: &[(usize, Instruction)],
+    ) -> Result<Option<MethodHandleTarget<'a>>, ResolveError> {
+        let mut access = None;
+        for (_, instruction) in instructions {
+            let (reference_kind, index) = match *instruction {
+                Instruction::GetField(index) => (1, index),
+                Instruction::GetStatic(index) => (2, index),
+                Instruction::PutField(index) => (3, index),
+                Instruction::PutStatic(index) => (4, index),
+                Instruction::InvokeVirtual(index) => (5, index),
+                Instruction::InvokeStatic(index) => (6, index),
+                Instruction::InvokeSpecial(index) => (7, index),
+                Instruction::InvokeInterface { index, .. } => (9, index),
+                Instruction::ILoad(_)
+                | Instruction::LLoad(_)
+                | Instruction::FLoad(_)
+                | Instruction::DLoad(_)
+                | Instruction::ALoad(_)
+                | Instruction::ILoad0
+                | Instruction::ILoad1
+                | Instruction::ILoad2
+                | Instruction::ILoad3
+                | Instruction::LLoad0
+                | Instruction::LLoad1
+                | Instruction::LLoad2
+                | Instruction::LLoad3
+                | Instruction::FLoad0
+                | Instruction::FLoad1
+                | Instruction::FLoad2
+                | Instruction::FLoad3
+                | Instruction::DLoad0
+                | Instruction::DLoad1
+                | Instruction::DLoad2
+                | Instruction::DLoad3
+                | Instruction::ALoad0
+                | Instruction::ALoad1
+                | Instruction::ALoad2
+                | Instruction::ALoad3
+                | Instruction::Dup
+                | Instruction::DupX1
+                | Instruction::DupX2
+                | Instruction::Dup2
+                | Instruction::Dup2X1
+                | Instruction::Dup2X2
+                | Instruction::IReturn
+                | Instruction::LReturn
+                | Instruction::FReturn
+                | Instruction::DReturn
+                | Instruction::AReturn
+                | Instruction::Return => continue,
+                _ => return Ok(None),
+            };
+            if access.replace((reference_kind, index)).is_some() {
+                return Ok(None);
+            }
+        }
+
+        access
+            .map(|(reference_kind, index)| self.member_reference(reference_kind, index))
+            .transpose()
+    }
+
+    // empty if the class has no BootstrapMethods attribute
+    fn bootstrap_methods(&self) -> &'c [BootstrapMethod] {
+        self.classfile
+            .attributes
+            .iter()
+            .find_map(|attribute| match &attribute.info {
+                AttributeInfo::BootstrapMethods { bootstrap_methods } => {
+                    Some(bootstrap_methods.as_slice())
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    fn class_signature(&self) -> Result<Option<ClassSignature>, ResolveError> {
+        let Some(signature_index) = signature_index(&self.classfile.attributes) else {
+            return Ok(None);
+        };
+        let signature = self.utf8(signature_index)?;
+
+        class_signature_from_str(&signature)
+            .map(Some)
+            .ok_or_else(|| ResolveError::MalformedSignature {
+                signature: signature.into_owned(),
+            })
+    }
+
+    // `None` if the element value is not a constant of the enum with the given type descriptor
+    fn enum_const_name(
+        &self,
+        value: &ElementValue,
+        enum_descriptor: &str,
+    ) -> Result<Option<Cow<'a, str>>, ResolveError> {
+        let ElementValue::EnumConst {
+            type_name_index,
+            const_name_index,
+        } = value
+        else {
+            return Ok(None);
+        };
+
+        if self.utf8(*type_name_index)? != enum_descriptor {
+            return Ok(None);
+        }
+
+        Ok(Some(self.utf8(*const_name_index)?))
+    }
+
+    // the method index, offset and constant pool index of every invokedynamic instruction, in method and code
+    // order
+    fn invokedynamic_sites(&self) -> Result<Vec<(usize, usize, u16)>, ResolveError> {
+        let mut sites = Vec::new();
+        for (method_index, method) in self.classfile.methods.iter().enumerate() {
+            let Some(AttributeInfo::Code { code, .. }) = method.code().map(|code| &code.info)
+            else {
+                continue;
+            };
+            let Ok((_, instructions)) = instructions_from_bytes(code) else {
+                return Err(ResolveError::MalformedCode { method_index });
+            };
+
+            sites.extend(
+                instructions
+                    .into_iter()
+                    .filter_map(|(offset, instruction)| match instruction {
+                        Instruction::InvokeDynamic(index) => Some((method_index, offset, index)),
+                        _ => None,
+                    }),
+            );
+        }
+
+        Ok(sites)
+    }
+
+    fn is_lambda_implementation(&self, method: &Method) -> Result<bool, ResolveError> {
+        Ok(method.access_flags & MethodAccessFlags::SYNTHETIC != 0
+            && self.utf8(method.name_index)?.starts_with("lambda$"))
+    }
+
+    // whether the entry at `index` is a Methodref to the given method of java/lang/String
+    fn is_string_method(
+        &self,
+        index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<bool, ResolveError> {
+        let ConstantPoolEntry::MethodRef {
+            class_index,
+            name_and_type_index,
+        } = *self.classfile.constant_pool.get(index)?
+        else {
+            return Ok(false);
+        };
+
+        Ok(self.class_name(class_index)? == "java/lang/String"
+            && self.name_and_type(name_and_type_index)? == (name.into(), descriptor.into()))
+    }
+
+    fn lambda_site(
+        &self,
+        bootstrap_methods: &[BootstrapMethod],
+        method_index: usize,
+        offset: usize,
+        index: u16,
+    ) -> Result<Option<LambdaSite<'a>>, ResolveError> {
+        let ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } = *self.classfile.constant_pool.get(index)?
+        else {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index,
+                expected: "InvokeDynamic",
+            });
+        };
+
+        let Some(bootstrap_method) = bootstrap_methods.get(bootstrap_method_attr_index as usize)
+        else {
+            return Err(ResolveError::MissingBootstrapMethod {
+                index: bootstrap_method_attr_index,
+            });
+        };
+        let bootstrap = self.method_handle(bootstrap_method.bootstrap_method_ref)?;
+        if bootstrap.class != LAMBDA_METAFACTORY
+            || !matches!(bootstrap.name.as_ref(), "metafactory" | "altMetafactory")
+        {
+            return Ok(None);
+        }
+
+        // altMetafactory takes further arguments after the three both factories share; with fewer, the call
+        // site would fail to link
+        let [sam_method_type, implementation, instantiated_method_type, ..] =
+            bootstrap_method.bootstrap_arguments[..]
+        else {
+            return Ok(None);
+        };
+        let (interface_method_name, _) = self.name_and_type(name_and_type_index)?;
+
+        Ok(Some(LambdaSite {
+            method_index,
+            offset,
+            interface_method_name,
+            sam_method_type: self.method_type(sam_method_type)?,
+            implementation: self.method_handle(implementation)?,
+            instantiated_method_type: self.method_type(instantiated_method_type)?,
+        }))
+    }
+
+    fn member_api(
+        &self,
+        kind: MemberKind,
+        access_flags: u16,
+        name_index: u16,
+        descriptor_index: u16,
+    ) -> Result<MemberApi<'a>, ResolveError> {
+        let name = self.utf8(name_index)?;
+        let descriptor = self.utf8(descriptor_index)?;
+
+        let kind = match kind {
+            MemberKind::Field => MemberKind::Field,
+            _ => method_kind(&name),
+        };
+        let parsed = match kind {
+            MemberKind::Field => {
+                field_descriptor_from_str(&descriptor).map(MemberDescriptor::Field)
+            }
+            _ => method_descriptor_from_str(&descriptor).map(MemberDescriptor::Method),
+        };
+        let Some(descriptor) = parsed else {
+            return Err(ResolveError::MalformedDescriptor {
+                descriptor: descriptor.into_owned(),
+            });
+        };
+
+        Ok(MemberApi {
+            kind,
+            name,
+            descriptor,
+            access_flags,
+        })
+    }
+
+    // the field or method the Fieldref, Methodref or InterfaceMethodref entry at `index` refers to
+    fn member_reference(
+        &self,
+        reference_kind: u8,
+        index: u16,
+    ) -> Result<MethodHandleTarget<'a>, ResolveError> {
+        let (ConstantPoolEntry::FieldRef {
+            class_index,
+            name_and_type_index,
+        }
+        | ConstantPoolEntry::MethodRef {
+            class_index,
+            name_and_type_index,
+        }
+        | ConstantPoolEntry::InstanceMethodRef {
+            class_index,
+            name_and_type_index,
+        }) = *self.classfile.constant_pool.get(index)?
+        else {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index,
+                expected: "Fieldref, Methodref or InterfaceMethodref",
+            });
+        };
+        let (name, descriptor) = self.name_and_type(name_and_type_index)?;
+
+        Ok(MethodHandleTarget {
+            reference_kind,
+            class: self.class_name(class_index)?,
+            name,
+            descriptor,
+        })
+    }
+
+    fn method_handle(&self, index: u16) -> Result<MethodHandleTarget<'a>, ResolveError> {
+        let ConstantPoolEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        } = *self.classfile.constant_pool.get(index)?
+        else {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index,
+                expected: "MethodHandle",
+            });
+        };
+
+        self.member_reference(reference_kind, reference_index)
+    }
+
+    fn method_type(&self, index: u16) -> Result<MethodDescriptor, ResolveError> {
+        let ConstantPoolEntry::MethodType { reference_index } =
+            *self.classfile.constant_pool.get(index)?
+        else {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index,
+                expected: "MethodType",
+            });
+        };
+        let descriptor = self.utf8(reference_index)?;
+
+        method_descriptor_from_str(&descriptor).ok_or_else(|| ResolveError::MalformedDescriptor {
+            descriptor: descriptor.into_owned(),
+        })
+    }
+
+    fn module_header(&self) -> Result<String, ResolveError> {
+        let classfile = self.classfile;
+        let module = classfile
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute.info {
+                AttributeInfo::Module {
+                    module_name_index,
+                    module_flags,
+                    ..
+                } => Some((module_name_index, module_flags)),
+                _ => None,
+            });
+        let Some((module_name_index, module_flags)) = module else {
+            return Ok(format!("module {}", self.class_name(classfile.this_class)?));
+        };
+
+        let ConstantPoolEntry::Module { name_index } =
+            *classfile.constant_pool.get(module_name_index)?
+        else {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index: module_name_index,
+                expected: "Module",
+            });
+        };
+        let name = self.utf8(name_index)?;
+
+        Ok(match module_flags & ModuleFlags::OPEN {
+            0 => format!("module {name}"),
+            _ => format!("open module {name}"),
+        })
+    }
+
+    fn name_and_type(&self, index: u16) -> Result<(Cow<'a, str>, Cow<'a, str>), ResolveError> {
+        let ConstantPoolEntry::NameAndType {
+            name_index,
+            descriptor_index,
+        } = *self.classfile.constant_pool.get(index)?
+        else {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index,
+                expected: "NameAndType",
+            });
+        };
+
+        Ok((self.utf8(name_index)?, self.utf8(descriptor_index)?))
+    }
+
+    // each component as its type, generic if the component has a signature, followed by its name
+    fn record_components_to_source(&self) -> Result<Vec<String>, ResolveError> {
+        let components = self
+            .classfile
+            .attributes
+            .iter()
+            .find_map(|attribute| match &attribute.info {
+                AttributeInfo::Record { components } => Some(components),
+                _ => None,
+            })
+            .map_or(&[][..], Vec::as_slice);
+
+        components
+            .iter()
+            .map(|component| {
+                let name = self.utf8(component.name_index)?;
+                let component_type = match signature_index(&component.attributes) {
+                    Some(signature_index) => {
+                        let signature = self.utf8(signature_index)?;
+                        let Some(signature) = field_signature_from_str(&signature) else {
+                            return Err(ResolveError::MalformedSignature {
+                                signature: signature.into_owned(),
+                            });
+                        };
+
+                        type_signature_to_source(&signature)
+                    }
+                    None => {
+                        let descriptor = self.utf8(component.descriptor_index)?;
+                        let Some(field_type) = field_descriptor_from_str(&descriptor) else {
+                            return Err(ResolveError::MalformedDescriptor {
+                                descriptor: descriptor.into_owned(),
+                            });
+                        };
+
+                        field_type_to_source(&field_type)
+                    }
+                };
+
+                Ok(format!("{component_type} {name}"))
+            })
+            .collect()
+    }
+
+    fn string_comparison(
+        &self,
+        instructions: &[(usize, Instruction)],
+        positions: &HashMap<usize, usize>,
+        start: usize,
+    ) -> Result<Option<StringComparison<'a>>, ResolveError> {
+        let Some([(_, load), (_, constant), (_, invoke), (offset, branch)]) =
+            instructions.get(start..start + 4)
+        else {
+            return Ok(None);
+        };
+        let (Instruction::InvokeVirtual(equals), Instruction::IfEq(delta)) = (invoke, branch)
+        else {
+            return Ok(None);
+        };
+
+        let Some(local) = reference_load_index(load) else {
+            return Ok(None);
+        };
+        let index = match *constant {
+            Instruction::Ldc(index) => index.into(),
+            Instruction::LdcW(index) => index,
+            _ => return Ok(None),
+        };
+        let ConstantPoolEntry::String { string_index } =
+            *self.classfile.constant_pool.get(index)?
+        else {
+            return Ok(None);
+        };
+        if !self.is_string_method(*equals, "equals", "(Ljava/lang/Object;)Z")? {
+            return Ok(None);
+        }
+        let Some(mismatched) =
+            branch_target(*offset, (*delta).into()).filter(|target| positions.contains_key(target))
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(StringComparison {
+            local,
+            value: self.utf8(string_index)?,
+            matched: start + 4,
+            mismatched,
+        }))
+    }
+
+    fn string_concat_site(
+        &self,
+        bootstrap_methods: &[BootstrapMethod],
+        method_index: usize,
+        offset: usize,
+        index: u16,
+    ) -> Result<Option<StringConcat<'a>>, ResolveError> {
+        let ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } = *self.classfile.constant_pool.get(index)?
+        else {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index,
+                expected: "InvokeDynamic",
+            });
+        };
+
+        let Some(bootstrap_method) = bootstrap_methods.get(bootstrap_method_attr_index as usize)
+        else {
+            return Err(ResolveError::MissingBootstrapMethod {
+                index: bootstrap_method_attr_index,
+            });
+        };
+        let bootstrap = self.method_handle(bootstrap_method.bootstrap_method_ref)?;
+        if bootstrap.class != STRING_CONCAT_FACTORY {
+            return Ok(None);
+        }
+
+        let (_, descriptor) = self.name_and_type(name_and_type_index)?;
+        let Some(descriptor) = method_descriptor_from_str(&descriptor) else {
+            return Err(ResolveError::MalformedDescriptor {
+                descriptor: descriptor.into_owned(),
+            });
+        };
+        let arguments = &bootstrap_method.bootstrap_arguments;
+        let (recipe, constants) = match (bootstrap.name.as_ref(), arguments.split_first()) {
+            ("makeConcat", _) => (
+                Cow::Owned("\u{1}".repeat(descriptor.parameters.len())),
+                &[][..],
+            ),
+            ("makeConcatWithConstants", Some((&recipe, constants))) => {
+                let ConstantPoolEntry::String { string_index } =
+                    *self.classfile.constant_pool.get(recipe)?
+                else {
+                    return Err(ResolveError::UnexpectedConstantPoolEntry {
+                        index: recipe,
+                        expected: "String",
+                    });
+                };
+
+                (self.utf8(string_index)?, constants)
+            }
+            _ => return Ok(None),
+        };
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let (mut next_argument, mut next_constant) = (0, 0);
+        for character in recipe.chars() {
+            let part = match character {
+                '\u{1}' => {
+                    next_argument += 1;
+                    StringConcatPart::Argument(next_argument - 1)
+                }
+                '\u{2}' => {
+                    let Some(&constant) = constants.get(next_constant) else {
+                        return Ok(None);
+                    };
+                    next_constant += 1;
+                    StringConcatPart::Constant(constant)
+                }
+                _ => {
+                    literal.push(character);
+                    continue;
+                }
+            };
+
+            if !literal.is_empty() {
+                parts.push(StringConcatPart::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(part);
+        }
+        if !literal.is_empty() {
+            parts.push(StringConcatPart::Literal(literal));
+        }
+        if next_argument != descriptor.parameters.len() || next_constant != constants.len() {
+            return Ok(None);
+        }
+
+        Ok(Some(StringConcat {
+            method_index,
+            offset,
+            recipe,
+            constants: constants.to_vec(),
+            parts,
+        }))
+    }
+}
+
+impl<'a> Attribute<'a> {
+    // the name the attribute was read with, or for one assembled by hand the name its kind is stored under
+    pub fn name(&self, constant_pool: &ConstantPool<'a>) -> Result<Cow<'a, str>, ResolveError> {
+        if self.name_index == 0 {
+            return self.info.name(constant_pool);
+        }
+
+        Ok(constant_pool.utf8(self.name_index)?)
+    }
+}
+
+impl<'a> AttributeInfo<'a> {
+    // The exception table entries whose [start_pc, end_pc) range covers `pc`, as their catch type and
+    // handler_pc, in table order, which is the order the JVM tries them in. An entry catching any exception
+    // (a `finally` block) has the catch type "any". Only a Code attribute has handlers.
+    pub fn active_handlers(
+        &self,
+        pc: u16,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<Vec<(Cow<'a, str>, u16)>, ResolveError> {
+        let Self::Code {
+            exception_table, ..
+        } = self
+        else {
+            return Ok(Vec::new());
+        };
+
+        exception_table
+            .iter()
+            .filter(|entry| (entry.start_pc..entry.end_pc).contains(&pc))
+            .map(|entry| {
+                let catch_type = match entry.catch_type {
+                    0 => Cow::Borrowed("any"),
+                    index => constant_pool.class_name(index)?,
+                };
+
+                Ok((catch_type, entry.handler_pc))
+            })
+            .collect()
+    }
+
+    // `None` unless this is an AnnotationDefault attribute
+    pub fn annotation_default_resolved(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<Option<ResolvedElementValue<'a>>, ResolveError> {
+        let Self::AnnotationDefault { default_value } = self else {
+            return Ok(None);
+        };
+
+        resolved_element_value(default_value, constant_pool).map(Some)
+    }
+
+    // the name the attribute is stored under, which for an unknown attribute is looked up in the constant pool
+    pub fn name(&self, constant_pool: &ConstantPool<'a>) -> Result<Cow<'a, str>, ResolveError> {
+        match (self, self.standard_name()) {
+            (Self::Unknown { name_index, .. }, _) => Ok(constant_pool.utf8(*name_index)?),
+            (_, Some(name)) => Ok(Cow::Borrowed(name)),
+            (_, None) => unreachable!("every attribute but an unknown one has a standard name"),
+        }
+    }
+
+    // the name the JVMS gives the attribute, or `None` for an unknown attribute
+    pub fn standard_name(&self) -> Option<&'static str> {
+        let name = match self {
+            Self::AnnotationDefault { .. } => "AnnotationDefault",
+            Self::BootstrapMethods { .. } => "BootstrapMethods",
+            Self::Code { .. } => "Code",
+            Self::ConstantValue { .. } => "ConstantValue",
+            Self::Deprecated => "Deprecated",
+            Self::EnclosingMethod { .. } => "EnclosingMethod",
+            Self::Exceptions { .. } => "Exceptions",
+            Self::InnerClasses { .. } => "InnerClasses",
+            Self::LineNumberTable { .. } => "LineNumberTable",
+            Self::LocalVariableTable { .. } => "LocalVariableTable",
+            Self::LocalVariableTypeTable { .. } => "LocalVariableTypeTable",
+            Self::MethodParameters { .. } => "MethodParameters",
+            Self::Module { .. } => "Module",
+            Self::ModuleMainClass { .. } => "ModuleMainClass",
+            Self::ModulePackages { .. } => "ModulePackages",
+            Self::NestHost { .. } => "NestHost",
+            Self::NestMembers { .. } => "NestMembers",
+            Self::PermittedSubclasses { .. } => "PermittedSubclasses",
+            Self::Record { .. } => "Record",
+            Self::RuntimeInvisibleAnnotations { .. } => "RuntimeInvisibleAnnotations",
+            Self::RuntimeInvisibleParameterAnnotations { .. } => {
+                "RuntimeInvisibleParameterAnnotations"
+            }
+            Self::RuntimeInvisibleTypeAnnotations { .. } => "RuntimeInvisibleTypeAnnotations",
+            Self::RuntimeVisibleAnnotations { .. } => "RuntimeVisibleAnnotations",
+            Self::RuntimeVisibleParameterAnnotations { .. } => "RuntimeVisibleParameterAnnotations",
+            Self::RuntimeVisibleTypeAnnotations { .. } => "RuntimeVisibleTypeAnnotations",
+            Self::Signature { .. } => "Signature",
+            Self::SourceDebugExtension { .. } => "SourceDebugExtension",
+            Self::SourceFile { .. } => "SourceFile",
+            Self::StackMapTable { .. } => "StackMapTable",
+            Self::Synthetic => "Synthetic",
+            Self::Unknown { .. } => return None,
+        };
+
+        Some(name)
+    }
+}
+
+impl<'a> Classfile<'a> {
+    // The class attributes stored under `name`, in classfile order. Attributes whose name does not resolve
+    // are skipped.
+    pub fn attributes_by_name<'s>(
+        &'s self,
+        name: &'s str,
+    ) -> impl Iterator<Item = &'s AttributeInfo<'a>> + 's {
+        self.attributes
+            .iter()
+            .filter(move |attribute| {
+                attribute
+                    .name(&self.constant_pool)
+                    .is_ok_and(|attribute_name| attribute_name == name)
+            })
+            .map(|attribute| &attribute.info)
+    }
+
+    // A pool read from a class file cannot have too many entries, but a Long or Double entry in its last
+    // index would spill over into an index the count cannot represent; so can a pool assembled by hand.
+    pub fn check_pool_limit(&self) -> Result<(), ResolveError> {
+        let slots = self
+            .constant_pool
+            .entries()
+            .iter()
+            .map(|entry| match entry {
+                ConstantPoolEntry::Long { .. } | ConstantPoolEntry::Double { .. } => 2,
+                ConstantPoolEntry::Unusable => 0,
+                _ => 1,
+            })
+            .sum();
+
+        if slots > MAX_CONSTANT_POOL_SLOTS {
+            return Err(ResolveError::PoolLimitExceeded { slots });
+        }
+
+        Ok(())
+    }
+
+    // `None` if the class has no NestHost attribute, in which case it is the host of its own nest
+    pub fn nest_host(&self) -> Result<Option<Cow<'a, str>>, ResolveError> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute.info {
+                AttributeInfo::NestHost { host_class_index } => Some(host_class_index),
+                _ => None,
+            })
+            .map(|index| Ok(self.constant_pool.class_name(index)?))
+            .transpose()
+    }
+
+    pub fn nest_members(&self) -> Result<Vec<Cow<'a, str>>, ResolveError> {
+        self.attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.info {
+                AttributeInfo::NestMembers { classes } => Some(classes),
+                _ => None,
+            })
+            .flatten()
+            .map(|&index| Ok(self.constant_pool.class_name(index)?))
+            .collect()
+    }
+
+    // A class counts as module-info if it is flagged as a module or has a Module attribute, and as
+    // package-info if its name is `package-info` in whatever package; a name that does not resolve is neither.
+    pub fn special_kind(&self) -> Option<SpecialClass> {
+        let is_module = self.access_flags & AccessFlags::MODULE != 0
+            || self
+                .attributes
+                .iter()
+                .any(|attribute| matches!(attribute.info, AttributeInfo::Module { .. }));
+        if is_module {
+            return Some(SpecialClass::ModuleInfo);
+        }
+
+        let name = self.constant_pool.class_name(self.this_class).ok()?;
+        let simple_name = name.rsplit('/').next().unwrap_or(&name);
+
+        (simple_name == "package-info").then_some(SpecialClass::PackageInfo)
+    }
+
+    // `None` for the classes without a superclass, which are `java/lang/Object` and module-info classes
+    pub fn super_class_name(&self) -> Result<Option<Cow<'a, str>>, ResolveError> {
+        ResolvedClass::new(self).optional_class_name(self.super_class)
+    }
+}
+
+impl<'a> Method<'a> {
+    // A hash of what the callers and overriders of the method depend on: its name, descriptor, access flags,
+    // thrown exceptions and generic signature, all by content rather than by constant pool index. The code
+    // is left out, as are the flags that only affect how the body runs, so that a change to the body alone
+    // keeps the hash. The order of the thrown exceptions does not matter either.
+    pub fn abi_hash(&self, constant_pool: &ConstantPool<'a>) -> Result<u64, ResolveError> {
+        let body_flags =
+            MethodAccessFlags::SYNCHRONIZED | MethodAccessFlags::NATIVE | MethodAccessFlags::STRICT;
+        let signature = match signature_index(&self.attributes) {
+            Some(signature_index) => constant_pool.utf8(signature_index)?,
+            None => Cow::Borrowed(""),
+        };
+        let mut throws = self.throws(constant_pool)?;
+        throws.sort();
+
+        let mut hash = FNV_OFFSET_BASIS;
+        hash = fnv1a(hash, constant_pool.utf8(self.name_index)?.as_bytes());
+        hash = fnv1a(hash, constant_pool.utf8(self.descriptor_index)?.as_bytes());
+        hash = fnv1a(hash, &(self.access_flags & !body_flags).to_be_bytes());
+        hash = fnv1a(hash, signature.as_bytes());
+        for exception in &throws {
+            hash = fnv1a(hash, exception.as_bytes());
+        }
+
+        Ok(hash)
+    }
+
+    // the method's Code attribute, which abstract and native methods legally have none of, decoded on the
+    // first call if parsing with lazy_code left it undecoded
+    pub fn code(&self) -> Option<&Attribute<'a>> {
+        self.attributes
+            .iter()
+            .find(|attribute| matches!(attribute.info, AttributeInfo::Code { .. }))
+            .or_else(|| self.lazy_code.get())
+    }
+
+    pub fn deprecation(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<Option<Deprecation<'a>>, ResolveError> {
+        deprecation(&self.attributes, constant_pool)
+    }
+
+    // `classfile` is the class declaring the method, whose interface flag tells default methods apart
+    pub fn effective_kind(&self, classfile: &Classfile) -> MethodKind {
+        if self.is_abstract() {
+            MethodKind::Abstract
+        } else if self.access_flags & MethodAccessFlags::STATIC != 0 {
+            MethodKind::Static
+        } else if self.access_flags & MethodAccessFlags::PRIVATE != 0 {
+            MethodKind::Private
+        } else if classfile.access_flags & AccessFlags::INTERFACE != 0 {
+            MethodKind::Default
+        } else {
+            MethodKind::Concrete
+        }
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.access_flags & MethodAccessFlags::ABSTRACT != 0
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.access_flags & MethodAccessFlags::NATIVE != 0
+    }
+
+    pub fn is_varargs(&self) -> bool {
+        self.access_flags & MethodAccessFlags::VARARGS != 0
+    }
+
+    // The symbol the JVM looks up when linking the method as a native method, such as
+    // `Java_java_lang_Object_hashCode` for `hashCode` in `java/lang/Object`. This is the short form without the
+    // mangled argument signature that distinguishes overloads.
+    pub fn jni_mangled_name(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+        class_name: &str,
+    ) -> Result<String, ResolveError> {
+        let name = constant_pool.utf8(self.name_index)?;
+
+        let mut symbol = "Java_".to_string();
+        jni_mangle(class_name, &mut symbol);
+        symbol.push('_');
+        jni_mangle(&name, &mut symbol);
+
+        Ok(symbol)
+    }
+
+    // JNI takes method signatures in the same form as the JVM descriptors
+    pub fn jni_signature(&self, constant_pool: &ConstantPool<'a>) -> Result<String, ResolveError> {
+        Ok(constant_pool.utf8(self.descriptor_index)?.into_owned())
+    }
+
+    // The local variables of the LocalVariableTable attributes of the method's Code attribute, in table
+    // order. A method compiled without debug information has none.
+    pub fn local_variable_names(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<Vec<ResolvedLocalVariable<'a>>, ResolveError> {
+        let Some(AttributeInfo::Code { attributes, .. }) = self.code().map(|code| &code.info)
+        else {
+            return Ok(Vec::new());
+        };
+
+        attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.info {
+                AttributeInfo::LocalVariableTable {
+                    local_variable_table,
+                } => Some(local_variable_table),
+                _ => None,
+            })
+            .flatten()
+            .map(|variable| {
+                let descriptor = constant_pool.utf8(variable.descriptor_index)?;
+                let Some(field_type) = field_descriptor_from_str(&descriptor) else {
+                    return Err(ResolveError::MalformedDescriptor {
+                        descriptor: descriptor.into_owned(),
+                    });
+                };
+
+                Ok(ResolvedLocalVariable {
+                    index: variable.index,
+                    name: constant_pool.utf8(variable.name_index)?,
+                    field_type,
+                    start_pc: variable.start_pc,
+                    length: variable.length,
+                })
+            })
+            .collect()
+    }
+
+    // the internal names of the classes listed in the method's Exceptions attribute, in attribute order
+    pub fn throws(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<Vec<Cow<'a, str>>, ResolveError> {
+        self.attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.info {
+                AttributeInfo::Exceptions {
+                    exception_index_table,
+                } => Some(exception_index_table),
+                _ => None,
+            })
+            .flatten()
+            .map(|&index| Ok(constant_pool.class_name(index)?))
+            .collect()
+    }
+
+    // The return type, name, parameter types and throws clause as they would be written in Java source, such
+    // as `void write(byte[], int, int) throws java.io.IOException`, with the last parameter of a varargs method
+    // written as in `String format(java.lang.String, java.lang.Object...)`. Modifiers are not included.
+    pub fn to_source_signature(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<String, ResolveError> {
+        let name = constant_pool.utf8(self.name_index)?;
+        let descriptor = constant_pool.utf8(self.descriptor_index)?;
+        let Some(descriptor) = method_descriptor_from_str(&descriptor) else {
+            return Err(ResolveError::MalformedDescriptor {
+                descriptor: descriptor.into_owned(),
+            });
+        };
+
+        let return_type = descriptor
+            .return_type
+            .as_ref()
+            .map_or_else(|| "void".to_string(), field_type_to_source);
+        let mut parameters = descriptor
+            .parameters
+            .iter()
+            .map(field_type_to_source)
+            .collect::<Vec<_>>();
+        varargs_parameter(&mut parameters, self.is_varargs());
+        let mut signature = format!("{return_type} {name}({})", parameters.join(", "));
+
+        let throws = self.throws(constant_pool)?;
+        if !throws.is_empty() {
+            let throws = throws
+                .iter()
+                .map(|name| name.replace('/', "."))
+                .collect::<Vec<_>>();
+            signature.push_str(" throws ");
+            signature.push_str(&throws.join(", "));
+        }
+
+        Ok(signature)
+    }
+}
+
+impl VerificationTypeInfo {
+    pub fn resolved<'a>(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<ResolvedVerificationType<'a>, ResolveError> {
+        Ok(match *self {
+            Self::DoubleVariable => ResolvedVerificationType::Double,
+            Self::FloatVariable => ResolvedVerificationType::Float,
+            Self::IntegerVariable => ResolvedVerificationType::Integer,
+            Self::LongVariable => ResolvedVerificationType::Long,
+            Self::NullVariable => ResolvedVerificationType::Null,
+            Self::ObjectVariable(index) => {
+                ResolvedVerificationType::Object(constant_pool.class_name(index)?)
+            }
+            Self::TopVariable => ResolvedVerificationType::Top,
+            Self::UninitializedThisVariable => ResolvedVerificationType::UninitializedThis,
+            Self::UninitializedVariable(offset) => {
+                ResolvedVerificationType::Uninitialized { offset }
+            }
+        })
+    }
+}
+
+pub fn is_object_method(name: &str, descriptor: &str) -> bool {
+    OBJECT_METHODS.contains(&(name, descriptor))
+}
+
+// The number of operand stack slots the instruction pops and pushes, with long and double values taking two.
+// Field and invoke instructions read the descriptor they refer to; the other instructions have a fixed
+// effect. An instruction such as dup_x1 that rearranges values pops and pushes all it touches, and athrow
+// is taken as popping the exception only, though it empties the stack.
+pub fn stack_effect(
+    instruction: &Instruction,
+    constant_pool: &ConstantPool,
+) -> Result<(u8, u8), ResolveError> {
+    Ok(match *instruction {
+        Instruction::Nop
+        | Instruction::IInc { .. }
+        | Instruction::Goto(_)
+        | Instruction::GotoW(_)
+        | Instruction::Ret(_)
+        | Instruction::Return => (0, 0),
+        Instruction::AConstNull
+        | Instruction::IConstM1
+        | Instruction::IConst0
+        | Instruction::IConst1
+        | Instruction::IConst2
+        | Instruction::IConst3
+        | Instruction::IConst4
+        | Instruction::IConst5
+        | Instruction::FConst0
+        | Instruction::FConst1
+        | Instruction::FConst2
+        | Instruction::BiPush(_)
+        | Instruction::SiPush(_)
+        | Instruction::Ldc(_)
+        | Instruction::LdcW(_)
+        | Instruction::ILoad(_)
+        | Instruction::FLoad(_)
+        | Instruction::ALoad(_)
+        | Instruction::ILoad0
+        | Instruction::ILoad1
+        | Instruction::ILoad2
+        | Instruction::ILoad3
+        | Instruction::FLoad0
+        | Instruction::FLoad1
+        | Instruction::FLoad2
+        | Instruction::FLoad3
+        | Instruction::ALoad0
+        | Instruction::ALoad1
+        | Instruction::ALoad2
+        | Instruction::ALoad3
+        | Instruction::Jsr(_)
+        | Instruction::JsrW(_)
+        | Instruction::New(_) => (0, 1),
+        Instruction::LConst0
+        | Instruction::LConst1
+        | Instruction::DConst0
+        | Instruction::DConst1
+        | Instruction::Ldc2W(_)
+        | Instruction::LLoad(_)
+        | Instruction::DLoad(_)
+        | Instruction::LLoad0
+        | Instruction::LLoad1
+        | Instruction::LLoad2
+        | Instruction::LLoad3
+        | Instruction::DLoad0
+        | Instruction::DLoad1
+        | Instruction::DLoad2
+        | Instruction::DLoad3 => (0, 2),
+        Instruction::IStore(_)
+        | Instruction::FStore(_)
+        | Instruction::AStore(_)
+        | Instruction::IStore0
+        | Instruction::IStore1
+        | Instruction::IStore2
+        | Instruction::IStore3
+        | Instruction::FStore0
+        | Instruction::FStore1
+        | Instruction::FStore2
+        | Instruction::FStore3
+        | Instruction::AStore0
+        | Instruction::AStore1
+        | Instruction::AStore2
+        | Instruction::AStore3
+        | Instruction::Pop
+        | Instruction::IfEq(_)
+        | Instruction::IfNe(_)
+        | Instruction::IfLt(_)
+        | Instruction::IfGe(_)
+        | Instruction::IfGt(_)
+        | Instruction::IfLe(_)
+        | Instruction::IfNull(_)
+        | Instruction::IfNonNull(_)
+        | Instruction::TableSwitch { .. }
+        | Instruction::LookupSwitch { .. }
+        | Instruction::IReturn
+        | Instruction::FReturn
+        | Instruction::AReturn
+        | Instruction::AThrow
+        | Instruction::MonitorEnter
+        | Instruction::MonitorExit => (1, 0),
+        Instruction::INeg
+        | Instruction::FNeg
+        | Instruction::I2F
+        | Instruction::F2I
+        | Instruction::I2B
+        | Instruction::I2C
+        | Instruction::I2S
+        | Instruction::NewArray(_)
+        | Instruction::ANewArray(_)
+        | Instruction::ArrayLength
+        | Instruction::CheckCast(_)
+        | Instruction::InstanceOf(_) => (1, 1),
+        Instruction::I2L
+        | Instruction::I2D
+        | Instruction::F2L
+        | Instruction::F2D
+        | Instruction::Dup => (1, 2),
+        Instruction::LStore(_)
+        | Instruction::DStore(_)
+        | Instruction::LStore0
+        | Instruction::LStore1
+        | Instruction::LStore2
+        | Instruction::LStore3
+        | Instruction::DStore0
+        | Instruction::DStore1
+        | Instruction::DStore2
+        | Instruction::DStore3
+        | Instruction::Pop2
+        | Instruction::IfICmpEq(_)
+        | Instruction::IfICmpNe(_)
+        | Instruction::IfICmpLt(_)
+        | Instruction::IfICmpGe(_)
+        | Instruction::IfICmpGt(_)
+        | Instruction::IfICmpLe(_)
+        | Instruction::IfACmpEq(_)
+        | Instruction::IfACmpNe(_)
+        | Instruction::LReturn
+        | Instruction::DReturn => (2, 0),
+        Instruction::IALoad
+        | Instruction::FALoad
+        | Instruction::AALoad
+        | Instruction::BALoad
+        | Instruction::CALoad
+        | Instruction::SALoad
+        | Instruction::IAdd
+        | Instruction::FAdd
+        | Instruction::ISub
+        | Instruction::FSub
+        | Instruction::IMul
+        | Instruction::FMul
+        | Instruction::IDiv
+        | Instruction::FDiv
+        | Instruction::IRem
+        | Instruction::FRem
+        | Instruction::IShl
+        | Instruction::IShr
+        | Instruction::IUShr
+        | Instruction::IAnd
+        | Instruction::IOr
+        | Instruction::IXor
+        | Instruction::L2I
+        | Instruction::L2F
+        | Instruction::D2I
+        | Instruction::D2F
+        | Instruction::FCmpL
+        | Instruction::FCmpG => (2, 1),
+        Instruction::LALoad
+        | Instruction::DALoad
+        | Instruction::LNeg
+        | Instruction::DNeg
+        | Instruction::L2D
+        | Instruction::D2L
+        | Instruction::Swap => (2, 2),
+        Instruction::DupX1 => (2, 3),
+        Instruction::Dup2 => (2, 4),
+        Instruction::IAStore
+        | Instruction::FAStore
+        | Instruction::AAStore
+        | Instruction::BAStore
+        | Instruction::CAStore
+        | Instruction::SAStore => (3, 0),
+        // the shift distance is an int
+        Instruction::LShl | Instruction::LShr | Instruction::LUShr => (3, 2),
+        Instruction::DupX2 => (3, 4),
+        Instruction::Dup2X1 => (3, 5),
+        Instruction::LAStore | Instruction::DAStore => (4, 0),
+        Instruction::LCmp | Instruction::DCmpL | Instruction::DCmpG => (4, 1),
+        Instruction::LAdd
+        | Instruction::DAdd
+        | Instruction::LSub
+        | Instruction::DSub
+        | Instruction::LMul
+        | Instruction::DMul
+        | Instruction::LDiv
+        | Instruction::DDiv
+        | Instruction::LRem
+        | Instruction::DRem
+        | Instruction::LAnd
+        | Instruction::LOr
+        | Instruction::LXor => (4, 2),
+        Instruction::Dup2X2 => (4, 6),
+        Instruction::MultiANewArray { dimensions, .. } => (dimensions, 1),
+        Instruction::GetStatic(index)
+        | Instruction::PutStatic(index)
+        | Instruction::GetField(index)
+        | Instruction::PutField(index) => {
+            let descriptor = reference_descriptor(constant_pool, index)?;
+            let Some(field_type) = field_descriptor_from_str(&descriptor) else {
+                return Err(ResolveError::MalformedDescriptor {
+                    descriptor: descriptor.into_owned(),
+                });
+            };
+            let slots = field_type_slots(&field_type);
+
+            match *instruction {
+                Instruction::GetStatic(_) => (0, slots),
+                Instruction::PutStatic(_) => (slots, 0),
+                Instruction::GetField(_) => (1, slots),
+                _ => (1 + slots, 0),
+            }
+        }
+        Instruction::InvokeVirtual(index)
+        | Instruction::InvokeSpecial(index)
+        | Instruction::InvokeStatic(index)
+        | Instruction::InvokeInterface { index, .. }
+        | Instruction::InvokeDynamic(index) => {
+            let descriptor = reference_descriptor(constant_pool, index)?;
+            let malformed = || ResolveError::MalformedDescriptor {
+                descriptor: descriptor.to_string(),
+            };
+            let method_descriptor =
+                method_descriptor_from_str(&descriptor).ok_or_else(malformed)?;
+
+            let receiver = match *instruction {
+                Instruction::InvokeStatic(_) | Instruction::InvokeDynamic(_) => 0,
+                _ => 1,
+            };
+            // a descriptor with more than 255 parameter slots, counting the receiver, is malformed
+            let pops = method_descriptor
+                .parameters
+                .iter()
+                .map(|parameter| usize::from(field_type_slots(parameter)))
+                .sum::<usize>()
+                + receiver;
+            let pops = u8::try_from(pops).map_err(|_| malformed())?;
+            let pushes = method_descriptor
+                .return_type
+                .as_ref()
+                .map_or(0, field_type_slots);
+
+            (pops, pushes)
+        }
+    })
+}
+
+fn branch_target(offset: usize, delta: i64) -> Option<usize> {
+    usize::try_from(offset as i64 + delta).ok()
+}
+
+fn class_type_signature_classes(class_type: &ClassTypeSignature, classes: &mut BTreeSet<String>) {
+    classes.insert(class_type.binary_name());
+
+    let nested_type_arguments = class_type
+        .nested
+        .iter()
+        .flat_map(|nested| &nested.type_arguments);
+    for type_argument in class_type
+        .type_arguments
+        .iter()
+        .chain(nested_type_arguments)
+    {
+        if let TypeArgument::Exact(signature)
+        | TypeArgument::Extends(signature)
+        | TypeArgument::Super(signature) = type_argument
+        {
+            type_signature_classes(signature, classes);
+        }
+    }
+}
+
+fn class_type_signature_to_source(class_type: &ClassTypeSignature) -> String {
+    let mut source = class_type.name.replace('/', ".");
+    source.push_str(&type_arguments_to_source(&class_type.type_arguments));
+    for nested in &class_type.nested {
+        source.push('.');
+        source.push_str(&nested.name);
+        source.push_str(&type_arguments_to_source(&nested.type_arguments));
+    }
+
+    source
+}
+
+// Either a Deprecated attribute or a `@Deprecated` annotation marks an element as deprecated; javac emits
+// both, but other compilers may emit only one.
+fn deprecation<'a>(
+    attributes: &[Attribute],
+    constant_pool: &ConstantPool<'a>,
+) -> Result<Option<Deprecation<'a>>, ResolveError> {
+    let mut deprecation = None;
+
+    for attribute in attributes {
+        match &attribute.info {
+            AttributeInfo::Deprecated => {
+                deprecation.get_or_insert(Deprecation {
+                    since: None,
+                    for_removal: false,
+                });
+            }
+            AttributeInfo::RuntimeVisibleAnnotations { annotations } => {
+                for annotation in annotations {
+                    if constant_pool.utf8(annotation.type_index)? != "Ljava/lang/Deprecated;" {
+                        continue;
+                    }
+
+                    let mut since = None;
+                    let mut for_removal = false;
+                    for pair in &annotation.element_value_pairs {
+                        // elements of other types are ill-formed, and left at their defaults
+                        let ElementValue::ConstValue {
+                            tag,
+                            const_value_index,
+                        } = pair.value
+                        else {
+                            continue;
+                        };
+
+                        match (constant_pool.utf8(pair.element_name_index)?.as_ref(), tag) {
+                            ("since", b's') => since = Some(constant_pool.utf8(const_value_index)?),
+                            ("forRemoval", b'Z') => {
+                                let ConstantPoolEntry::Integer { bytes } =
+                                    *constant_pool.get(const_value_index)?
+                                else {
+                                    return Err(ResolveError::UnexpectedConstantPoolEntry {
+                                        index: const_value_index,
+                                        expected: "Integer",
+                                    });
+                                };
+
+                                for_removal = bytes != 0;
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    // the annotation says more than the attribute, so it wins when both are present
+                    deprecation = Some(Deprecation { since, for_removal });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(deprecation)
+}
+
+fn erased_class_type(name: &str) -> ClassTypeSignature {
+    ClassTypeSignature {
+        name: name.to_string(),
+        type_arguments: Vec::new(),
+        nested: Vec::new(),
+    }
+}
+
+fn field_type_classes(field_type: &FieldType, classes: &mut BTreeSet<String>) {
+    match field_type {
+        FieldType::Object(name) => {
+            classes.insert(name.clone());
+        }
+        FieldType::Array(component_type) => field_type_classes(component_type, classes),
+        _ => {}
+    }
+}
+
+// long and double values take two slots of the operand stack and the local variables
+fn field_type_slots(field_type: &FieldType) -> u8 {
+    match field_type {
+        FieldType::Double | FieldType::Long => 2,
+        _ => 1,
+    }
+}
+
+fn field_type_to_source(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::Byte => "byte".to_string(),
+        FieldType::Char => "char".to_string(),
+        FieldType::Double => "double".to_string(),
+        FieldType::Float => "float".to_string(),
+        FieldType::Int => "int".to_string(),
+        FieldType::Long => "long".to_string(),
+        FieldType::Short => "short".to_string(),
+        FieldType::Boolean => "boolean".to_string(),
+        FieldType::Object(name) => name.replace('/', "."),
+        FieldType::Array(component_type) => format!("{}[]", field_type_to_source(component_type)),
+    }
+}
+
+// Feeds `part` into the hash, followed by a 0xFF byte that cannot occur in UTF-8 so that the boundaries
+// between the parts are kept.
+fn fnv1a(hash: u64, part: &[u8]) -> u64 {
+    part.iter().chain(&[0xFF]).fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn int_constant(instruction: &Instruction) -> Option<i32> {
+    match *instruction {
+        Instruction::IConstM1 => Some(-1),
+        Instruction::IConst0 => Some(0),
+        Instruction::IConst1 => Some(1),
+        Instruction::IConst2 => Some(2),
+        Instruction::IConst3 => Some(3),
+        Instruction::IConst4 => Some(4),
+        Instruction::IConst5 => Some(5),
+        Instruction::BiPush(value) => Some(value.into()),
+        Instruction::SiPush(value) => Some(value.into()),
+        _ => None,
+    }
+}
+
+fn is_int_store(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::IStore(_)
+            | Instruction::IStore0
+            | Instruction::IStore1
+            | Instruction::IStore2
+            | Instruction::IStore3
+    )
+}
+
+// the value of `String.hashCode` for the string, which is computed over its UTF-16 code units
+fn java_hash_code(value: &str) -> i32 {
+    value.encode_utf16().fold(0, |hash: i32, unit| {
+        hash.wrapping_mul(31).wrapping_add(unit.into())
+    })
+}
+
+// appends `name` with the escapes of the JNI specification applied, where `/` separates the components of an
+// internal class name and every character other than an ASCII letter or digit is escaped
+fn jni_mangle(name: &str, symbol: &mut String) {
+    for character in name.chars() {
+        match character {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => symbol.push(character),
+            '/' => symbol.push('_'),
+            '_' => symbol.push_str("_1"),
+            ';' => symbol.push_str("_2"),
+            '[' => symbol.push_str("_3"),
+            // characters outside the basic multilingual plane are escaped as their two surrogates
+            _ => {
+                for unit in character.encode_utf16(&mut [0; 2]) {
+                    symbol.push_str(&format!("_0{unit:04x}"));
+                }
+            }
+        }
+    }
+}
+
+// the special methods are recognized by name alone, as the JVM does
+fn method_kind(name: &str) -> MemberKind {
+    match name {
+        "<clinit>" => MemberKind::StaticInitializer,
+        "<init>" => MemberKind::Constructor,
+        _ => MemberKind::Method,
+    }
+}
+
+// the descriptor of a field, method or invokedynamic call site the entry at `index` refers to
+fn reference_descriptor<'a>(
+    constant_pool: &ConstantPool<'a>,
+    index: u16,
+) -> Result<Cow<'a, str>, ResolveError> {
+    let name_and_type_index = match *constant_pool.get(index)? {
+        ConstantPoolEntry::FieldRef {
+            name_and_type_index,
+            ..
+        }
+        | ConstantPoolEntry::MethodRef {
+            name_and_type_index,
+            ..
+        }
+        | ConstantPoolEntry::InstanceMethodRef {
+            name_and_type_index,
+            ..
+        }
+        | ConstantPoolEntry::InvokeDynamic {
+            name_and_type_index,
+            ..
+        } => name_and_type_index,
+        _ => {
+            return Err(ResolveError::UnexpectedConstantPoolEntry {
+                index,
+                expected: "Fieldref, Methodref, InterfaceMethodref or InvokeDynamic",
+            })
+        }
+    };
+    let ConstantPoolEntry::NameAndType {
+        descriptor_index, ..
+    } = *constant_pool.get(name_and_type_index)?
+    else {
+        return Err(ResolveError::UnexpectedConstantPoolEntry {
+            index: name_and_type_index,
+            expected: "NameAndType",
+        });
+    };
+
+    Ok(constant_pool.utf8(descriptor_index)?)
+}
+
+fn reference_load_index(instruction: &Instruction) -> Option<u16> {
+    match *instruction {
+        Instruction::ALoad(index) => Some(index),
+        Instruction::ALoad0 => Some(0),
+        Instruction::ALoad1 => Some(1),
+        Instruction::ALoad2 => Some(2),
+        Instruction::ALoad3 => Some(3),
+        _ => None,
+    }
+}
+
+fn resolved_annotation<'a>(
+    annotation: &Annotation,
+    constant_pool: &ConstantPool<'a>,
+) -> Result<ResolvedAnnotation<'a>, ResolveError> {
+    let elements = annotation
+        .element_value_pairs
+        .iter()
+        .map(|pair| {
+            Ok((
+                constant_pool.utf8(pair.element_name_index)?,
+                resolved_element_value(&pair.value, constant_pool)?,
+            ))
+        })
+        .collect::<Result<_, ResolveError>>()?;
+
+    Ok(ResolvedAnnotation {
+        type_name: constant_pool.utf8(annotation.type_index)?,
+        elements,
+    })
+}
+
+fn resolved_element_value<'a>(
+    value: &ElementValue,
+    constant_pool: &ConstantPool<'a>,
+) -> Result<ResolvedElementValue<'a>, ResolveError> {
+    let (tag, index) = match *value {
+        ElementValue::Annotation(ref annotation) => {
+            return Ok(ResolvedElementValue::Annotation(resolved_annotation(
+                annotation,
+                constant_pool,
+            )?));
+        }
+        ElementValue::Array { ref values } => {
+            return Ok(ResolvedElementValue::Array(
+                values
+                    .iter()
+                    .map(|value| resolved_element_value(value, constant_pool))
+                    .collect::<Result<_, _>>()?,
+            ));
+        }
+        ElementValue::ClassInfo(class_info_index) => {
+            return Ok(ResolvedElementValue::Class(
+                constant_pool.utf8(class_info_index)?,
+            ));
+        }
+        ElementValue::EnumConst {
+            type_name_index,
+            const_name_index,
+        } => {
+            return Ok(ResolvedElementValue::Enum {
+                type_name: constant_pool.utf8(type_name_index)?,
+                const_name: constant_pool.utf8(const_name_index)?,
+            });
+        }
+        ElementValue::ConstValue {
+            tag,
+            const_value_index,
+        } => (tag, const_value_index),
+    };
+
+    let unexpected = |expected| ResolveError::UnexpectedConstantPoolEntry { index, expected };
+    let entry = constant_pool.get(index)?;
+
+    // the narrower integral types are stored in Integer entries, truncated as by a cast
+    Ok(match (tag, entry) {
+        (b's', _) => match constant_pool.utf8_or_raw(index)? {
+            Utf8Constant::Raw(bytes) => ResolvedElementValue::StringBytes(bytes),
+            Utf8Constant::Text(value) => ResolvedElementValue::String(value),
+        },
+        (b'B' | b'C' | b'I' | b'S' | b'Z', &ConstantPoolEntry::Integer { bytes }) => match tag {
+            b'B' => ResolvedElementValue::Byte(bytes as i8),
+            b'C' => ResolvedElementValue::Char(bytes as u16),
+            b'S' => ResolvedElementValue::Short(bytes as i16),
+            b'Z' => ResolvedElementValue::Boolean(bytes != 0),
+            _ => ResolvedElementValue::Int(bytes as i32),
+        },
+        (b'B' | b'C' | b'I' | b'S' | b'Z', _) => return Err(unexpected("Integer")),
+        (b'D', &ConstantPoolEntry::Double { value }) => ResolvedElementValue::Double(value),
+        (b'D', _) => return Err(unexpected("Double")),
+        (b'F', &ConstantPoolEntry::Float { value }) => ResolvedElementValue::Float(value),
+        (b'F', _) => return Err(unexpected("Float")),
+        (b'J', &ConstantPoolEntry::Long { value }) => ResolvedElementValue::Long(value as i64),
+        (b'J', _) => return Err(unexpected("Long")),
+        // the parser only produces constant element values for the tags above
+        _ => return Err(unexpected("constant")),
+    })
+}
+
+fn signature_index(attributes: &[Attribute]) -> Option<u16> {
+    attributes
+        .iter()
+        .find_map(|attribute| match attribute.info {
+            AttributeInfo::Signature { signature_index } => Some(signature_index),
+            _ => None,
+        })
+}
+
+// the strings among the values of an array element, in order
+fn string_elements<'a>(values: Vec<ResolvedElementValue<'a>>) -> Vec<Cow<'a, str>> {
+    values
+        .into_iter()
+        .filter_map(|value| match value {
+            ResolvedElementValue::String(value) => Some(value),
+            _ => None,
+        })
+        .collect()
+}
+
+// `<>` around the arguments, or nothing for a type without any
+fn type_arguments_to_source(type_arguments: &[TypeArgument]) -> String {
+    if type_arguments.is_empty() {
+        return String::new();
+    }
+
+    let type_arguments = type_arguments
+        .iter()
+        .map(|type_argument| match type_argument {
+            TypeArgument::Any => "?".to_string(),
+            TypeArgument::Exact(signature) => type_signature_to_source(signature),
+            TypeArgument::Extends(bound) => {
+                format!("? extends {}", type_signature_to_source(bound))
+            }
+            TypeArgument::Super(bound) => format!("? super {}", type_signature_to_source(bound)),
+        })
+        .collect::<Vec<_>>();
+
+    format!("<{}>", type_arguments.join(", "))
+}
+
+fn type_parameter_classes(type_parameters: &[TypeParameter], classes: &mut BTreeSet<String>) {
+    for type_parameter in type_parameters {
+        for bound in type_parameter
+            .class_bound
+            .iter()
+            .chain(&type_parameter.interface_bounds)
+        {
+            type_signature_classes(bound, classes);
+        }
+    }
+}
+
+// Type parameters as in `<T extends java.lang.Comparable<? super T>, K>`. A bound of java/lang/Object alone
+// is what a parameter declared without one gets, so it is left out.
+fn type_parameters_to_source(type_parameters: &[TypeParameter]) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
+    }
+
+    let type_parameters = type_parameters
+        .iter()
+        .map(|type_parameter| {
+            let bounds = type_parameter
+                .class_bound
+                .iter()
+                .filter(|bound| {
+                    !matches!(bound, TypeSignature::Class(class_type)
+                        if class_type.binary_name() == OBJECT && class_type.type_arguments.is_empty())
+                })
+                .chain(&type_parameter.interface_bounds)
+                .map(type_signature_to_source)
+                .collect::<Vec<_>>();
+
+            match bounds.is_empty() {
+                true => type_parameter.name.clone(),
+                false => format!("{} extends {}", type_parameter.name, bounds.join(" & ")),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    format!("<{}>", type_parameters.join(", "))
+}
+
+fn type_signature_classes(signature: &TypeSignature, classes: &mut BTreeSet<String>) {
+    match signature {
+        TypeSignature::Array(component_type) => type_signature_classes(component_type, classes),
+        TypeSignature::Class(class_type) => class_type_signature_classes(class_type, classes),
+        TypeSignature::Base(_) | TypeSignature::TypeVariable(_) => {}
+    }
+}
+
+fn type_signature_to_source(signature: &TypeSignature) -> String {
+    match signature {
+        TypeSignature::Array(component_type) => {
+            format!("{}[]", type_signature_to_source(component_type))
+        }
+        TypeSignature::Base(field_type) => field_type_to_source(field_type),
+        TypeSignature::Class(class_type) => class_type_signature_to_source(class_type),
+        TypeSignature::TypeVariable(name) => name.clone(),
+    }
+}
+
+// Writes the last of the parameters of a varargs method as `T...` rather than `T[]`. A method that has
+// ACC_VARARGS set without ending in an array parameter keeps the parameters as they are.
+fn varargs_parameter(parameters: &mut [String], is_varargs: bool) {
+    let Some(last) = parameters.last_mut().filter(|_| is_varargs) else {
+        return;
+    };
+    if let Some(component_type) = last.strip_suffix("[]") {
+        *last = format!("{component_type}...");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::instructions_from_bytes;
+    use crate::parse::classfile_from_bytes;
+    use crate::spec::Attribute;
+    use crate::spec::AttributeInfo;
+    use crate::spec::RecordComponent;
+    use crate::spec::Version;
+    use crate::write::ClassBuilder;
+    use crate::write::ConstantPoolBuilder;
+    use crate::write::MethodBuilder;
+
+    use super::is_object_method;
+    use super::jni_mangle;
+    use super::stack_effect;
+    use super::MethodKind;
+    use super::ResolvedClass;
+
+    #[test]
+    fn jni_mangle_escapes() {
+        for (name, mangled) in [
+            ("java/lang/Object", "java_lang_Object"),
+            ("my_method", "my_1method"),
+            ("[Ljava/lang/String;", "_3Ljava_lang_String_2"),
+            ("caf\u{E9}", "caf_000e9"),
+            // U+1F600 is encoded as the surrogate pair D83D DE00
+            ("smile\u{1F600}", "smile_0d83d_0de00"),
+        ] {
+            let mut symbol = String::new();
+            jni_mangle(name, &mut symbol);
+            assert_eq!(symbol, mangled, "mangling {name:?}");
+        }
+    }
+
+    #[test]
+    fn abi_hash_ignores_the_body() {
+        let abi_hash = |descriptor: &str, code: Vec<u8>| {
+            let mut class = ClassBuilder::new(
+                Version {
+                    minor: 0,
+                    major: 50,
+                },
+                0x21,
+                "Test",
+                Some("java/lang/Object"),
+            );
+            let mut method = MethodBuilder::new(0x9, "run", descriptor);
+            method.code(2, 0, code);
+            class.add_method(method).unwrap();
+            let bytes = class.build().unwrap();
+            let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+
+            classfile.methods[0]
+                .abi_hash(&classfile.constant_pool)
+                .unwrap()
+        };
+
+        // return 0; against return 1; against a long return 0L;
+        let original = abi_hash("()I", vec![0x03, 0xAC]);
+        assert_eq!(abi_hash("()I", vec![0x04, 0xAC]), original);
+        assert_ne!(abi_hash("()J", vec![0x09, 0xAD]), original);
+    }
+
+    #[test]
+    fn stack_effects_add_up() {
+        let mut builder = ConstantPoolBuilder::new();
+        let [r1, r2] = builder
+            .method_ref("java/lang/Math", "round", "(D)J")
+            .unwrap()
+            .to_be_bytes();
+        let [f1, f2] = builder
+            .field_ref("Counter", "count", "J")
+            .unwrap()
+            .to_be_bytes();
+        let [h1, h2] = builder
+            .method_ref("java/lang/Object", "hashCode", "()I")
+            .unwrap()
+            .to_be_bytes();
+        let [m1, m2] = builder
+            .method_ref("java/lang/Math", "max", "(JJ)J")
+            .unwrap()
+            .to_be_bytes();
+        let constant_pool = builder.to_constant_pool();
+
+        // static long sum(long a, double b, Counter c) {
+        //     return Math.max(a + Math.round(b) + c.count + c.hashCode(), 1L);
+        // }
+        let (_, instructions) = instructions_from_bytes(&[
+            0x1E, 0x28, 0xB8, r1, r2, 0x61, 0x19, 0x04, 0xB4, f1, f2, 0x61, 0x19, 0x04, 0xB6, h1,
+            h2, 0x85, 0x61, 0x0A, 0xB8, m1, m2, 0xAD,
+        ])
+        .unwrap();
+        let effects = instructions
+            .iter()
+            .map(|(_, instruction)| stack_effect(instruction, &constant_pool).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            effects,
+            [
+                (0, 2),
+                (0, 2),
+                (2, 2),
+                (4, 2),
+                (0, 1),
+                (1, 2),
+                (4, 2),
+                (0, 1),
+                (1, 1),
+                (1, 2),
+                (4, 2),
+                (0, 2),
+                (4, 2),
+                (2, 0),
+            ]
+        );
+
+        // the stack ends empty, never holding more than the two longs being added
+        let depths = effects
+            .iter()
+            .scan(0, |depth, (pops, pushes)| {
+                *depth = *depth - i32::from(*pops) + i32::from(*pushes);
+                Some(*depth)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(depths.iter().max(), Some(&4));
+        assert_eq!(depths.last(), Some(&0));
+    }
+
+    #[test]
+    fn interface_method_kinds() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 52,
+            },
+            0x601,
+            "Shape",
+            Some("java/lang/Object"),
+        );
+        for (access_flags, name) in [
+            (0x401, "area"),
+            (0x1, "describe"),
+            (0x9, "unit"),
+            (0x2, "helper"),
+            (0xA, "staticHelper"),
+        ] {
+            let mut method = MethodBuilder::new(access_flags, name, "()V");
+            if access_flags & 0x400 == 0 {
+                method.code(0, 1, vec![0xB1]);
+            }
+            class.add_method(method).unwrap();
+        }
+        let bytes = class.build().unwrap();
+
+        let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+        let kinds = classfile
+            .methods
+            .iter()
+            .map(|method| method.effective_kind(&classfile))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                MethodKind::Abstract,
+                MethodKind::Default,
+                MethodKind::Static,
+                MethodKind::Private,
+                MethodKind::Static,
+            ]
+        );
+    }
+
+    #[test]
+    fn object_methods_do_not_count_towards_functional_interfaces() {
+        let is_functional_interface = |methods: &[(&str, &str)]| {
+            let mut class = ClassBuilder::new(
+                Version {
+                    minor: 0,
+                    major: 52,
+                },
+                0x601,
+                "Callback",
+                Some("java/lang/Object"),
+            );
+            for (name, descriptor) in methods {
+                class
+                    .add_method(MethodBuilder::new(0x401, name, descriptor))
+                    .unwrap();
+            }
+            let bytes = class.build().unwrap();
+            let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+
+            ResolvedClass::new(&classfile)
+                .is_functional_interface()
+                .unwrap()
+        };
+
+        // like java.util.Comparator, which redeclares equals
+        assert!(is_functional_interface(&[
+            ("compare", "(Ljava/lang/Object;Ljava/lang/Object;)I"),
+            ("equals", "(Ljava/lang/Object;)Z"),
+            ("toString", "()Ljava/lang/String;"),
+        ]));
+        // clone is protected in Object, so an interface redeclaring it has a second abstract method
+        assert!(!is_functional_interface(&[
+            ("run", "()V"),
+            ("clone", "()Ljava/lang/Object;"),
+        ]));
+    }
+
+    #[test]
+    fn object_methods_match_name_and_descriptor() {
+        for (name, descriptor, expected) in [
+            ("equals", "(Ljava/lang/Object;)Z", true),
+            ("hashCode", "()I", true),
+            ("toString", "()Ljava/lang/String;", true),
+            ("clone", "()Ljava/lang/Object;", true),
+            ("finalize", "()V", true),
+            ("getClass", "()Ljava/lang/Class;", true),
+            ("notifyAll", "()V", true),
+            ("wait", "()V", true),
+            ("wait", "(J)V", true),
+            ("wait", "(JI)V", true),
+            // Object has no wait taking an int, and equals is not overloaded
+            ("wait", "(I)V", false),
+            ("equals", "(LPoint;)Z", false),
+            ("hashcode", "()I", false),
+            ("<init>", "()V", false),
+        ] {
+            assert_eq!(
+                is_object_method(name, descriptor),
+                expected,
+                "{name}{descriptor}"
+            );
+        }
+    }
+
+    #[test]
+    fn declaration_headers_read_like_source() {
+        // the header of a class with the Signature attribute `signature`, if any, and a Record attribute
+        // listing `components` if it extends java/lang/Record
+        let header = |access_flags: u16,
+                      name: &str,
+                      superclass: &str,
+                      interfaces: &[&str],
+                      signature: Option<&str>,
+                      components: &[(&str, &str)]| {
+            let mut class = ClassBuilder::new(
+                Version {
+                    minor: 0,
+                    major: 61,
+                },
+                access_flags,
+                name,
+                Some(superclass),
+            );
+            for interface in interfaces {
+                class.add_interface(interface).unwrap();
+            }
+            let mut attributes = Vec::new();
+            if let Some(signature) = signature {
+                attributes.push(Attribute {
+                    name_index: class.constant_pool().utf8("Signature").unwrap(),
+                    info: AttributeInfo::Signature {
+                        signature_index: class.constant_pool().utf8(signature).unwrap(),
+                    },
+                    raw: None,
+                });
+            }
+            if superclass == "java/lang/Record" {
+                let components = components
+                    .iter()
+                    .map(|(name, descriptor)| RecordComponent {
+                        name_index: class.constant_pool().utf8(name).unwrap(),
+                        descriptor_index: class.constant_pool().utf8(descriptor).unwrap(),
+                        attributes: Vec::new(),
+                    })
+                    .collect();
+                attributes.push(Attribute {
+                    name_index: class.constant_pool().utf8("Record").unwrap(),
+                    info: AttributeInfo::Record { components },
+                    raw: None,
+                });
+            }
+            let bytes = class.build().unwrap();
+            let (_, mut classfile) = classfile_from_bytes(&bytes).unwrap();
+            classfile.attributes = attributes;
+
+            ResolvedClass::new(&classfile).declaration_header().unwrap()
+        };
+
+        assert_eq!(
+            header(
+                0x31,
+                "com/example/Box",
+                "java/util/AbstractList",
+                &["java/io/Serializable"],
+                Some("<T:Ljava/lang/Object;>Ljava/util/AbstractList<TT;>;Ljava/io/Serializable;"),
+                &[],
+            ),
+            "public final class com.example.Box<T> extends java.util.AbstractList<T> implements \
+             java.io.Serializable"
+        );
+        assert_eq!(
+            header(
+                0x601,
+                "com/example/Shape",
+                "java/lang/Object",
+                &["java/lang/Comparable"],
+                Some("<T:Ljava/lang/Object;>Ljava/lang/Object;Ljava/lang/Comparable<TT;>;"),
+                &[],
+            ),
+            "public interface com.example.Shape<T> extends java.lang.Comparable<T>"
+        );
+        assert_eq!(
+            header(
+                0x4031,
+                "com/example/Color",
+                "java/lang/Enum",
+                &["java/lang/Runnable"],
+                Some("Ljava/lang/Enum<Lcom/example/Color;>;Ljava/lang/Runnable;"),
+                &[],
+            ),
+            "public enum com.example.Color implements java.lang.Runnable"
+        );
+        assert_eq!(
+            header(
+                0x31,
+                "com/example/Point",
+                "java/lang/Record",
+                &[],
+                None,
+                &[("x", "I"), ("label", "Ljava/lang/String;")],
+            ),
+            "public record com.example.Point(int x, java.lang.String label)"
+        );
+        assert_eq!(
+            header(
+                0x2601,
+                "com/example/Marker",
+                "java/lang/Object",
+                &["java/lang/annotation/Annotation"],
+                None,
+                &[],
+            ),
+            "public @interface com.example.Marker"
+        );
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use nom::bytes::complete::take_till1;
+use nom::character::complete::anychar;
+use nom::character::complete::char;
+use nom::combinator::all_consuming;
+use nom::combinator::opt;
+use nom::error::Error;
+use nom::error::ErrorKind;
+use nom::multi::many0;
+use nom::multi::many1;
+use nom::sequence::delimited;
+use nom::sequence::preceded;
+use nom::Err;
+use nom::IResult;
+
+use crate::descriptor::FieldType;
+
+// The generic signatures of the Signature attribute, which unlike descriptors keep type arguments, type
+// variables and the type parameters of generic classes and methods.
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TypeArgument {
+    // Signature: *
+    Any,
+    // Signature: ReferenceTypeSignature
+    Exact(TypeSignature),
+    // Signature: + ReferenceTypeSignature
+    Extends(TypeSignature),
+    // Signature: - ReferenceTypeSignature
+    Super(TypeSignature),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TypeSignature {
+    // Signature: [ JavaTypeSignature
+    Array(Box<TypeSignature>),
+    // one of the primitive field types, which only stand for themselves and never as a type argument
+    Base(FieldType),
+    // Signature: L ClassName TypeArguments ;
+    Class(ClassTypeSignature),
+    // Signature: T Identifier ;
+    TypeVariable(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub superclass: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClassTypeSignature {
+    // the binary name of the outermost class, such as `java/util/Map`
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+    // the member classes selected from it in turn, such as `Entry` in `Ljava/util/Map<TK;TV;>.Entry;`
+    pub nested: Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<TypeSignature>,
+    // `None` for a void return
+    pub return_type: Option<TypeSignature>,
+    // class types and type variables
+    pub throws: Vec<TypeSignature>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TypeParameter {
+    pub name: String,
+    // `None` in `T::Ljava/lang/Comparable;`, where the bounds are all interfaces
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+impl ClassTypeSignature {
+    // the binary name of the class the signature ends in, the nested classes being joined to their outer
+    // classes with `$` as javac names them
+    pub fn binary_name(&self) -> String {
+        let mut name = self.name.clone();
+        for nested in &self.nested {
+            name.push('$');
+            name.push_str(&nested.name);
+        }
+
+        name
+    }
+}
+
+pub fn class_signature_from_str(signature: &str) -> Option<ClassSignature> {
+    all_consuming(class_signature)(signature)
+        .ok()
+        .map(|(_, class_signature)| class_signature)
+}
+
+// fields, and record components, can only be of a reference type if they have a signature at all
+pub fn field_signature_from_str(signature: &str) -> Option<TypeSignature> {
+    all_consuming(reference_type_signature)(signature)
+        .ok()
+        .map(|(_, field_signature)| field_signature)
+}
+
+pub fn method_signature_from_str(signature: &str) -> Option<MethodSignature> {
+    all_consuming(method_signature)(signature)
+        .ok()
+        .map(|(_, method_signature)| method_signature)
+}
+
+fn class_signature(input: &str) -> IResult<&str, ClassSignature> {
+    let (input_1, type_parameters) = type_parameters(input)?;
+    let (input_2, superclass) = class_type_signature(input_1)?;
+    let (input_3, interfaces) = many0(class_type_signature)(input_2)?;
+
+    Ok((
+        input_3,
+        ClassSignature {
+            type_parameters,
+            superclass,
+            interfaces,
+        },
+    ))
+}
+
+fn class_type_signature(input: &str) -> IResult<&str, ClassTypeSignature> {
+    let (input_1, _) = char('L')(input)?;
+    let (input_2, name) = take_till1(|c| ".;[<>:".contains(c))(input_1)?;
+    let (input_3, type_arguments) = type_arguments(input_2)?;
+    let (input_4, nested) = many0(preceded(char('.'), simple_class_type_signature))(input_3)?;
+    let (input_5, _) = char(';')(input_4)?;
+
+    // the package specifier is a run of identifiers each followed by a slash
+    if name.split('/').any(str::is_empty) {
+        return Err(Err::Error(Error::new(input_1, ErrorKind::Verify)));
+    }
+
+    Ok((
+        input_5,
+        ClassTypeSignature {
+            name: name.to_string(),
+            type_arguments,
+            nested,
+        },
+    ))
+}
+
+fn identifier(input: &str) -> IResult<&str, &str> {
+    take_till1(|c| ".;[/<>:".contains(c))(input)
+}
+
+fn java_type_signature(input: &str) -> IResult<&str, TypeSignature> {
+    let (input_1, tag) = anychar(input)?;
+
+    let base_type = match tag {
+        'B' => FieldType::Byte,
+        'C' => FieldType::Char,
+        'D' => FieldType::Double,
+        'F' => FieldType::Float,
+        'I' => FieldType::Int,
+        'J' => FieldType::Long,
+        'S' => FieldType::Short,
+        'Z' => FieldType::Boolean,
+        _ => return reference_type_signature(input),
+    };
+
+    Ok((input_1, TypeSignature::Base(base_type)))
+}
+
+fn method_signature(input: &str) -> IResult<&str, MethodSignature> {
+    let (input_1, type_parameters) = type_parameters(input)?;
+    let (input_2, parameters) =
+        delimited(char('('), many0(java_type_signature), char(')'))(input_1)?;
+    let (input_3, return_type) = match input_2.strip_prefix('V') {
+        Some(input_3) => (input_3, None),
+        None => {
+            let (input_3, return_type) = java_type_signature(input_2)?;
+
+            (input_3, Some(return_type))
+        }
+    };
+    let (input_4, throws) = many0(preceded(char('^'), reference_type_signature))(input_3)?;
+
+    // only class types and type variables can be thrown
+    if throws
+        .iter()
+        .any(|thrown| matches!(thrown, TypeSignature::Array(_)))
+    {
+        return Err(Err::Error(Error::new(input_3, ErrorKind::Verify)));
+    }
+
+    Ok((
+        input_4,
+        MethodSignature {
+            type_parameters,
+            parameters,
+            return_type,
+            throws,
+        },
+    ))
+}
+
+fn reference_type_signature(input: &str) -> IResult<&str, TypeSignature> {
+    let (input_1, tag) = anychar(input)?;
+
+    Ok(match tag {
+        'L' => {
+            let (input_2, class_type) = class_type_signature(input)?;
+
+            (input_2, TypeSignature::Class(class_type))
+        }
+        'T' => {
+            let (input_2, name) = identifier(input_1)?;
+            let (input_3, _) = char(';')(input_2)?;
+
+            (input_3, TypeSignature::TypeVariable(name.to_string()))
+        }
+        '[' => {
+            let (input_2, component_type) = java_type_signature(input_1)?;
+
+            (input_2, TypeSignature::Array(Box::new(component_type)))
+        }
+        _ => return Err(Err::Error(Error::new(input, ErrorKind::Tag))),
+    })
+}
+
+fn simple_class_type_signature(input: &str) -> IResult<&str, SimpleClassTypeSignature> {
+    let (input_1, name) = identifier(input)?;
+    let (input_2, type_arguments) = type_arguments(input_1)?;
+
+    Ok((
+        input_2,
+        SimpleClassTypeSignature {
+            name: name.to_string(),
+            type_arguments,
+        },
+    ))
+}
+
+fn type_argument(input: &str) -> IResult<&str, TypeArgument> {
+    let (input_1, tag) = anychar(input)?;
+
+    Ok(match tag {
+        '*' => (input_1, TypeArgument::Any),
+        '+' => {
+            let (input_2, bound) = reference_type_signature(input_1)?;
+
+            (input_2, TypeArgument::Extends(bound))
+        }
+        '-' => {
+            let (input_2, bound) = reference_type_signature(input_1)?;
+
+            (input_2, TypeArgument::Super(bound))
+        }
+        _ => {
+            let (input_2, argument) = reference_type_signature(input)?;
+
+            (input_2, TypeArgument::Exact(argument))
+        }
+    })
+}
+
+// absent type arguments, for a raw or non-generic type, come out empty
+fn type_arguments(input: &str) -> IResult<&str, Vec<TypeArgument>> {
+    if !input.starts_with('<') {
+        return Ok((input, Vec::new()));
+    }
+
+    delimited(char('<'), many1(type_argument), char('>'))(input)
+}
+
+fn type_parameter(input: &str) -> IResult<&str, TypeParameter> {
+    let (input_1, name) = identifier(input)?;
+    let (input_2, _) = char(':')(input_1)?;
+    let (input_3, class_bound) = opt(reference_type_signature)(input_2)?;
+    let (input_4, interface_bounds) =
+        many0(preceded(char(':'), reference_type_signature))(input_3)?;
+
+    Ok((
+        input_4,
+        TypeParameter {
+            name: name.to_string(),
+            class_bound,
+            interface_bounds,
+        },
+    ))
+}
+
+// a class or method that is not generic has no type parameters, not an empty list of them
+fn type_parameters(input: &str) -> IResult<&str, Vec<TypeParameter>> {
+    if !input.starts_with('<') {
+        return Ok((input, Vec::new()));
+    }
+
+    delimited(char('<'), many1(type_parameter), char('>'))(input)
+}
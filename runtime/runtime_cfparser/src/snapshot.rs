@@ -0,0 +1,152 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::owned::OwnedClassfile;
+use crate::spec::Classfile;
+
+// bumped whenever the encoding changes in a way the crate version alone would not reveal
+const FORMAT_VERSION: u16 = 2;
+
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Encoding(postcard::Error),
+    Stale {
+        format_version: u16,
+        crate_version: String,
+    },
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Encoding(error) => write!(f, "{error}"),
+            Self::Stale {
+                format_version,
+                crate_version,
+            } => write!(
+                f,
+                "snapshot was written in format {format_version} by version {crate_version}, expected format \
+                 {FORMAT_VERSION} by version {CRATE_VERSION}"
+            ),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<postcard::Error> for SnapshotError {
+    fn from(error: postcard::Error) -> Self {
+        Self::Encoding(error)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct Header<'a> {
+    format_version: u16,
+    crate_version: &'a str,
+}
+
+pub fn classfile_from_snapshot(bytes: &[u8]) -> Result<OwnedClassfile, SnapshotError> {
+    let (header, rest) = postcard::take_from_bytes::<Header>(bytes)?;
+    if header.format_version != FORMAT_VERSION || header.crate_version != CRATE_VERSION {
+        return Err(SnapshotError::Stale {
+            format_version: header.format_version,
+            crate_version: header.crate_version.to_owned(),
+        });
+    }
+
+    // byte fields are always deserialized as owned, so nothing refers back to `bytes`
+    Ok(postcard::from_bytes::<OwnedClassfile>(rest)?)
+}
+
+pub fn classfile_to_snapshot(classfile: &Classfile) -> Result<Vec<u8>, SnapshotError> {
+    let header = Header {
+        format_version: FORMAT_VERSION,
+        crate_version: CRATE_VERSION,
+    };
+
+    let mut bytes = postcard::to_allocvec(&header)?;
+    bytes.extend(postcard::to_allocvec(classfile)?);
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::classfile_from_bytes;
+    use crate::spec::Version;
+    use crate::write::ClassBuilder;
+    use crate::write::MethodBuilder;
+
+    use super::classfile_from_snapshot;
+    use super::classfile_to_snapshot;
+    use super::Header;
+    use super::SnapshotError;
+    use super::CRATE_VERSION;
+    use super::FORMAT_VERSION;
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 52,
+            },
+            0x21,
+            "Test",
+            Some("java/lang/Object"),
+        );
+        class.add_field(0x2, "count", "J").unwrap();
+        let mut method = MethodBuilder::new(0x9, "run", "()V");
+        method.code(0, 0, vec![0xB1]);
+        class.add_method(method).unwrap();
+        let bytes = class.build().unwrap();
+        let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+
+        let snapshot = classfile_to_snapshot(&classfile).unwrap();
+        assert_eq!(classfile_from_snapshot(&snapshot).unwrap(), classfile);
+
+        // a snapshot written in another format or by another version of the crate is refused
+        let body = postcard::to_allocvec(&classfile).unwrap();
+        for header in [
+            Header {
+                format_version: FORMAT_VERSION + 1,
+                crate_version: CRATE_VERSION,
+            },
+            Header {
+                format_version: FORMAT_VERSION,
+                crate_version: "0.0.0",
+            },
+        ] {
+            let mut stale = postcard::to_allocvec(&header).unwrap();
+            stale.extend_from_slice(&body);
+            assert!(matches!(
+                classfile_from_snapshot(&stale),
+                Err(SnapshotError::Stale { .. })
+            ));
+        }
+    }
+}
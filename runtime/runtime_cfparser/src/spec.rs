@@ -14,9 +14,21 @@
  * limitations under the License.
  */
 
+use std::borrow::Cow;
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::constant_pool::ConstantPool;
+use crate::parse::LazyCode;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum AttributeInfo<'class> {
     AnnotationDefault {
-        default_value: ElementValue,
+        default_value: ElementValue<'class>,
     },
     BootstrapMethods {
         bootstrap_methods: Vec<BootstrapMethod>,
@@ -24,7 +36,7 @@ pub enum AttributeInfo<'class> {
     Code {
         max_stack: u16,
         max_locals: u16,
-        code: &'class [u8],
+        code: Cow<'class, [u8]>,
         exception_table: Vec<ExceptionTableEntry>,
         attributes: Vec<Attribute<'class>>,
     },
@@ -84,28 +96,28 @@ pub enum AttributeInfo<'class> {
         components: Vec<RecordComponent<'class>>,
     },
     RuntimeInvisibleAnnotations {
-        annotations: Vec<Annotation>,
+        annotations: Vec<Annotation<'class>>,
     },
     RuntimeInvisibleParameterAnnotations {
-        parameter_annotations: Vec<Annotation>,
+        parameter_annotations: Vec<Annotation<'class>>,
     },
     RuntimeInvisibleTypeAnnotations {
-        type_annotations: Vec<TypeAnnotation>,
+        type_annotations: Vec<TypeAnnotation<'class>>,
     },
     RuntimeVisibleAnnotations {
-        annotations: Vec<Annotation>,
+        annotations: Vec<Annotation<'class>>,
     },
     RuntimeVisibleParameterAnnotations {
-        parameter_annotations: Vec<Annotation>,
+        parameter_annotations: Vec<Annotation<'class>>,
     },
     RuntimeVisibleTypeAnnotations {
-        type_annotations: Vec<TypeAnnotation>,
+        type_annotations: Vec<TypeAnnotation<'class>>,
     },
     Signature {
         signature_index: u16,
     },
     SourceDebugExtension {
-        debug_extension: &'class [u8],
+        debug_extension: Cow<'class, [u8]>,
     },
     SourceFile {
         sourcefile_index: u16,
@@ -114,13 +126,19 @@ pub enum AttributeInfo<'class> {
         entries: Vec<StackMapFrame>,
     },
     Synthetic,
+    // an attribute this parser does not recognise, which the JVMS requires to be silently ignored
+    Unknown {
+        name_index: u16,
+        info: Cow<'class, [u8]>,
+    },
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum ConstantPoolEntry<'class> {
     // Tag: 1
     Utf8 {
-        bytes: &'class [u8],
+        bytes: Cow<'class, [u8]>,
     },
     // Tag: 3
     Integer {
@@ -156,7 +174,7 @@ pub enum ConstantPoolEntry<'class> {
         class_index: u16,
         name_and_type_index: u16,
     },
-    // Tag: 11
+    // Tag: 11, CONSTANT_InterfaceMethodref: a method of an interface, where MethodRef is one of a class
     InstanceMethodRef {
         class_index: u16,
         name_and_type_index: u16,
@@ -193,21 +211,30 @@ pub enum ConstantPoolEntry<'class> {
     Package {
         name_index: u16,
     },
+    // no tag; stands in for the second of the two indices a Long or Double entry takes up
+    Unusable,
 }
 
-pub enum ElementValue {
-    Annotation(Annotation),
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum ElementValue<'class> {
+    Annotation(Annotation<'class>),
     ClassInfo(u16),
-    ConstValue(u16),
+    ConstValue {
+        tag: u8,
+        const_value_index: u16,
+    },
     EnumConst {
         type_name_index: u16,
         const_name_index: u16,
     },
     Array {
-        values: Vec<ElementValue>,
+        values: Vec<ElementValue<'class>>,
     },
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum StackMapFrame {
     AppendFrame {
         offset_delta: u16,
@@ -215,17 +242,21 @@ pub enum StackMapFrame {
     },
     ChopFrame {
         offset_delta: u16,
+        absent_locals: u8,
     },
     FullFrame {
         offset_delta: u16,
         locals: Vec<VerificationTypeInfo>,
         stack: Vec<VerificationTypeInfo>,
     },
-    SameFrame,
+    SameFrame {
+        offset_delta: u8,
+    },
     SameFrameExtended {
         offset_delta: u16,
     },
     SameLocals1StackItemFrame {
+        offset_delta: u8,
         stack: VerificationTypeInfo,
     },
     SameLocals1StackItemFrameExtended {
@@ -234,6 +265,8 @@ pub enum StackMapFrame {
     },
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum TargetInfo {
     // Tag: 0x00, 0x01
     TypeParameter(u8),
@@ -265,6 +298,8 @@ pub enum TargetInfo {
     },
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum VerificationTypeInfo {
     DoubleVariable,
     FloatVariable,
@@ -291,23 +326,37 @@ impl AccessFlags {
     pub const MODULE: u16 = 0x8000;
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Attribute<'class> {
+    // 0 for an attribute assembled by hand rather than parsed, which is stored under its standard name
+    pub name_index: u16,
     pub info: AttributeInfo<'class>,
+    // the attribute body following its name index and length, if retained while parsing
+    pub raw: Option<Cow<'class, [u8]>>,
 }
 
-pub struct Annotation {
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Annotation<'class> {
     pub type_index: u16,
-    pub element_value_pairs: Vec<ElementValuePair>,
+    pub element_value_pairs: Vec<ElementValuePair<'class>>,
+    // the encoded annotation, if retained while parsing
+    pub raw: Option<Cow<'class, [u8]>>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct BootstrapMethod {
     pub bootstrap_method_ref: u16,
     pub bootstrap_arguments: Vec<u16>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Classfile<'a> {
     pub version: Version,
-    pub constant_pool: Vec<ConstantPoolEntry<'a>>,
+    pub constant_pool: ConstantPool<'a>,
     pub access_flags: u16,
     pub this_class: u16,
     pub super_class: u16,
@@ -317,11 +366,15 @@ pub struct Classfile<'a> {
     pub attributes: Vec<Attribute<'a>>,
 }
 
-pub struct ElementValuePair {
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ElementValuePair<'class> {
     pub element_name_index: u16,
-    pub value: ElementValue,
+    pub value: ElementValue<'class>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ExceptionTableEntry {
     pub start_pc: u16,
     pub end_pc: u16,
@@ -329,6 +382,8 @@ pub struct ExceptionTableEntry {
     pub catch_type: u16,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Field<'a> {
     pub access_flags: u16,
     pub name_index: u16,
@@ -350,6 +405,8 @@ impl FieldAccessFlags {
     pub const ENUM: u16 = 0x4000;
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct InnerClass {
     pub inner_class_info_index: u16,
     pub outer_class_info_index: u16,
@@ -357,17 +414,23 @@ pub struct InnerClass {
     pub inner_class_access_flags: u16,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct LineNumber {
     pub start_pc: u16,
     pub line_number: u16,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct LocalVar {
     pub start_pc: u16,
     pub length: u16,
     pub index: u16,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct LocalVariable {
     pub start_pc: u16,
     pub length: u16,
@@ -376,6 +439,8 @@ pub struct LocalVariable {
     pub index: u16,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct LocalVariableType {
     pub start_pc: u16,
     pub length: u16,
@@ -384,18 +449,43 @@ pub struct LocalVariableType {
     pub index: u16,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Method<'a> {
     pub access_flags: u16,
     pub name_index: u16,
     pub descriptor_index: u16,
     pub attributes: Vec<Attribute<'a>>,
+    // the Code attribute left undecoded among the attributes when parsing with lazy_code
+    pub lazy_code: LazyCode<'a>,
+}
+
+pub struct MethodAccessFlags;
+
+impl MethodAccessFlags {
+    pub const PUBLIC: u16 = 0x0001;
+    pub const PRIVATE: u16 = 0x0002;
+    pub const PROTECTED: u16 = 0x0004;
+    pub const STATIC: u16 = 0x0008;
+    pub const FINAL: u16 = 0x0010;
+    pub const SYNCHRONIZED: u16 = 0x0020;
+    pub const BRIDGE: u16 = 0x0040;
+    pub const VARARGS: u16 = 0x0080;
+    pub const NATIVE: u16 = 0x0100;
+    pub const ABSTRACT: u16 = 0x0400;
+    pub const STRICT: u16 = 0x0800;
+    pub const SYNTHETIC: u16 = 0x1000;
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct MethodParameter {
     pub name_index: u16,
     pub access_flags: u16,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ModuleExports {
     pub exports_index: u16,
     pub exports_flags: u16,
@@ -417,6 +507,8 @@ impl ModuleFlags {
     pub const MANDATED: u16 = 0x8000;
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ModuleOpens {
     pub opens_index: u16,
     pub opens_flags: u16,
@@ -430,11 +522,15 @@ impl ModuleOpensFlags {
     pub const MANDATED: u16 = 0x8000;
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ModuleProvides {
     pub provides_index: u16,
     pub provides_with_indices: Vec<u16>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ModuleRequires {
     pub requires_index: u16,
     pub requires_flags: u16,
@@ -450,29 +546,39 @@ impl ModuleRequiresFlags {
     pub const MANDATED: u16 = 0x8000;
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct RecordComponent<'a> {
     pub name_index: u16,
     pub descriptor_index: u16,
     pub attributes: Vec<Attribute<'a>>,
 }
 
-pub struct TypeAnnotation {
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct TypeAnnotation<'class> {
     pub target_type: u8,
     pub target_info: TargetInfo,
     pub target_path: TypePath,
     pub type_index: u16,
-    pub element_value_pairs: Vec<ElementValuePair>,
+    pub element_value_pairs: Vec<ElementValuePair<'class>>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct TypePath {
     pub path: Vec<TypePathSegment>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct TypePathSegment {
     pub type_path_kind: u8,
     pub type_argument_index: u8,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Version {
     pub minor: u16,
     pub major: u16,
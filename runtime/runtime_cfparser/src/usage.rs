@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::ConstantPoolError;
+use crate::instruction::instructions_from_bytes;
+use crate::instruction::Instruction;
+use crate::spec::AttributeInfo;
+use crate::spec::ConstantPoolEntry;
+
+#[derive(Debug)]
+pub enum UsageError {
+    ConstantPool(ConstantPoolError),
+    MalformedCode,
+    NotCode,
+}
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConstantPool(error) => write!(f, "{error}"),
+            Self::MalformedCode => write!(f, "code does not decode"),
+            Self::NotCode => write!(f, "attribute is not a Code attribute"),
+        }
+    }
+}
+
+impl Error for UsageError {}
+
+impl From<ConstantPoolError> for UsageError {
+    fn from(error: ConstantPoolError) -> Self {
+        Self::ConstantPool(error)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FieldAccess<'a> {
+    // of the getfield, putfield, getstatic or putstatic instruction
+    pub offset: usize,
+    // the class named by the Fieldref, which need not be the one declaring the field
+    pub owner: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    pub descriptor: Cow<'a, str>,
+    pub is_static: bool,
+    pub is_write: bool,
+}
+
+// Every field read and write of a Code attribute, in code order. With `deduplicate`, only the first of the
+// accesses of the same kind to the same field is kept.
+pub fn field_accesses<'a>(
+    code: &AttributeInfo,
+    constant_pool: &ConstantPool<'a>,
+    deduplicate: bool,
+) -> Result<Vec<FieldAccess<'a>>, UsageError> {
+    let AttributeInfo::Code { code, .. } = code else {
+        return Err(UsageError::NotCode);
+    };
+    let Ok((_, instructions)) = instructions_from_bytes(code) else {
+        return Err(UsageError::MalformedCode);
+    };
+
+    let mut accesses = Vec::new();
+    let mut seen = HashSet::new();
+    for (offset, instruction) in instructions {
+        let (index, is_static, is_write) = match instruction {
+            Instruction::GetField(index) => (index, false, false),
+            Instruction::GetStatic(index) => (index, true, false),
+            Instruction::PutField(index) => (index, false, true),
+            Instruction::PutStatic(index) => (index, true, true),
+            _ => continue,
+        };
+        let access = field_access(constant_pool, index, offset, is_static, is_write)?;
+
+        if deduplicate {
+            let key = (
+                access.owner.clone(),
+                access.name.clone(),
+                access.descriptor.clone(),
+                is_static,
+                is_write,
+            );
+            if !seen.insert(key) {
+                continue;
+            }
+        }
+
+        accesses.push(access);
+    }
+
+    Ok(accesses)
+}
+
+fn field_access<'a>(
+    constant_pool: &ConstantPool<'a>,
+    index: u16,
+    offset: usize,
+    is_static: bool,
+    is_write: bool,
+) -> Result<FieldAccess<'a>, UsageError> {
+    let ConstantPoolEntry::FieldRef {
+        class_index,
+        name_and_type_index,
+    } = *constant_pool.get(index)?
+    else {
+        return Err(ConstantPoolError::UnexpectedEntry {
+            index,
+            expected: "Fieldref",
+        }
+        .into());
+    };
+    let ConstantPoolEntry::NameAndType {
+        name_index,
+        descriptor_index,
+    } = *constant_pool.get(name_and_type_index)?
+    else {
+        return Err(ConstantPoolError::UnexpectedEntry {
+            index: name_and_type_index,
+            expected: "NameAndType",
+        }
+        .into());
+    };
+
+    Ok(FieldAccess {
+        offset,
+        owner: constant_pool.class_name(class_index)?,
+        name: constant_pool.utf8(name_index)?,
+        descriptor: constant_pool.utf8(descriptor_index)?,
+        is_static,
+        is_write,
+    })
+}
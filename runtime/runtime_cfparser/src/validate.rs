@@ -0,0 +1,1155 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::slice;
+
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::ConstantPoolError;
+use crate::descriptor::field_descriptor_from_str;
+use crate::descriptor::FieldType;
+use crate::flow::validate_branch_targets;
+use crate::flow::BadBranch;
+use crate::instruction::instruction_boundaries;
+use crate::instruction::instructions_from_bytes;
+use crate::instruction::Instruction;
+use crate::spec::AccessFlags;
+use crate::spec::Annotation;
+use crate::spec::Attribute;
+use crate::spec::AttributeInfo;
+use crate::spec::Classfile;
+use crate::spec::ConstantPoolEntry;
+use crate::spec::ElementValue;
+use crate::spec::ElementValuePair;
+use crate::spec::Field;
+use crate::spec::StackMapFrame;
+use crate::spec::VerificationTypeInfo;
+use crate::spec::Version;
+
+// what an IntegrityIssue is about, in the order integrity_check looks for them
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IntegrityCategory {
+    Structure,
+    DanglingReference,
+    MalformedCode,
+    BranchTarget,
+    StackMapFrame,
+    Version,
+}
+
+#[derive(Debug)]
+pub enum ValidationError {
+    CodeOnAbstractMethod {
+        method: String,
+    },
+    DuplicateCode {
+        method: String,
+    },
+    InvalidConstantPoolIndex {
+        index: u16,
+    },
+    InvalidSuperClass {
+        class: String,
+        index: u16,
+        error: ConstantPoolError,
+    },
+    InvalidThisClass {
+        index: u16,
+        error: ConstantPoolError,
+    },
+    InvalidUtf8 {
+        index: u16,
+    },
+    MalformedCode {
+        method: String,
+    },
+    MalformedDescriptor {
+        descriptor: String,
+    },
+    MethodDescriptorOnField {
+        descriptor: String,
+    },
+    MissingCode {
+        method: String,
+    },
+    MissingModuleAttribute,
+    ModuleEntryOutsideModule {
+        index: u16,
+    },
+    UnexpectedConstantPoolEntry {
+        index: u16,
+        expected: &'static str,
+    },
+    VoidField,
+}
+
+// A constant pool entry or instruction the class file format only allows from some version on; the versions
+// of the entries are those of table 4.4-B of the JVMS.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VersionedFeature {
+    DynamicEntry { index: u16 },
+    // invokestatic or invokespecial of an InterfaceMethodref, to call a static or private interface method
+    InterfaceMethodInvocation { method: String, offset: usize },
+    InvokeDynamicEntry { index: u16 },
+    InvokeDynamicInstruction { method: String, offset: usize },
+    MethodHandleEntry { index: u16 },
+    MethodTypeEntry { index: u16 },
+    ModuleEntry { index: u16 },
+    PackageEntry { index: u16 },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CodeOnAbstractMethod { method } => {
+                write!(
+                    f,
+                    "abstract or native method `{method}` has a Code attribute"
+                )
+            }
+            Self::DuplicateCode { method } => {
+                write!(f, "method `{method}` has more than one Code attribute")
+            }
+            Self::InvalidConstantPoolIndex { index } => {
+                write!(f, "constant pool index {index} is out of range")
+            }
+            Self::InvalidSuperClass {
+                class,
+                index,
+                error,
+            } => {
+                write!(
+                    f,
+                    "super_class {index} of `{class}` is not a valid Class entry: {error}"
+                )
+            }
+            Self::InvalidThisClass { index, error } => {
+                write!(f, "this_class {index} is not a valid Class entry: {error}")
+            }
+            Self::InvalidUtf8 { index } => {
+                write!(f, "constant pool entry {index} is not valid modified UTF-8")
+            }
+            Self::MalformedCode { method } => {
+                write!(f, "the code of method `{method}` could not be decoded")
+            }
+            Self::MalformedDescriptor { descriptor } => {
+                write!(f, "malformed descriptor `{descriptor}`")
+            }
+            Self::MethodDescriptorOnField { descriptor } => {
+                write!(f, "field has a method descriptor `{descriptor}`")
+            }
+            Self::MissingCode { method } => {
+                write!(
+                    f,
+                    "method `{method}` is neither abstract nor native but has no Code attribute"
+                )
+            }
+            Self::MissingModuleAttribute => {
+                write!(f, "module-info class has no Module attribute")
+            }
+            Self::ModuleEntryOutsideModule { index } => {
+                write!(
+                    f,
+                    "constant pool entry {index} is a Module or Package entry outside a module-info class"
+                )
+            }
+            Self::UnexpectedConstantPoolEntry { index, expected } => {
+                write!(f, "constant pool entry {index} is not a {expected} entry")
+            }
+            Self::VoidField => write!(f, "field has a void descriptor"),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+impl From<ConstantPoolError> for ValidationError {
+    fn from(error: ConstantPoolError) -> Self {
+        match error {
+            ConstantPoolError::InvalidIndex { index } => Self::InvalidConstantPoolIndex { index },
+            ConstantPoolError::InvalidUtf8 { index } => Self::InvalidUtf8 { index },
+            ConstantPoolError::UnexpectedEntry { index, expected } => {
+                Self::UnexpectedConstantPoolEntry { index, expected }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DanglingRef {
+    // where the index was found, such as `methods[2].attributes[0].exception_table[1].catch_type`
+    pub location: String,
+    pub error: ConstantPoolError,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IntegrityIssue {
+    pub category: IntegrityCategory,
+    // where the issue is, such as `methods[2].attributes[0]`, or `class` for the class as a whole
+    pub location: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RequiredVersion {
+    // the lowest version allowing every feature found, or 45.0 if there are none
+    pub version: Version,
+    // constant pool entries in index order, then instructions in method and code order
+    pub features: Vec<VersionedFeature>,
+}
+
+// collects the dangling references found while walking a classfile
+struct DanglingRefs<'p, 'a> {
+    constant_pool: &'p ConstantPool<'a>,
+    found: Vec<DanglingRef>,
+}
+
+impl<'a> Classfile<'a> {
+    pub fn dangling_references(&self) -> Vec<DanglingRef> {
+        let mut refs = DanglingRefs {
+            constant_pool: &self.constant_pool,
+            found: Vec::new(),
+        };
+
+        refs.check("this_class".to_string(), self.this_class, "Class");
+        refs.check_optional("super_class".to_string(), self.super_class, "Class");
+        for (i, &interface) in self.interfaces.iter().enumerate() {
+            refs.check(format!("interfaces[{i}]"), interface, "Class");
+        }
+
+        for (i, field) in self.fields.iter().enumerate() {
+            let location = format!("fields[{i}]");
+            refs.check(format!("{location}.name_index"), field.name_index, "Utf8");
+            refs.check(
+                format!("{location}.descriptor_index"),
+                field.descriptor_index,
+                "Utf8",
+            );
+            refs.attributes(&location, &field.attributes);
+        }
+
+        for (i, method) in self.methods.iter().enumerate() {
+            let location = format!("methods[{i}]");
+            refs.check(format!("{location}.name_index"), method.name_index, "Utf8");
+            refs.check(
+                format!("{location}.descriptor_index"),
+                method.descriptor_index,
+                "Utf8",
+            );
+            refs.attributes(&location, &method.attributes);
+        }
+
+        refs.attributes("", &self.attributes);
+
+        refs.found
+    }
+
+    // Runs every check this module has and collects what they find: the structural checks of validate, which
+    // stop at the first problem, dangling constant pool references, code that does not decode, branches and
+    // exception handlers that miss instruction boundaries, stack map frames that do the same, and entries or
+    // instructions the declared version does not allow yet. Attribute lengths are not checked, as a class
+    // file whose attribute lengths do not match their contents fails to parse in the first place.
+    pub fn integrity_check(&self) -> Result<(), Vec<IntegrityIssue>> {
+        let mut issues = Vec::new();
+        let mut issue = |category, location: String, message: String| {
+            issues.push(IntegrityIssue {
+                category,
+                location,
+                message,
+            });
+        };
+
+        if let Err(error) = self.validate() {
+            issue(
+                IntegrityCategory::Structure,
+                "class".to_string(),
+                error.to_string(),
+            );
+        }
+
+        for reference in self.dangling_references() {
+            issue(
+                IntegrityCategory::DanglingReference,
+                reference.location,
+                reference.error.to_string(),
+            );
+        }
+
+        for (i, method) in self.methods.iter().enumerate() {
+            for (j, attribute) in method.attributes.iter().enumerate() {
+                // a Code attribute that parsing with lazy_code left undecoded is checked in its decoded form
+                let attribute = match (&attribute.info, method.code()) {
+                    (AttributeInfo::Unknown { name_index, .. }, Some(code))
+                        if code.name_index == *name_index =>
+                    {
+                        code
+                    }
+                    _ => attribute,
+                };
+                let AttributeInfo::Code {
+                    code, attributes, ..
+                } = &attribute.info
+                else {
+                    continue;
+                };
+                let location = format!("methods[{i}].attributes[{j}]");
+
+                let bad_branches = match validate_branch_targets(&attribute.info) {
+                    Ok(bad_branches) => bad_branches,
+                    Err(error) => {
+                        issue(
+                            IntegrityCategory::MalformedCode,
+                            location,
+                            error.to_string(),
+                        );
+                        continue;
+                    }
+                };
+                for bad_branch in bad_branches {
+                    issue(
+                        IntegrityCategory::BranchTarget,
+                        location.clone(),
+                        bad_branch_message(&bad_branch),
+                    );
+                }
+
+                // the code decoded above, so its boundaries do too
+                let boundaries = instruction_boundaries(code, 0).unwrap_or_default();
+                for (k, attribute) in attributes.iter().enumerate() {
+                    let AttributeInfo::StackMapTable { entries } = &attribute.info else {
+                        continue;
+                    };
+
+                    for (frame, offset) in stack_map_frame_offsets(entries).enumerate() {
+                        if boundaries.binary_search(&offset).is_err() {
+                            issue(
+                                IntegrityCategory::StackMapFrame,
+                                format!("{location}.attributes[{k}]"),
+                                format!(
+                                    "frame {frame} at offset {offset} does not start at an instruction"
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        // code that does not decode was reported above, and keeps the features from being found
+        if let Ok(required) = min_version_for_features(self, &self.constant_pool) {
+            for feature in required.features {
+                let version = feature.version();
+                if version.major > self.version.major {
+                    issue(
+                        IntegrityCategory::Version,
+                        "class".to_string(),
+                        format!(
+                            "{feature} needs class file version {}.{}, but the class declares {}.{}",
+                            version.major, version.minor, self.version.major, self.version.minor
+                        ),
+                    );
+                }
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    // Checks that this_class is a Class entry and super_class is either 0 or one, that Module and Package
+    // entries only appear in a module-info class, which has a Module attribute, and that abstract and native
+    // methods have no Code attribute while every other method has exactly one.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let class = self
+            .constant_pool
+            .class_name(self.this_class)
+            .map_err(|error| ValidationError::InvalidThisClass {
+                index: self.this_class,
+                error,
+            })?;
+        if self.super_class != 0 {
+            if let Err(error) = self.constant_pool.class_name(self.super_class) {
+                return Err(ValidationError::InvalidSuperClass {
+                    class: class.into_owned(),
+                    index: self.super_class,
+                    error,
+                });
+            }
+        }
+
+        if self.access_flags & AccessFlags::MODULE != 0 {
+            let has_module = self
+                .attributes
+                .iter()
+                .any(|attribute| matches!(attribute.info, AttributeInfo::Module { .. }));
+            if !has_module {
+                return Err(ValidationError::MissingModuleAttribute);
+            }
+        } else if let Some((index, _)) =
+            (1..).zip(self.constant_pool.entries()).find(|(_, entry)| {
+                matches!(
+                    entry,
+                    ConstantPoolEntry::Module { .. } | ConstantPoolEntry::Package { .. }
+                )
+            })
+        {
+            return Err(ValidationError::ModuleEntryOutsideModule { index });
+        }
+
+        for method in &self.methods {
+            // a Code attribute that parsing with lazy_code left undecoded is only found through Method::code
+            let code_count = match method.code() {
+                Some(_) => method
+                    .attributes
+                    .iter()
+                    .filter(|attribute| matches!(attribute.info, AttributeInfo::Code { .. }))
+                    .count()
+                    .max(1),
+                None => 0,
+            };
+            let expected_count = if method.is_abstract() || method.is_native() {
+                0
+            } else {
+                1
+            };
+
+            if code_count == expected_count {
+                continue;
+            }
+
+            let method = self.constant_pool.utf8(method.name_index)?.into_owned();
+            return Err(match (expected_count, code_count) {
+                (0, _) => ValidationError::CodeOnAbstractMethod { method },
+                (_, 0) => ValidationError::MissingCode { method },
+                _ => ValidationError::DuplicateCode { method },
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Field<'a> {
+    pub fn validate_descriptor(
+        &self,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<FieldType, ValidationError> {
+        let descriptor = constant_pool.utf8(self.descriptor_index)?;
+
+        if descriptor.contains('(') {
+            return Err(ValidationError::MethodDescriptorOnField {
+                descriptor: descriptor.into_owned(),
+            });
+        }
+
+        if descriptor == "V" {
+            return Err(ValidationError::VoidField);
+        }
+
+        field_descriptor_from_str(&descriptor).ok_or_else(|| ValidationError::MalformedDescriptor {
+            descriptor: descriptor.into_owned(),
+        })
+    }
+}
+
+impl RequiredVersion {
+    // the features that need `version` itself, and so forced the minimum
+    pub fn forced_by(&self) -> impl Iterator<Item = &VersionedFeature> {
+        self.features
+            .iter()
+            .filter(|feature| feature.version() == self.version)
+    }
+}
+
+impl VersionedFeature {
+    pub fn version(&self) -> Version {
+        let major = match self {
+            Self::InvokeDynamicEntry { .. }
+            | Self::InvokeDynamicInstruction { .. }
+            | Self::MethodHandleEntry { .. }
+            | Self::MethodTypeEntry { .. } => 51,
+            Self::InterfaceMethodInvocation { .. } => 52,
+            Self::ModuleEntry { .. } | Self::PackageEntry { .. } => 53,
+            Self::DynamicEntry { .. } => 55,
+        };
+
+        Version { minor: 0, major }
+    }
+}
+
+impl Display for VersionedFeature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DynamicEntry { index } => write!(f, "Dynamic constant pool entry {index}"),
+            Self::InterfaceMethodInvocation { method, offset } => {
+                write!(
+                    f,
+                    "invocation of an interface method at offset {offset} of method `{method}`"
+                )
+            }
+            Self::InvokeDynamicEntry { index } => {
+                write!(f, "InvokeDynamic constant pool entry {index}")
+            }
+            Self::InvokeDynamicInstruction { method, offset } => {
+                write!(f, "invokedynamic at offset {offset} of method `{method}`")
+            }
+            Self::MethodHandleEntry { index } => {
+                write!(f, "MethodHandle constant pool entry {index}")
+            }
+            Self::MethodTypeEntry { index } => write!(f, "MethodType constant pool entry {index}"),
+            Self::ModuleEntry { index } => write!(f, "Module constant pool entry {index}"),
+            Self::PackageEntry { index } => write!(f, "Package constant pool entry {index}"),
+        }
+    }
+}
+
+impl<'p, 'a> DanglingRefs<'p, 'a> {
+    fn annotation(&mut self, location: &str, annotation: &Annotation) {
+        self.check(
+            format!("{location}.type_index"),
+            annotation.type_index,
+            "Utf8",
+        );
+        self.element_value_pairs(location, &annotation.element_value_pairs);
+    }
+
+    fn attributes(&mut self, location: &str, attributes: &[Attribute<'a>]) {
+        // the root of the classfile has no location of its own
+        let prefix = if location.is_empty() {
+            String::new()
+        } else {
+            format!("{location}.")
+        };
+
+        for (i, attribute) in attributes.iter().enumerate() {
+            let location = format!("{prefix}attributes[{i}]");
+
+            match &attribute.info {
+                AttributeInfo::AnnotationDefault { default_value } => {
+                    self.element_value(&format!("{location}.default_value"), default_value);
+                }
+                AttributeInfo::BootstrapMethods { bootstrap_methods } => {
+                    for (j, bootstrap_method) in bootstrap_methods.iter().enumerate() {
+                        let location = format!("{location}.bootstrap_methods[{j}]");
+                        self.check(
+                            format!("{location}.bootstrap_method_ref"),
+                            bootstrap_method.bootstrap_method_ref,
+                            "MethodHandle",
+                        );
+                        for (k, &argument) in
+                            bootstrap_method.bootstrap_arguments.iter().enumerate()
+                        {
+                            self.check(
+                                format!("{location}.bootstrap_arguments[{k}]"),
+                                argument,
+                                "loadable",
+                            );
+                        }
+                    }
+                }
+                AttributeInfo::Code {
+                    exception_table,
+                    attributes,
+                    ..
+                } => {
+                    for (j, entry) in exception_table.iter().enumerate() {
+                        self.check_optional(
+                            format!("{location}.exception_table[{j}].catch_type"),
+                            entry.catch_type,
+                            "Class",
+                        );
+                    }
+                    self.attributes(&location, attributes);
+                }
+                AttributeInfo::ConstantValue {
+                    constantvalue_index,
+                } => {
+                    self.check(
+                        format!("{location}.constantvalue_index"),
+                        *constantvalue_index,
+                        "ConstantValue",
+                    );
+                }
+                AttributeInfo::EnclosingMethod {
+                    class_index,
+                    method_index,
+                } => {
+                    self.check(format!("{location}.class_index"), *class_index, "Class");
+                    self.check_optional(
+                        format!("{location}.method_index"),
+                        *method_index,
+                        "NameAndType",
+                    );
+                }
+                AttributeInfo::Exceptions {
+                    exception_index_table,
+                } => {
+                    self.checks(
+                        &format!("{location}.exception_index_table"),
+                        exception_index_table,
+                        "Class",
+                    );
+                }
+                AttributeInfo::InnerClasses { classes } => {
+                    for (j, class) in classes.iter().enumerate() {
+                        let location = format!("{location}.classes[{j}]");
+                        self.check(
+                            format!("{location}.inner_class_info_index"),
+                            class.inner_class_info_index,
+                            "Class",
+                        );
+                        self.check_optional(
+                            format!("{location}.outer_class_info_index"),
+                            class.outer_class_info_index,
+                            "Class",
+                        );
+                        self.check_optional(
+                            format!("{location}.inner_name_index"),
+                            class.inner_name_index,
+                            "Utf8",
+                        );
+                    }
+                }
+                AttributeInfo::LocalVariableTable {
+                    local_variable_table,
+                } => {
+                    for (j, variable) in local_variable_table.iter().enumerate() {
+                        let location = format!("{location}.local_variable_table[{j}]");
+                        self.check(
+                            format!("{location}.name_index"),
+                            variable.name_index,
+                            "Utf8",
+                        );
+                        self.check(
+                            format!("{location}.descriptor_index"),
+                            variable.descriptor_index,
+                            "Utf8",
+                        );
+                    }
+                }
+                AttributeInfo::LocalVariableTypeTable {
+                    local_variable_type_table,
+                } => {
+                    for (j, variable) in local_variable_type_table.iter().enumerate() {
+                        let location = format!("{location}.local_variable_type_table[{j}]");
+                        self.check(
+                            format!("{location}.name_index"),
+                            variable.name_index,
+                            "Utf8",
+                        );
+                        self.check(
+                            format!("{location}.descriptor_index"),
+                            variable.descriptor_index,
+                            "Utf8",
+                        );
+                    }
+                }
+                AttributeInfo::MethodParameters { parameters } => {
+                    for (j, parameter) in parameters.iter().enumerate() {
+                        self.check_optional(
+                            format!("{location}.parameters[{j}].name_index"),
+                            parameter.name_index,
+                            "Utf8",
+                        );
+                    }
+                }
+                AttributeInfo::Module {
+                    module_name_index,
+                    module_version_index,
+                    requires,
+                    exports,
+                    opens,
+                    uses,
+                    provides,
+                    ..
+                } => {
+                    self.check(
+                        format!("{location}.module_name_index"),
+                        *module_name_index,
+                        "Module",
+                    );
+                    self.check_optional(
+                        format!("{location}.module_version_index"),
+                        *module_version_index,
+                        "Utf8",
+                    );
+                    for (j, require) in requires.iter().enumerate() {
+                        let location = format!("{location}.requires[{j}]");
+                        self.check(
+                            format!("{location}.requires_index"),
+                            require.requires_index,
+                            "Module",
+                        );
+                        self.check_optional(
+                            format!("{location}.requires_version_index"),
+                            require.requires_version_index,
+                            "Utf8",
+                        );
+                    }
+                    for (j, export) in exports.iter().enumerate() {
+                        let location = format!("{location}.exports[{j}]");
+                        self.check(
+                            format!("{location}.exports_index"),
+                            export.exports_index,
+                            "Package",
+                        );
+                        self.checks(
+                            &format!("{location}.exports_to_indices"),
+                            &export.exports_to_indices,
+                            "Module",
+                        );
+                    }
+                    for (j, open) in opens.iter().enumerate() {
+                        let location = format!("{location}.opens[{j}]");
+                        self.check(
+                            format!("{location}.opens_index"),
+                            open.opens_index,
+                            "Package",
+                        );
+                        self.checks(
+                            &format!("{location}.opens_to_indices"),
+                            &open.opens_to_indices,
+                            "Module",
+                        );
+                    }
+                    self.checks(&format!("{location}.uses"), uses, "Class");
+                    for (j, provide) in provides.iter().enumerate() {
+                        let location = format!("{location}.provides[{j}]");
+                        self.check(
+                            format!("{location}.provides_index"),
+                            provide.provides_index,
+                            "Class",
+                        );
+                        self.checks(
+                            &format!("{location}.provides_with_indices"),
+                            &provide.provides_with_indices,
+                            "Class",
+                        );
+                    }
+                }
+                AttributeInfo::ModuleMainClass { main_class_index } => {
+                    self.check(
+                        format!("{location}.main_class_index"),
+                        *main_class_index,
+                        "Class",
+                    );
+                }
+                AttributeInfo::ModulePackages { package_index } => {
+                    self.checks(
+                        &format!("{location}.package_index"),
+                        package_index,
+                        "Package",
+                    );
+                }
+                AttributeInfo::NestHost { host_class_index } => {
+                    self.check(
+                        format!("{location}.host_class_index"),
+                        *host_class_index,
+                        "Class",
+                    );
+                }
+                AttributeInfo::NestMembers { classes }
+                | AttributeInfo::PermittedSubclasses { classes } => {
+                    self.checks(&format!("{location}.classes"), classes, "Class");
+                }
+                AttributeInfo::Record { components } => {
+                    for (j, component) in components.iter().enumerate() {
+                        let location = format!("{location}.components[{j}]");
+                        self.check(
+                            format!("{location}.name_index"),
+                            component.name_index,
+                            "Utf8",
+                        );
+                        self.check(
+                            format!("{location}.descriptor_index"),
+                            component.descriptor_index,
+                            "Utf8",
+                        );
+                        self.attributes(&location, &component.attributes);
+                    }
+                }
+                AttributeInfo::RuntimeInvisibleAnnotations { annotations }
+                | AttributeInfo::RuntimeVisibleAnnotations { annotations } => {
+                    for (j, annotation) in annotations.iter().enumerate() {
+                        self.annotation(&format!("{location}.annotations[{j}]"), annotation);
+                    }
+                }
+                AttributeInfo::RuntimeInvisibleParameterAnnotations {
+                    parameter_annotations,
+                }
+                | AttributeInfo::RuntimeVisibleParameterAnnotations {
+                    parameter_annotations,
+                } => {
+                    for (j, annotation) in parameter_annotations.iter().enumerate() {
+                        self.annotation(
+                            &format!("{location}.parameter_annotations[{j}]"),
+                            annotation,
+                        );
+                    }
+                }
+                AttributeInfo::RuntimeInvisibleTypeAnnotations { type_annotations }
+                | AttributeInfo::RuntimeVisibleTypeAnnotations { type_annotations } => {
+                    for (j, type_annotation) in type_annotations.iter().enumerate() {
+                        let location = format!("{location}.type_annotations[{j}]");
+                        self.check(
+                            format!("{location}.type_index"),
+                            type_annotation.type_index,
+                            "Utf8",
+                        );
+                        self.element_value_pairs(&location, &type_annotation.element_value_pairs);
+                    }
+                }
+                AttributeInfo::Signature { signature_index } => {
+                    self.check(
+                        format!("{location}.signature_index"),
+                        *signature_index,
+                        "Utf8",
+                    );
+                }
+                AttributeInfo::SourceFile { sourcefile_index } => {
+                    self.check(
+                        format!("{location}.sourcefile_index"),
+                        *sourcefile_index,
+                        "Utf8",
+                    );
+                }
+                AttributeInfo::StackMapTable { entries } => {
+                    for (j, entry) in entries.iter().enumerate() {
+                        let location = format!("{location}.entries[{j}]");
+                        match entry {
+                            StackMapFrame::AppendFrame { locals, .. } => {
+                                self.verification_types(&format!("{location}.locals"), locals);
+                            }
+                            StackMapFrame::FullFrame { locals, stack, .. } => {
+                                self.verification_types(&format!("{location}.locals"), locals);
+                                self.verification_types(&format!("{location}.stack"), stack);
+                            }
+                            StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+                            | StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => {
+                                self.verification_types(
+                                    &format!("{location}.stack"),
+                                    slice::from_ref(stack),
+                                );
+                            }
+                            StackMapFrame::ChopFrame { .. }
+                            | StackMapFrame::SameFrame { .. }
+                            | StackMapFrame::SameFrameExtended { .. } => {}
+                        }
+                    }
+                }
+                // the names of recognised attributes were resolved while parsing
+                AttributeInfo::Unknown { name_index, .. } => {
+                    self.check(format!("{location}.name_index"), *name_index, "Utf8");
+                }
+                AttributeInfo::Deprecated
+                | AttributeInfo::LineNumberTable { .. }
+                | AttributeInfo::SourceDebugExtension { .. }
+                | AttributeInfo::Synthetic => {}
+            }
+        }
+    }
+
+    fn check(&mut self, location: String, index: u16, expected: &'static str) {
+        let error = match self.constant_pool.get(index) {
+            Ok(entry) if entry_has_kind(entry, expected) => return,
+            Ok(_) => ConstantPoolError::UnexpectedEntry { index, expected },
+            Err(error) => error,
+        };
+
+        self.found.push(DanglingRef { location, error });
+    }
+
+    // for indices where 0 stands for the absence of a reference
+    fn check_optional(&mut self, location: String, index: u16, expected: &'static str) {
+        if index != 0 {
+            self.check(location, index, expected);
+        }
+    }
+
+    fn checks(&mut self, location: &str, indices: &[u16], expected: &'static str) {
+        for (i, &index) in indices.iter().enumerate() {
+            self.check(format!("{location}[{i}]"), index, expected);
+        }
+    }
+
+    fn element_value(&mut self, location: &str, element_value: &ElementValue) {
+        match element_value {
+            ElementValue::Annotation(annotation) => {
+                self.annotation(&format!("{location}.annotation_value"), annotation);
+            }
+            ElementValue::ClassInfo(class_info_index) => {
+                self.check(
+                    format!("{location}.class_info_index"),
+                    *class_info_index,
+                    "Utf8",
+                );
+            }
+            ElementValue::ConstValue {
+                tag,
+                const_value_index,
+            } => {
+                let expected = match tag {
+                    b'D' => "Double",
+                    b'F' => "Float",
+                    b'J' => "Long",
+                    b's' => "Utf8",
+                    _ => "Integer",
+                };
+                self.check(
+                    format!("{location}.const_value_index"),
+                    *const_value_index,
+                    expected,
+                );
+            }
+            ElementValue::EnumConst {
+                type_name_index,
+                const_name_index,
+            } => {
+                self.check(
+                    format!("{location}.type_name_index"),
+                    *type_name_index,
+                    "Utf8",
+                );
+                self.check(
+                    format!("{location}.const_name_index"),
+                    *const_name_index,
+                    "Utf8",
+                );
+            }
+            ElementValue::Array { values } => {
+                for (i, value) in values.iter().enumerate() {
+                    self.element_value(&format!("{location}.values[{i}]"), value);
+                }
+            }
+        }
+    }
+
+    fn element_value_pairs(&mut self, location: &str, pairs: &[ElementValuePair]) {
+        for (i, pair) in pairs.iter().enumerate() {
+            let location = format!("{location}.element_value_pairs[{i}]");
+            self.check(
+                format!("{location}.element_name_index"),
+                pair.element_name_index,
+                "Utf8",
+            );
+            self.element_value(&format!("{location}.value"), &pair.value);
+        }
+    }
+
+    fn verification_types(&mut self, location: &str, types: &[VerificationTypeInfo]) {
+        for (i, verification_type) in types.iter().enumerate() {
+            if let VerificationTypeInfo::ObjectVariable(cpool_index) = verification_type {
+                self.check(format!("{location}[{i}]"), *cpool_index, "Class");
+            }
+        }
+    }
+}
+
+// Finds the constant pool entries and instructions of the class that need a later class file version than
+// the first, and the lowest version allowing all of them. A class declaring an older version than that is
+// rejected by the JVM, which usually means a transformation added them without raising the version.
+pub fn min_version_for_features(
+    classfile: &Classfile,
+    constant_pool: &ConstantPool,
+) -> Result<RequiredVersion, ValidationError> {
+    let mut features = Vec::new();
+    for (index, entry) in (1..).zip(constant_pool.entries()) {
+        features.extend(match entry {
+            ConstantPoolEntry::Dynamic { .. } => Some(VersionedFeature::DynamicEntry { index }),
+            ConstantPoolEntry::InvokeDynamic { .. } => {
+                Some(VersionedFeature::InvokeDynamicEntry { index })
+            }
+            ConstantPoolEntry::MethodHandle { .. } => {
+                Some(VersionedFeature::MethodHandleEntry { index })
+            }
+            ConstantPoolEntry::MethodType { .. } => {
+                Some(VersionedFeature::MethodTypeEntry { index })
+            }
+            ConstantPoolEntry::Module { .. } => Some(VersionedFeature::ModuleEntry { index }),
+            ConstantPoolEntry::Package { .. } => Some(VersionedFeature::PackageEntry { index }),
+            _ => None,
+        });
+    }
+
+    for method in &classfile.methods {
+        let Some(AttributeInfo::Code { code, .. }) = method.code().map(|code| &code.info) else {
+            continue;
+        };
+        let name = constant_pool.utf8(method.name_index)?;
+        let Ok((_, instructions)) = instructions_from_bytes(code) else {
+            return Err(ValidationError::MalformedCode {
+                method: name.into_owned(),
+            });
+        };
+
+        for (offset, instruction) in instructions {
+            match instruction {
+                Instruction::InvokeDynamic(_) => {
+                    features.push(VersionedFeature::InvokeDynamicInstruction {
+                        method: name.to_string(),
+                        offset,
+                    });
+                }
+                Instruction::InvokeSpecial(index) | Instruction::InvokeStatic(index)
+                    if matches!(
+                        constant_pool.get(index),
+                        Ok(ConstantPoolEntry::InstanceMethodRef { .. })
+                    ) =>
+                {
+                    features.push(VersionedFeature::InterfaceMethodInvocation {
+                        method: name.to_string(),
+                        offset,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let version = features
+        .iter()
+        .map(VersionedFeature::version)
+        .max_by_key(|version| version.major)
+        .unwrap_or(Version {
+            minor: 0,
+            major: 45,
+        });
+
+    Ok(RequiredVersion { version, features })
+}
+
+fn bad_branch_message(bad_branch: &BadBranch) -> String {
+    match bad_branch {
+        BadBranch::Branch { offset, target } => format!(
+            "the instruction at offset {offset} branches to {target}, not to an instruction"
+        ),
+        BadBranch::ExceptionTable { entry, pc } => {
+            format!("exception table entry {entry} has the pc {pc}, not that of an instruction")
+        }
+        BadBranch::FallsOffEnd { offset } => {
+            format!("execution continues past the end of the code after offset {offset}")
+        }
+    }
+}
+
+// `expected` is either the name of an entry kind, or one of the groups of kinds the JVMS allows in some places
+fn entry_has_kind(entry: &ConstantPoolEntry, expected: &str) -> bool {
+    match entry {
+        ConstantPoolEntry::Utf8 { .. } => expected == "Utf8",
+        ConstantPoolEntry::Integer { .. } => {
+            matches!(expected, "Integer" | "ConstantValue" | "loadable")
+        }
+        ConstantPoolEntry::Float { .. } => {
+            matches!(expected, "Float" | "ConstantValue" | "loadable")
+        }
+        ConstantPoolEntry::Long { .. } => {
+            matches!(expected, "Long" | "ConstantValue" | "loadable")
+        }
+        ConstantPoolEntry::Double { .. } => {
+            matches!(expected, "Double" | "ConstantValue" | "loadable")
+        }
+        ConstantPoolEntry::Class { .. } => matches!(expected, "Class" | "loadable"),
+        ConstantPoolEntry::String { .. } => {
+            matches!(expected, "String" | "ConstantValue" | "loadable")
+        }
+        ConstantPoolEntry::FieldRef { .. } => expected == "Fieldref",
+        ConstantPoolEntry::MethodRef { .. } => expected == "Methodref",
+        ConstantPoolEntry::InstanceMethodRef { .. } => expected == "InterfaceMethodref",
+        ConstantPoolEntry::NameAndType { .. } => expected == "NameAndType",
+        ConstantPoolEntry::MethodHandle { .. } => matches!(expected, "MethodHandle" | "loadable"),
+        ConstantPoolEntry::MethodType { .. } => matches!(expected, "MethodType" | "loadable"),
+        ConstantPoolEntry::Dynamic { .. } => matches!(expected, "Dynamic" | "loadable"),
+        ConstantPoolEntry::InvokeDynamic { .. } => expected == "InvokeDynamic",
+        ConstantPoolEntry::Module { .. } => expected == "Module",
+        ConstantPoolEntry::Package { .. } => expected == "Package",
+        ConstantPoolEntry::Unusable => false,
+    }
+}
+
+// the first frame applies at its offset_delta, and each later one offset_delta + 1 bytes past the one before
+fn stack_map_frame_offsets(frames: &[StackMapFrame]) -> impl Iterator<Item = usize> + '_ {
+    frames
+        .iter()
+        .scan(None, |previous: &mut Option<usize>, frame| {
+            let offset_delta = match *frame {
+                StackMapFrame::SameFrame { offset_delta }
+                | StackMapFrame::SameLocals1StackItemFrame { offset_delta, .. } => {
+                    offset_delta.into()
+                }
+                StackMapFrame::AppendFrame { offset_delta, .. }
+                | StackMapFrame::ChopFrame { offset_delta, .. }
+                | StackMapFrame::FullFrame { offset_delta, .. }
+                | StackMapFrame::SameFrameExtended { offset_delta }
+                | StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. } => {
+                    offset_delta as usize
+                }
+            };
+            let offset = previous.map_or(offset_delta, |previous| previous + offset_delta + 1);
+            *previous = Some(offset);
+
+            Some(offset)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::classfile_from_bytes_with_options;
+    use crate::parse::ParseOptions;
+    use crate::spec::Version;
+    use crate::write::ClassBuilder;
+    use crate::write::MethodBuilder;
+
+    use super::IntegrityCategory;
+
+    #[test]
+    fn lazy_code_is_validated() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 50,
+            },
+            0x21,
+            "Lazy",
+            Some("java/lang/Object"),
+        );
+        let mut run = MethodBuilder::new(0x8, "run", "()V");
+        run.code(0, 0, vec![0x00, 0xB1]);
+        class.add_method(run).unwrap();
+        // a goto past the end of the code
+        let mut jump = MethodBuilder::new(0x8, "jump", "()V");
+        jump.code(0, 0, vec![0xA7, 0x00, 0x05, 0xB1]);
+        class.add_method(jump).unwrap();
+        let bytes = class.build().unwrap();
+
+        let options = ParseOptions {
+            lazy_code: true,
+            ..ParseOptions::default()
+        };
+        let (_, classfile) = classfile_from_bytes_with_options(&bytes, &options).unwrap();
+        assert!(classfile.validate().is_ok());
+
+        let issues = classfile.integrity_check().unwrap_err();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].category, IntegrityCategory::BranchTarget);
+        assert_eq!(issues[0].location, "methods[1].attributes[0]");
+    }
+}
@@ -0,0 +1,2011 @@
+/*
+ * Copyright (c) 2024 The Caffeine Project Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::constant_pool::ConstantPool;
+use crate::constant_pool::ConstantPoolError;
+use crate::descriptor::method_descriptor_from_str;
+use crate::descriptor::FieldType;
+use crate::instruction::instruction_boundaries;
+use crate::instruction::InstructionError;
+use crate::mutf8::str_to_mutf8;
+use crate::parse::LazyCode;
+use crate::spec::Annotation;
+use crate::spec::Attribute;
+use crate::spec::AttributeInfo;
+use crate::spec::Classfile;
+use crate::spec::ConstantPoolEntry;
+use crate::spec::ElementValue;
+use crate::spec::ElementValuePair;
+use crate::spec::ExceptionTableEntry;
+use crate::spec::Field;
+use crate::spec::Method;
+use crate::spec::MethodAccessFlags;
+use crate::spec::StackMapFrame;
+use crate::spec::TargetInfo;
+use crate::spec::TypeAnnotation;
+use crate::spec::VerificationTypeInfo;
+use crate::spec::Version;
+
+// the first class file version whose verifier requires a StackMapTable for every method with code
+const STACK_MAP_TABLE_MAJOR_VERSION: u16 = 51;
+
+#[derive(Debug)]
+pub enum WriteError {
+    BranchOutOfRange { offset: usize, target: i64 },
+    ConstantPool(ConstantPoolError),
+    Instruction(InstructionError),
+    MalformedAnnotation,
+    MalformedDescriptor { descriptor: String },
+    MissingAttributeName { name: &'static str },
+    PoolLimitExceeded { slots: usize },
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BranchOutOfRange { offset, target } => {
+                write!(
+                    f,
+                    "branch at offset {offset} targets {target}, outside the code array"
+                )
+            }
+            Self::ConstantPool(error) => write!(f, "{error}"),
+            Self::Instruction(error) => write!(f, "{error}"),
+            Self::MalformedAnnotation => write!(f, "malformed annotation"),
+            Self::MalformedDescriptor { descriptor } => {
+                write!(f, "malformed descriptor `{descriptor}`")
+            }
+            Self::MissingAttributeName { name } => {
+                write!(
+                    f,
+                    "constant pool has no Utf8 entry for attribute name `{name}`"
+                )
+            }
+            Self::PoolLimitExceeded { slots } => {
+                write!(
+                    f,
+                    "constant pool needs {slots} indices, more than a class file can address"
+                )
+            }
+        }
+    }
+}
+
+impl Error for WriteError {}
+
+impl From<ConstantPoolError> for WriteError {
+    fn from(error: ConstantPoolError) -> Self {
+        Self::ConstantPool(error)
+    }
+}
+
+impl From<InstructionError> for WriteError {
+    fn from(error: InstructionError) -> Self {
+        Self::Instruction(error)
+    }
+}
+
+pub struct ClassBuilder {
+    version: Version,
+    access_flags: u16,
+    this_class: u16,
+    super_class: u16,
+    interfaces: Vec<u16>,
+    constant_pool: ConstantPoolBuilder,
+    fields: Vec<BuiltMember>,
+    methods: Vec<BuiltMember>,
+    // encoded annotations, with their constant pool indices already pointing into this class
+    visible_annotations: Vec<Vec<u8>>,
+    invisible_annotations: Vec<Vec<u8>>,
+    emit_stack_map_table: bool,
+}
+
+impl ClassBuilder {
+    pub fn new(
+        version: Version,
+        access_flags: u16,
+        this_class: &str,
+        super_class: Option<&str>,
+    ) -> Self {
+        // an empty constant pool has room for the four entries naming the two classes
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let this_class = constant_pool
+            .class(this_class)
+            .expect("empty constant pool");
+        let super_class = super_class.map_or(0, |name| {
+            constant_pool.class(name).expect("empty constant pool")
+        });
+
+        Self {
+            version,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces: Vec::new(),
+            constant_pool,
+            fields: Vec::new(),
+            methods: Vec::new(),
+            visible_annotations: Vec::new(),
+            invisible_annotations: Vec::new(),
+            emit_stack_map_table: version.major >= STACK_MAP_TABLE_MAJOR_VERSION,
+        }
+    }
+
+    pub fn constant_pool(&mut self) -> &mut ConstantPoolBuilder {
+        &mut self.constant_pool
+    }
+
+    pub fn add_interface(&mut self, name: &str) -> Result<&mut Self, WriteError> {
+        let index = self.constant_pool.class(name)?;
+        self.interfaces.push(index);
+
+        Ok(self)
+    }
+
+    pub fn add_field(
+        &mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<&mut Self, WriteError> {
+        let name_index = self.constant_pool.utf8(name)?;
+        let descriptor_index = self.constant_pool.utf8(descriptor)?;
+
+        self.fields.push(BuiltMember {
+            access_flags,
+            name: name.to_string(),
+            name_index,
+            descriptor: descriptor.to_string(),
+            descriptor_index,
+            code: None,
+            visible_annotations: Vec::new(),
+        });
+
+        Ok(self)
+    }
+
+    pub fn add_method(&mut self, method: MethodBuilder) -> Result<&mut Self, WriteError> {
+        let name_index = self.constant_pool.utf8(&method.name)?;
+        let descriptor_index = self.constant_pool.utf8(&method.descriptor)?;
+
+        let code = match method.code {
+            Some(code) => {
+                let mut exception_table = Vec::with_capacity(code.exception_handlers.len());
+                for handler in &code.exception_handlers {
+                    exception_table.push(ExceptionTableEntry {
+                        start_pc: handler.start_pc,
+                        end_pc: handler.end_pc,
+                        handler_pc: handler.handler_pc,
+                        catch_type: match handler.catch_type.as_deref() {
+                            Some(name) => self.constant_pool.class(name)?,
+                            None => 0,
+                        },
+                    });
+                }
+
+                Some(BuiltCode {
+                    max_stack: code.max_stack,
+                    max_locals: code.max_locals,
+                    code: code.code,
+                    exception_table,
+                })
+            }
+            None => None,
+        };
+
+        let visible_annotations = method
+            .annotations
+            .iter()
+            .map(|annotation| {
+                let mut buffer = Vec::new();
+                let type_index = self
+                    .constant_pool
+                    .utf8(&format!("L{};", annotation.type_name))?;
+                buffer.extend_from_slice(&type_index.to_be_bytes());
+                buffer.extend_from_slice(&(annotation.elements.len() as u16).to_be_bytes());
+                for (name, value) in &annotation.elements {
+                    let name_index = self.constant_pool.utf8(name)?;
+                    buffer.extend_from_slice(&name_index.to_be_bytes());
+                    buffer.extend_from_slice(value);
+                }
+
+                Ok(buffer)
+            })
+            .collect::<Result<_, WriteError>>()?;
+
+        self.methods.push(BuiltMember {
+            access_flags: method.access_flags,
+            name: method.name,
+            name_index,
+            descriptor: method.descriptor,
+            descriptor_index,
+            code,
+            visible_annotations,
+        });
+
+        Ok(self)
+    }
+
+    // Copies an annotation from another class, keeping its encoding (and with it the order of its
+    // elements) as parsed when its raw bytes were retained. Only the constant pool indices are rewritten,
+    // to point at equivalent entries of this class.
+    pub fn copy_annotation(
+        &mut self,
+        annotation: &Annotation,
+        source: &ConstantPool,
+        visible: bool,
+    ) -> Result<&mut Self, WriteError> {
+        let mut encoded = Vec::new();
+        let mut bytes = match &annotation.raw {
+            Some(raw) => raw.as_ref(),
+            None => {
+                annotation_to_bytes(&mut encoded, annotation);
+                &encoded
+            }
+        };
+
+        let mut buffer = Vec::with_capacity(bytes.len());
+        copy_annotation_bytes(&mut buffer, &mut bytes, source, &mut self.constant_pool)?;
+        if !bytes.is_empty() {
+            return Err(WriteError::MalformedAnnotation);
+        }
+
+        if visible {
+            self.visible_annotations.push(buffer);
+        } else {
+            self.invisible_annotations.push(buffer);
+        }
+
+        Ok(self)
+    }
+
+    // Frames are computed for simple methods only: every frame assumes the locals still hold the types
+    // they were given on method entry and that the operand stack is empty at branch targets. Methods
+    // which keep values on the stack across a branch, or reuse a local slot with a different type, need
+    // their StackMapTable supplied by other means.
+    pub fn emit_stack_map_table(&mut self, emit: bool) -> &mut Self {
+        self.emit_stack_map_table = emit;
+
+        self
+    }
+
+    pub fn build(mut self) -> Result<Vec<u8>, WriteError> {
+        let mut stack_map_tables = Vec::with_capacity(self.methods.len());
+        for method in &self.methods {
+            let frames = match &method.code {
+                Some(code) if self.emit_stack_map_table => Some(stack_map_frames(
+                    method,
+                    code,
+                    self.this_class,
+                    self.super_class,
+                    &mut self.constant_pool,
+                )?),
+                _ => None,
+            };
+
+            stack_map_tables.push(frames);
+        }
+
+        let code_name_index = self.constant_pool.utf8("Code")?;
+        let mut stack_map_table_name_index = 0;
+        if stack_map_tables
+            .iter()
+            .flatten()
+            .any(|frames| !frames.is_empty())
+        {
+            stack_map_table_name_index = self.constant_pool.utf8("StackMapTable")?;
+        }
+        let mut method_annotations_name_index = 0;
+        if self
+            .methods
+            .iter()
+            .any(|method| !method.visible_annotations.is_empty())
+        {
+            method_annotations_name_index = self.constant_pool.utf8("RuntimeVisibleAnnotations")?;
+        }
+
+        // the annotations are already encoded, so they are written out as the raw bytes of their attributes
+        let visible_annotations = if self.visible_annotations.is_empty() {
+            None
+        } else {
+            Some((
+                self.constant_pool.utf8("RuntimeVisibleAnnotations")?,
+                annotation_list_to_bytes(&self.visible_annotations),
+            ))
+        };
+        let invisible_annotations = if self.invisible_annotations.is_empty() {
+            None
+        } else {
+            Some((
+                self.constant_pool.utf8("RuntimeInvisibleAnnotations")?,
+                annotation_list_to_bytes(&self.invisible_annotations),
+            ))
+        };
+
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| Field {
+                access_flags: field.access_flags,
+                name_index: field.name_index,
+                descriptor_index: field.descriptor_index,
+                attributes: Vec::new(),
+            })
+            .collect();
+
+        let methods = self
+            .methods
+            .iter()
+            .zip(stack_map_tables)
+            .map(|(method, frames)| Method {
+                access_flags: method.access_flags,
+                name_index: method.name_index,
+                descriptor_index: method.descriptor_index,
+                attributes: method
+                    .code
+                    .as_ref()
+                    .map(|code| Attribute {
+                        name_index: code_name_index,
+                        info: AttributeInfo::Code {
+                            max_stack: code.max_stack,
+                            max_locals: code.max_locals,
+                            code: Cow::Borrowed(&code.code),
+                            exception_table: code
+                                .exception_table
+                                .iter()
+                                .map(|entry| ExceptionTableEntry {
+                                    start_pc: entry.start_pc,
+                                    end_pc: entry.end_pc,
+                                    handler_pc: entry.handler_pc,
+                                    catch_type: entry.catch_type,
+                                })
+                                .collect(),
+                            attributes: frames
+                                .filter(|entries| !entries.is_empty())
+                                .map(|entries| Attribute {
+                                    name_index: stack_map_table_name_index,
+                                    info: AttributeInfo::StackMapTable { entries },
+                                    raw: None,
+                                })
+                                .into_iter()
+                                .collect(),
+                        },
+                        raw: None,
+                    })
+                    .into_iter()
+                    .chain((!method.visible_annotations.is_empty()).then(|| Attribute {
+                        name_index: method_annotations_name_index,
+                        info: AttributeInfo::RuntimeVisibleAnnotations {
+                            annotations: Vec::new(),
+                        },
+                        raw: Some(Cow::Owned(annotation_list_to_bytes(
+                            &method.visible_annotations,
+                        ))),
+                    }))
+                    .collect(),
+                lazy_code: LazyCode::default(),
+            })
+            .collect();
+
+        let classfile = Classfile {
+            version: self.version,
+            constant_pool: ConstantPool::new(self.constant_pool.entries().collect()),
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces.clone(),
+            fields,
+            methods,
+            attributes: visible_annotations
+                .as_ref()
+                .map(|(name_index, raw)| Attribute {
+                    name_index: *name_index,
+                    info: AttributeInfo::RuntimeVisibleAnnotations {
+                        annotations: Vec::new(),
+                    },
+                    raw: Some(Cow::Borrowed(raw)),
+                })
+                .into_iter()
+                .chain(
+                    invisible_annotations
+                        .as_ref()
+                        .map(|(name_index, raw)| Attribute {
+                            name_index: *name_index,
+                            info: AttributeInfo::RuntimeInvisibleAnnotations {
+                                annotations: Vec::new(),
+                            },
+                            raw: Some(Cow::Borrowed(raw)),
+                        }),
+                )
+                .collect(),
+        };
+
+        classfile_to_bytes(&classfile)
+    }
+}
+
+#[derive(Default)]
+pub struct ConstantPoolBuilder {
+    // slot `i` holds the entry at constant pool index `i + 1`; the slot following a Long or Double is
+    // `None`, as the entry occupies two indices
+    entries: Vec<Option<ConstantKey>>,
+    indices: HashMap<ConstantKey, u16>,
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn utf8(&mut self, value: &str) -> Result<u16, WriteError> {
+        self.insert(ConstantKey::Utf8(str_to_mutf8(value).into_owned()))
+    }
+
+    pub fn integer(&mut self, value: i32) -> Result<u16, WriteError> {
+        self.insert(ConstantKey::Integer(value as u32))
+    }
+
+    pub fn float(&mut self, value: f32) -> Result<u16, WriteError> {
+        self.insert(ConstantKey::Float(value.to_bits()))
+    }
+
+    pub fn long(&mut self, value: i64) -> Result<u16, WriteError> {
+        self.insert(ConstantKey::Long(value as u64))
+    }
+
+    pub fn double(&mut self, value: f64) -> Result<u16, WriteError> {
+        self.insert(ConstantKey::Double(value.to_bits()))
+    }
+
+    pub fn class(&mut self, name: &str) -> Result<u16, WriteError> {
+        let name_index = self.utf8(name)?;
+
+        self.insert(ConstantKey::Class(name_index))
+    }
+
+    pub fn string(&mut self, value: &str) -> Result<u16, WriteError> {
+        let string_index = self.utf8(value)?;
+
+        self.insert(ConstantKey::String(string_index))
+    }
+
+    pub fn field_ref(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, WriteError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.insert(ConstantKey::FieldRef(class_index, name_and_type_index))
+    }
+
+    pub fn method_ref(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, WriteError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.insert(ConstantKey::MethodRef(class_index, name_and_type_index))
+    }
+
+    pub fn interface_method_ref(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, WriteError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.insert(ConstantKey::InterfaceMethodRef(
+            class_index,
+            name_and_type_index,
+        ))
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, WriteError> {
+        let name_index = self.utf8(name)?;
+        let descriptor_index = self.utf8(descriptor)?;
+
+        self.insert(ConstantKey::NameAndType(name_index, descriptor_index))
+    }
+
+    pub fn method_handle(
+        &mut self,
+        reference_kind: u8,
+        reference_index: u16,
+    ) -> Result<u16, WriteError> {
+        self.insert(ConstantKey::MethodHandle(reference_kind, reference_index))
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> Result<u16, WriteError> {
+        let descriptor_index = self.utf8(descriptor)?;
+
+        self.insert(ConstantKey::MethodType(descriptor_index))
+    }
+
+    // `bootstrap_method_attr_index` indexes the BootstrapMethods attribute of the class being built
+    pub fn dynamic(
+        &mut self,
+        bootstrap_method_attr_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, WriteError> {
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.insert(ConstantKey::Dynamic(
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        ))
+    }
+
+    pub fn invoke_dynamic(
+        &mut self,
+        bootstrap_method_attr_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, WriteError> {
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+
+        self.insert(ConstantKey::InvokeDynamic(
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        ))
+    }
+
+    pub fn module(&mut self, name: &str) -> Result<u16, WriteError> {
+        let name_index = self.utf8(name)?;
+
+        self.insert(ConstantKey::Module(name_index))
+    }
+
+    pub fn package(&mut self, name: &str) -> Result<u16, WriteError> {
+        let name_index = self.utf8(name)?;
+
+        self.insert(ConstantKey::Package(name_index))
+    }
+
+    // the entries added so far, as a constant pool a classfile can be assembled with
+    pub fn to_constant_pool(&self) -> ConstantPool<'static> {
+        ConstantPool::new(self.entries().collect()).into_owned()
+    }
+
+    fn entries(&self) -> impl Iterator<Item = ConstantPoolEntry<'_>> {
+        self.entries.iter().map(|key| match *key {
+            Some(ConstantKey::Utf8(ref bytes)) => ConstantPoolEntry::Utf8 {
+                bytes: Cow::Borrowed(bytes),
+            },
+            Some(ConstantKey::Integer(bytes)) => ConstantPoolEntry::Integer { bytes },
+            Some(ConstantKey::Float(bits)) => ConstantPoolEntry::Float {
+                value: f32::from_bits(bits),
+            },
+            Some(ConstantKey::Long(value)) => ConstantPoolEntry::Long { value },
+            Some(ConstantKey::Double(bits)) => ConstantPoolEntry::Double {
+                value: f64::from_bits(bits),
+            },
+            Some(ConstantKey::Class(name_index)) => ConstantPoolEntry::Class { name_index },
+            Some(ConstantKey::String(string_index)) => ConstantPoolEntry::String { string_index },
+            Some(ConstantKey::FieldRef(class_index, name_and_type_index)) => {
+                ConstantPoolEntry::FieldRef {
+                    class_index,
+                    name_and_type_index,
+                }
+            }
+            Some(ConstantKey::MethodRef(class_index, name_and_type_index)) => {
+                ConstantPoolEntry::MethodRef {
+                    class_index,
+                    name_and_type_index,
+                }
+            }
+            Some(ConstantKey::InterfaceMethodRef(class_index, name_and_type_index)) => {
+                ConstantPoolEntry::InstanceMethodRef {
+                    class_index,
+                    name_and_type_index,
+                }
+            }
+            Some(ConstantKey::NameAndType(name_index, descriptor_index)) => {
+                ConstantPoolEntry::NameAndType {
+                    name_index,
+                    descriptor_index,
+                }
+            }
+            Some(ConstantKey::MethodHandle(reference_kind, reference_index)) => {
+                ConstantPoolEntry::MethodHandle {
+                    reference_kind,
+                    reference_index,
+                }
+            }
+            Some(ConstantKey::MethodType(reference_index)) => {
+                ConstantPoolEntry::MethodType { reference_index }
+            }
+            Some(ConstantKey::Dynamic(bootstrap_method_attr_index, name_and_type_index)) => {
+                ConstantPoolEntry::Dynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                }
+            }
+            Some(ConstantKey::InvokeDynamic(bootstrap_method_attr_index, name_and_type_index)) => {
+                ConstantPoolEntry::InvokeDynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                }
+            }
+            Some(ConstantKey::Module(name_index)) => ConstantPoolEntry::Module { name_index },
+            Some(ConstantKey::Package(name_index)) => ConstantPoolEntry::Package { name_index },
+            // the second index of a Long or Double entry
+            None => ConstantPoolEntry::Unusable,
+        })
+    }
+
+    fn get(&self, index: u16) -> Option<&ConstantKey> {
+        self.entries.get((index as usize).checked_sub(1)?)?.as_ref()
+    }
+
+    // A Long or Double is only accepted if both of its indices fit, as its second one cannot be left off.
+    fn insert(&mut self, key: ConstantKey) -> Result<u16, WriteError> {
+        if let Some(&index) = self.indices.get(&key) {
+            return Ok(index);
+        }
+
+        let is_wide = matches!(key, ConstantKey::Long(_) | ConstantKey::Double(_));
+        let slots = self.entries.len() + if is_wide { 2 } else { 1 };
+        // the constant_pool_count field is one more than the indices in use, and must fit in a u16
+        if slots >= u16::MAX as usize {
+            return Err(WriteError::PoolLimitExceeded { slots });
+        }
+        let index = (self.entries.len() + 1) as u16;
+
+        self.indices.insert(key.clone(), index);
+        self.entries.push(Some(key));
+        if is_wide {
+            self.entries.push(None);
+        }
+
+        Ok(index)
+    }
+}
+
+pub struct MethodBuilder {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    code: Option<CodeBuilder>,
+    annotations: Vec<PendingAnnotation>,
+}
+
+impl MethodBuilder {
+    pub fn new(access_flags: u16, name: &str, descriptor: &str) -> Self {
+        Self {
+            access_flags,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            code: None,
+            annotations: Vec::new(),
+        }
+    }
+
+    // Adds a RuntimeVisibleAnnotations entry for the annotation interface with the internal name
+    // `type_name`. The entries for the type and the element names are added to the constant pool of the
+    // class the method is added to, but the indices inside the element values (including those of nested
+    // annotations) must already point into that constant pool, as returned by `ClassBuilder::constant_pool`.
+    pub fn add_annotation(
+        &mut self,
+        type_name: &str,
+        elements: &[(&str, ElementValue)],
+    ) -> &mut Self {
+        let elements = elements
+            .iter()
+            .map(|(name, value)| {
+                let mut buffer = Vec::new();
+                element_value_to_bytes(&mut buffer, value);
+
+                (name.to_string(), buffer)
+            })
+            .collect();
+
+        self.annotations.push(PendingAnnotation {
+            type_name: type_name.to_string(),
+            elements,
+        });
+
+        self
+    }
+
+    pub fn code(&mut self, max_stack: u16, max_locals: u16, code: Vec<u8>) -> &mut Self {
+        self.code = Some(CodeBuilder {
+            max_stack,
+            max_locals,
+            code,
+            exception_handlers: Vec::new(),
+        });
+
+        self
+    }
+
+    // a `catch_type` of `None` catches any exception, as used by `finally` blocks
+    pub fn exception_handler(
+        &mut self,
+        start_pc: u16,
+        end_pc: u16,
+        handler_pc: u16,
+        catch_type: Option<&str>,
+    ) -> &mut Self {
+        if let Some(code) = self.code.as_mut() {
+            code.exception_handlers.push(ExceptionHandler {
+                start_pc,
+                end_pc,
+                handler_pc,
+                catch_type: catch_type.map(str::to_string),
+            });
+        }
+
+        self
+    }
+}
+
+struct BranchScan {
+    // offsets of branch targets, and of instructions following an unconditional control transfer
+    targets: Vec<usize>,
+    invokespecials: Vec<(usize, u16)>,
+}
+
+struct BuiltCode {
+    max_stack: u16,
+    max_locals: u16,
+    code: Vec<u8>,
+    exception_table: Vec<ExceptionTableEntry>,
+}
+
+struct BuiltMember {
+    access_flags: u16,
+    name: String,
+    name_index: u16,
+    descriptor: String,
+    descriptor_index: u16,
+    code: Option<BuiltCode>,
+    // encoded annotations, like those of the class
+    visible_annotations: Vec<Vec<u8>>,
+}
+
+struct CodeBuilder {
+    max_stack: u16,
+    max_locals: u16,
+    code: Vec<u8>,
+    exception_handlers: Vec<ExceptionHandler>,
+}
+
+#[derive(Clone, Eq, Hash, PartialEq)]
+enum ConstantKey {
+    Utf8(Vec<u8>),
+    Integer(u32),
+    Float(u32),
+    Long(u64),
+    Double(u64),
+    Class(u16),
+    String(u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    InterfaceMethodRef(u16, u16),
+    NameAndType(u16, u16),
+    MethodHandle(u8, u16),
+    MethodType(u16),
+    Dynamic(u16, u16),
+    InvokeDynamic(u16, u16),
+    Module(u16),
+    Package(u16),
+}
+
+struct ExceptionHandler {
+    start_pc: u16,
+    end_pc: u16,
+    handler_pc: u16,
+    catch_type: Option<String>,
+}
+
+struct PendingAnnotation {
+    type_name: String,
+    // the names of the elements, with their values already encoded
+    elements: Vec<(String, Vec<u8>)>,
+}
+
+pub fn classfile_to_bytes(classfile: &Classfile) -> Result<Vec<u8>, WriteError> {
+    let constant_pool = classfile.constant_pool.entries();
+    let mut buffer = Vec::new();
+
+    // magic bytes and classfile version
+    buffer.extend_from_slice(&[0xCA, 0xFE, 0xBA, 0xBE]);
+    buffer.extend_from_slice(&classfile.version.minor.to_be_bytes());
+    buffer.extend_from_slice(&classfile.version.major.to_be_bytes());
+
+    // constant pool count (one greater than the number of slots, as index 0 is reserved) and constant pool;
+    // the Unusable entry after a Long or Double is counted with it, in case a pool assembled by hand lacks it
+    let slots = constant_pool
+        .iter()
+        .map(|entry| match entry {
+            ConstantPoolEntry::Long { .. } | ConstantPoolEntry::Double { .. } => 2,
+            ConstantPoolEntry::Unusable => 0,
+            _ => 1,
+        })
+        .sum::<usize>();
+    let constant_pool_count =
+        u16::try_from(slots + 1).map_err(|_| WriteError::PoolLimitExceeded { slots })?;
+    buffer.extend_from_slice(&constant_pool_count.to_be_bytes());
+    for entry in constant_pool {
+        constant_pool_entry_to_bytes(&mut buffer, entry);
+    }
+
+    buffer.extend_from_slice(&classfile.access_flags.to_be_bytes());
+    buffer.extend_from_slice(&classfile.this_class.to_be_bytes());
+    buffer.extend_from_slice(&classfile.super_class.to_be_bytes());
+    u16_table_to_bytes(&mut buffer, &classfile.interfaces);
+
+    buffer.extend_from_slice(&(classfile.fields.len() as u16).to_be_bytes());
+    for field in &classfile.fields {
+        member_to_bytes(
+            &mut buffer,
+            field.access_flags,
+            field.name_index,
+            field.descriptor_index,
+            &field.attributes,
+            constant_pool,
+        )?;
+    }
+
+    buffer.extend_from_slice(&(classfile.methods.len() as u16).to_be_bytes());
+    for method in &classfile.methods {
+        member_to_bytes(
+            &mut buffer,
+            method.access_flags,
+            method.name_index,
+            method.descriptor_index,
+            &method.attributes,
+            constant_pool,
+        )?;
+    }
+
+    attributes_to_bytes(&mut buffer, &classfile.attributes, constant_pool)?;
+
+    Ok(buffer)
+}
+
+fn annotation_list_to_bytes(annotations: &[Vec<u8>]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(&(annotations.len() as u16).to_be_bytes());
+    for annotation in annotations {
+        buffer.extend_from_slice(annotation);
+    }
+
+    buffer
+}
+
+fn annotation_to_bytes(buffer: &mut Vec<u8>, annotation: &Annotation) {
+    if let Some(raw) = &annotation.raw {
+        buffer.extend_from_slice(raw);
+        return;
+    }
+
+    buffer.extend_from_slice(&annotation.type_index.to_be_bytes());
+    element_value_pairs_to_bytes(buffer, &annotation.element_value_pairs);
+}
+
+fn annotations_to_bytes(buffer: &mut Vec<u8>, annotations: &[Annotation]) {
+    buffer.extend_from_slice(&(annotations.len() as u16).to_be_bytes());
+    for annotation in annotations {
+        annotation_to_bytes(buffer, annotation);
+    }
+}
+
+fn attribute_info_to_bytes(
+    buffer: &mut Vec<u8>,
+    info: &AttributeInfo,
+    constant_pool: &[ConstantPoolEntry],
+) -> Result<(), WriteError> {
+    match info {
+        AttributeInfo::AnnotationDefault { default_value } => {
+            element_value_to_bytes(buffer, default_value);
+        }
+        AttributeInfo::BootstrapMethods { bootstrap_methods } => {
+            buffer.extend_from_slice(&(bootstrap_methods.len() as u16).to_be_bytes());
+            for bootstrap_method in bootstrap_methods {
+                buffer.extend_from_slice(&bootstrap_method.bootstrap_method_ref.to_be_bytes());
+                u16_table_to_bytes(buffer, &bootstrap_method.bootstrap_arguments);
+            }
+        }
+        AttributeInfo::Code {
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+            attributes,
+        } => {
+            buffer.extend_from_slice(&max_stack.to_be_bytes());
+            buffer.extend_from_slice(&max_locals.to_be_bytes());
+            buffer.extend_from_slice(&(code.len() as u32).to_be_bytes());
+            buffer.extend_from_slice(code);
+            buffer.extend_from_slice(&(exception_table.len() as u16).to_be_bytes());
+            for entry in exception_table {
+                buffer.extend_from_slice(&entry.start_pc.to_be_bytes());
+                buffer.extend_from_slice(&entry.end_pc.to_be_bytes());
+                buffer.extend_from_slice(&entry.handler_pc.to_be_bytes());
+                buffer.extend_from_slice(&entry.catch_type.to_be_bytes());
+            }
+            attributes_to_bytes(buffer, attributes, constant_pool)?;
+        }
+        AttributeInfo::ConstantValue {
+            constantvalue_index,
+        } => buffer.extend_from_slice(&constantvalue_index.to_be_bytes()),
+        AttributeInfo::Deprecated | AttributeInfo::Synthetic => {}
+        AttributeInfo::EnclosingMethod {
+            class_index,
+            method_index,
+        } => {
+            buffer.extend_from_slice(&class_index.to_be_bytes());
+            buffer.extend_from_slice(&method_index.to_be_bytes());
+        }
+        AttributeInfo::Exceptions {
+            exception_index_table,
+        } => u16_table_to_bytes(buffer, exception_index_table),
+        AttributeInfo::InnerClasses { classes } => {
+            buffer.extend_from_slice(&(classes.len() as u16).to_be_bytes());
+            for class in classes {
+                buffer.extend_from_slice(&class.inner_class_info_index.to_be_bytes());
+                buffer.extend_from_slice(&class.outer_class_info_index.to_be_bytes());
+                buffer.extend_from_slice(&class.inner_name_index.to_be_bytes());
+                buffer.extend_from_slice(&class.inner_class_access_flags.to_be_bytes());
+            }
+        }
+        AttributeInfo::LineNumberTable { line_number_table } => {
+            buffer.extend_from_slice(&(line_number_table.len() as u16).to_be_bytes());
+            for line_number in line_number_table {
+                buffer.extend_from_slice(&line_number.start_pc.to_be_bytes());
+                buffer.extend_from_slice(&line_number.line_number.to_be_bytes());
+            }
+        }
+        AttributeInfo::LocalVariableTable {
+            local_variable_table,
+        } => {
+            buffer.extend_from_slice(&(local_variable_table.len() as u16).to_be_bytes());
+            for local_variable in local_variable_table {
+                buffer.extend_from_slice(&local_variable.start_pc.to_be_bytes());
+                buffer.extend_from_slice(&local_variable.length.to_be_bytes());
+                buffer.extend_from_slice(&local_variable.name_index.to_be_bytes());
+                buffer.extend_from_slice(&local_variable.descriptor_index.to_be_bytes());
+                buffer.extend_from_slice(&local_variable.index.to_be_bytes());
+            }
+        }
+        AttributeInfo::LocalVariableTypeTable {
+            local_variable_type_table,
+        } => {
+            buffer.extend_from_slice(&(local_variable_type_table.len() as u16).to_be_bytes());
+            for local_variable_type in local_variable_type_table {
+                buffer.extend_from_slice(&local_variable_type.start_pc.to_be_bytes());
+                buffer.extend_from_slice(&local_variable_type.length.to_be_bytes());
+                buffer.extend_from_slice(&local_variable_type.name_index.to_be_bytes());
+                buffer.extend_from_slice(&local_variable_type.descriptor_index.to_be_bytes());
+                buffer.extend_from_slice(&local_variable_type.index.to_be_bytes());
+            }
+        }
+        AttributeInfo::MethodParameters { parameters } => {
+            buffer.push(parameters.len() as u8);
+            for parameter in parameters {
+                buffer.extend_from_slice(&parameter.name_index.to_be_bytes());
+                buffer.extend_from_slice(&parameter.access_flags.to_be_bytes());
+            }
+        }
+        AttributeInfo::Module {
+            module_name_index,
+            module_flags,
+            module_version_index,
+            requires,
+            exports,
+            opens,
+            uses,
+            provides,
+        } => {
+            buffer.extend_from_slice(&module_name_index.to_be_bytes());
+            buffer.extend_from_slice(&module_flags.to_be_bytes());
+            buffer.extend_from_slice(&module_version_index.to_be_bytes());
+            buffer.extend_from_slice(&(requires.len() as u16).to_be_bytes());
+            for require in requires {
+                buffer.extend_from_slice(&require.requires_index.to_be_bytes());
+                buffer.extend_from_slice(&require.requires_flags.to_be_bytes());
+                buffer.extend_from_slice(&require.requires_version_index.to_be_bytes());
+            }
+            buffer.extend_from_slice(&(exports.len() as u16).to_be_bytes());
+            for export in exports {
+                buffer.extend_from_slice(&export.exports_index.to_be_bytes());
+                buffer.extend_from_slice(&export.exports_flags.to_be_bytes());
+                u16_table_to_bytes(buffer, &export.exports_to_indices);
+            }
+            buffer.extend_from_slice(&(opens.len() as u16).to_be_bytes());
+            for open in opens {
+                buffer.extend_from_slice(&open.opens_index.to_be_bytes());
+                buffer.extend_from_slice(&open.opens_flags.to_be_bytes());
+                u16_table_to_bytes(buffer, &open.opens_to_indices);
+            }
+            u16_table_to_bytes(buffer, uses);
+            buffer.extend_from_slice(&(provides.len() as u16).to_be_bytes());
+            for provide in provides {
+                buffer.extend_from_slice(&provide.provides_index.to_be_bytes());
+                u16_table_to_bytes(buffer, &provide.provides_with_indices);
+            }
+        }
+        AttributeInfo::ModuleMainClass { main_class_index } => {
+            buffer.extend_from_slice(&main_class_index.to_be_bytes());
+        }
+        AttributeInfo::ModulePackages { package_index } => {
+            u16_table_to_bytes(buffer, package_index);
+        }
+        AttributeInfo::NestHost { host_class_index } => {
+            buffer.extend_from_slice(&host_class_index.to_be_bytes());
+        }
+        AttributeInfo::NestMembers { classes } | AttributeInfo::PermittedSubclasses { classes } => {
+            u16_table_to_bytes(buffer, classes);
+        }
+        AttributeInfo::Record { components } => {
+            buffer.extend_from_slice(&(components.len() as u16).to_be_bytes());
+            for component in components {
+                buffer.extend_from_slice(&component.name_index.to_be_bytes());
+                buffer.extend_from_slice(&component.descriptor_index.to_be_bytes());
+                attributes_to_bytes(buffer, &component.attributes, constant_pool)?;
+            }
+        }
+        AttributeInfo::RuntimeInvisibleAnnotations { annotations }
+        | AttributeInfo::RuntimeVisibleAnnotations { annotations } => {
+            annotations_to_bytes(buffer, annotations);
+        }
+        AttributeInfo::RuntimeInvisibleParameterAnnotations {
+            parameter_annotations,
+        }
+        | AttributeInfo::RuntimeVisibleParameterAnnotations {
+            parameter_annotations,
+        } => annotations_to_bytes(buffer, parameter_annotations),
+        AttributeInfo::RuntimeInvisibleTypeAnnotations { type_annotations }
+        | AttributeInfo::RuntimeVisibleTypeAnnotations { type_annotations } => {
+            buffer.extend_from_slice(&(type_annotations.len() as u16).to_be_bytes());
+            for type_annotation in type_annotations {
+                type_annotation_to_bytes(buffer, type_annotation);
+            }
+        }
+        AttributeInfo::Signature { signature_index } => {
+            buffer.extend_from_slice(&signature_index.to_be_bytes());
+        }
+        AttributeInfo::SourceDebugExtension { debug_extension } => {
+            buffer.extend_from_slice(debug_extension);
+        }
+        AttributeInfo::SourceFile { sourcefile_index } => {
+            buffer.extend_from_slice(&sourcefile_index.to_be_bytes());
+        }
+        AttributeInfo::StackMapTable { entries } => {
+            buffer.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+            for entry in entries {
+                stack_map_frame_to_bytes(buffer, entry);
+            }
+        }
+        AttributeInfo::Unknown { info, .. } => buffer.extend_from_slice(info),
+    }
+
+    Ok(())
+}
+
+fn attribute_to_bytes(
+    buffer: &mut Vec<u8>,
+    attribute: &Attribute,
+    constant_pool: &[ConstantPoolEntry],
+) -> Result<(), WriteError> {
+    // a parsed attribute is written under the name it was read with
+    let name_index = if attribute.name_index != 0 {
+        attribute.name_index
+    } else if let AttributeInfo::Unknown { name_index, .. } = attribute.info {
+        name_index
+    } else {
+        let Some(name) = attribute.info.standard_name() else {
+            unreachable!("unknown attributes are written with the name index they were parsed with")
+        };
+        let Some(position) = constant_pool.iter().position(
+            |entry| matches!(entry, ConstantPoolEntry::Utf8 { bytes } if *bytes == name.as_bytes()),
+        ) else {
+            return Err(WriteError::MissingAttributeName { name });
+        };
+
+        position as u16 + 1
+    };
+
+    // the attribute body is written out of line first, as its length precedes it; a body retained
+    // while parsing is written back as is, rather than encoding the decoded form again
+    let mut info = Vec::new();
+    match &attribute.raw {
+        Some(raw) => info.extend_from_slice(raw),
+        None => attribute_info_to_bytes(&mut info, &attribute.info, constant_pool)?,
+    }
+
+    buffer.extend_from_slice(&name_index.to_be_bytes());
+    buffer.extend_from_slice(&(info.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(&info);
+
+    Ok(())
+}
+
+fn attributes_to_bytes(
+    buffer: &mut Vec<u8>,
+    attributes: &[Attribute],
+    constant_pool: &[ConstantPoolEntry],
+) -> Result<(), WriteError> {
+    buffer.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+    for attribute in attributes {
+        attribute_to_bytes(buffer, attribute, constant_pool)?;
+    }
+
+    Ok(())
+}
+
+// copies the annotation at the start of `bytes`, advancing past it
+fn copy_annotation_bytes(
+    buffer: &mut Vec<u8>,
+    bytes: &mut &[u8],
+    source: &ConstantPool,
+    destination: &mut ConstantPoolBuilder,
+) -> Result<(), WriteError> {
+    let type_index = take_u16(bytes)?;
+    buffer.extend_from_slice(&destination.utf8(&source.utf8(type_index)?)?.to_be_bytes());
+
+    let num_element_value_pairs = take_u16(bytes)?;
+    buffer.extend_from_slice(&num_element_value_pairs.to_be_bytes());
+    for _ in 0..num_element_value_pairs {
+        let element_name_index = take_u16(bytes)?;
+        buffer.extend_from_slice(
+            &destination
+                .utf8(&source.utf8(element_name_index)?)?
+                .to_be_bytes(),
+        );
+        copy_element_value_bytes(buffer, bytes, source, destination)?;
+    }
+
+    Ok(())
+}
+
+// copies the element value at the start of `bytes`, advancing past it
+fn copy_element_value_bytes(
+    buffer: &mut Vec<u8>,
+    bytes: &mut &[u8],
+    source: &ConstantPool,
+    destination: &mut ConstantPoolBuilder,
+) -> Result<(), WriteError> {
+    let (&tag, rest) = bytes.split_first().ok_or(WriteError::MalformedAnnotation)?;
+    *bytes = rest;
+    buffer.push(tag);
+
+    match tag {
+        b'B' | b'C' | b'I' | b'S' | b'Z' => {
+            let index = take_u16(bytes)?;
+            let ConstantPoolEntry::Integer { bytes: value } = *source.get(index)? else {
+                return Err(ConstantPoolError::UnexpectedEntry {
+                    index,
+                    expected: "Integer",
+                }
+                .into());
+            };
+            buffer.extend_from_slice(&destination.integer(value as i32)?.to_be_bytes());
+        }
+        b'D' => {
+            let index = take_u16(bytes)?;
+            let ConstantPoolEntry::Double { value } = *source.get(index)? else {
+                return Err(ConstantPoolError::UnexpectedEntry {
+                    index,
+                    expected: "Double",
+                }
+                .into());
+            };
+            buffer.extend_from_slice(&destination.double(value)?.to_be_bytes());
+        }
+        b'F' => {
+            let index = take_u16(bytes)?;
+            let ConstantPoolEntry::Float { value } = *source.get(index)? else {
+                return Err(ConstantPoolError::UnexpectedEntry {
+                    index,
+                    expected: "Float",
+                }
+                .into());
+            };
+            buffer.extend_from_slice(&destination.float(value)?.to_be_bytes());
+        }
+        b'J' => {
+            let index = take_u16(bytes)?;
+            let ConstantPoolEntry::Long { value } = *source.get(index)? else {
+                return Err(ConstantPoolError::UnexpectedEntry {
+                    index,
+                    expected: "Long",
+                }
+                .into());
+            };
+            buffer.extend_from_slice(&destination.long(value as i64)?.to_be_bytes());
+        }
+        // string, and class given by its return descriptor
+        b's' | b'c' => {
+            let index = take_u16(bytes)?;
+            buffer.extend_from_slice(&destination.utf8(&source.utf8(index)?)?.to_be_bytes());
+        }
+        b'e' => {
+            for _ in 0..2 {
+                let index = take_u16(bytes)?;
+                buffer.extend_from_slice(&destination.utf8(&source.utf8(index)?)?.to_be_bytes());
+            }
+        }
+        b'@' => copy_annotation_bytes(buffer, bytes, source, destination)?,
+        b'[' => {
+            let num_values = take_u16(bytes)?;
+            buffer.extend_from_slice(&num_values.to_be_bytes());
+            for _ in 0..num_values {
+                copy_element_value_bytes(buffer, bytes, source, destination)?;
+            }
+        }
+        _ => return Err(WriteError::MalformedAnnotation),
+    }
+
+    Ok(())
+}
+
+fn constant_pool_entry_to_bytes(buffer: &mut Vec<u8>, entry: &ConstantPoolEntry) {
+    match entry {
+        ConstantPoolEntry::Utf8 { bytes } => {
+            buffer.push(1);
+            buffer.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+            buffer.extend_from_slice(bytes);
+        }
+        ConstantPoolEntry::Integer { bytes } => {
+            buffer.push(3);
+            buffer.extend_from_slice(&bytes.to_be_bytes());
+        }
+        ConstantPoolEntry::Float { value } => {
+            buffer.push(4);
+            buffer.extend_from_slice(&value.to_bits().to_be_bytes());
+        }
+        ConstantPoolEntry::Long { value } => {
+            buffer.push(5);
+            buffer.extend_from_slice(&value.to_be_bytes());
+        }
+        ConstantPoolEntry::Double { value } => {
+            buffer.push(6);
+            buffer.extend_from_slice(&value.to_bits().to_be_bytes());
+        }
+        ConstantPoolEntry::Class { name_index } => {
+            buffer.push(7);
+            buffer.extend_from_slice(&name_index.to_be_bytes());
+        }
+        ConstantPoolEntry::String { string_index } => {
+            buffer.push(8);
+            buffer.extend_from_slice(&string_index.to_be_bytes());
+        }
+        ConstantPoolEntry::FieldRef {
+            class_index,
+            name_and_type_index,
+        } => {
+            buffer.push(9);
+            buffer.extend_from_slice(&class_index.to_be_bytes());
+            buffer.extend_from_slice(&name_and_type_index.to_be_bytes());
+        }
+        ConstantPoolEntry::MethodRef {
+            class_index,
+            name_and_type_index,
+        } => {
+            buffer.push(10);
+            buffer.extend_from_slice(&class_index.to_be_bytes());
+            buffer.extend_from_slice(&name_and_type_index.to_be_bytes());
+        }
+        ConstantPoolEntry::InstanceMethodRef {
+            class_index,
+            name_and_type_index,
+        } => {
+            buffer.push(11);
+            buffer.extend_from_slice(&class_index.to_be_bytes());
+            buffer.extend_from_slice(&name_and_type_index.to_be_bytes());
+        }
+        ConstantPoolEntry::NameAndType {
+            name_index,
+            descriptor_index,
+        } => {
+            buffer.push(12);
+            buffer.extend_from_slice(&name_index.to_be_bytes());
+            buffer.extend_from_slice(&descriptor_index.to_be_bytes());
+        }
+        ConstantPoolEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        } => {
+            buffer.push(15);
+            buffer.push(*reference_kind);
+            buffer.extend_from_slice(&reference_index.to_be_bytes());
+        }
+        ConstantPoolEntry::MethodType { reference_index } => {
+            buffer.push(16);
+            buffer.extend_from_slice(&reference_index.to_be_bytes());
+        }
+        ConstantPoolEntry::Dynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => {
+            buffer.push(17);
+            buffer.extend_from_slice(&bootstrap_method_attr_index.to_be_bytes());
+            buffer.extend_from_slice(&name_and_type_index.to_be_bytes());
+        }
+        ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => {
+            buffer.push(18);
+            buffer.extend_from_slice(&bootstrap_method_attr_index.to_be_bytes());
+            buffer.extend_from_slice(&name_and_type_index.to_be_bytes());
+        }
+        ConstantPoolEntry::Module { name_index } => {
+            buffer.push(19);
+            buffer.extend_from_slice(&name_index.to_be_bytes());
+        }
+        ConstantPoolEntry::Package { name_index } => {
+            buffer.push(20);
+            buffer.extend_from_slice(&name_index.to_be_bytes());
+        }
+        // the second index of a Long or Double entry is not written out
+        ConstantPoolEntry::Unusable => {}
+    }
+}
+
+fn element_value_pairs_to_bytes(buffer: &mut Vec<u8>, element_value_pairs: &[ElementValuePair]) {
+    buffer.extend_from_slice(&(element_value_pairs.len() as u16).to_be_bytes());
+    for pair in element_value_pairs {
+        buffer.extend_from_slice(&pair.element_name_index.to_be_bytes());
+        element_value_to_bytes(buffer, &pair.value);
+    }
+}
+
+fn element_value_to_bytes(buffer: &mut Vec<u8>, element_value: &ElementValue) {
+    match element_value {
+        ElementValue::Annotation(annotation) => {
+            buffer.push(b'@');
+            annotation_to_bytes(buffer, annotation);
+        }
+        ElementValue::ClassInfo(class_info_index) => {
+            buffer.push(b'c');
+            buffer.extend_from_slice(&class_info_index.to_be_bytes());
+        }
+        ElementValue::ConstValue {
+            tag,
+            const_value_index,
+        } => {
+            buffer.push(*tag);
+            buffer.extend_from_slice(&const_value_index.to_be_bytes());
+        }
+        ElementValue::EnumConst {
+            type_name_index,
+            const_name_index,
+        } => {
+            buffer.push(b'e');
+            buffer.extend_from_slice(&type_name_index.to_be_bytes());
+            buffer.extend_from_slice(&const_name_index.to_be_bytes());
+        }
+        ElementValue::Array { values } => {
+            buffer.push(b'[');
+            buffer.extend_from_slice(&(values.len() as u16).to_be_bytes());
+            for value in values {
+                element_value_to_bytes(buffer, value);
+            }
+        }
+    }
+}
+
+fn field_type_to_descriptor(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::Byte => "B".to_string(),
+        FieldType::Char => "C".to_string(),
+        FieldType::Double => "D".to_string(),
+        FieldType::Float => "F".to_string(),
+        FieldType::Int => "I".to_string(),
+        FieldType::Long => "J".to_string(),
+        FieldType::Short => "S".to_string(),
+        FieldType::Boolean => "Z".to_string(),
+        FieldType::Object(class_name) => format!("L{class_name};"),
+        FieldType::Array(component_type) => {
+            format!("[{}", field_type_to_descriptor(component_type))
+        }
+    }
+}
+
+fn is_constructor_call(
+    index: u16,
+    this_class: u16,
+    super_class: u16,
+    constant_pool: &ConstantPoolBuilder,
+) -> bool {
+    let Some(&ConstantKey::MethodRef(class_index, name_and_type_index)) = constant_pool.get(index)
+    else {
+        return false;
+    };
+    let Some(&ConstantKey::NameAndType(name_index, _)) = constant_pool.get(name_and_type_index)
+    else {
+        return false;
+    };
+
+    (class_index == this_class || class_index == super_class)
+        && matches!(constant_pool.get(name_index), Some(ConstantKey::Utf8(name)) if name == b"<init>")
+}
+
+fn member_to_bytes(
+    buffer: &mut Vec<u8>,
+    access_flags: u16,
+    name_index: u16,
+    descriptor_index: u16,
+    attributes: &[Attribute],
+    constant_pool: &[ConstantPoolEntry],
+) -> Result<(), WriteError> {
+    buffer.extend_from_slice(&access_flags.to_be_bytes());
+    buffer.extend_from_slice(&name_index.to_be_bytes());
+    buffer.extend_from_slice(&descriptor_index.to_be_bytes());
+
+    attributes_to_bytes(buffer, attributes, constant_pool)
+}
+
+fn scan_branches(code: &[u8]) -> Result<BranchScan, WriteError> {
+    // the boundaries are only found once every operand is known to lie within the code array
+    let read_i16 = |offset: usize| i16::from_be_bytes([code[offset], code[offset + 1]]) as i64;
+    let read_i32 = |offset: usize| {
+        i32::from_be_bytes([
+            code[offset],
+            code[offset + 1],
+            code[offset + 2],
+            code[offset + 3],
+        ]) as i64
+    };
+
+    let mut scan = BranchScan {
+        targets: Vec::new(),
+        invokespecials: Vec::new(),
+    };
+    let mut branch_offsets = Vec::new();
+
+    let boundaries = instruction_boundaries(code, 0)?;
+    for (i, &offset) in boundaries.iter().enumerate() {
+        let opcode = code[offset];
+        let mut unconditional = false;
+
+        match opcode {
+            // ifeq ... if_acmpne, goto, jsr, ifnull, ifnonnull
+            0x99..=0xA8 | 0xC6 | 0xC7 => {
+                branch_offsets.push(read_i16(offset + 1));
+                unconditional = opcode == 0xA7;
+            }
+            // goto_w, jsr_w
+            0xC8 | 0xC9 => {
+                branch_offsets.push(read_i32(offset + 1));
+                unconditional = opcode == 0xC8;
+            }
+            // tableswitch, lookupswitch
+            0xAA | 0xAB => {
+                // the operands are aligned to a multiple of four bytes from the start of the code array
+                let operands = offset + 1 + (3 - offset % 4);
+                branch_offsets.push(read_i32(operands));
+
+                if opcode == 0xAA {
+                    let cases = read_i32(operands + 8) - read_i32(operands + 4) + 1;
+                    for case in 0..cases as usize {
+                        branch_offsets.push(read_i32(operands + 12 + case * 4));
+                    }
+                } else {
+                    let pairs = read_i32(operands + 4);
+                    for pair in 0..pairs as usize {
+                        branch_offsets.push(read_i32(operands + 12 + pair * 8));
+                    }
+                }
+
+                unconditional = true;
+            }
+            // invokespecial
+            0xB7 => {
+                let index = read_i16(offset + 1) as u16;
+                scan.invokespecials.push((offset, index));
+            }
+            // ret, ireturn ... return, athrow
+            0xA9..=0xB1 | 0xBF => unconditional = true,
+            _ => {}
+        }
+
+        for branch_offset in branch_offsets.drain(..) {
+            let target = offset as i64 + branch_offset;
+            if target < 0 || target >= code.len() as i64 {
+                return Err(WriteError::BranchOutOfRange { offset, target });
+            }
+
+            scan.targets.push(target as usize);
+        }
+
+        if let Some(&next) = boundaries.get(i + 1) {
+            if unconditional {
+                scan.targets.push(next);
+            }
+        }
+    }
+
+    Ok(scan)
+}
+
+fn stack_map_frame_to_bytes(buffer: &mut Vec<u8>, frame: &StackMapFrame) {
+    match frame {
+        StackMapFrame::SameFrame { offset_delta } => buffer.push(*offset_delta),
+        StackMapFrame::SameLocals1StackItemFrame {
+            offset_delta,
+            stack,
+        } => {
+            buffer.push(offset_delta + 64);
+            verification_type_info_to_bytes(buffer, stack);
+        }
+        StackMapFrame::SameLocals1StackItemFrameExtended {
+            offset_delta,
+            stack,
+        } => {
+            buffer.push(247);
+            buffer.extend_from_slice(&offset_delta.to_be_bytes());
+            verification_type_info_to_bytes(buffer, stack);
+        }
+        StackMapFrame::ChopFrame {
+            offset_delta,
+            absent_locals,
+        } => {
+            buffer.push(251 - absent_locals);
+            buffer.extend_from_slice(&offset_delta.to_be_bytes());
+        }
+        StackMapFrame::SameFrameExtended { offset_delta } => {
+            buffer.push(251);
+            buffer.extend_from_slice(&offset_delta.to_be_bytes());
+        }
+        StackMapFrame::AppendFrame {
+            offset_delta,
+            locals,
+        } => {
+            buffer.push(251 + locals.len() as u8);
+            buffer.extend_from_slice(&offset_delta.to_be_bytes());
+            for local in locals {
+                verification_type_info_to_bytes(buffer, local);
+            }
+        }
+        StackMapFrame::FullFrame {
+            offset_delta,
+            locals,
+            stack,
+        } => {
+            buffer.push(255);
+            buffer.extend_from_slice(&offset_delta.to_be_bytes());
+            buffer.extend_from_slice(&(locals.len() as u16).to_be_bytes());
+            for local in locals {
+                verification_type_info_to_bytes(buffer, local);
+            }
+            buffer.extend_from_slice(&(stack.len() as u16).to_be_bytes());
+            for item in stack {
+                verification_type_info_to_bytes(buffer, item);
+            }
+        }
+    }
+}
+
+fn stack_map_frames(
+    method: &BuiltMember,
+    code: &BuiltCode,
+    this_class: u16,
+    super_class: u16,
+    constant_pool: &mut ConstantPoolBuilder,
+) -> Result<Vec<StackMapFrame>, WriteError> {
+    let Some(descriptor) = method_descriptor_from_str(&method.descriptor) else {
+        return Err(WriteError::MalformedDescriptor {
+            descriptor: method.descriptor.clone(),
+        });
+    };
+
+    let is_constructor = method.name == "<init>";
+    let mut entry_locals = Vec::new();
+    if method.access_flags & MethodAccessFlags::STATIC == 0 {
+        entry_locals.push(if is_constructor {
+            VerificationTypeInfo::UninitializedThisVariable
+        } else {
+            VerificationTypeInfo::ObjectVariable(this_class)
+        });
+    }
+    for parameter in &descriptor.parameters {
+        entry_locals.push(verification_type_of(parameter, constant_pool)?);
+    }
+
+    let scan = scan_branches(&code.code)?;
+
+    // within a constructor, `this` only becomes initialized once the `this(...)` or `super(...)` call
+    // has returned
+    let initialized_at = scan
+        .invokespecials
+        .iter()
+        .find(|(_, index)| is_constructor_call(*index, this_class, super_class, constant_pool))
+        .map(|(offset, _)| *offset);
+
+    let mut frame_states = BTreeMap::new();
+    for target in scan.targets {
+        frame_states.insert(target, None);
+    }
+    for entry in &code.exception_table {
+        let catch_type = match entry.catch_type {
+            0 => constant_pool.class("java/lang/Throwable")?,
+            catch_type => catch_type,
+        };
+
+        frame_states.insert(entry.handler_pc as usize, Some(catch_type));
+    }
+
+    let mut frames = Vec::with_capacity(frame_states.len());
+    let mut previous_locals = entry_locals.clone();
+    let mut previous_offset = None;
+    for (offset, handler_catch_type) in frame_states {
+        let mut locals = entry_locals.clone();
+        if is_constructor && initialized_at.is_some_and(|initialized_at| offset > initialized_at) {
+            locals[0] = VerificationTypeInfo::ObjectVariable(this_class);
+        }
+
+        let stack = handler_catch_type
+            .map(VerificationTypeInfo::ObjectVariable)
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        // the first explicit frame is relative to the implicit frame on method entry
+        let offset_delta = match previous_offset {
+            Some(previous_offset) => offset - previous_offset - 1,
+            None => offset,
+        } as u16;
+
+        frames.push(if locals != previous_locals {
+            StackMapFrame::FullFrame {
+                offset_delta,
+                locals: locals.clone(),
+                stack,
+            }
+        } else if let Some(item) = stack.into_iter().next() {
+            match u8::try_from(offset_delta) {
+                Ok(offset_delta) if offset_delta < 64 => StackMapFrame::SameLocals1StackItemFrame {
+                    offset_delta,
+                    stack: item,
+                },
+                _ => StackMapFrame::SameLocals1StackItemFrameExtended {
+                    offset_delta,
+                    stack: item,
+                },
+            }
+        } else {
+            match u8::try_from(offset_delta) {
+                Ok(offset_delta) if offset_delta < 64 => StackMapFrame::SameFrame { offset_delta },
+                _ => StackMapFrame::SameFrameExtended { offset_delta },
+            }
+        });
+
+        previous_locals = locals;
+        previous_offset = Some(offset);
+    }
+
+    Ok(frames)
+}
+
+fn take_u16(bytes: &mut &[u8]) -> Result<u16, WriteError> {
+    let (value, rest) = bytes
+        .split_first_chunk()
+        .ok_or(WriteError::MalformedAnnotation)?;
+    *bytes = rest;
+
+    Ok(u16::from_be_bytes(*value))
+}
+
+fn target_info_to_bytes(buffer: &mut Vec<u8>, target_info: &TargetInfo) {
+    match target_info {
+        TargetInfo::TypeParameter(type_parameter_index) => buffer.push(*type_parameter_index),
+        TargetInfo::Supertype(supertype_index) => {
+            buffer.extend_from_slice(&supertype_index.to_be_bytes());
+        }
+        TargetInfo::TypeParameterBound {
+            type_parameter_index,
+            bound_index,
+        } => {
+            buffer.push(*type_parameter_index);
+            buffer.push(*bound_index);
+        }
+        TargetInfo::Empty => {}
+        TargetInfo::FormalParameter(formal_parameter_index) => buffer.push(*formal_parameter_index),
+        TargetInfo::Throws(throws_type_index) => {
+            buffer.extend_from_slice(&throws_type_index.to_be_bytes());
+        }
+        TargetInfo::LocalVar { table } => {
+            buffer.extend_from_slice(&(table.len() as u16).to_be_bytes());
+            for local_var in table {
+                buffer.extend_from_slice(&local_var.start_pc.to_be_bytes());
+                buffer.extend_from_slice(&local_var.length.to_be_bytes());
+                buffer.extend_from_slice(&local_var.index.to_be_bytes());
+            }
+        }
+        TargetInfo::Catch(exception_table_index) => {
+            buffer.extend_from_slice(&exception_table_index.to_be_bytes());
+        }
+        TargetInfo::Offset(offset) => buffer.extend_from_slice(&offset.to_be_bytes()),
+        TargetInfo::TypeArgument {
+            offset,
+            type_argument_index,
+        } => {
+            buffer.extend_from_slice(&offset.to_be_bytes());
+            buffer.push(*type_argument_index);
+        }
+    }
+}
+
+fn type_annotation_to_bytes(buffer: &mut Vec<u8>, type_annotation: &TypeAnnotation) {
+    buffer.push(type_annotation.target_type);
+    target_info_to_bytes(buffer, &type_annotation.target_info);
+    buffer.push(type_annotation.target_path.path.len() as u8);
+    for segment in &type_annotation.target_path.path {
+        buffer.push(segment.type_path_kind);
+        buffer.push(segment.type_argument_index);
+    }
+    buffer.extend_from_slice(&type_annotation.type_index.to_be_bytes());
+    element_value_pairs_to_bytes(buffer, &type_annotation.element_value_pairs);
+}
+
+fn u16_table_to_bytes(buffer: &mut Vec<u8>, values: &[u16]) {
+    buffer.extend_from_slice(&(values.len() as u16).to_be_bytes());
+    for value in values {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn verification_type_info_to_bytes(buffer: &mut Vec<u8>, info: &VerificationTypeInfo) {
+    match info {
+        VerificationTypeInfo::TopVariable => buffer.push(0),
+        VerificationTypeInfo::IntegerVariable => buffer.push(1),
+        VerificationTypeInfo::FloatVariable => buffer.push(2),
+        VerificationTypeInfo::DoubleVariable => buffer.push(3),
+        VerificationTypeInfo::LongVariable => buffer.push(4),
+        VerificationTypeInfo::NullVariable => buffer.push(5),
+        VerificationTypeInfo::UninitializedThisVariable => buffer.push(6),
+        VerificationTypeInfo::ObjectVariable(cpool_index) => {
+            buffer.push(7);
+            buffer.extend_from_slice(&cpool_index.to_be_bytes());
+        }
+        VerificationTypeInfo::UninitializedVariable(offset) => {
+            buffer.push(8);
+            buffer.extend_from_slice(&offset.to_be_bytes());
+        }
+    }
+}
+
+fn verification_type_of(
+    field_type: &FieldType,
+    constant_pool: &mut ConstantPoolBuilder,
+) -> Result<VerificationTypeInfo, WriteError> {
+    Ok(match field_type {
+        FieldType::Byte
+        | FieldType::Char
+        | FieldType::Int
+        | FieldType::Short
+        | FieldType::Boolean => VerificationTypeInfo::IntegerVariable,
+        FieldType::Float => VerificationTypeInfo::FloatVariable,
+        FieldType::Long => VerificationTypeInfo::LongVariable,
+        FieldType::Double => VerificationTypeInfo::DoubleVariable,
+        FieldType::Object(class_name) => {
+            VerificationTypeInfo::ObjectVariable(constant_pool.class(class_name)?)
+        }
+        FieldType::Array(_) => VerificationTypeInfo::ObjectVariable(
+            constant_pool.class(&field_type_to_descriptor(field_type))?,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::classfile_from_bytes;
+    use crate::spec::Annotation;
+    use crate::spec::AttributeInfo;
+    use crate::spec::ElementValue;
+    use crate::spec::ElementValuePair;
+    use crate::spec::StackMapFrame;
+    use crate::spec::VerificationTypeInfo;
+    use crate::spec::Version;
+
+    use super::ClassBuilder;
+    use super::ConstantPoolBuilder;
+    use super::MethodBuilder;
+    use super::WriteError;
+
+    #[test]
+    fn builder_reports_full_pool() {
+        let mut builder = ConstantPoolBuilder::new();
+        for value in 0..65532 {
+            builder.integer(value).unwrap();
+        }
+
+        // a Long or Double needs both of the last two indices
+        assert_eq!(builder.integer(65532).unwrap(), 65533);
+        assert!(matches!(
+            builder.long(0),
+            Err(WriteError::PoolLimitExceeded { slots: 65535 })
+        ));
+        assert_eq!(builder.integer(65533).unwrap(), 65534);
+        assert!(matches!(
+            builder.utf8("full"),
+            Err(WriteError::PoolLimitExceeded { slots: 65535 })
+        ));
+        // entries already in the pool are still found
+        assert_eq!(builder.integer(0).unwrap(), 1);
+    }
+
+    #[test]
+    fn built_class_has_stack_map_frames() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 52,
+            },
+            0x21,
+            "Gen",
+            Some("java/lang/Object"),
+        );
+        let exception = class
+            .constant_pool()
+            .class("java/lang/RuntimeException")
+            .unwrap();
+        let [e1, e2] = exception.to_be_bytes();
+        let [i1, i2] = class
+            .constant_pool()
+            .method_ref("java/lang/RuntimeException", "<init>", "()V")
+            .unwrap()
+            .to_be_bytes();
+
+        // static void count(int n) { while (n > 0) n--; try { throw new RuntimeException(); } catch (...) {} }
+        let mut method = MethodBuilder::new(0x8, "count", "(I)V");
+        method
+            .code(
+                2,
+                1,
+                vec![
+                    0x1A, 0x9E, 0x00, 0x09, 0x84, 0x00, 0xFF, 0xA7, 0xFF, 0xF9, 0xBB, e1, e2, 0x59,
+                    0xB7, i1, i2, 0xBF, 0x57, 0xB1,
+                ],
+            )
+            .exception_handler(10, 18, 18, Some("java/lang/RuntimeException"));
+        class.add_method(method).unwrap();
+        class
+            .add_method(MethodBuilder::new(0x401, "run", "()V"))
+            .unwrap();
+
+        let bytes = class.build().unwrap();
+        let (rest, classfile) = classfile_from_bytes(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert!(classfile.integrity_check().is_ok());
+        assert_eq!(classfile.methods.len(), 2);
+        assert!(classfile.methods[1].attributes.is_empty());
+
+        let AttributeInfo::Code { attributes, .. } = &classfile.methods[0].attributes[0].info
+        else {
+            panic!("count has no Code attribute");
+        };
+        let AttributeInfo::StackMapTable { entries } = &attributes[0].info else {
+            panic!("count has no StackMapTable");
+        };
+        // the loop condition, the code after the loop and the handler
+        assert_eq!(
+            entries,
+            &[
+                StackMapFrame::SameFrame { offset_delta: 0 },
+                StackMapFrame::SameFrame { offset_delta: 9 },
+                StackMapFrame::SameLocals1StackItemFrame {
+                    offset_delta: 7,
+                    stack: VerificationTypeInfo::ObjectVariable(exception),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn method_annotations_round_trip() {
+        let mut class = ClassBuilder::new(
+            Version {
+                minor: 0,
+                major: 50,
+            },
+            0x21,
+            "Annotated",
+            Some("java/lang/Object"),
+        );
+        let inner_type = class.constant_pool().utf8("LInner;").unwrap();
+        let value_name = class.constant_pool().utf8("value").unwrap();
+        let seven = class.constant_pool().integer(7).unwrap();
+        let first = class.constant_pool().utf8("a").unwrap();
+        let second = class.constant_pool().utf8("b").unwrap();
+        let nested = ElementValue::Annotation(Annotation {
+            type_index: inner_type,
+            element_value_pairs: vec![ElementValuePair {
+                element_name_index: value_name,
+                value: ElementValue::ConstValue {
+                    tag: b'I',
+                    const_value_index: seven,
+                },
+            }],
+            raw: None,
+        });
+        let array = ElementValue::Array {
+            values: vec![
+                ElementValue::ConstValue {
+                    tag: b's',
+                    const_value_index: first,
+                },
+                ElementValue::ConstValue {
+                    tag: b's',
+                    const_value_index: second,
+                },
+            ],
+        };
+
+        let mut method = MethodBuilder::new(0x401, "run", "()V");
+        method
+            .add_annotation("java/lang/Deprecated", &[])
+            .add_annotation(
+                "Outer",
+                &[("inner", nested.clone()), ("names", array.clone())],
+            );
+        class.add_method(method).unwrap();
+
+        let bytes = class.build().unwrap();
+        let (_, classfile) = classfile_from_bytes(&bytes).unwrap();
+        let constant_pool = &classfile.constant_pool;
+        let [attribute] = &classfile.methods[0].attributes[..] else {
+            panic!("run has more than its annotations");
+        };
+        let AttributeInfo::RuntimeVisibleAnnotations { annotations } = &attribute.info else {
+            panic!("run has no RuntimeVisibleAnnotations");
+        };
+        let [deprecated, outer] = &annotations[..] else {
+            panic!("run does not have two annotations");
+        };
+
+        assert_eq!(
+            constant_pool.utf8(deprecated.type_index).unwrap(),
+            "Ljava/lang/Deprecated;"
+        );
+        assert!(deprecated.element_value_pairs.is_empty());
+        assert_eq!(constant_pool.utf8(outer.type_index).unwrap(), "LOuter;");
+        let elements = outer
+            .element_value_pairs
+            .iter()
+            .map(|pair| {
+                (
+                    constant_pool.utf8(pair.element_name_index).unwrap(),
+                    &pair.value,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            elements,
+            [("inner".into(), &nested), ("names".into(), &array)]
+        );
+    }
+}